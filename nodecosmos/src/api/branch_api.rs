@@ -7,10 +7,14 @@ use std::collections::HashSet;
 
 use crate::api::data::RequestData;
 use crate::api::types::Response;
+use crate::models::branch::sync::SyncParams;
+use crate::models::branch::title_conflict::TitleResolution;
 use crate::models::branch::update::BranchUpdate;
 use crate::models::branch::{Branch, GetNodeIdBranch};
-use crate::models::traits::Authorization;
+use crate::models::commit::Commit;
+use crate::models::traits::{Authorization, ObjectType};
 use crate::models::user::ShowUser;
+use crate::resources::resource_locker::ResourceLocker;
 
 #[get("/{id}")]
 pub async fn show_branch(
@@ -35,6 +39,37 @@ pub async fn get_branch_node_id(db_session: web::Data<CachingSession>, id: web::
     Ok(HttpResponse::Ok().json(branch))
 }
 
+#[get("/{id}/commits/{node_id}")]
+pub async fn get_node_commits(
+    db_session: web::Data<CachingSession>,
+    opt_cu: OptCurrentUser,
+    params: web::Path<(Uuid, Uuid)>,
+) -> Response {
+    let (id, node_id) = params.into_inner();
+    let mut branch = Branch::find_by_id(id).execute(&db_session).await?;
+
+    branch.auth_view(&db_session, &opt_cu).await?;
+
+    let commits = Commit::node_commits(&db_session, id, node_id).await?;
+
+    Ok(HttpResponse::Ok().json(commits))
+}
+
+#[get("/{id}/merge_preview")]
+pub async fn get_merge_preview(
+    db_session: web::Data<CachingSession>,
+    opt_cu: OptCurrentUser,
+    id: web::Path<Uuid>,
+) -> Response {
+    let mut branch = Branch::find_by_id(id.into_inner()).execute(&db_session).await?;
+
+    branch.auth_view(&db_session, &opt_cu).await?;
+
+    let preview = branch.merge_preview(&db_session).await?;
+
+    Ok(HttpResponse::Ok().json(preview))
+}
+
 #[derive(Deserialize)]
 pub struct BranchPayload {
     #[serde(rename = "branchId")]
@@ -44,6 +79,64 @@ pub struct BranchPayload {
     pub object_id: Uuid,
 }
 
+#[derive(Deserialize)]
+pub struct SyncBranchPayload {
+    #[serde(rename = "branchId")]
+    pub branch_id: Uuid,
+
+    #[serde(flatten)]
+    pub params: SyncParams,
+}
+
+#[put("/sync_with_original")]
+pub async fn sync_branch_with_original(data: RequestData, payload: web::Json<SyncBranchPayload>) -> Response {
+    let payload = payload.into_inner();
+    let mut branch = Branch::find_by_id(payload.branch_id).execute(data.db_session()).await?;
+    let root_id = branch.root_id;
+
+    branch.auth_update(&data).await?;
+
+    data.resource_locker()
+        .lock_resource(root_id, branch.id, ResourceLocker::FIVE_MINUTES)
+        .await?;
+
+    let res = branch.sync_with_original(&data, &payload.params).await;
+
+    data.resource_locker()
+        .unlock_resource(root_id, payload.branch_id)
+        .await?;
+
+    Ok(HttpResponse::Ok().json(res?))
+}
+
+#[derive(Deserialize)]
+pub struct ResolveTitleConflictPayload {
+    #[serde(rename = "branchId")]
+    pub branch_id: Uuid,
+
+    #[serde(rename = "objectId")]
+    pub object_id: Uuid,
+
+    #[serde(rename = "objectType")]
+    pub object_type: ObjectType,
+
+    pub resolution: TitleResolution,
+}
+
+#[put("/resolve_title_conflict")]
+pub async fn resolve_title_conflict(data: RequestData, payload: web::Json<ResolveTitleConflictPayload>) -> Response {
+    let payload = payload.into_inner();
+    let mut branch = Branch::find_by_id(payload.branch_id).execute(data.db_session()).await?;
+
+    branch.auth_update(&data).await?;
+
+    let branch = branch
+        .resolve_title_conflict(&data, payload.object_id, payload.object_type, payload.resolution)
+        .await?;
+
+    Ok(HttpResponse::Ok().json(branch))
+}
+
 #[put("/restore_node")]
 pub async fn restore_node(data: RequestData, params: web::Json<BranchPayload>) -> Response {
     let params = params.into_inner();
@@ -52,8 +145,9 @@ pub async fn restore_node(data: RequestData, params: web::Json<BranchPayload>) -
     branch.auth_update(&data).await?;
 
     let branch = Branch::update(
-        data.db_session(),
+        &data,
         params.branch_id,
+        params.object_id,
         BranchUpdate::RestoreNode(params.object_id),
     )
     .await?;
@@ -69,8 +163,9 @@ pub async fn undo_delete_node(data: RequestData, params: web::Json<BranchPayload
     branch.auth_update(&data).await?;
 
     let branch = Branch::update(
-        data.db_session(),
+        &data,
         params.branch_id,
+        params.object_id,
         BranchUpdate::UndoDeleteNodes(vec![params.object_id]),
     )
     .await?;
@@ -85,9 +180,13 @@ pub async fn restore_flow(data: RequestData, params: web::Json<BranchPayload>) -
 
     branch.auth_update(&data).await?;
 
+    let node_id = branch
+        .object_node_id(data.db_session(), params.object_id, ObjectType::Flow)
+        .await?;
     let branch = Branch::update(
-        data.db_session(),
+        &data,
         params.branch_id,
+        node_id,
         BranchUpdate::RestoreFlow(params.object_id),
     )
     .await?;
@@ -102,9 +201,13 @@ pub async fn undo_delete_flow(data: RequestData, params: web::Json<BranchPayload
 
     branch.auth_update(&data).await?;
 
+    let node_id = branch
+        .object_node_id(data.db_session(), params.object_id, ObjectType::Flow)
+        .await?;
     let branch = Branch::update(
-        data.db_session(),
+        &data,
         params.branch_id,
+        node_id,
         BranchUpdate::UndoDeleteFlow(params.object_id),
     )
     .await?;
@@ -119,9 +222,13 @@ pub async fn restore_flow_step(data: RequestData, params: web::Json<BranchPayloa
 
     branch.auth_update(&data).await?;
 
+    let node_id = branch
+        .object_node_id(data.db_session(), params.object_id, ObjectType::FlowStep)
+        .await?;
     let branch = Branch::update(
-        data.db_session(),
+        &data,
         params.branch_id,
+        node_id,
         BranchUpdate::RestoreFlowStep(params.object_id),
     )
     .await?;
@@ -136,9 +243,13 @@ pub async fn keep_flow_step(data: RequestData, params: web::Json<BranchPayload>)
 
     branch.auth_update(&data).await?;
 
+    let node_id = branch
+        .object_node_id(data.db_session(), params.object_id, ObjectType::FlowStep)
+        .await?;
     let branch = Branch::update(
-        data.db_session(),
+        &data,
         params.branch_id,
+        node_id,
         BranchUpdate::KeepFlowStep(params.object_id),
     )
     .await?;
@@ -153,9 +264,13 @@ pub async fn undo_delete_flow_step(data: RequestData, params: web::Json<BranchPa
 
     branch.auth_update(&data).await?;
 
+    let node_id = branch
+        .object_node_id(data.db_session(), params.object_id, ObjectType::FlowStep)
+        .await?;
     let branch = Branch::update(
-        data.db_session(),
+        &data,
         params.branch_id,
+        node_id,
         BranchUpdate::UndoDeleteFlowStep(params.object_id),
     )
     .await?;
@@ -170,9 +285,13 @@ pub async fn restore_io(data: RequestData, params: web::Json<BranchPayload>) ->
 
     branch.auth_update(&data).await?;
 
+    let node_id = branch
+        .object_node_id(data.db_session(), params.object_id, ObjectType::Io)
+        .await?;
     let branch = Branch::update(
-        data.db_session(),
+        &data,
         params.branch_id,
+        node_id,
         BranchUpdate::RestoreIo(params.object_id),
     )
     .await?;
@@ -187,11 +306,12 @@ pub async fn undo_delete_flow_step_io(data: RequestData, params: web::Path<(Uuid
 
     branch.auth_update(&data).await?;
 
-    Branch::update(data.db_session(), branch_id, BranchUpdate::UndoDeleteIo(io_id)).await?;
+    Branch::update(&data, branch_id, fs_node_id, BranchUpdate::UndoDeleteIo(io_id)).await?;
 
     branch = Branch::update(
-        data.db_session(),
+        &data,
         branch_id,
+        fs_node_id,
         BranchUpdate::UndoDeleteOutput((fs_id, fs_node_id, io_id)),
     )
     .await?;
@@ -206,14 +326,17 @@ pub async fn undo_delete_initial_io(data: RequestData, params: web::Path<(Uuid,
 
     branch.auth_update(&data).await?;
 
-    Branch::update(data.db_session(), branch_id, BranchUpdate::UndoDeleteIo(io_id)).await?;
+    let node_id = branch.object_node_id(data.db_session(), io_id, ObjectType::Io).await?;
+
+    Branch::update(&data, branch_id, node_id, BranchUpdate::UndoDeleteIo(io_id)).await?;
 
     let mut set = HashSet::new();
     set.insert(io_id);
 
     branch = Branch::update(
-        data.db_session(),
+        &data,
         branch_id,
+        node_id,
         BranchUpdate::UndoDeleteWorkflowInitialInputs(set),
     )
     .await?;
@@ -92,7 +92,7 @@ pub async fn create_comment(data: RequestData, payload: web::Json<CreateCommentP
 pub async fn update_comment_content(data: RequestData, mut comment: web::Json<UpdateContentComment>) -> Response {
     comment.as_native().auth_update(&data).await?;
 
-    comment.update_cb(&None).execute(data.db_session()).await?;
+    comment.update_cb(&data).execute(data.db_session()).await?;
 
     Ok(HttpResponse::Ok().json(comment))
 }
@@ -0,0 +1,33 @@
+use actix_web::{get, put, web, HttpResponse};
+use charybdis::types::Uuid;
+use serde::Deserialize;
+
+use crate::api::data::RequestData;
+use crate::api::types::Response;
+use crate::models::consistency::TreeConsistency;
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ConsistencyParams {
+    pub root_id: Uuid,
+    pub branch_id: Uuid,
+}
+
+#[get("/{rootId}/{branchId}")]
+pub async fn check_consistency(data: RequestData, params: web::Path<ConsistencyParams>) -> Response {
+    data.validate_admin()?;
+
+    let report = TreeConsistency::check(&data, params.root_id, params.branch_id, false).await?;
+
+    Ok(HttpResponse::Ok().json(report))
+}
+
+/// Repairs what can be repaired and reports all found inconsistencies.
+#[put("/{rootId}/{branchId}/repair")]
+pub async fn repair_consistency(data: RequestData, params: web::Path<ConsistencyParams>) -> Response {
+    data.validate_admin()?;
+
+    let report = TreeConsistency::check(&data, params.root_id, params.branch_id, true).await?;
+
+    Ok(HttpResponse::Ok().json(report))
+}
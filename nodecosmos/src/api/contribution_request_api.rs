@@ -1,19 +1,27 @@
 use actix_web::{delete, get, post, put, web, HttpResponse};
 use charybdis::model::AsNative;
-use charybdis::operations::{DeleteWithCallbacks, Find, InsertWithCallbacks, New, UpdateWithCallbacks};
+use charybdis::operations::{Delete, DeleteWithCallbacks, Find, Insert, InsertWithCallbacks, New, UpdateWithCallbacks};
 use charybdis::types::Uuid;
 use scylla::client::caching_session::CachingSession;
+use serde::Deserialize;
 use serde_json::json;
 
+use crate::api::current_user::OptCurrentUser;
 use crate::api::data::RequestData;
 use crate::api::types::{ActionTypes, Response};
 use crate::errors::NodecosmosError;
+use crate::models::branch::merge::selection::MergeSelection;
 use crate::models::branch::Branch;
-use crate::models::comment::BaseComment;
+use crate::models::comment::{BaseComment, Comment};
 use crate::models::comment_thread::CommentThread;
 use crate::models::contribution_request::{
-    BaseContributionRequest, ContributionRequest, UpdateContributionRequestDescription, UpdateContributionRequestTitle,
+    BaseContributionRequest, ContributionRequest, UpdateContributionRequestDescription,
+    UpdateContributionRequestReviewers, UpdateContributionRequestTitle,
 };
+use crate::models::merge_queue::MergeQueueItem;
+use crate::models::node::{AuthNode, Node};
+use crate::models::review::{Review, ReviewStatus};
+use crate::models::review_settings::ReviewSettings;
 use crate::models::traits::Authorization;
 use crate::resources::resource_locker::ResourceLocker;
 
@@ -50,11 +58,18 @@ pub async fn get_contribution_request(
         .try_collect()
         .await?;
 
+    let reviews: Vec<Review> = Review::find_by_branch_id(contribution_request.id)
+        .execute(&db_session)
+        .await?
+        .try_collect()
+        .await?;
+
     Ok(HttpResponse::Ok().json(json!({
         "contributionRequest": contribution_request,
         "branch": branch,
         "comments": comments,
         "threads": threads,
+        "reviews": reviews,
     })))
 }
 
@@ -99,6 +114,134 @@ pub async fn update_contribution_request_description(
     Ok(HttpResponse::Ok().json(contribution_request))
 }
 
+#[put("/reviewers")]
+pub async fn update_contribution_request_reviewers(
+    data: RequestData,
+    contribution_request: web::Json<UpdateContributionRequestReviewers>,
+) -> Response {
+    let contribution_request = contribution_request.into_inner();
+    let mut native_cr = contribution_request
+        .as_native()
+        .find_by_primary_key()
+        .execute(data.db_session())
+        .await?;
+
+    native_cr.auth_update(&data).await?;
+
+    native_cr
+        .update_reviewers(&data, contribution_request.reviewer_ids.unwrap_or_default())
+        .await?;
+
+    Ok(HttpResponse::Ok().json(native_cr))
+}
+
+#[derive(Deserialize)]
+pub struct CreateReviewPayload {
+    pub review: Review,
+
+    #[serde(rename = "newThread")]
+    pub new_thread: Option<CommentThread>,
+
+    /// Body of the review. It's stored as a comment, so it can be discussed in its thread.
+    pub comment: Option<Comment>,
+}
+
+#[post("/reviews")]
+pub async fn create_review(data: RequestData, payload: web::Json<CreateReviewPayload>) -> Response {
+    let CreateReviewPayload {
+        mut review,
+        new_thread,
+        mut comment,
+    } = payload.into_inner();
+    let status = review.review_status()?;
+    let contribution_request = review.contribution_request(data.db_session()).await?;
+
+    // approvals count towards the merge requirement, so only users that can merge can approve
+    if status == ReviewStatus::Comment {
+        contribution_request
+            .auth_view(data.db_session(), &OptCurrentUser(Some(data.current_user.clone())))
+            .await?;
+    } else {
+        contribution_request
+            .node(data.db_session())
+            .await?
+            .auth_edit_access(&data)
+            .await?;
+    }
+
+    // authorize and validate everything first, so a rejected review doesn't leave its thread or comment behind
+    let mut new_thread = comment.as_ref().and(new_thread);
+
+    if let Some(thread) = new_thread.as_mut() {
+        thread.auth_creation(&data).await?;
+    } else if let Some(comment) = comment.as_mut() {
+        comment.auth_creation(&data).await?;
+    }
+
+    review.validate(&data).await?;
+
+    if let Some(thread) = new_thread.as_mut() {
+        thread.insert_cb(&data).execute(data.db_session()).await?;
+        review.thread_id = Some(thread.id);
+    } else if let Some(comment) = &comment {
+        review.thread_id = Some(comment.thread_id);
+    }
+
+    review.insert_cb(&data).execute(data.db_session()).await?;
+
+    let mut comment_res = None;
+
+    if let Some(mut comment) = comment {
+        if let Some(thread) = new_thread {
+            comment.assign_thread(thread);
+        }
+
+        comment.insert_cb(&data).execute(data.db_session()).await?;
+        comment_res = Some(comment);
+    }
+
+    Ok(HttpResponse::Created().json(json!({
+        "review": review,
+        "comment": comment_res,
+    })))
+}
+
+#[get("/review_settings/{root_id}")]
+pub async fn get_review_settings(
+    db_session: web::Data<CachingSession>,
+    opt_cu: OptCurrentUser,
+    root_id: web::Path<Uuid>,
+) -> Response {
+    let root_id = root_id.into_inner();
+
+    AuthNode::auth_view(&db_session, &opt_cu, root_id, root_id, root_id).await?;
+
+    let settings = ReviewSettings::find_or_default(&db_session, root_id).await?;
+
+    Ok(HttpResponse::Ok().json(settings))
+}
+
+#[put("/review_settings")]
+pub async fn update_review_settings(data: RequestData, settings: web::Json<ReviewSettings>) -> Response {
+    let mut settings = settings.into_inner();
+    let mut root = Node::find_by_branch_id_and_id(settings.root_id, settings.root_id)
+        .execute(data.db_session())
+        .await?;
+
+    root.auth_edit_access(&data).await?;
+
+    if settings.required_approvals < 0 {
+        return Err(NodecosmosError::BadRequest(
+            "Required approvals can not be negative".to_string(),
+        ));
+    }
+
+    settings.updated_at = chrono::Utc::now();
+    settings.insert().execute(data.db_session()).await?;
+
+    Ok(HttpResponse::Ok().json(settings))
+}
+
 #[delete("/{nodeId}/{rootId}/{id}")]
 pub async fn delete_contribution_request(
     data: RequestData,
@@ -124,20 +267,33 @@ pub async fn publish(data: RequestData, mut contribution_request: web::Json<Cont
     Ok(HttpResponse::Ok().finish())
 }
 
+#[derive(Deserialize)]
+pub struct MergeContributionRequestPayload {
+    #[serde(flatten)]
+    pub contribution_request: ContributionRequest,
+
+    /// Merges only the selected objects from the branch.
+    pub selection: Option<MergeSelection>,
+}
+
 #[put("/merge")]
 pub async fn merge_contribution_request(
     data: RequestData,
-    contribution_request: web::Json<ContributionRequest>,
+    payload: web::Json<MergeContributionRequestPayload>,
 ) -> Response {
+    let MergeContributionRequestPayload {
+        contribution_request,
+        selection,
+    } = payload.into_inner();
     let mut contribution_request = contribution_request
         .find_by_primary_key()
         .execute(data.db_session())
         .await?;
     let branch_id = contribution_request.id;
-    let node = contribution_request.node(data.db_session()).await?;
-    let root_id = node.root_id;
 
-    node.auth_update(&data).await?;
+    auth_merge(&data, &mut contribution_request).await?;
+
+    let root_id = contribution_request.node(data.db_session()).await?.root_id;
 
     // first lock the complete resource to avoid all types of race conditions
     data.resource_locker()
@@ -171,7 +327,7 @@ pub async fn merge_contribution_request(
     }
 
     // execute merge
-    let res = contribution_request.merge(&data).await;
+    let res = contribution_request.merge(&data, selection).await;
 
     // unlock complete resource
     data.resource_locker().unlock_resource(root_id, root_id).await?;
@@ -192,3 +348,97 @@ pub async fn merge_contribution_request(
         },
     }
 }
+
+/// Stacked contribution requests merge into their parent branch, so its editors can merge them.
+async fn auth_merge(data: &RequestData, contribution_request: &mut ContributionRequest) -> Result<(), NodecosmosError> {
+    let maybe_parent = contribution_request
+        .branch(data.db_session())
+        .await?
+        .open_parent(data.db_session())
+        .await?;
+
+    match maybe_parent {
+        Some(mut parent) => parent.auth_update(data).await,
+        None => {
+            contribution_request
+                .node(data.db_session())
+                .await?
+                .auth_edit_access(data)
+                .await
+        }
+    }
+}
+
+#[get("/merge_queue/{root_id}")]
+pub async fn get_merge_queue(
+    db_session: web::Data<CachingSession>,
+    opt_cu: OptCurrentUser,
+    root_id: web::Path<Uuid>,
+) -> Response {
+    let root_id = root_id.into_inner();
+
+    AuthNode::auth_view(&db_session, &opt_cu, root_id, root_id, root_id).await?;
+
+    let queue: Vec<MergeQueueItem> = MergeQueueItem::find_by_root_id(root_id)
+        .execute(&db_session)
+        .await?
+        .try_collect()
+        .await?;
+
+    Ok(HttpResponse::Ok().json(queue))
+}
+
+#[post("/merge_queue")]
+pub async fn enqueue_merge(data: RequestData, contribution_request: web::Json<ContributionRequest>) -> Response {
+    let mut contribution_request = contribution_request
+        .find_by_primary_key()
+        .execute(data.db_session())
+        .await?;
+
+    auth_merge(&data, &mut contribution_request).await?;
+
+    let item = MergeQueueItem::enqueue(&data, &contribution_request).await?;
+
+    Ok(HttpResponse::Ok().json(item))
+}
+
+#[delete("/merge_queue/{nodeId}/{rootId}/{id}")]
+pub async fn dequeue_merge(data: RequestData, contribution_request: web::Path<ContributionRequest>) -> Response {
+    let mut contribution_request = contribution_request
+        .find_by_primary_key()
+        .execute(data.db_session())
+        .await?;
+
+    if contribution_request.owner_id != data.current_user.id {
+        auth_merge(&data, &mut contribution_request).await?;
+    }
+
+    let item = MergeQueueItem::find_queued(&data, contribution_request.root_id, contribution_request.id)
+        .await?
+        .ok_or_else(|| NodecosmosError::NotFound("Contribution request is not queued for merge".to_string()))?;
+
+    item.delete().execute(data.db_session()).await?;
+
+    Ok(HttpResponse::Ok().finish())
+}
+
+#[put("/revert")]
+pub async fn revert_contribution_request(
+    data: RequestData,
+    contribution_request: web::Json<ContributionRequest>,
+) -> Response {
+    let mut contribution_request = contribution_request
+        .find_by_primary_key()
+        .execute(data.db_session())
+        .await?;
+
+    contribution_request
+        .node(data.db_session())
+        .await?
+        .auth_edit_access(&data)
+        .await?;
+
+    let revert_contribution_request = contribution_request.revert(&data).await?;
+
+    Ok(HttpResponse::Ok().json(revert_contribution_request))
+}
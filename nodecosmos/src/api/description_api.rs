@@ -4,9 +4,13 @@ use crate::api::types::Response;
 use crate::errors::NodecosmosError;
 use crate::models::archived_description::ArchivedDescription;
 use crate::models::description::{BaseDescription, Description};
+use crate::models::description_version::DescriptionVersion;
 use crate::models::like::Like;
 use crate::models::node::{AuthNode, FindCoverImageNode};
-use crate::models::traits::{Branchable, ObjectType};
+use crate::models::traits::{Branchable, ObjectId, ObjectType};
+use crate::models::utils::DescriptionDiff;
+use crate::resources::description_room::DescriptionRoom;
+use crate::resources::presence::{Presence, PresenceTracker};
 use crate::resources::ws_broadcast::WsConnection;
 use actix_web::{get, post, web, HttpRequest, HttpResponse};
 use actix_web_actors::ws::WsResponseBuilder;
@@ -16,6 +20,7 @@ use charybdis::types::Uuid;
 use scylla::client::caching_session::CachingSession;
 use serde::Deserialize;
 use serde_json::json;
+use std::collections::HashSet;
 use std::str::FromStr;
 
 #[get("/{branchId}/{objectId}/{rootId}/{objectType}/{nodeId}/base")]
@@ -68,31 +73,8 @@ pub async fn get_description(
 pub async fn get_base64_description(data: RequestData, mut description: web::Path<Description>) -> Response {
     AuthNode::auth_update(&data, description.branch_id, description.node_id, description.root_id).await?;
 
-    description.find_branched(data.db_session()).await?;
-
     // we always return merged description as we want to keep branched description in sync with original
-    if description.is_branch() {
-        let original = Description::find_by_branch_id_and_object_id(description.original_id(), description.object_id)
-            .execute(data.db_session())
-            .await;
-
-        if let Ok(mut original) = original {
-            match description.base64 {
-                Some(_) => {
-                    if description.base64 != original.base64 {
-                        original.merge(&description).await?;
-                        description.html = original.html;
-                        description.markdown = original.markdown;
-                        description.base64 = original.base64;
-                    }
-                }
-
-                None => {
-                    description.base64 = original.base64;
-                }
-            }
-        }
-    }
+    description.find_branched_merged(data.db_session()).await?;
 
     Ok(HttpResponse::Ok().json(description.into_inner()))
 }
@@ -112,22 +94,62 @@ pub async fn get_original_description(
     )
     .await?;
 
+    let description = find_original(&db_session, &*d_params).await?;
+
+    Ok(HttpResponse::Ok().json(description))
+}
+
+/// Block level diff of the branched description against its original, so contribution request reviewers don't
+/// have to compare full documents.
+#[get("/{branchId}/{objectId}/{rootId}/{objectType}/{nodeId}/diff")]
+pub async fn get_description_diff(
+    db_session: web::Data<CachingSession>,
+    opt_cu: OptCurrentUser,
+    mut description: web::Path<Description>,
+) -> Response {
+    AuthNode::auth_view(
+        &db_session,
+        &opt_cu,
+        description.branch_id,
+        description.node_id,
+        description.root_id,
+    )
+    .await?;
+
+    let original = find_original(&db_session, &*description).await?.unwrap_or_default();
+
+    // merged description, so changes of the original made after branching are not reported
+    match description.find_branched_merged(&db_session).await {
+        Ok(_) | Err(NodecosmosError::CharybdisError(CharybdisError::NotFoundError(_))) => (),
+        Err(e) => return Err(e),
+    }
+
+    let diff = DescriptionDiff::new(&original, &description)?;
+
+    Ok(HttpResponse::Ok().json(diff))
+}
+
+/// Original description of the branched one. Original archived description is used if the object was deleted.
+async fn find_original<D: Branchable + ObjectId>(
+    db_session: &CachingSession,
+    d_params: &D,
+) -> Result<Option<Description>, NodecosmosError> {
     let mut description =
-        Description::maybe_find_first_by_branch_id_and_object_id(d_params.original_id(), d_params.object_id)
-            .execute(&db_session)
+        Description::maybe_find_first_by_branch_id_and_object_id(d_params.original_id(), d_params.object_id())
+            .execute(db_session)
             .await?;
 
     if description.is_none() && d_params.is_branch() {
         description = ArchivedDescription::maybe_find_first_by_branch_id_and_object_id(
             d_params.original_id(),
-            d_params.object_id,
+            d_params.object_id(),
         )
-        .execute(&db_session)
+        .execute(db_session)
         .await?
         .map(|desc| desc.into());
     }
 
-    Ok(HttpResponse::Ok().json(description))
+    Ok(description)
 }
 
 #[post("")]
@@ -139,6 +161,88 @@ pub async fn save_description(data: RequestData, mut description: web::Json<Desc
     Ok(HttpResponse::Ok().json(description))
 }
 
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct VersionPathParams {
+    branch_id: Uuid,
+    object_id: Uuid,
+    root_id: Uuid,
+    node_id: Uuid,
+
+    #[serde(default)]
+    id: Uuid,
+
+    /// Version compared to the `id` version.
+    #[serde(default)]
+    other_id: Uuid,
+}
+
+#[get("/{branchId}/{objectId}/{rootId}/{nodeId}/versions")]
+pub async fn get_description_versions(
+    db_session: web::Data<CachingSession>,
+    opt_cu: OptCurrentUser,
+    params: web::Path<VersionPathParams>,
+) -> Response {
+    AuthNode::auth_view(&db_session, &opt_cu, params.branch_id, params.node_id, params.root_id).await?;
+
+    let versions = DescriptionVersion::versions(&db_session, params.branch_id, params.object_id).await?;
+
+    Ok(HttpResponse::Ok().json(versions))
+}
+
+#[get("/{branchId}/{objectId}/{rootId}/{nodeId}/versions/{id}")]
+pub async fn get_description_version(
+    db_session: web::Data<CachingSession>,
+    opt_cu: OptCurrentUser,
+    params: web::Path<VersionPathParams>,
+) -> Response {
+    AuthNode::auth_view(&db_session, &opt_cu, params.branch_id, params.node_id, params.root_id).await?;
+
+    let version =
+        DescriptionVersion::find_by_branch_id_and_object_id_and_id(params.branch_id, params.object_id, params.id)
+            .execute(&db_session)
+            .await?;
+
+    Ok(HttpResponse::Ok().json(version))
+}
+
+#[get("/{branchId}/{objectId}/{rootId}/{nodeId}/versions/{id}/diff/{otherId}")]
+pub async fn get_description_version_diff(
+    db_session: web::Data<CachingSession>,
+    opt_cu: OptCurrentUser,
+    params: web::Path<VersionPathParams>,
+) -> Response {
+    AuthNode::auth_view(&db_session, &opt_cu, params.branch_id, params.node_id, params.root_id).await?;
+
+    let version =
+        DescriptionVersion::find_by_branch_id_and_object_id_and_id(params.branch_id, params.object_id, params.id)
+            .execute(&db_session)
+            .await?;
+    let other =
+        DescriptionVersion::find_by_branch_id_and_object_id_and_id(params.branch_id, params.object_id, params.other_id)
+            .execute(&db_session)
+            .await?;
+
+    Ok(HttpResponse::Ok().json(json!({
+        "diff": version.diff(&other),
+    })))
+}
+
+/// Restores the version as a new update, so clients editing the description converge to the restored content.
+#[post("/{branchId}/{objectId}/{rootId}/{nodeId}/versions/{id}/restore")]
+pub async fn restore_description_version(data: RequestData, params: web::Path<VersionPathParams>) -> Response {
+    AuthNode::auth_update(&data, params.branch_id, params.node_id, params.root_id).await?;
+
+    let version =
+        DescriptionVersion::find_by_branch_id_and_object_id_and_id(params.branch_id, params.object_id, params.id)
+            .execute(data.db_session())
+            .await?;
+
+    version.restore(&data).await?;
+
+    Ok(HttpResponse::Ok().finish())
+}
+
 #[derive(Deserialize)]
 pub struct WsPathParams {
     // TODO: check if we can route Load Balancer connections based on room_id params
@@ -148,31 +252,81 @@ pub struct WsPathParams {
     root_id: Uuid,
 }
 
-/// Websocket connection to sync description updates
-/// between attached clients.
-/// It can be used for all models that have description_base64 field.
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct WsQuery {
+    /// Required only when description of the object doesn't exist yet.
+    #[serde(default)]
+    object_type: String,
+}
+
+/// Websocket connection to sync description updates between attached clients by the y-sync protocol.
+/// Room id is id of the object the description belongs to. Server holds the room document and persists it.
 #[get("/descriptions/{branch_id}/{node_id}/{root_id}/{room_id}")]
 pub async fn description_ws(
     req: HttpRequest,
     stream: web::Payload,
     params: web::Path<WsPathParams>,
+    query: web::Query<WsQuery>,
     data: RequestData,
 ) -> Response {
     AuthNode::auth_update(&data, params.branch_id, params.node_id, params.root_id).await?;
 
+    let room_id = format!("{}{}", params.branch_id, params.room_id);
+    let broadcast = data.ws_broadcast();
+
+    // connection is counted by the room right away, so the room isn't dropped while the connection starts
+    let room = match broadcast.join_room(&room_id) {
+        Some(room) => room,
+        None => {
+            let description = Description {
+                branch_id: params.branch_id,
+                object_id: params.room_id,
+                node_id: params.node_id,
+                root_id: params.root_id,
+                object_type: query.into_inner().object_type,
+                ..Default::default()
+            };
+            let room = DescriptionRoom::load(data.db_session(), description).await?;
+
+            broadcast.insert_room(&room_id, room)
+        }
+    };
+
+    // presence is not essential for editing, so connection is not refused if it fails
+    let presence = Presence::editing(&data.current_user, params.root_id, params.branch_id, params.room_id);
+    let presence_id = data
+        .presence()
+        .join(&data, presence)
+        .await
+        .map_err(|e| log::error!("Failed to join presence of room {}: {:?}", room_id, e))
+        .ok();
+
     let ws_desc_conn = WsConnection {
-        room_id: format!("{}{}", params.branch_id, params.room_id),
-        broadcast: data.ws_broadcast(),
+        room_id: room_id.clone(),
+        broadcast,
+        room,
+        data: data.clone(),
+        client_ids: HashSet::new(),
+        presence_id,
     };
     let ws_builder = WsResponseBuilder::new(ws_desc_conn.clone(), &req, stream);
-    let (addr, resp) = ws_builder
-        .start_with_addr()
-        .map_err(|e| NodecosmosError::InternalServerError(format!("Failed to start websocket connection: {}", e)))?;
+    let (addr, resp) = ws_builder.start_with_addr().map_err(|e| {
+        if let Some(presence_id) = presence_id {
+            PresenceTracker::spawn_leave(&data, presence_id);
+        }
+
+        ws_desc_conn
+            .broadcast
+            .leave_room(&ws_desc_conn.room_id, &ws_desc_conn.room);
+
+        NodecosmosError::InternalServerError(format!("Failed to start websocket connection: {}", e))
+    })?;
 
     ws_desc_conn
         .broadcast
         .connections
-        .entry(format!("{}{}", params.branch_id, params.room_id))
+        .entry(room_id)
         .or_default()
         .push(addr);
 
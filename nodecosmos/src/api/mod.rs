@@ -1,6 +1,7 @@
 pub use attachment_api::*;
 pub use branch_api::*;
 pub use comment_api::*;
+pub use consistency_api::*;
 pub use contact_api::*;
 pub use contribution_request_api::*;
 pub use description_api::*;
@@ -11,6 +12,7 @@ pub use invitation_api::*;
 pub use like_api::*;
 pub use node_api::*;
 pub use notification_api::*;
+pub use recovery_api::*;
 pub use request::*;
 pub use subscription_api::*;
 pub use task_api::*;
@@ -22,6 +24,7 @@ pub mod types;
 mod attachment_api;
 mod branch_api;
 mod comment_api;
+mod consistency_api;
 mod contact_api;
 mod contribution_request_api;
 mod description_api;
@@ -32,6 +35,7 @@ mod invitation_api;
 mod like_api;
 mod node_api;
 mod notification_api;
+mod recovery_api;
 pub mod request;
 mod subscription_api;
 mod task_api;
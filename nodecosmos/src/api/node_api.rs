@@ -1,7 +1,7 @@
 use actix_multipart::Multipart;
-use actix_web::{delete, get, post, put, web, HttpResponse};
+use actix_web::{delete, get, post, put, web, HttpRequest, HttpResponse};
 use charybdis::model::AsNative;
-use charybdis::operations::{DeleteWithCallbacks, InsertWithCallbacks, UpdateWithCallbacks};
+use charybdis::operations::{DeleteWithCallbacks, Insert, InsertWithCallbacks, UpdateWithCallbacks};
 use charybdis::types::Uuid;
 use futures::StreamExt;
 use scylla::client::caching_session::CachingSession;
@@ -14,16 +14,23 @@ use crate::api::request::data::RequestData;
 use crate::api::types::{ActionObject, ActionTypes, Response};
 use crate::app::App;
 use crate::errors::NodecosmosError;
+use crate::models::branch_protection::BranchProtection;
 use crate::models::invitation::Invitation;
+use crate::models::node::clone::CloneParams;
+use crate::models::node::export::Export;
 use crate::models::node::import::Import;
 use crate::models::node::reorder::ReorderParams;
+use crate::models::node::root_move::RootMoveParams;
 use crate::models::node::search::{NodeSearch, NodeSearchQuery};
 use crate::models::node::*;
 use crate::models::subscription::{Subscription, SubscriptionStatus};
 use crate::models::traits::Authorization;
 use crate::models::traits::Descendants;
+use crate::models::traits::{FindBranchedOrOriginalNode, NodeBranchParams};
 use crate::models::user::ShowUser;
+use crate::resources::presence::{Presence, PresenceGuard, PresenceTracker};
 use crate::resources::resource_locker::ResourceLocker;
+use crate::resources::sse_broadcast::{EventFilter, EventId};
 
 #[get("")]
 pub async fn get_nodes(app: web::Data<App>, query: web::Query<NodeSearchQuery>, opt_cu: OptCurrentUser) -> Response {
@@ -273,6 +280,101 @@ pub async fn reorder_nodes(params: web::Json<ReorderParams>, data: RequestData)
     }
 }
 
+#[put("/move_to_root")]
+pub async fn move_node_to_root(params: web::Json<RootMoveParams>, data: RequestData) -> Response {
+    let params = params.into_inner();
+    let root_ids = [params.root_id, params.new_root_id];
+
+    // node must be editable within both roots
+    AuthNode::auth_update(&data, params.root_id, params.id, params.root_id).await?;
+    AuthNode::auth_update(&data, params.new_root_id, params.new_parent_id, params.new_root_id).await?;
+
+    // lock both trees to avoid all kinds of race conditions
+    let mut locked_root_ids = vec![];
+
+    for root_id in root_ids {
+        let mut lock_res = data
+            .resource_locker()
+            .lock_resource(root_id, root_id, ResourceLocker::ONE_HOUR)
+            .await;
+
+        if lock_res.is_ok() {
+            locked_root_ids.push(root_id);
+
+            // validate that reorder is allowed
+            lock_res = data
+                .resource_locker()
+                .validate_resource_action_unlocked(ActionTypes::Reorder(ActionObject::Node), root_id, root_id, true)
+                .await;
+        }
+
+        if let Err(e) = lock_res {
+            for locked_root_id in locked_root_ids {
+                data.resource_locker()
+                    .unlock_resource(locked_root_id, locked_root_id)
+                    .await?;
+            }
+
+            return Err(e);
+        }
+    }
+
+    let res = Node::move_to_root(&data, params).await;
+
+    match &res {
+        // in case of fatal errors, resources stay locked until recovery is done
+        Ok(_) | Err(NodecosmosError::BadRequest(_)) | Err(NodecosmosError::Forbidden(_)) => {
+            for root_id in root_ids {
+                data.resource_locker().unlock_resource(root_id, root_id).await?;
+            }
+        }
+        _ => {}
+    }
+
+    res?;
+
+    Ok(HttpResponse::Ok().finish())
+}
+
+#[post("/clone")]
+pub async fn clone_node(params: web::Json<CloneParams>, data: RequestData) -> Response {
+    let params = params.into_inner();
+    let opt_cu = OptCurrentUser(Some(data.current_user.clone()));
+
+    AuthNode::auth_view(data.db_session(), &opt_cu, params.branch_id, params.id, params.root_id).await?;
+    AuthNode::auth_update(&data, params.new_branch_id, params.new_parent_id, params.new_root_id).await?;
+
+    let new_root_id = params.new_root_id;
+    let new_branch_id = params.new_branch_id;
+
+    // prevent reorder & merge of the target tree while the subtree is being cloned
+    data.resource_locker()
+        .lock_resource_actions(
+            new_root_id,
+            new_branch_id,
+            &[ActionTypes::Reorder(ActionObject::Node), ActionTypes::Merge],
+            ResourceLocker::ONE_HOUR,
+        )
+        .await?;
+
+    let res = Node::clone_subtree(&data, params).await;
+
+    // keep the lock in place for recovery if undoing the clone failed
+    if !matches!(res, Err(NodecosmosError::FatalCloneError(_))) {
+        data.resource_locker()
+            .unlock_resource_actions(
+                new_root_id,
+                new_branch_id,
+                &[ActionTypes::Reorder(ActionObject::Node), ActionTypes::Merge],
+            )
+            .await?;
+    }
+
+    let node = res?;
+
+    Ok(HttpResponse::Ok().json(node))
+}
+
 #[post("/{branchId}/{id}/{rootId}/upload_cover_image")]
 async fn upload_cover_image(
     mut node: web::Path<UpdateCoverImageNode>,
@@ -297,15 +399,76 @@ async fn delete_cover_image(mut node: web::Path<UpdateCoverImageNode>, data: Req
     Ok(HttpResponse::Ok().finish())
 }
 
+/// Streams model events of the root. Clients reconnecting with `Last-Event-ID` get the missed events replayed.
+/// Events can be filtered by branch and object types, e.g. `?branchId=<id>&objectTypes=FLOW,FLOW_STEP`.
 #[get("/{root_id}/events/listen")]
-pub async fn listen_node_events(root_id: web::Path<Uuid>, data: RequestData) -> Response {
+pub async fn listen_node_events(
+    req: HttpRequest,
+    root_id: web::Path<Uuid>,
+    filter: web::Query<EventFilter>,
+    data: RequestData,
+) -> Response {
     let root_id = *root_id;
+    let filter = filter.into_inner();
+    let opt_cu = OptCurrentUser(Some(data.current_user.clone()));
+
+    AuthNode::auth_view(data.db_session(), &opt_cu, root_id, root_id, root_id).await?;
+
+    if let Some(branch_id) = filter.branch_id.filter(|branch_id| *branch_id != root_id) {
+        AuthNode::auth_view(data.db_session(), &opt_cu, branch_id, root_id, root_id).await?;
+    }
+
     let broadcaster = data.sse_broadcast();
+
+    // receiver is built before replay, so no event is missed in between
     let receiver = broadcaster.build_receiver(root_id);
-    let stream = BroadcastStream::new(receiver).map(|msg| match msg {
-        Ok(data) => Ok::<_, actix_web::Error>(data),
-        Err(_) => Err(NodecosmosError::InternalServerError("Failed to send event".to_string()).into()),
-    });
+
+    let last_event_id = req.headers().get("Last-Event-ID").and_then(|id| id.to_str().ok());
+    let (replayed, last_replayed_id) = match last_event_id {
+        Some(last_event_id) => broadcaster.replay(&data, root_id, last_event_id).await?,
+        None => (vec![], None),
+    };
+
+    // listener is viewing the tree until the stream is dropped
+    let session_id = data
+        .presence()
+        .join(&data, Presence::viewing(&data.current_user, root_id))
+        .await?;
+    let presence_guard = PresenceGuard {
+        data: data.clone(),
+        session_id,
+    };
+
+    let replayed = replayed
+        .into_iter()
+        .filter(|msg| filter.matches(msg))
+        .map(|msg| Ok::<_, actix_web::Error>(web::Bytes::from(msg.data)))
+        .collect::<Vec<_>>();
+    let live = BroadcastStream::new(receiver)
+        .filter(move |msg| {
+            let is_sent = match msg {
+                Ok(msg) => {
+                    let is_replayed = matches!(
+                        (msg.id.as_deref().and_then(EventId::parse), last_replayed_id),
+                        (Some(id), Some(last_replayed_id)) if id <= last_replayed_id
+                    );
+
+                    !is_replayed && filter.matches(msg)
+                }
+                Err(_) => true,
+            };
+
+            futures::future::ready(is_sent)
+        })
+        .map(move |msg| {
+            let _ = &presence_guard;
+
+            match msg {
+                Ok(msg) => Ok::<_, actix_web::Error>(web::Bytes::from(msg.data.clone())),
+                Err(_) => Err(NodecosmosError::InternalServerError("Failed to send event".to_string()).into()),
+            }
+        });
+    let stream = futures::stream::iter(replayed).chain(live);
 
     Ok(HttpResponse::Ok()
         .content_type("text/event-stream")
@@ -314,6 +477,19 @@ pub async fn listen_node_events(root_id: web::Path<Uuid>, data: RequestData) ->
         .streaming(stream))
 }
 
+/// Lists users currently viewing the tree and editing its descriptions.
+#[get("/{root_id}/presence")]
+pub async fn get_presence(data: RequestData, root_id: web::Path<Uuid>) -> Response {
+    let root_id = *root_id;
+    let opt_cu = OptCurrentUser(Some(data.current_user.clone()));
+
+    AuthNode::auth_view(data.db_session(), &opt_cu, root_id, root_id, root_id).await?;
+
+    let presences = PresenceTracker::list(&data, root_id).await?;
+
+    Ok(HttpResponse::Ok().json(presences))
+}
+
 #[get("/{branchId}/{id}/editors")]
 pub async fn get_node_editors(db_session: web::Data<CachingSession>, pk: web::Path<PrimaryKeyNode>) -> Response {
     let node = AuthNode::find_by_branch_id_and_id(pk.branch_id, pk.id)
@@ -325,6 +501,43 @@ pub async fn get_node_editors(db_session: web::Data<CachingSession>, pk: web::Pa
     Ok(HttpResponse::Ok().json(users))
 }
 
+#[get("/{root_id}/protection")]
+pub async fn get_branch_protection(
+    db_session: web::Data<CachingSession>,
+    opt_cu: OptCurrentUser,
+    root_id: web::Path<Uuid>,
+) -> Response {
+    let root_id = root_id.into_inner();
+
+    AuthNode::auth_view(&db_session, &opt_cu, root_id, root_id, root_id).await?;
+
+    let protection = BranchProtection::find_or_default(&db_session, root_id).await?;
+
+    Ok(HttpResponse::Ok().json(protection))
+}
+
+#[put("/protection")]
+pub async fn update_branch_protection(data: RequestData, protection: web::Json<BranchProtection>) -> Response {
+    let mut protection = protection.into_inner();
+    let mut root = AuthNode::find_by_branch_id_and_id(protection.root_id, protection.root_id)
+        .execute(data.db_session())
+        .await?;
+
+    root.auth_update(&data).await?;
+
+    // maintainers should not be able to lift the protection
+    if root.owner_id != data.current_user.id {
+        return Err(NodecosmosError::Forbidden(
+            "Only the owner can change protection of the node!".to_string(),
+        ));
+    }
+
+    protection.updated_at = chrono::Utc::now();
+    protection.insert().execute(data.db_session()).await?;
+
+    Ok(HttpResponse::Ok().json(protection))
+}
+
 #[delete("/{branchId}/{id}/editors/{editorId}")]
 pub async fn delete_node_editor(
     db_session: web::Data<CachingSession>,
@@ -384,3 +597,30 @@ pub async fn import_nodes(data: RequestData, json_file: Multipart, params: web::
 
     Ok(HttpResponse::Ok().finish())
 }
+
+#[get("/{branchId}/{id}/{rootId}/export_nodes")]
+pub async fn export_nodes(
+    db_session: web::Data<CachingSession>,
+    opt_cu: OptCurrentUser,
+    params: web::Path<(Uuid, Uuid, Uuid)>,
+) -> Response {
+    let (branch_id, id, root_id) = params.into_inner();
+
+    AuthNode::auth_view(&db_session, &opt_cu, branch_id, id, root_id).await?;
+
+    let node = Node::find_branched_or_original(
+        &db_session,
+        NodeBranchParams {
+            root_id,
+            branch_id,
+            node_id: id,
+        },
+    )
+    .await?;
+
+    let export_nodes = Export::new(&db_session, &node).await?.run().await?;
+
+    Ok(HttpResponse::Ok()
+        .insert_header(("Content-Disposition", format!("attachment; filename=\"{}.json\"", id)))
+        .json(export_nodes))
+}
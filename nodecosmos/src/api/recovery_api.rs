@@ -0,0 +1,153 @@
+use actix_web::{get, put, web, HttpResponse};
+use base64::engine::general_purpose::URL_SAFE;
+use base64::Engine;
+use charybdis::operations::Find;
+use charybdis::scylla::{PagingState, PagingStateResponse};
+use charybdis::types::Uuid;
+use serde::Deserialize;
+use serde_json::json;
+
+use crate::api::data::RequestData;
+use crate::api::types::{ActionTypes, Response};
+use crate::errors::NodecosmosError;
+use crate::models::recovery::Recovery;
+use crate::resources::resource_locker::ResourceLocker;
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RecoveryPk {
+    pub branch_id: Uuid,
+    pub object_type: i8,
+    pub id: Uuid,
+}
+
+#[derive(Deserialize)]
+pub struct AbandonRecoveryPayload {
+    pub reason: String,
+}
+
+fn recovery_json(recovery: &Recovery) -> serde_json::Value {
+    json!({
+        "branchId": recovery.branch_id,
+        "objectType": recovery.object_type_name(),
+        "objectTypeId": recovery.object_type,
+        "id": recovery.id,
+        "step": recovery.step,
+        "stepName": recovery.step_name(),
+        "status": recovery.status,
+        "attempts": recovery.attempts.unwrap_or_default(),
+        "lastError": recovery.last_error,
+        "abandonReason": recovery.abandon_reason,
+        "updatedAt": recovery.updated_at,
+        "payload": recovery.payload_summary(),
+    })
+}
+
+async fn find_recovery(data: &RequestData, pk: RecoveryPk) -> Result<Recovery, NodecosmosError> {
+    Recovery {
+        branch_id: pk.branch_id,
+        object_type: pk.object_type,
+        id: pk.id,
+        ..Default::default()
+    }
+    .maybe_find_by_primary_key()
+    .execute(data.db_session())
+    .await?
+    .ok_or_else(|| NodecosmosError::NotFound(format!("Recovery entry {} not found", pk.id)))
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PagedQuery {
+    pub paging_state: Option<String>,
+}
+
+#[get("/")]
+pub async fn get_recoveries(data: RequestData, q: web::Query<PagedQuery>) -> Response {
+    data.validate_admin()?;
+
+    let paging_state = match q.paging_state.as_ref() {
+        Some(paging_state) => PagingState::new_from_raw_bytes(URL_SAFE.decode(paging_state)?),
+        None => PagingState::start(),
+    };
+
+    let (recoveries, paging_state_response) = Recovery::find_entries_page(data.db_session(), paging_state).await?;
+    let recoveries: Vec<serde_json::Value> = recoveries.iter().map(recovery_json).collect();
+    let paging_state = match paging_state_response {
+        PagingStateResponse::HasMorePages { state } => state.as_bytes_slice().map(|b| URL_SAFE.encode(b)),
+        PagingStateResponse::NoMorePages => None,
+    };
+
+    Ok(HttpResponse::Ok().json(json!({
+        "recoveries": recoveries,
+        "pagingState": paging_state,
+    })))
+}
+
+/// Puts the entry back to the recovery task with a fresh attempt count.
+#[put("/{branchId}/{objectType}/{id}/retry")]
+pub async fn retry_recovery(data: RequestData, pk: web::Path<RecoveryPk>) -> Response {
+    data.validate_admin()?;
+
+    let mut recovery = find_recovery(&data, pk.into_inner()).await?;
+
+    recovery.retry(data.db_session()).await?;
+
+    Ok(HttpResponse::Ok().json(recovery_json(&recovery)))
+}
+
+/// Undoes the saga back to `BeforeStart` right away instead of waiting for the recovery task.
+#[put("/{branchId}/{objectType}/{id}/rollback")]
+pub async fn rollback_recovery(data: RequestData, pk: web::Path<RecoveryPk>) -> Response {
+    data.validate_admin()?;
+
+    let mut recovery = find_recovery(&data, pk.into_inner()).await?;
+
+    data.resource_locker()
+        .validate_resource_action_unlocked(ActionTypes::Recover, recovery.id, recovery.branch_id, true)
+        .await?;
+
+    data.resource_locker()
+        .lock_resource_actions(
+            recovery.id,
+            recovery.branch_id,
+            &[ActionTypes::Recover],
+            ResourceLocker::FIVE_MINUTES,
+        )
+        .await?;
+
+    let res = recovery.recover(&data).await;
+
+    data.resource_locker()
+        .unlock_resource_actions(recovery.id, recovery.branch_id, &[ActionTypes::Recover])
+        .await?;
+
+    if let Err(e) = res {
+        recovery.record_failure(data.db_session(), e.to_string()).await?;
+
+        return Err(e);
+    }
+
+    Ok(HttpResponse::Ok().finish())
+}
+
+#[put("/{branchId}/{objectType}/{id}/abandon")]
+pub async fn abandon_recovery(
+    data: RequestData,
+    pk: web::Path<RecoveryPk>,
+    payload: web::Json<AbandonRecoveryPayload>,
+) -> Response {
+    data.validate_admin()?;
+
+    let reason = payload.into_inner().reason;
+
+    if reason.trim().is_empty() {
+        return Err(NodecosmosError::BadRequest("Reason is required".to_string()));
+    }
+
+    let mut recovery = find_recovery(&data, pk.into_inner()).await?;
+
+    recovery.abandon(data.db_session(), reason).await?;
+
+    Ok(HttpResponse::Ok().json(recovery_json(&recovery)))
+}
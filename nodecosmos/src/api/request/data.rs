@@ -5,6 +5,7 @@ use crate::api::current_user::get_current_user;
 use crate::app::{App, StripeCfg};
 use crate::errors::NodecosmosError;
 use crate::models::user::CurrentUser;
+use crate::resources::presence::PresenceTracker;
 use crate::resources::resource_locker::ResourceLocker;
 use crate::resources::sse_broadcast::SseBroadcast;
 use crate::resources::ws_broadcast::WsBroadcast;
@@ -46,6 +47,14 @@ impl RequestData {
         &self.app.mailer
     }
 
+    pub fn validate_admin(&self) -> Result<(), NodecosmosError> {
+        if !self.app.config.admin_ids.contains(&self.current_user.id) {
+            return Err(NodecosmosError::Forbidden("Admin access required".to_string()));
+        }
+
+        Ok(())
+    }
+
     pub fn resource_locker(&self) -> &ResourceLocker {
         &self.app.resource_locker
     }
@@ -58,6 +67,10 @@ impl RequestData {
         self.app.sse_broadcast.clone()
     }
 
+    pub fn presence(&self) -> Arc<PresenceTracker> {
+        self.app.presence.clone()
+    }
+
     pub async fn redis_connection(
         &self,
     ) -> Result<deadpool::managed::Object<crate::resources::resource::RedisClusterManager>, NodecosmosError> {
@@ -1,20 +1,45 @@
 use crate::api::current_user::OptCurrentUser;
 use crate::api::data::RequestData;
-use crate::api::types::Response;
+use crate::api::types::{ActionObject, ActionTypes, Response};
 use crate::models::description::Description;
-use crate::models::node::AuthNode;
+use crate::models::node::{AuthNode, PrimaryKeyNode};
 use crate::models::task::{Task, UpdateAssigneesTask, UpdateDueAtTask, UpdatePositionTask, UpdateTitleTask};
 use crate::models::task_section::{TaskSection, UpdateOrderIndexTaskSection, UpdateTitleTaskSection};
+use crate::resources::sse_broadcast::ModelEvent;
 use actix_web::{delete, get, post, put, web, HttpResponse};
 use charybdis::operations::{DeleteWithCallbacks, InsertWithCallbacks, UpdateWithCallbacks};
 use charybdis::types::Uuid;
+use serde::Serialize;
 use serde_json::json;
 
+/// Task callbacks don't get request data, so task events are sent from here. Tasks don't hold root id, so the root
+/// channel of the event is resolved from the node.
+async fn emit_task_event<M: Serialize>(
+    data: &RequestData,
+    branch_id: Uuid,
+    node_id: Uuid,
+    action_type: ActionTypes,
+    model: &M,
+) {
+    let node = PrimaryKeyNode::find_by_branch_id_and_id(branch_id, node_id)
+        .execute(data.db_session())
+        .await;
+
+    match node {
+        Ok(node) => {
+            ModelEvent::new(node.root_id, branch_id, action_type, model)
+                .emit(data)
+                .await
+        }
+        Err(e) => log::error!("Error finding root of task node {}: {}", node_id, e),
+    }
+}
+
 #[post("/sections")]
 pub async fn create_task_section(data: RequestData, task_section: web::Json<TaskSection>) -> Response {
     let mut task_section = task_section.into_inner();
 
-    AuthNode::auth_update(
+    AuthNode::auth_edit_access(
         &data,
         task_section.branch_id,
         task_section.node_id,
@@ -24,6 +49,15 @@ pub async fn create_task_section(data: RequestData, task_section: web::Json<Task
 
     task_section.insert_cb(&None).execute(data.db_session()).await?;
 
+    emit_task_event(
+        &data,
+        task_section.branch_id,
+        task_section.node_id,
+        ActionTypes::Create(ActionObject::TaskSection),
+        &task_section,
+    )
+    .await;
+
     Ok(HttpResponse::Created().json(task_section))
 }
 
@@ -34,7 +68,7 @@ pub async fn update_section_order_index(
 ) -> Response {
     let mut task_section = task_section.into_inner();
 
-    AuthNode::auth_update(
+    AuthNode::auth_edit_access(
         &data,
         task_section.branch_id,
         task_section.node_id,
@@ -44,6 +78,15 @@ pub async fn update_section_order_index(
 
     task_section.update_cb(&None).execute(data.db_session()).await?;
 
+    emit_task_event(
+        &data,
+        task_section.branch_id,
+        task_section.node_id,
+        ActionTypes::Update(ActionObject::TaskSection),
+        &task_section,
+    )
+    .await;
+
     Ok(HttpResponse::Ok().json(task_section))
 }
 
@@ -51,7 +94,7 @@ pub async fn update_section_order_index(
 pub async fn update_section_title(data: RequestData, task_section: web::Json<UpdateTitleTaskSection>) -> Response {
     let mut task_section = task_section.into_inner();
 
-    AuthNode::auth_update(
+    AuthNode::auth_edit_access(
         &data,
         task_section.branch_id,
         task_section.node_id,
@@ -61,6 +104,15 @@ pub async fn update_section_title(data: RequestData, task_section: web::Json<Upd
 
     task_section.update_cb(&None).execute(data.db_session()).await?;
 
+    emit_task_event(
+        &data,
+        task_section.branch_id,
+        task_section.node_id,
+        ActionTypes::Update(ActionObject::TaskSection),
+        &task_section,
+    )
+    .await;
+
     Ok(HttpResponse::Ok().json(task_section))
 }
 
@@ -68,12 +120,21 @@ pub async fn update_section_title(data: RequestData, task_section: web::Json<Upd
 pub async fn delete_task_section(data: RequestData, task_section: web::Path<(Uuid, Uuid, Uuid)>) -> Response {
     let (branch_id, node_id, task_section_id) = task_section.into_inner();
 
-    AuthNode::auth_update(&data, branch_id, node_id, branch_id).await?;
+    AuthNode::auth_edit_access(&data, branch_id, node_id, branch_id).await?;
 
     TaskSection::delete_by_branch_id_and_node_id_and_id(branch_id, node_id, task_section_id)
         .execute(data.db_session())
         .await?;
 
+    emit_task_event(
+        &data,
+        branch_id,
+        node_id,
+        ActionTypes::Delete(ActionObject::TaskSection),
+        &json!({ "branchId": branch_id, "nodeId": node_id, "id": task_section_id }),
+    )
+    .await;
+
     Ok(HttpResponse::NoContent().finish())
 }
 
@@ -112,10 +173,19 @@ pub async fn get_node_tasks(data: RequestData, path: web::Path<(Uuid, Uuid)>) ->
 pub async fn create_task(data: RequestData, task: web::Json<Task>) -> Response {
     let mut task = task.into_inner();
 
-    AuthNode::auth_update(&data, task.branch_id, task.node_id, task.branch_id).await?;
+    AuthNode::auth_edit_access(&data, task.branch_id, task.node_id, task.branch_id).await?;
 
     task.insert_cb(&data).execute(data.db_session()).await?;
 
+    emit_task_event(
+        &data,
+        task.branch_id,
+        task.node_id,
+        ActionTypes::Create(ActionObject::Task),
+        &task,
+    )
+    .await;
+
     Ok(HttpResponse::Created().json(task))
 }
 
@@ -150,10 +220,19 @@ pub async fn get_task(data: RequestData, path: web::Path<(Uuid, Uuid, Uuid)>) ->
 pub async fn update_task_title(data: RequestData, task: web::Json<UpdateTitleTask>) -> Response {
     let mut task = task.into_inner();
 
-    AuthNode::auth_update(&data, task.branch_id, task.node_id, task.branch_id).await?;
+    AuthNode::auth_edit_access(&data, task.branch_id, task.node_id, task.branch_id).await?;
 
     task.update_cb(&None).execute(data.db_session()).await?;
 
+    emit_task_event(
+        &data,
+        task.branch_id,
+        task.node_id,
+        ActionTypes::Update(ActionObject::Task),
+        &task,
+    )
+    .await;
+
     Ok(HttpResponse::Ok().json(task))
 }
 
@@ -161,10 +240,19 @@ pub async fn update_task_title(data: RequestData, task: web::Json<UpdateTitleTas
 pub async fn update_assignees(data: RequestData, task: web::Json<UpdateAssigneesTask>) -> Response {
     let mut task = task.into_inner();
 
-    AuthNode::auth_update(&data, task.branch_id, task.node_id, task.branch_id).await?;
+    AuthNode::auth_edit_access(&data, task.branch_id, task.node_id, task.branch_id).await?;
 
     task.update_cb(&data).execute(data.db_session()).await?;
 
+    emit_task_event(
+        &data,
+        task.branch_id,
+        task.node_id,
+        ActionTypes::Update(ActionObject::Task),
+        &task,
+    )
+    .await;
+
     Ok(HttpResponse::Ok().json(task))
 }
 
@@ -172,10 +260,19 @@ pub async fn update_assignees(data: RequestData, task: web::Json<UpdateAssignees
 pub async fn update_task_position(data: RequestData, task: web::Json<UpdatePositionTask>) -> Response {
     let mut task = task.into_inner();
 
-    AuthNode::auth_update(&data, task.branch_id, task.node_id, task.branch_id).await?;
+    AuthNode::auth_edit_access(&data, task.branch_id, task.node_id, task.branch_id).await?;
 
     task.update_cb(&None).execute(data.db_session()).await?;
 
+    emit_task_event(
+        &data,
+        task.branch_id,
+        task.node_id,
+        ActionTypes::Update(ActionObject::Task),
+        &task,
+    )
+    .await;
+
     Ok(HttpResponse::Ok().json(task))
 }
 
@@ -183,10 +280,19 @@ pub async fn update_task_position(data: RequestData, task: web::Json<UpdatePosit
 pub async fn update_task_due_at(data: RequestData, task: web::Json<UpdateDueAtTask>) -> Response {
     let mut task = task.into_inner();
 
-    AuthNode::auth_update(&data, task.branch_id, task.node_id, task.branch_id).await?;
+    AuthNode::auth_edit_access(&data, task.branch_id, task.node_id, task.branch_id).await?;
 
     task.update_cb(&None).execute(data.db_session()).await?;
 
+    emit_task_event(
+        &data,
+        task.branch_id,
+        task.node_id,
+        ActionTypes::Update(ActionObject::Task),
+        &task,
+    )
+    .await;
+
     Ok(HttpResponse::Ok().json(task))
 }
 
@@ -194,7 +300,7 @@ pub async fn update_task_due_at(data: RequestData, task: web::Json<UpdateDueAtTa
 pub async fn delete_task(data: RequestData, task: web::Path<(Uuid, Uuid, Uuid)>) -> Response {
     let (branch_id, node_id, task_id) = task.into_inner();
 
-    AuthNode::auth_update(&data, branch_id, node_id, branch_id).await?;
+    AuthNode::auth_edit_access(&data, branch_id, node_id, branch_id).await?;
 
     Task {
         branch_id,
@@ -206,5 +312,14 @@ pub async fn delete_task(data: RequestData, task: web::Path<(Uuid, Uuid, Uuid)>)
     .execute(data.db_session())
     .await?;
 
+    emit_task_event(
+        &data,
+        branch_id,
+        node_id,
+        ActionTypes::Delete(ActionObject::Task),
+        &json!({ "branchId": branch_id, "nodeId": node_id, "id": task_id }),
+    )
+    .await;
+
     Ok(HttpResponse::NoContent().finish())
 }
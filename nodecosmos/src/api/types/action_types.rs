@@ -11,6 +11,38 @@ pub enum ActionTypes {
     Reorder(ActionObject),
     Recover,
     Merge,
+    MergeQueue,
+    PresenceJoin,
+    PresenceLeave,
+}
+
+impl ActionTypes {
+    /// Action without the object, e.g. `CREATE`.
+    pub fn action(&self) -> &'static str {
+        match self {
+            ActionTypes::Create(_) => "CREATE",
+            ActionTypes::Read(_) => "READ",
+            ActionTypes::Update(_) => "UPDATE",
+            ActionTypes::Delete(_) => "DELETE",
+            ActionTypes::Reorder(_) => "REORDER",
+            ActionTypes::Recover => "RECOVER",
+            ActionTypes::Merge => "MERGE",
+            ActionTypes::MergeQueue => "MERGE_QUEUE",
+            ActionTypes::PresenceJoin => "PRESENCE_JOIN",
+            ActionTypes::PresenceLeave => "PRESENCE_LEAVE",
+        }
+    }
+
+    pub fn object(&self) -> Option<ActionObject> {
+        match self {
+            ActionTypes::Create(object)
+            | ActionTypes::Read(object)
+            | ActionTypes::Update(object)
+            | ActionTypes::Delete(object)
+            | ActionTypes::Reorder(object) => Some(*object),
+            _ => None,
+        }
+    }
 }
 
 impl Display for ActionTypes {
@@ -22,7 +54,10 @@ impl Display for ActionTypes {
             ActionTypes::Delete(action_object) => write!(f, "DELETE_{}", action_object),
             ActionTypes::Reorder(action_object) => write!(f, "REORDER_{}", action_object),
             ActionTypes::Merge => write!(f, "MERGE"),
+            ActionTypes::MergeQueue => write!(f, "MERGE_QUEUE"),
             ActionTypes::Recover => write!(f, "Recover"),
+            ActionTypes::PresenceJoin => write!(f, "PRESENCE_JOIN"),
+            ActionTypes::PresenceLeave => write!(f, "PRESENCE_LEAVE"),
         }
     }
 }
@@ -36,6 +71,9 @@ pub enum ActionObject {
     FlowStep,
     Io,
     Comment,
+    Task,
+    TaskSection,
+    ContributionRequest,
 }
 
 impl Display for ActionObject {
@@ -47,6 +85,9 @@ impl Display for ActionObject {
             ActionObject::FlowStep => write!(f, "FLOW_STEP"),
             ActionObject::Io => write!(f, "INPUT_OUTPUT"),
             ActionObject::Comment => write!(f, "COMMENT"),
+            ActionObject::Task => write!(f, "TASK"),
+            ActionObject::TaskSection => write!(f, "TASK_SECTION"),
+            ActionObject::ContributionRequest => write!(f, "CONTRIBUTION_REQUEST"),
         }
     }
 }
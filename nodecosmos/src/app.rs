@@ -3,6 +3,7 @@ use actix_session::config::PersistentSession;
 use actix_session::SessionMiddleware;
 use actix_web::cookie::Key;
 use actix_web::{http, web};
+use charybdis::types::Uuid;
 use elasticsearch::Elasticsearch;
 use scylla::client::caching_session::CachingSession;
 use serde::Deserialize;
@@ -17,6 +18,7 @@ use crate::models::traits::ElasticIndex;
 use crate::models::user::User;
 use crate::resources::email_client::TlsMode;
 use crate::resources::mailer::Mailer;
+use crate::resources::presence::PresenceTracker;
 use crate::resources::resource::{RedisClusterManager, Resource};
 use crate::resources::resource_locker::ResourceLocker;
 use crate::resources::sse_broadcast::SseBroadcast;
@@ -100,6 +102,10 @@ pub struct Config {
     pub cert: Option<String>,
     pub key: Option<String>,
     pub region: Option<String>,
+
+    /// Users that can access the admin API.
+    #[serde(default)]
+    pub admin_ids: Vec<Uuid>,
 }
 
 type RedisClients = Vec<redis::Client>;
@@ -118,6 +124,7 @@ pub struct App {
     pub resource_locker: Arc<ResourceLocker>,
     pub ws_broadcast: Arc<WsBroadcast>,
     pub sse_broadcast: Arc<SseBroadcast>,
+    pub presence: Arc<PresenceTracker>,
     pub redis_pool: RedisClusterManagerPool,
     pub redis_clients: RedisClients,
     pub mailer: Arc<Mailer>,
@@ -147,6 +154,7 @@ impl App {
         let resource_locker = ResourceLocker::init_resource((&redis_pool, config.redis.replicas)).await;
         let ws_broadcast = WsBroadcast::init_resource(()).await;
         let sse_broadcast = SseBroadcast::init_resource(()).await;
+        let presence = PresenceTracker::init_resource(()).await;
         let mailer = Mailer::init_resource(&config).await;
 
         let stripe_secret_key = env::var("STRIPE_SECRET_KEY").ok();
@@ -191,6 +199,7 @@ impl App {
             resource_locker: Arc::new(resource_locker),
             ws_broadcast: Arc::new(ws_broadcast),
             sse_broadcast: Arc::new(sse_broadcast),
+            presence: Arc::new(presence),
             mailer: Arc::new(mailer),
             secret_key,
         })
@@ -207,9 +216,13 @@ impl App {
             current_user: Default::default(),
         };
 
-        tasks::recovery_task(data).await;
+        tasks::recovery_task(data.clone()).await;
+        tasks::merge_queue_task(data.clone()).await;
         tasks::cleanup_rooms_task(self.sse_broadcast.clone()).await;
+        tasks::ws_heartbeat_task(data.clone()).await;
+        tasks::presence_task(data.clone()).await;
         tasks::listen_redis_events(self).await;
+        tasks::listen_ws_room_events(data).await;
     }
 
     pub fn cors(&self) -> Cors {
@@ -231,6 +244,7 @@ impl App {
                 http::header::X_FORWARDED_FOR,
                 http::header::X_FORWARDED_PROTO,
                 http::header::X_FORWARDED_HOST,
+                http::header::HeaderName::from_static("last-event-id"),
             ])
             .expose_headers(vec![http::header::LOCATION, http::header::ACCESS_CONTROL_ALLOW_ORIGIN])
             .max_age(86400)
@@ -0,0 +1,48 @@
+use actix_web::web;
+use charybdis::types::Uuid;
+
+use crate::api::data::RequestData;
+use crate::app::App;
+use crate::errors::NodecosmosError;
+use crate::models::consistency::TreeConsistency;
+
+const CHECK_TREE_USAGE: &str = "Usage: nodecosmos check-tree <root_id> [branch_id] [--repair]";
+
+/// Runs maintenance command given as program arguments instead of starting the server.
+pub async fn run(app: App, args: &[String]) {
+    let data = RequestData {
+        app: web::Data::new(app),
+        current_user: Default::default(),
+    };
+
+    let res = match args[0].as_str() {
+        "check-tree" => check_tree(&data, &args[1..]).await,
+        command => Err(NodecosmosError::BadRequest(format!("Unknown command: {}", command))),
+    };
+
+    if let Err(e) = res {
+        log::error!("{}", e);
+        std::process::exit(1);
+    }
+}
+
+/// Prints consistency report of the tree as JSON. Original tree is checked if branch is not given.
+async fn check_tree(data: &RequestData, args: &[String]) -> Result<(), NodecosmosError> {
+    let repair = args.iter().any(|arg| arg == "--repair");
+    let ids = args
+        .iter()
+        .filter(|arg| !arg.starts_with("--"))
+        .map(|arg| Uuid::parse_str(arg).map_err(|_| NodecosmosError::BadRequest(format!("Invalid id: {}", arg))))
+        .collect::<Result<Vec<Uuid>, NodecosmosError>>()?;
+
+    let root_id = *ids
+        .first()
+        .ok_or_else(|| NodecosmosError::BadRequest(CHECK_TREE_USAGE.to_string()))?;
+    let branch_id = ids.get(1).copied().unwrap_or(root_id);
+
+    let report = TreeConsistency::check(data, root_id, branch_id, repair).await?;
+
+    println!("{}", serde_json::to_string_pretty(&report)?);
+
+    Ok(())
+}
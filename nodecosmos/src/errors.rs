@@ -42,6 +42,12 @@ pub enum NodecosmosError {
     /// Reorder and recovery fails
     FatalReorderError(String),
 
+    /// Clone and recovery fails
+    FatalCloneError(String),
+
+    /// Move to another root and recovery fails
+    FatalRootMoveError(String),
+
     InternalServerError(String),
     QuickXmlError(quick_xml::Error),
     BroadcastError(String),
@@ -81,6 +87,8 @@ impl fmt::Display for NodecosmosError {
             NodecosmosError::FatalDeleteError(e) => write!(f, "Fatal Delete Error: {}", e),
             NodecosmosError::FatalMergeError(e) => write!(f, "Fatal Merge Error: {}", e),
             NodecosmosError::FatalReorderError(e) => write!(f, "Fatal Reorder Error: {}", e),
+            NodecosmosError::FatalCloneError(e) => write!(f, "Fatal Clone Error: {}", e),
+            NodecosmosError::FatalRootMoveError(e) => write!(f, "Fatal Root Move Error: {}", e),
             NodecosmosError::QuickXmlError(e) => write!(f, "QuickXmlError Error: {}", e),
             NodecosmosError::InternalServerError(e) => write!(f, "InternalServerError: {}", e),
             NodecosmosError::BroadcastError(e) => write!(f, "BroadcastError: {}", e),
@@ -12,6 +12,7 @@ use app::App;
 
 mod api;
 mod app;
+mod cli;
 mod constants;
 mod errors;
 mod models;
@@ -38,6 +39,13 @@ fn main() {
                 }
 
                 let app = app_res.unwrap();
+                let args: Vec<String> = std::env::args().skip(1).collect();
+
+                if !args.is_empty() {
+                    cli::run(app, &args).await;
+                    return;
+                }
+
                 let port = app.port();
 
                 app.init().await;
@@ -87,12 +95,18 @@ fn main() {
                                 .service(update_node_title)
                                 .service(delete_node)
                                 .service(reorder_nodes)
+                                .service(clone_node)
+                                .service(move_node_to_root)
                                 .service(upload_cover_image)
                                 .service(delete_cover_image)
                                 .service(get_node_editors)
                                 .service(delete_node_editor)
+                                .service(get_branch_protection)
+                                .service(update_branch_protection)
                                 .service(listen_node_events)
-                                .service(import_nodes),
+                                .service(get_presence)
+                                .service(import_nodes)
+                                .service(export_nodes),
                         )
                         .service(web::scope("/no-compress-nodes").service(listen_node_events))
                         .service(
@@ -138,9 +152,17 @@ fn main() {
                                 .service(create_contribution_request)
                                 .service(update_contribution_request_title)
                                 .service(update_contribution_request_description)
+                                .service(update_contribution_request_reviewers)
                                 .service(delete_contribution_request)
                                 .service(publish)
-                                .service(merge_contribution_request),
+                                .service(merge_contribution_request)
+                                .service(get_merge_queue)
+                                .service(enqueue_merge)
+                                .service(dequeue_merge)
+                                .service(revert_contribution_request)
+                                .service(create_review)
+                                .service(get_review_settings)
+                                .service(update_review_settings),
                         )
                         .service(
                             web::scope("attachments")
@@ -153,6 +175,10 @@ fn main() {
                                 .wrap(Compress::default())
                                 .service(show_branch)
                                 .service(get_branch_node_id)
+                                .service(get_node_commits)
+                                .service(get_merge_preview)
+                                .service(sync_branch_with_original)
+                                .service(resolve_title_conflict)
                                 .service(restore_node)
                                 .service(undo_delete_node)
                                 .service(restore_io)
@@ -181,7 +207,12 @@ fn main() {
                                 .service(get_description)
                                 .service(get_base64_description)
                                 .service(get_original_description)
-                                .service(save_description),
+                                .service(get_description_diff)
+                                .service(save_description)
+                                .service(get_description_versions)
+                                .service(get_description_version)
+                                .service(get_description_version_diff)
+                                .service(restore_description_version),
                         )
                         .service(web::scope("ws").service(description_ws))
                         .service(
@@ -200,6 +231,18 @@ fn main() {
                                 .service(get_notifications)
                                 .service(mark_all_as_read),
                         )
+                        .service(
+                            web::scope("recoveries")
+                                .service(get_recoveries)
+                                .service(retry_recovery)
+                                .service(rollback_recovery)
+                                .service(abandon_recovery),
+                        )
+                        .service(
+                            web::scope("consistency")
+                                .service(check_consistency)
+                                .service(repair_consistency),
+                        )
                         .service(web::scope("contacts").service(create_contact_us))
                         .service(
                             web::scope("subscriptions")
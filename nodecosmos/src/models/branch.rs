@@ -1,9 +1,9 @@
 use crate::errors::NodecosmosError;
 use crate::models::flow::Flow;
-use crate::models::flow_step::FlowStep;
+use crate::models::flow_step::{FlowStep, FlowStepChanges};
 use crate::models::io::Io;
 use crate::models::node::Node;
-use crate::models::traits::{Branchable, Id, ObjectType};
+use crate::models::traits::{Branchable, FindOriginalOrBranched, Id, ModelBranchParams, ObjectType};
 use crate::models::udts::{BranchReorderData, Conflict};
 use crate::models::udts::{Profile, TextChange};
 use crate::stream::MergedModelStream;
@@ -15,6 +15,9 @@ use serde::{Deserialize, Serialize};
 use std::collections::HashSet;
 
 pub mod merge;
+pub mod stack;
+pub mod sync;
+pub mod title_conflict;
 pub mod update;
 
 #[derive(Copy, Clone, strum_macros::Display, strum_macros::EnumString)]
@@ -58,6 +61,9 @@ pub struct Branch {
     pub viewer_ids: Option<Set<Uuid>>,
     pub is_public: Boolean,
     pub is_contribution_request: Option<Boolean>,
+    /// Branch this branch was created from. Reads fall through the parent before reaching the original
+    /// and merging goes into the parent while it's open.
+    pub parent_id: Option<Uuid>,
     // nodes
     pub created_nodes: Option<Set<Uuid>>,
     pub restored_nodes: Option<Set<Uuid>>,
@@ -124,6 +130,40 @@ impl Branch {
             .map_err(NodecosmosError::from)
     }
 
+    /// Id of the node that owns the branch object. Objects are looked up in the original first.
+    pub async fn object_node_id(
+        &self,
+        db_session: &CachingSession,
+        object_id: Uuid,
+        object_type: ObjectType,
+    ) -> Result<Uuid, NodecosmosError> {
+        let params = ModelBranchParams {
+            original_id: self.original_id(),
+            branch_id: self.id,
+            id: object_id,
+        };
+
+        match object_type {
+            ObjectType::Node | ObjectType::Workflow => Ok(object_id),
+            ObjectType::Flow => Ok(Flow::find_original_or_branched(db_session, params).await?.node_id),
+            ObjectType::FlowStep => Ok(FlowStep::find_original_or_branched(db_session, params).await?.node_id),
+            ObjectType::Io => {
+                let ids = HashSet::from([object_id]);
+                let mut ios =
+                    Io::find_by_branch_id_and_root_id_and_ids(db_session, self.original_id(), self.root_id, &ids)
+                        .await?;
+
+                if ios.is_empty() {
+                    ios = Io::find_by_branch_id_and_root_id_and_ids(db_session, self.id, self.root_id, &ids).await?;
+                }
+
+                ios.first()
+                    .map(|io| io.node_id)
+                    .ok_or_else(|| NodecosmosError::NotFound(format!("Io {} not found", object_id)))
+            }
+        }
+    }
+
     pub fn all_edited_description_ids(&self) -> HashSet<Uuid> {
         let mut edited_object_ids = HashSet::new();
 
@@ -178,6 +218,34 @@ impl Branch {
         }
     }
 
+    pub fn created_flow_step_changes(&self, flow_step_id: Uuid) -> FlowStepChanges<'_> {
+        FlowStepChanges {
+            node_ids: self.created_flow_step_nodes.as_ref().and_then(|m| m.get(&flow_step_id)),
+            input_ids_by_node_id: self
+                .created_flow_step_inputs_by_node
+                .as_ref()
+                .and_then(|m| m.get(&flow_step_id)),
+            output_ids_by_node_id: self
+                .created_flow_step_outputs_by_node
+                .as_ref()
+                .and_then(|m| m.get(&flow_step_id)),
+        }
+    }
+
+    pub fn deleted_flow_step_changes(&self, flow_step_id: Uuid) -> FlowStepChanges<'_> {
+        FlowStepChanges {
+            node_ids: self.deleted_flow_step_nodes.as_ref().and_then(|m| m.get(&flow_step_id)),
+            input_ids_by_node_id: self
+                .deleted_flow_step_inputs_by_node
+                .as_ref()
+                .and_then(|m| m.get(&flow_step_id)),
+            output_ids_by_node_id: self
+                .deleted_flow_step_outputs_by_node
+                .as_ref()
+                .and_then(|m| m.get(&flow_step_id)),
+        }
+    }
+
     fn deleted_ids(&self, object_type: ObjectType) -> &Option<Set<Uuid>> {
         match object_type {
             ObjectType::Node => &self.deleted_nodes,
@@ -314,6 +382,20 @@ partial_branch!(GetNodeIdBranch, id, node_id);
 
 partial_branch!(AuthBranch, id, owner_id, editor_ids, viewer_ids, is_public, status);
 
+partial_branch!(ParentIdBranch, id, parent_id);
+
+partial_branch!(
+    ParentBranch,
+    id,
+    parent_id,
+    status,
+    deleted_nodes,
+    deleted_flows,
+    deleted_flow_steps,
+    deleted_ios,
+    deleted_initial_inputs
+);
+
 partial_branch!(UpdateCreatedNodesBranch, id, created_nodes);
 
 partial_branch!(UpdateDeletedNodesBranch, id, deleted_nodes);
@@ -383,3 +465,5 @@ partial_branch!(UpdateRestoredIosBranch, id, restored_ios);
 partial_branch!(UpdateEditedTitleIosBranch, id, edited_title_ios);
 
 partial_branch!(UpdateEditedDescriptionIosBranch, id, edited_description_ios);
+
+partial_branch!(UpdateTitleChangeBranch, id, title_change_by_object);
@@ -13,8 +13,12 @@ use crate::models::branch::merge::flow_steps::MergeFlowSteps;
 use crate::models::branch::merge::flows::MergeFlows;
 use crate::models::branch::merge::ios::MergeIos;
 use crate::models::branch::merge::nodes::MergeNodes;
+use crate::models::branch::merge::preview::MergePreview;
+use crate::models::branch::merge::selection::MergeSelection;
 use crate::models::branch::{Branch, BranchStatus};
 use crate::models::recovery::{RecoveryLog, RecoveryObjectType};
+use crate::models::saga::{saga_steps, Saga, SagaStep};
+use crate::models::udts::Conflict;
 
 mod conflicts;
 mod descriptions;
@@ -22,6 +26,8 @@ mod flow_steps;
 mod flows;
 mod ios;
 mod nodes;
+pub mod preview;
+pub mod selection;
 
 #[derive(Debug)]
 pub struct MergeError {
@@ -29,77 +35,32 @@ pub struct MergeError {
     pub branch: Branch,
 }
 
-#[derive(Clone, Copy, Serialize, Deserialize, PartialOrd, PartialEq, Debug)]
-pub enum MergeStep {
-    BeforeStart = -1,
-    Start = 0,
-    RestoreNodes = 1,
-    CreateNodes = 2,
-    DeleteNodes = 3,
-    UpdateNodesTitles = 4,
-    ReorderNodes = 5,
-    RestoreFlows = 6,
-    CreateFlows = 7,
-    DeleteFlows = 8,
-    UpdateFlowsTitles = 9,
-    DeleteFlowSteps = 10,
-    RestoreFlowSteps = 11,
-    CreateFlowSteps = 12,
-    CreateFlowStepNodes = 13,
-    DeleteFlowStepNodes = 14,
-    CreateFlowStepInputs = 15,
-    DeleteFlowStepInputs = 16,
-    RestoreIos = 17,
-    CreateIos = 18,
-    DeleteIos = 19,
-    UpdateIoTitles = 20,
-    UpdateDescriptions = 21,
-    DeleteDescriptions = 22,
-    Finish = 23,
-    AfterFinish = 24,
-}
-
-impl MergeStep {
-    pub fn increment(&mut self) {
-        *self = MergeStep::from(*self as i8 + 1);
-    }
-
-    pub fn decrement(&mut self) {
-        *self = MergeStep::from(*self as i8 - 1);
-    }
-}
-
-impl From<i8> for MergeStep {
-    fn from(value: i8) -> Self {
-        match value {
-            -1 => MergeStep::BeforeStart,
-            0 => MergeStep::Start,
-            1 => MergeStep::RestoreNodes,
-            2 => MergeStep::CreateNodes,
-            3 => MergeStep::DeleteNodes,
-            4 => MergeStep::UpdateNodesTitles,
-            5 => MergeStep::ReorderNodes,
-            6 => MergeStep::RestoreFlows,
-            7 => MergeStep::CreateFlows,
-            8 => MergeStep::DeleteFlows,
-            9 => MergeStep::UpdateFlowsTitles,
-            10 => MergeStep::DeleteFlowSteps,
-            11 => MergeStep::RestoreFlowSteps,
-            12 => MergeStep::CreateFlowSteps,
-            13 => MergeStep::CreateFlowStepNodes,
-            14 => MergeStep::DeleteFlowStepNodes,
-            15 => MergeStep::CreateFlowStepInputs,
-            16 => MergeStep::DeleteFlowStepInputs,
-            17 => MergeStep::RestoreIos,
-            18 => MergeStep::CreateIos,
-            19 => MergeStep::DeleteIos,
-            20 => MergeStep::UpdateIoTitles,
-            21 => MergeStep::UpdateDescriptions,
-            22 => MergeStep::DeleteDescriptions,
-            23 => MergeStep::Finish,
-            24 => MergeStep::AfterFinish,
-            _ => panic!("Invalid merge step value: {}", value),
-        }
+saga_steps! {
+    pub enum MergeStep {
+        RestoreNodes = 1,
+        CreateNodes = 2,
+        DeleteNodes = 3,
+        UpdateNodesTitles = 4,
+        ReorderNodes = 5,
+        RestoreFlows = 6,
+        CreateFlows = 7,
+        DeleteFlows = 8,
+        UpdateFlowsTitles = 9,
+        DeleteFlowSteps = 10,
+        RestoreFlowSteps = 11,
+        CreateFlowSteps = 12,
+        CreateFlowStepNodes = 13,
+        DeleteFlowStepNodes = 14,
+        CreateFlowStepInputs = 15,
+        DeleteFlowStepInputs = 16,
+        RestoreIos = 17,
+        CreateIos = 18,
+        DeleteIos = 19,
+        UpdateIoTitles = 20,
+        UpdateDescriptions = 21,
+        DeleteDescriptions = 22,
+        Finish = 23,
+        AfterFinish = 24,
     }
 }
 
@@ -168,6 +129,13 @@ impl BranchMerge {
         Ok(self)
     }
 
+    /// Runs conflict detection on the in-memory merge plans. Neither the conflicts nor the plans are persisted.
+    pub async fn preview(mut self, db_session: &CachingSession) -> Result<MergePreview, NodecosmosError> {
+        MergeConflicts::new(&mut self).extract_conflicts(db_session).await?;
+
+        Ok(MergePreview::new(&self))
+    }
+
     pub async fn run(mut self, data: &RequestData) -> Result<Self, MergeError> {
         match self.merge(data).await {
             Ok(_) => {
@@ -199,106 +167,12 @@ impl BranchMerge {
     }
 
     async fn merge(&mut self, data: &RequestData) -> Result<(), NodecosmosError> {
-        while self.merge_step <= MergeStep::Finish {
-            // log current step
-            if self.merge_step > MergeStep::Start && self.merge_step < MergeStep::Finish {
-                self.update_recovery_log_step(data.db_session(), self.merge_step as i8)
-                    .await?;
-            }
-
-            match self.merge_step {
-                MergeStep::BeforeStart => {
-                    log::error!("should not hit before placeholder");
-                }
-                MergeStep::Start => {
-                    self.create_recovery_log(data.db_session()).await?;
-                }
-                MergeStep::RestoreNodes => self.nodes.restore_nodes(data, &mut self.branch).await?,
-                MergeStep::CreateNodes => self.nodes.create_nodes(data, &mut self.branch).await?,
-                MergeStep::DeleteNodes => self.nodes.delete_nodes(data).await?,
-                MergeStep::UpdateNodesTitles => self.nodes.update_title(data, &mut self.branch).await?,
-                MergeStep::ReorderNodes => self.nodes.reorder_nodes(data, &self.branch).await?,
-                MergeStep::RestoreFlows => self.flows.restore_flows(data).await?,
-                MergeStep::CreateFlows => self.flows.create_flows(data).await?,
-                MergeStep::DeleteFlows => self.flows.delete_flows(data).await?,
-                MergeStep::UpdateFlowsTitles => self.flows.update_title(data, &mut self.branch).await?,
-                MergeStep::DeleteFlowSteps => self.flow_steps.delete_flow_steps(data).await?,
-                MergeStep::RestoreFlowSteps => self.flow_steps.restore_flow_steps(data).await?,
-                MergeStep::CreateFlowSteps => self.flow_steps.create_flow_steps(data, &self.branch).await?,
-                MergeStep::CreateFlowStepNodes => self.flow_steps.create_flow_step_nodes(data).await?,
-                MergeStep::DeleteFlowStepNodes => self.flow_steps.delete_flow_step_nodes(data, &self.branch).await?,
-                MergeStep::CreateFlowStepInputs => self.flow_steps.create_inputs(data).await?,
-                MergeStep::DeleteFlowStepInputs => self.flow_steps.delete_inputs(data, &self.branch).await?,
-                MergeStep::RestoreIos => self.ios.restore_ios(data).await?,
-                MergeStep::CreateIos => self.ios.create_ios(data).await?,
-                MergeStep::DeleteIos => self.ios.delete_ios(data).await?,
-                MergeStep::UpdateIoTitles => self.ios.update_title(data, &mut self.branch).await?,
-                MergeStep::UpdateDescriptions => self.descriptions.update_descriptions(data, &mut self.branch).await?,
-                MergeStep::DeleteDescriptions => self.descriptions.delete_descriptions(data).await?,
-                MergeStep::Finish => {
-                    self.delete_recovery_log(data.db_session()).await?;
-                }
-                MergeStep::AfterFinish => {
-                    log::error!("should not hit after placeholder");
-                }
-            }
-
-            self.merge_step.increment();
-        }
-
-        Ok(())
+        self.run_saga(data).await
     }
 
     /// Recover from merge failure in reverse order
     pub async fn recover(&mut self, data: &RequestData) -> Result<(), NodecosmosError> {
-        while self.merge_step >= MergeStep::Start {
-            // log current step
-            if self.merge_step < MergeStep::Finish && self.merge_step > MergeStep::Start {
-                self.update_recovery_log_step(data.db_session(), self.merge_step as i8)
-                    .await?;
-            }
-
-            match self.merge_step {
-                MergeStep::BeforeStart => {
-                    log::error!("should not hit before placeholder");
-                }
-                MergeStep::Start => {
-                    self.delete_recovery_log(data.db_session()).await?;
-                }
-                MergeStep::RestoreNodes => self.nodes.undo_restore_nodes(data).await?,
-                MergeStep::CreateNodes => self.nodes.undo_create_nodes(data).await?,
-                MergeStep::DeleteNodes => self.nodes.undo_delete_nodes(data).await?,
-                MergeStep::UpdateNodesTitles => self.nodes.undo_update_title(data).await?,
-                MergeStep::ReorderNodes => self.nodes.undo_reorder_nodes(data, &self.branch).await?,
-                MergeStep::RestoreFlows => self.flows.undo_restore_flows(data).await?,
-                MergeStep::CreateFlows => self.flows.undo_create_flows(data).await?,
-                MergeStep::DeleteFlows => self.flows.undo_delete_flows(data).await?,
-                MergeStep::UpdateFlowsTitles => self.flows.undo_update_title(data).await?,
-                MergeStep::DeleteFlowSteps => self.flow_steps.undo_delete_flow_steps(data).await?,
-                MergeStep::RestoreFlowSteps => self.flow_steps.undo_restore_flow_steps(data).await?,
-                MergeStep::CreateFlowSteps => self.flow_steps.undo_create_flow_steps(data).await?,
-                MergeStep::CreateFlowStepNodes => self.flow_steps.undo_create_flow_step_nodes(data).await?,
-                MergeStep::DeleteFlowStepNodes => self.flow_steps.undo_delete_flow_step_nodes(data).await?,
-                MergeStep::CreateFlowStepInputs => self.flow_steps.undo_create_inputs(data).await?,
-                MergeStep::DeleteFlowStepInputs => self.flow_steps.undo_delete_inputs(data).await?,
-                MergeStep::RestoreIos => self.ios.undo_restore_ios(data).await?,
-                MergeStep::CreateIos => self.ios.undo_create_ios(data).await?,
-                MergeStep::DeleteIos => self.ios.undo_delete_ios(data).await?,
-                MergeStep::UpdateIoTitles => self.ios.undo_update_title(data).await?,
-                MergeStep::UpdateDescriptions => self.descriptions.undo_update_description(data).await?,
-                MergeStep::DeleteDescriptions => self.descriptions.undo_delete_descriptions(data).await?,
-                MergeStep::Finish => {
-                    log::error!("should not recover finished process");
-                }
-                MergeStep::AfterFinish => {
-                    log::error!("should not hit after placeholder");
-                }
-            }
-
-            self.merge_step.decrement();
-        }
-
-        Ok(())
+        self.compensate_saga(data).await
     }
 
     async fn unlock_resource(&mut self, data: &RequestData) -> Result<(), NodecosmosError> {
@@ -328,8 +202,10 @@ impl RecoveryLog<'_> for BranchMerge {
         RecoveryObjectType::Merge
     }
 
-    fn set_step(&mut self, step: i8) {
-        self.merge_step = MergeStep::from(step);
+    fn set_step(&mut self, step: i8) -> Result<(), NodecosmosError> {
+        self.merge_step = MergeStep::try_from_value(step)?;
+
+        Ok(())
     }
 
     async fn recover_from_log(&mut self, data: &RequestData) -> Result<(), NodecosmosError> {
@@ -356,6 +232,80 @@ impl RecoveryLog<'_> for BranchMerge {
     }
 }
 
+impl Saga for BranchMerge {
+    type Step = MergeStep;
+
+    fn step(&self) -> MergeStep {
+        self.merge_step
+    }
+
+    fn set_saga_step(&mut self, step: MergeStep) {
+        self.merge_step = step;
+    }
+
+    async fn execute(&mut self, data: &RequestData, step: MergeStep) -> Result<(), NodecosmosError> {
+        match step {
+            MergeStep::RestoreNodes => self.nodes.restore_nodes(data, &mut self.branch).await?,
+            MergeStep::CreateNodes => self.nodes.create_nodes(data, &mut self.branch).await?,
+            MergeStep::DeleteNodes => self.nodes.delete_nodes(data).await?,
+            MergeStep::UpdateNodesTitles => self.nodes.update_title(data, &mut self.branch).await?,
+            MergeStep::ReorderNodes => self.nodes.reorder_nodes(data, &self.branch).await?,
+            MergeStep::RestoreFlows => self.flows.restore_flows(data).await?,
+            MergeStep::CreateFlows => self.flows.create_flows(data).await?,
+            MergeStep::DeleteFlows => self.flows.delete_flows(data).await?,
+            MergeStep::UpdateFlowsTitles => self.flows.update_title(data, &mut self.branch).await?,
+            MergeStep::DeleteFlowSteps => self.flow_steps.delete_flow_steps(data).await?,
+            MergeStep::RestoreFlowSteps => self.flow_steps.restore_flow_steps(data).await?,
+            MergeStep::CreateFlowSteps => self.flow_steps.create_flow_steps(data, &self.branch).await?,
+            MergeStep::CreateFlowStepNodes => self.flow_steps.create_flow_step_nodes(data).await?,
+            MergeStep::DeleteFlowStepNodes => self.flow_steps.delete_flow_step_nodes(data, &self.branch).await?,
+            MergeStep::CreateFlowStepInputs => self.flow_steps.create_inputs(data).await?,
+            MergeStep::DeleteFlowStepInputs => self.flow_steps.delete_inputs(data, &self.branch).await?,
+            MergeStep::RestoreIos => self.ios.restore_ios(data).await?,
+            MergeStep::CreateIos => self.ios.create_ios(data).await?,
+            MergeStep::DeleteIos => self.ios.delete_ios(data).await?,
+            MergeStep::UpdateIoTitles => self.ios.update_title(data, &mut self.branch).await?,
+            MergeStep::UpdateDescriptions => self.descriptions.update_descriptions(data, &mut self.branch).await?,
+            MergeStep::DeleteDescriptions => self.descriptions.delete_descriptions(data).await?,
+            // log and placeholder steps are handled by the saga executor
+            MergeStep::BeforeStart | MergeStep::Start | MergeStep::Finish | MergeStep::AfterFinish => (),
+        }
+
+        Ok(())
+    }
+
+    async fn compensate(&mut self, data: &RequestData, step: MergeStep) -> Result<(), NodecosmosError> {
+        match step {
+            MergeStep::RestoreNodes => self.nodes.undo_restore_nodes(data).await?,
+            MergeStep::CreateNodes => self.nodes.undo_create_nodes(data).await?,
+            MergeStep::DeleteNodes => self.nodes.undo_delete_nodes(data).await?,
+            MergeStep::UpdateNodesTitles => self.nodes.undo_update_title(data).await?,
+            MergeStep::ReorderNodes => self.nodes.undo_reorder_nodes(data, &self.branch).await?,
+            MergeStep::RestoreFlows => self.flows.undo_restore_flows(data).await?,
+            MergeStep::CreateFlows => self.flows.undo_create_flows(data).await?,
+            MergeStep::DeleteFlows => self.flows.undo_delete_flows(data).await?,
+            MergeStep::UpdateFlowsTitles => self.flows.undo_update_title(data).await?,
+            MergeStep::DeleteFlowSteps => self.flow_steps.undo_delete_flow_steps(data).await?,
+            MergeStep::RestoreFlowSteps => self.flow_steps.undo_restore_flow_steps(data).await?,
+            MergeStep::CreateFlowSteps => self.flow_steps.undo_create_flow_steps(data).await?,
+            MergeStep::CreateFlowStepNodes => self.flow_steps.undo_create_flow_step_nodes(data).await?,
+            MergeStep::DeleteFlowStepNodes => self.flow_steps.undo_delete_flow_step_nodes(data).await?,
+            MergeStep::CreateFlowStepInputs => self.flow_steps.undo_create_inputs(data).await?,
+            MergeStep::DeleteFlowStepInputs => self.flow_steps.undo_delete_inputs(data).await?,
+            MergeStep::RestoreIos => self.ios.undo_restore_ios(data).await?,
+            MergeStep::CreateIos => self.ios.undo_create_ios(data).await?,
+            MergeStep::DeleteIos => self.ios.undo_delete_ios(data).await?,
+            MergeStep::UpdateIoTitles => self.ios.undo_update_title(data).await?,
+            MergeStep::UpdateDescriptions => self.descriptions.undo_update_description(data).await?,
+            MergeStep::DeleteDescriptions => self.descriptions.undo_delete_descriptions(data).await?,
+            // log and placeholder steps are handled by the saga executor
+            MergeStep::BeforeStart | MergeStep::Start | MergeStep::Finish | MergeStep::AfterFinish => (),
+        }
+
+        Ok(())
+    }
+}
+
 impl Branch {
     pub async fn merge(self, data: &RequestData) -> Result<Self, MergeError> {
         // There are scenarios where we want to check for conflicts again before merging,
@@ -364,6 +314,15 @@ impl Branch {
         //     return Err(MergeError { inner: e, branch: self });
         // }
 
+        let parent = match self.open_parent(data.db_session()).await {
+            Ok(parent) => parent,
+            Err(e) => return Err(MergeError { inner: e, branch: self }),
+        };
+
+        if let Some(parent) = parent {
+            return self.merge_into_parent(data, parent).await;
+        }
+
         let merge = BranchMerge::new(data.db_session(), self)
             .await?
             .check_conflicts(data.db_session())
@@ -383,6 +342,107 @@ impl Branch {
         Ok(merge.branch)
     }
 
+    /// Merges only the selected objects and their dependencies. Merged changes are removed from the branch
+    /// and the rest of it stays open.
+    pub async fn merge_selection(mut self, data: &RequestData, selection: MergeSelection) -> Result<Self, MergeError> {
+        let db_session = data.db_session();
+
+        if let Err(e) = self.validate_no_open_parent(db_session).await {
+            return Err(MergeError { inner: e, branch: self });
+        }
+
+        let selection = match selection.with_dependencies(db_session, &self).await {
+            Ok(selection) => selection,
+            Err(e) => return Err(MergeError { inner: e, branch: self }),
+        };
+
+        let mut merge = match BranchMerge::new(db_session, selection.selected_branch(&self)).await {
+            Ok(merge) => merge,
+            Err(merge_error) => {
+                return Err(MergeError {
+                    inner: merge_error.inner,
+                    branch: self,
+                })
+            }
+        };
+
+        // conflicts are stored on the complete branch, so we don't use `BranchMerge::check_conflicts` here
+        if let Err(e) = MergeConflicts::new(&mut merge).extract_conflicts(db_session).await {
+            return Err(MergeError { inner: e, branch: self });
+        }
+
+        if merge.branch.conflict.is_some() {
+            self.conflict = merge.branch.conflict.take();
+
+            if let Err(e) = self.update().execute(db_session).await {
+                return Err(MergeError {
+                    inner: NodecosmosError::from(e),
+                    branch: self,
+                });
+            }
+
+            return Err(MergeError {
+                inner: NodecosmosError::Conflict("Conflict detected".to_string()),
+                branch: self,
+            });
+        }
+
+        let merged_branch = match merge.run(data).await {
+            Ok(merge) => merge.branch,
+            Err(merge_error) => {
+                self.status = merge_error.branch.status;
+
+                return Err(MergeError {
+                    inner: merge_error.inner,
+                    branch: self,
+                });
+            }
+        };
+
+        selection.remove_selected(&mut self);
+
+        // title and description changes are recorded by the merge
+        self.title_change_by_object = merged_branch.title_change_by_object;
+        self.description_change_by_object = merged_branch.description_change_by_object;
+        self.conflict = None;
+
+        if let Err(e) = self.update().execute(db_session).await {
+            return Err(MergeError {
+                inner: NodecosmosError::from(e),
+                branch: self,
+            });
+        }
+
+        Ok(self)
+    }
+
+    /// Dry run of the merge. It doesn't write to the database nor lock the resource.
+    pub async fn merge_preview(self, db_session: &CachingSession) -> Result<MergePreview, NodecosmosError> {
+        self.validate_no_open_parent(db_session).await?;
+
+        BranchMerge::new(db_session, self)
+            .await
+            .map_err(|merge_error| merge_error.inner)?
+            .preview(db_session)
+            .await
+    }
+
+    /// Conflicts with the branch this branch merges into, its open parent or the original. Unlike
+    /// `check_conflicts`, the conflict is not persisted on the branch.
+    pub async fn find_conflict(self, db_session: &CachingSession) -> Result<Option<Conflict>, NodecosmosError> {
+        if let Some(parent) = self.open_parent(db_session).await? {
+            return self.parent_conflict(db_session, &parent).await;
+        }
+
+        let preview = BranchMerge::new(db_session, self)
+            .await
+            .map_err(|merge_error| merge_error.inner)?
+            .preview(db_session)
+            .await?;
+
+        Ok(preview.conflict)
+    }
+
     #[allow(unused)]
     pub async fn validate_no_existing_conflicts(&mut self) -> Result<(), NodecosmosError> {
         if self.conflict.is_some() {
@@ -393,6 +453,16 @@ impl Branch {
     }
 
     pub async fn check_conflicts(self, db_session: &CachingSession) -> Result<Self, MergeError> {
+        let parent = match self.open_parent(db_session).await {
+            Ok(parent) => parent,
+            Err(e) => return Err(MergeError { inner: e, branch: self }),
+        };
+
+        // stacked branches merge into their parent, so conflicts are checked against it
+        if let Some(parent) = parent {
+            return self.check_parent_conflicts(db_session, &parent).await;
+        }
+
         let merge = BranchMerge::new(db_session, self)
             .await?
             .check_conflicts(db_session)
@@ -795,8 +865,9 @@ mod tests {
         // Clear the conflicts to simulate resolving them
         // restore the deleted node
         Branch::update(
-            test_merge.data.db_session(),
+            &test_merge.data,
             test_merge.branch_id,
+            node_to_delete.id,
             BranchUpdate::RestoreNode(node_to_delete.id),
         )
         .await
@@ -77,7 +77,8 @@ impl<'a> MergeConflicts<'a> {
         Ok(())
     }
 
-    pub async fn run_check(&mut self, db_session: &CachingSession) -> Result<(), NodecosmosError> {
+    /// Populates `branch.conflict` in memory without persisting it.
+    pub async fn extract_conflicts(&mut self, db_session: &CachingSession) -> Result<(), NodecosmosError> {
         self.reset_conflicts();
         self.extract_created_nodes_conflicts(db_session).await?;
         self.extract_deleted_edited_nodes(db_session).await?;
@@ -85,6 +86,13 @@ impl<'a> MergeConflicts<'a> {
         self.extract_deleted_edited_flow_steps(db_session).await?;
         self.extract_conflicting_flow_steps(db_session).await?;
         self.extract_deleted_ios(db_session).await?;
+        self.extract_edited_titles();
+
+        Ok(())
+    }
+
+    pub async fn run_check(&mut self, db_session: &CachingSession) -> Result<(), NodecosmosError> {
+        self.extract_conflicts(db_session).await?;
 
         let branch = &mut self.branch_merge.branch;
 
@@ -471,4 +479,21 @@ impl<'a> MergeConflicts<'a> {
 
         Ok(())
     }
+
+    /// Titles that were edited both in the branch and in the original since the branch edit
+    fn extract_edited_titles(&mut self) {
+        let branch = &self.branch_merge.branch;
+        let mut edited_titles = self.branch_merge.nodes.conflicting_title_ids(branch);
+
+        edited_titles.extend(self.branch_merge.flows.conflicting_title_ids(branch));
+        edited_titles.extend(self.branch_merge.ios.conflicting_title_ids(branch));
+
+        if !edited_titles.is_empty() {
+            self.branch_merge
+                .branch
+                .conflict
+                .get_or_insert_with(Conflict::default)
+                .edited_titles = Some(edited_titles.into_iter().collect());
+        }
+    }
 }
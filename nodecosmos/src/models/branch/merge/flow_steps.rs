@@ -8,6 +8,7 @@ use serde::{Deserialize, Serialize};
 
 use crate::api::data::RequestData;
 use crate::errors::NodecosmosError;
+use crate::models::branch::merge::preview::MergePreviewObjects;
 use crate::models::branch::Branch;
 use crate::models::flow_step::{FlowStep, PkFlowStep, UpdateInputIdsFlowStep, UpdateNodeIdsFlowStep};
 use crate::models::traits::{Branchable, FindForBranchMerge, FlowId, Id, IncrementFraction, NodeId, Reload};
@@ -263,6 +264,15 @@ impl MergeFlowSteps {
         })
     }
 
+    pub fn preview(&self) -> MergePreviewObjects {
+        MergePreviewObjects {
+            created: MergePreviewObjects::map(&self.created_flow_steps),
+            deleted: MergePreviewObjects::map(&self.deleted_flow_steps),
+            restored: MergePreviewObjects::map(&self.restored_flow_steps),
+            retitled: vec![],
+        }
+    }
+
     pub async fn delete_inserted_flow_steps(
         data: &RequestData,
         merge_flow_steps: &mut Option<Vec<FlowStep>>,
@@ -8,6 +8,7 @@ use serde::{Deserialize, Serialize};
 
 use crate::api::data::RequestData;
 use crate::errors::NodecosmosError;
+use crate::models::branch::merge::preview::{MergePreviewObjects, MergePreviewTitle};
 use crate::models::branch::Branch;
 use crate::models::flow::{Flow, UpdateTitleFlow};
 use crate::models::traits::{Branchable, FindForBranchMerge, GroupById, ObjectType};
@@ -29,12 +30,12 @@ impl MergeFlows {
         branch: &Branch,
     ) -> Result<Option<HashMap<Uuid, UpdateTitleFlow>>, NodecosmosError> {
         if let Some(ids) = &branch.edited_title_flows {
-            let ios_by_id = UpdateTitleFlow::find_by_branch_id_and_ids(db_session, branch.id, ids)
+            let flows_by_id = UpdateTitleFlow::find_by_branch_id_and_ids(db_session, branch.original_id(), ids)
                 .await
                 .group_by_id()
                 .await?;
 
-            return Ok(Some(ios_by_id));
+            return Ok(Some(flows_by_id));
         }
 
         Ok(None)
@@ -123,6 +124,42 @@ impl MergeFlows {
         })
     }
 
+    pub fn preview(&self) -> MergePreviewObjects {
+        MergePreviewObjects {
+            created: MergePreviewObjects::map(&self.created_flows),
+            deleted: MergePreviewObjects::map(&self.deleted_flows),
+            restored: MergePreviewObjects::map(&self.restored_flows),
+            retitled: self
+                .edited_title_flows
+                .iter()
+                .flatten()
+                .map(|edited| MergePreviewTitle {
+                    id: edited.id,
+                    old: self
+                        .original_title_flows
+                        .as_ref()
+                        .and_then(|originals| originals.get(&edited.id))
+                        .map(|original| original.title.clone()),
+                    new: Some(edited.title.clone()),
+                })
+                .collect(),
+        }
+    }
+
+    /// Edited titles whose original was changed after the first edit within the branch to a different value.
+    pub fn conflicting_title_ids(&self, branch: &Branch) -> Vec<Uuid> {
+        self.edited_title_flows
+            .iter()
+            .flatten()
+            .filter_map(|edited| {
+                let original = self.original_title_flows.as_ref()?.get(&edited.id)?;
+                let title_change = branch.title_change_by_object.as_ref()?.get(&edited.id)?;
+
+                (title_change.old != original.title && edited.title != original.title).then_some(edited.id)
+            })
+            .collect()
+    }
+
     async fn insert_flows(data: &RequestData, merge_flows: &mut Option<Vec<Flow>>) -> Result<(), NodecosmosError> {
         if let Some(merge_flows) = merge_flows {
             for merge_flow in merge_flows {
@@ -265,6 +302,9 @@ impl MergeFlows {
                         .title_change_by_object
                         .get_or_insert_with(HashMap::default)
                         .insert(edited_flow_title.id, text_change);
+                } else if let Some(title_changes) = branch.title_change_by_object.as_mut() {
+                    // title is not changed by the merge, so we drop the change tracked within the branch
+                    title_changes.remove(&edited_flow_title.id);
                 }
             }
         }
@@ -286,3 +326,62 @@ impl MergeFlows {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn title_flow(id: Uuid, title: &str) -> UpdateTitleFlow {
+        UpdateTitleFlow {
+            id,
+            title: title.to_string(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_conflicting_title_ids() {
+        let conflicting_id = Uuid::new_v4();
+        let unchanged_original_id = Uuid::new_v4();
+        let same_title_id = Uuid::new_v4();
+        let title_changes = [
+            (conflicting_id, "Base", "Branch"),
+            (unchanged_original_id, "Base", "Branch"),
+            (same_title_id, "Base", "Branch"),
+        ];
+        let branch = Branch {
+            title_change_by_object: Some(
+                title_changes
+                    .iter()
+                    .map(|(id, old, new)| {
+                        (
+                            *id,
+                            TextChange {
+                                old: old.to_string(),
+                                new: new.to_string(),
+                            },
+                        )
+                    })
+                    .collect(),
+            ),
+            ..Default::default()
+        };
+        let merge_flows = MergeFlows {
+            restored_flows: None,
+            created_flows: None,
+            deleted_flows: None,
+            edited_title_flows: Some(title_changes.iter().map(|(id, _, new)| title_flow(*id, new)).collect()),
+            original_title_flows: Some(
+                [
+                    (conflicting_id, title_flow(conflicting_id, "Original")),
+                    (unchanged_original_id, title_flow(unchanged_original_id, "Base")),
+                    (same_title_id, title_flow(same_title_id, "Branch")),
+                ]
+                .into_iter()
+                .collect(),
+            ),
+        };
+
+        assert_eq!(merge_flows.conflicting_title_ids(&branch), vec![conflicting_id]);
+    }
+}
@@ -8,6 +8,7 @@ use serde::{Deserialize, Serialize};
 
 use crate::api::data::RequestData;
 use crate::errors::NodecosmosError;
+use crate::models::branch::merge::preview::{MergePreviewObjects, MergePreviewTitle};
 use crate::models::branch::Branch;
 use crate::models::io::{find_update_title_io, Io, UpdateTitleIo};
 use crate::models::traits::{Branchable, GroupById, Pluck, WhereInChunksExec};
@@ -136,6 +137,43 @@ impl MergeIos {
         })
     }
 
+    pub fn preview(&self) -> MergePreviewObjects {
+        MergePreviewObjects {
+            created: MergePreviewObjects::map(&self.created_ios),
+            deleted: MergePreviewObjects::map(&self.deleted_ios),
+            restored: MergePreviewObjects::map(&self.restored_ios),
+            retitled: self
+                .edited_title_ios
+                .iter()
+                .flatten()
+                .map(|edited| MergePreviewTitle {
+                    id: edited.id,
+                    old: self
+                        .original_title_ios
+                        .as_ref()
+                        .and_then(|originals| originals.get(&edited.id))
+                        .and_then(|original| original.title.clone()),
+                    new: edited.title.clone(),
+                })
+                .collect(),
+        }
+    }
+
+    /// Edited titles whose original was changed after the first edit within the branch to a different value.
+    pub fn conflicting_title_ids(&self, branch: &Branch) -> Vec<Uuid> {
+        self.edited_title_ios
+            .iter()
+            .flatten()
+            .filter_map(|edited| {
+                let original = self.original_title_ios.as_ref()?.get(&edited.id)?;
+                let title_change = branch.title_change_by_object.as_ref()?.get(&edited.id)?;
+                let original_title = original.title.clone().unwrap_or_default();
+
+                (title_change.old != original_title && edited.title != original.title).then_some(edited.id)
+            })
+            .collect()
+    }
+
     async fn insert_ios(data: &RequestData, merge_ios: &mut Option<Vec<Io>>) -> Result<(), NodecosmosError> {
         if let Some(merge_ios) = merge_ios {
             for merge_io in merge_ios {
@@ -255,6 +293,9 @@ impl MergeIos {
                         .title_change_by_object
                         .get_or_insert_with(HashMap::default)
                         .insert(edited_io_title.id, text_change);
+                } else if let Some(title_changes) = branch.title_change_by_object.as_mut() {
+                    // title is not changed by the merge, so we drop the change tracked within the branch
+                    title_changes.remove(&edited_io_title.id);
                 }
             }
         }
@@ -8,6 +8,7 @@ use serde::{Deserialize, Serialize};
 
 use crate::api::data::RequestData;
 use crate::errors::NodecosmosError;
+use crate::models::branch::merge::preview::{MergePreviewObjects, MergePreviewTitle};
 use crate::models::branch::Branch;
 use crate::models::node::reorder::ReorderParams;
 use crate::models::node::sort::SortNodes;
@@ -178,6 +179,42 @@ impl MergeNodes {
         })
     }
 
+    pub fn preview(&self) -> MergePreviewObjects {
+        MergePreviewObjects {
+            created: MergePreviewObjects::map(&self.created_nodes),
+            deleted: MergePreviewObjects::map(&self.deleted_nodes),
+            restored: MergePreviewObjects::map(&self.restored_nodes),
+            retitled: self
+                .edited_title_nodes
+                .iter()
+                .flatten()
+                .map(|edited| MergePreviewTitle {
+                    id: edited.id,
+                    old: self
+                        .original_title_nodes
+                        .as_ref()
+                        .and_then(|originals| originals.get(&edited.id))
+                        .map(|original| original.title.clone()),
+                    new: Some(edited.title.clone()),
+                })
+                .collect(),
+        }
+    }
+
+    /// Edited titles whose original was changed after the first edit within the branch to a different value.
+    pub fn conflicting_title_ids(&self, branch: &Branch) -> Vec<Uuid> {
+        self.edited_title_nodes
+            .iter()
+            .flatten()
+            .filter_map(|edited| {
+                let original = self.original_title_nodes.as_ref()?.get(&edited.id)?;
+                let title_change = branch.title_change_by_object.as_ref()?.get(&edited.id)?;
+
+                (title_change.old != original.title && edited.title != original.title).then_some(edited.id)
+            })
+            .collect()
+    }
+
     async fn insert_nodes(
         data: &RequestData,
         branch: &mut Branch,
@@ -414,6 +451,9 @@ impl MergeNodes {
                         .title_change_by_object
                         .get_or_insert_with(HashMap::default)
                         .insert(edited_node_title.id, text_change);
+                } else if let Some(title_changes) = branch.title_change_by_object.as_mut() {
+                    // title is not changed by the merge, so we drop the change tracked within the branch
+                    title_changes.remove(&edited_node_title.id);
                 }
             }
         }
@@ -0,0 +1,133 @@
+use charybdis::types::{Text, Uuid};
+use serde::Serialize;
+
+use crate::models::branch::merge::nodes::MergeNodes;
+use crate::models::branch::merge::BranchMerge;
+use crate::models::description::Description;
+use crate::models::flow::Flow;
+use crate::models::flow_step::FlowStep;
+use crate::models::io::Io;
+use crate::models::node::Node;
+use crate::models::udts::{BranchReorderData, Conflict};
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MergePreviewObject {
+    pub id: Uuid,
+    pub node_id: Uuid,
+    pub title: Option<Text>,
+}
+
+impl From<&Node> for MergePreviewObject {
+    fn from(node: &Node) -> Self {
+        Self {
+            id: node.id,
+            node_id: node.id,
+            title: Some(node.title.clone()),
+        }
+    }
+}
+
+impl From<&Flow> for MergePreviewObject {
+    fn from(flow: &Flow) -> Self {
+        Self {
+            id: flow.id,
+            node_id: flow.node_id,
+            title: Some(flow.title.clone()),
+        }
+    }
+}
+
+impl From<&FlowStep> for MergePreviewObject {
+    fn from(flow_step: &FlowStep) -> Self {
+        Self {
+            id: flow_step.id,
+            node_id: flow_step.node_id,
+            title: None,
+        }
+    }
+}
+
+impl From<&Io> for MergePreviewObject {
+    fn from(io: &Io) -> Self {
+        Self {
+            id: io.id,
+            node_id: io.node_id,
+            title: io.title.clone(),
+        }
+    }
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MergePreviewTitle {
+    pub id: Uuid,
+    pub old: Option<Text>,
+    pub new: Option<Text>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MergePreviewDescription {
+    pub object_id: Uuid,
+    pub node_id: Uuid,
+    pub object_type: Text,
+}
+
+impl From<&Description> for MergePreviewDescription {
+    fn from(description: &Description) -> Self {
+        Self {
+            object_id: description.object_id,
+            node_id: description.node_id,
+            object_type: description.object_type.clone(),
+        }
+    }
+}
+
+#[derive(Serialize, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct MergePreviewObjects {
+    pub created: Vec<MergePreviewObject>,
+    pub deleted: Vec<MergePreviewObject>,
+    pub restored: Vec<MergePreviewObject>,
+    pub retitled: Vec<MergePreviewTitle>,
+}
+
+impl MergePreviewObjects {
+    pub fn map<T>(records: &Option<Vec<T>>) -> Vec<MergePreviewObject>
+    where
+        for<'a> &'a T: Into<MergePreviewObject>,
+    {
+        records.iter().flatten().map(Into::into).collect()
+    }
+}
+
+/// Summary of what merging a branch would do. It's built from the same plans that are used by the merge,
+/// so it reflects the branch state at the time of the request.
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MergePreview {
+    pub nodes: MergePreviewObjects,
+    pub reordered_nodes: Vec<BranchReorderData>,
+    pub flows: MergePreviewObjects,
+    pub flow_steps: MergePreviewObjects,
+    pub ios: MergePreviewObjects,
+    pub edited_descriptions: Vec<MergePreviewDescription>,
+    pub deleted_descriptions: Vec<MergePreviewDescription>,
+    pub conflict: Option<Conflict>,
+}
+
+impl MergePreview {
+    pub fn new(merge: &BranchMerge) -> Self {
+        Self {
+            nodes: merge.nodes.preview(),
+            reordered_nodes: MergeNodes::reordered_nodes_data(&merge.branch).unwrap_or_default(),
+            flows: merge.flows.preview(),
+            flow_steps: merge.flow_steps.preview(),
+            ios: merge.ios.preview(),
+            edited_descriptions: merge.descriptions.edited_descriptions.iter().map(Into::into).collect(),
+            deleted_descriptions: merge.descriptions.deleted_descriptions.iter().map(Into::into).collect(),
+            conflict: merge.branch.conflict.clone(),
+        }
+    }
+}
@@ -0,0 +1,490 @@
+use std::collections::{HashMap, HashSet};
+
+use charybdis::types::{Set, Uuid};
+use scylla::client::caching_session::CachingSession;
+use serde::Deserialize;
+
+use crate::errors::NodecosmosError;
+use crate::models::branch::Branch;
+use crate::models::flow::Flow;
+use crate::models::flow_step::FlowStep;
+use crate::models::io::Io;
+use crate::models::node::PkNode;
+use crate::models::traits::FindForBranchMerge;
+
+/// Objects selected for a partial merge of a branch.
+#[derive(Deserialize, Default, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct MergeSelection {
+    #[serde(default)]
+    pub node_ids: HashSet<Uuid>,
+
+    #[serde(default)]
+    pub flow_ids: HashSet<Uuid>,
+
+    #[serde(default)]
+    pub flow_step_ids: HashSet<Uuid>,
+
+    #[serde(default)]
+    pub io_ids: HashSet<Uuid>,
+
+    /// Objects whose description edits are merged.
+    #[serde(default)]
+    pub description_ids: HashSet<Uuid>,
+}
+
+fn new_ids(created_ids: &Option<Set<Uuid>>, restored_ids: &Option<Set<Uuid>>) -> HashSet<Uuid> {
+    created_ids
+        .iter()
+        .chain(restored_ids.iter())
+        .flatten()
+        .copied()
+        .collect()
+}
+
+/// Objects created or restored in the branch. Only those are pulled in as dependencies, as the rest already exist
+/// in the original.
+struct NewIds {
+    nodes: HashSet<Uuid>,
+    flows: HashSet<Uuid>,
+    flow_steps: HashSet<Uuid>,
+    ios: HashSet<Uuid>,
+}
+
+impl NewIds {
+    fn new(branch: &Branch) -> Self {
+        Self {
+            nodes: new_ids(&branch.created_nodes, &branch.restored_nodes),
+            flows: new_ids(&branch.created_flows, &branch.restored_flows),
+            flow_steps: new_ids(&branch.created_flow_steps, &branch.restored_flow_steps),
+            ios: new_ids(&branch.created_ios, &branch.restored_ios),
+        }
+    }
+}
+
+/// Branch records of the selected new objects.
+#[derive(Default)]
+struct DependencyRecords {
+    nodes: Vec<PkNode>,
+    flows: Vec<Flow>,
+    flow_steps: Vec<FlowStep>,
+    ios: Vec<Io>,
+}
+
+/// Adds dependencies that are created or restored in the branch and returns true if any of them were not selected.
+fn extend_selected(selected_ids: &mut HashSet<Uuid>, dependency_ids: HashSet<Uuid>, new_ids: &HashSet<Uuid>) -> bool {
+    let mut added = false;
+
+    for id in dependency_ids {
+        if new_ids.contains(&id) && selected_ids.insert(id) {
+            added = true;
+        }
+    }
+
+    added
+}
+
+fn retain_ids(ids: &mut Option<Set<Uuid>>, keep: impl Fn(&Uuid) -> bool) {
+    if let Some(set) = ids {
+        set.retain(|id| keep(id));
+
+        if set.is_empty() {
+            *ids = None;
+        }
+    }
+}
+
+fn retain_keys<V>(map: &mut Option<HashMap<Uuid, V>>, keep: impl Fn(&Uuid) -> bool) {
+    if let Some(inner) = map {
+        inner.retain(|id, _| keep(id));
+
+        if inner.is_empty() {
+            *map = None;
+        }
+    }
+}
+
+impl MergeSelection {
+    /// Pulls in created and restored objects that the selected ones depend on: ancestors of nodes, nodes and flows
+    /// of flow steps and ios, ios referenced by flow steps and objects with selected description edits.
+    /// Pulled in objects can have their own dependencies, so it runs until nothing new is added.
+    pub async fn with_dependencies(
+        mut self,
+        db_session: &CachingSession,
+        branch: &Branch,
+    ) -> Result<Self, NodecosmosError> {
+        while self.pull_dependencies(db_session, branch).await? {}
+
+        Ok(self)
+    }
+
+    async fn pull_dependencies(
+        &mut self,
+        db_session: &CachingSession,
+        branch: &Branch,
+    ) -> Result<bool, NodecosmosError> {
+        let new_ids = NewIds::new(branch);
+        let mut records = DependencyRecords::default();
+
+        let selected_node_ids = self
+            .node_ids
+            .intersection(&new_ids.nodes)
+            .copied()
+            .collect::<Vec<Uuid>>();
+
+        if !selected_node_ids.is_empty() {
+            records.nodes = PkNode::find_by_ids(db_session, branch.id, &selected_node_ids).await?;
+        }
+
+        let selected_flow_ids = self
+            .flow_ids
+            .intersection(&new_ids.flows)
+            .copied()
+            .collect::<Set<Uuid>>();
+
+        if !selected_flow_ids.is_empty() {
+            records.flows = Flow::find_by_branch_id_and_ids(db_session, branch.id, &selected_flow_ids)
+                .await
+                .try_collect()
+                .await?;
+        }
+
+        let selected_flow_step_ids = self
+            .flow_step_ids
+            .intersection(&new_ids.flow_steps)
+            .copied()
+            .collect::<Set<Uuid>>();
+
+        if !selected_flow_step_ids.is_empty() {
+            records.flow_steps = FlowStep::find_by_branch_id_and_ids(db_session, branch.id, &selected_flow_step_ids)
+                .await
+                .try_collect()
+                .await?;
+        }
+
+        let selected_io_ids = self.io_ids.intersection(&new_ids.ios).copied().collect::<Set<Uuid>>();
+
+        if !selected_io_ids.is_empty() {
+            records.ios =
+                Io::find_by_branch_id_and_root_id_and_ids(db_session, branch.id, branch.root_id, &selected_io_ids)
+                    .await?;
+        }
+
+        Ok(self.add_dependencies(&new_ids, records))
+    }
+
+    /// Adds dependencies of the selected records. Returns true if anything new was selected.
+    fn add_dependencies(&mut self, new_ids: &NewIds, records: DependencyRecords) -> bool {
+        let is_selected = |selected_ids: &HashSet<Uuid>, new_ids: &HashSet<Uuid>, id: &Uuid| {
+            selected_ids.contains(id) && new_ids.contains(id)
+        };
+
+        // description edits of new objects require the object itself
+        let mut node_ids = self.description_ids.clone();
+        let mut flow_ids = self.description_ids.clone();
+        let mut flow_step_ids = self.description_ids.clone();
+        let mut io_ids = self.description_ids.clone();
+
+        for node in records.nodes {
+            if is_selected(&self.node_ids, &new_ids.nodes, &node.id) {
+                node_ids.extend(node.ancestor_ids.into_iter().flatten());
+            }
+        }
+
+        for flow in records.flows {
+            if is_selected(&self.flow_ids, &new_ids.flows, &flow.id) {
+                node_ids.insert(flow.node_id);
+            }
+        }
+
+        for flow_step in records.flow_steps {
+            if !is_selected(&self.flow_step_ids, &new_ids.flow_steps, &flow_step.id) {
+                continue;
+            }
+
+            node_ids.insert(flow_step.node_id);
+            node_ids.extend(flow_step.node_ids.into_iter().flatten());
+            flow_ids.insert(flow_step.flow_id);
+
+            for ids_by_node_id in [flow_step.input_ids_by_node_id, flow_step.output_ids_by_node_id]
+                .into_iter()
+                .flatten()
+            {
+                io_ids.extend(ids_by_node_id.into_values().flatten());
+            }
+        }
+
+        for io in records.ios {
+            if is_selected(&self.io_ids, &new_ids.ios, &io.id) {
+                node_ids.insert(io.node_id);
+                flow_ids.extend(io.flow_id);
+                flow_step_ids.extend(io.flow_step_id);
+            }
+        }
+
+        let nodes_added = extend_selected(&mut self.node_ids, node_ids, &new_ids.nodes);
+        let flows_added = extend_selected(&mut self.flow_ids, flow_ids, &new_ids.flows);
+        let flow_steps_added = extend_selected(&mut self.flow_step_ids, flow_step_ids, &new_ids.flow_steps);
+        let ios_added = extend_selected(&mut self.io_ids, io_ids, &new_ids.ios);
+
+        nodes_added || flows_added || flow_steps_added || ios_added
+    }
+
+    /// Copy of the branch that contains only the selected changes.
+    pub fn selected_branch(&self, branch: &Branch) -> Branch {
+        let mut selected_branch = branch.clone();
+
+        self.retain(&mut selected_branch, true);
+
+        // conflicts are extracted for the selected changes
+        selected_branch.conflict = None;
+
+        selected_branch
+    }
+
+    /// Removes the selected changes from the branch.
+    pub fn remove_selected(&self, branch: &mut Branch) {
+        self.retain(branch, false);
+    }
+
+    fn retain(&self, branch: &mut Branch, selected: bool) {
+        let node = |id: &Uuid| self.node_ids.contains(id) == selected;
+        let flow = |id: &Uuid| self.flow_ids.contains(id) == selected;
+        let flow_step = |id: &Uuid| self.flow_step_ids.contains(id) == selected;
+        let io = |id: &Uuid| self.io_ids.contains(id) == selected;
+        let description = |id: &Uuid| self.description_ids.contains(id) == selected;
+
+        retain_ids(&mut branch.created_nodes, node);
+        retain_ids(&mut branch.restored_nodes, node);
+        retain_ids(&mut branch.deleted_nodes, node);
+        retain_ids(&mut branch.edited_title_nodes, node);
+
+        if let Some(reordered_nodes) = &mut branch.reordered_nodes {
+            reordered_nodes.retain(|reorder_data| node(&reorder_data.id));
+
+            if reordered_nodes.is_empty() {
+                branch.reordered_nodes = None;
+            }
+        }
+
+        // edited nodes only mark nodes with changes, so they are kept in the remainder
+        if selected {
+            retain_ids(&mut branch.edited_nodes, |id| {
+                self.node_ids.contains(id) || self.description_ids.contains(id)
+            });
+        }
+
+        retain_ids(&mut branch.created_initial_inputs, io);
+        retain_ids(&mut branch.deleted_initial_inputs, io);
+
+        retain_ids(&mut branch.created_flows, flow);
+        retain_ids(&mut branch.deleted_flows, flow);
+        retain_ids(&mut branch.restored_flows, flow);
+        retain_ids(&mut branch.edited_title_flows, flow);
+
+        retain_ids(&mut branch.created_flow_steps, flow_step);
+        retain_ids(&mut branch.deleted_flow_steps, flow_step);
+        retain_ids(&mut branch.restored_flow_steps, flow_step);
+        retain_ids(&mut branch.kept_flow_steps, flow_step);
+        retain_keys(&mut branch.created_flow_step_nodes, flow_step);
+        retain_keys(&mut branch.deleted_flow_step_nodes, flow_step);
+        retain_keys(&mut branch.created_flow_step_inputs_by_node, flow_step);
+        retain_keys(&mut branch.deleted_flow_step_inputs_by_node, flow_step);
+        retain_keys(&mut branch.created_flow_step_outputs_by_node, flow_step);
+        retain_keys(&mut branch.deleted_flow_step_outputs_by_node, flow_step);
+
+        retain_ids(&mut branch.created_ios, io);
+        retain_ids(&mut branch.deleted_ios, io);
+        retain_ids(&mut branch.restored_ios, io);
+        retain_ids(&mut branch.edited_title_ios, io);
+
+        retain_ids(&mut branch.edited_description_nodes, description);
+        retain_ids(&mut branch.edited_description_flows, description);
+        retain_ids(&mut branch.edited_description_flow_steps, description);
+        retain_ids(&mut branch.edited_description_ios, description);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::models::udts::Conflict;
+
+    fn ids(ids: &[Uuid]) -> Option<Set<Uuid>> {
+        Some(ids.iter().copied().collect())
+    }
+
+    #[test]
+    fn test_dependencies_of_selected_flow_step() {
+        let ancestor_id = Uuid::new_v4();
+        let node_id = Uuid::new_v4();
+        let original_node_id = Uuid::new_v4();
+        let flow_id = Uuid::new_v4();
+        let flow_step_id = Uuid::new_v4();
+        let input_id = Uuid::new_v4();
+        let original_io_id = Uuid::new_v4();
+        let branch = Branch {
+            created_nodes: ids(&[node_id]),
+            restored_nodes: ids(&[ancestor_id]),
+            created_flows: ids(&[flow_id]),
+            created_flow_steps: ids(&[flow_step_id]),
+            created_ios: ids(&[input_id]),
+            ..Default::default()
+        };
+        let new_ids = NewIds::new(&branch);
+        let records = || DependencyRecords {
+            nodes: vec![
+                PkNode {
+                    id: node_id,
+                    ancestor_ids: ids(&[original_node_id, ancestor_id]),
+                    ..Default::default()
+                },
+                PkNode {
+                    id: ancestor_id,
+                    ancestor_ids: ids(&[original_node_id]),
+                    ..Default::default()
+                },
+            ],
+            flows: vec![Flow {
+                id: flow_id,
+                node_id,
+                ..Default::default()
+            }],
+            flow_steps: vec![FlowStep {
+                id: flow_step_id,
+                node_id,
+                flow_id,
+                input_ids_by_node_id: Some(HashMap::from([(original_node_id, vec![input_id, original_io_id])])),
+                ..Default::default()
+            }],
+            ios: vec![Io {
+                id: input_id,
+                node_id: original_node_id,
+                ..Default::default()
+            }],
+        };
+        let mut selection = MergeSelection {
+            flow_step_ids: HashSet::from([flow_step_id]),
+            ..Default::default()
+        };
+
+        // each pass adds dependencies of records selected in previous one
+        while selection.add_dependencies(&new_ids, records()) {}
+
+        assert_eq!(selection.node_ids, HashSet::from([node_id, ancestor_id]));
+        assert_eq!(selection.flow_ids, HashSet::from([flow_id]));
+        assert_eq!(selection.flow_step_ids, HashSet::from([flow_step_id]));
+        assert_eq!(selection.io_ids, HashSet::from([input_id]));
+    }
+
+    #[test]
+    fn test_dependencies_ignore_unselected_records() {
+        let node_id = Uuid::new_v4();
+        let flow_id = Uuid::new_v4();
+        let branch = Branch {
+            created_nodes: ids(&[node_id]),
+            created_flows: ids(&[flow_id]),
+            ..Default::default()
+        };
+        let records = DependencyRecords {
+            flows: vec![Flow {
+                id: flow_id,
+                node_id,
+                ..Default::default()
+            }],
+            ..Default::default()
+        };
+        let mut selection = MergeSelection::default();
+
+        assert!(!selection.add_dependencies(&NewIds::new(&branch), records));
+        assert!(selection.node_ids.is_empty());
+    }
+
+    #[test]
+    fn test_description_selection_pulls_new_object() {
+        let node_id = Uuid::new_v4();
+        let io_id = Uuid::new_v4();
+        let branch = Branch {
+            created_nodes: ids(&[node_id]),
+            edited_description_nodes: ids(&[node_id]),
+            edited_description_ios: ids(&[io_id]),
+            ..Default::default()
+        };
+        let mut selection = MergeSelection {
+            description_ids: HashSet::from([node_id, io_id]),
+            ..Default::default()
+        };
+
+        assert!(selection.add_dependencies(&NewIds::new(&branch), DependencyRecords::default()));
+        assert_eq!(selection.node_ids, HashSet::from([node_id]));
+        // io is not new in the branch, so it already exists in the original
+        assert!(selection.io_ids.is_empty());
+    }
+
+    #[test]
+    fn test_remove_selected() {
+        let merged_node_id = Uuid::new_v4();
+        let kept_node_id = Uuid::new_v4();
+        let merged_flow_step_id = Uuid::new_v4();
+        let kept_flow_step_id = Uuid::new_v4();
+        let mut branch = Branch {
+            created_nodes: ids(&[merged_node_id, kept_node_id]),
+            edited_nodes: ids(&[merged_node_id, kept_node_id]),
+            edited_description_nodes: ids(&[merged_node_id]),
+            created_flow_steps: ids(&[merged_flow_step_id, kept_flow_step_id]),
+            created_flow_step_nodes: Some(HashMap::from([
+                (merged_flow_step_id, HashSet::from([merged_node_id])),
+                (kept_flow_step_id, HashSet::from([kept_node_id])),
+            ])),
+            deleted_flow_step_nodes: Some(HashMap::from([(merged_flow_step_id, HashSet::from([kept_node_id]))])),
+            ..Default::default()
+        };
+        let selection = MergeSelection {
+            node_ids: HashSet::from([merged_node_id]),
+            flow_step_ids: HashSet::from([merged_flow_step_id]),
+            description_ids: HashSet::from([merged_node_id]),
+            ..Default::default()
+        };
+
+        selection.remove_selected(&mut branch);
+
+        assert_eq!(branch.created_nodes, ids(&[kept_node_id]));
+        assert_eq!(branch.edited_nodes, ids(&[merged_node_id, kept_node_id]));
+        assert_eq!(branch.edited_description_nodes, None);
+        assert_eq!(branch.created_flow_steps, ids(&[kept_flow_step_id]));
+        assert_eq!(
+            branch.created_flow_step_nodes,
+            Some(HashMap::from([(kept_flow_step_id, HashSet::from([kept_node_id]))]))
+        );
+        assert_eq!(branch.deleted_flow_step_nodes, None);
+    }
+
+    #[test]
+    fn test_selected_branch() {
+        let merged_node_id = Uuid::new_v4();
+        let kept_node_id = Uuid::new_v4();
+        let branch = Branch {
+            created_nodes: ids(&[merged_node_id, kept_node_id]),
+            edited_nodes: ids(&[merged_node_id, kept_node_id]),
+            deleted_ios: ids(&[Uuid::new_v4()]),
+            conflict: Some(Conflict {
+                deleted_ancestors: ids(&[kept_node_id]),
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        let selection = MergeSelection {
+            node_ids: HashSet::from([merged_node_id]),
+            ..Default::default()
+        };
+
+        let selected_branch = selection.selected_branch(&branch);
+
+        assert_eq!(selected_branch.created_nodes, ids(&[merged_node_id]));
+        assert_eq!(selected_branch.edited_nodes, ids(&[merged_node_id]));
+        assert_eq!(selected_branch.deleted_ios, None);
+        assert!(selected_branch.conflict.is_none());
+        assert_eq!(branch.created_nodes, ids(&[merged_node_id, kept_node_id]));
+    }
+}
@@ -0,0 +1,1720 @@
+use std::collections::{HashMap, HashSet};
+use std::hash::Hash;
+
+use charybdis::batch::ModelBatch;
+use charybdis::operations::{Find, Update};
+use charybdis::types::{Frozen, Map, Set, Uuid};
+use log::{error, warn};
+use scylla::client::caching_session::CachingSession;
+use serde::{Deserialize, Serialize};
+
+use crate::api::data::RequestData;
+use crate::api::types::ActionTypes;
+use crate::errors::NodecosmosError;
+use crate::models::branch::merge::MergeError;
+use crate::models::branch::{Branch, BranchStatus, ParentBranch, ParentIdBranch};
+use crate::models::description::Description;
+use crate::models::flow::Flow;
+use crate::models::flow_step::FlowStep;
+use crate::models::io::Io;
+use crate::models::node::Node;
+use crate::models::node_descendant::NodeDescendant;
+use crate::models::recovery::{RecoveryLog, RecoveryObjectType};
+use crate::models::saga::{saga_steps, Saga, SagaStep};
+use crate::models::traits::{Merge, ObjectType};
+use crate::models::udts::{Conflict, TextChange};
+use crate::models::workflow::Workflow;
+
+/// Finds all records of the given model within the branch partition.
+macro_rules! find_partition {
+    ($model:ident, $db_session:expr, $branch_id:expr) => {
+        $model::find_by_branch_id($branch_id)
+            .execute($db_session)
+            .await?
+            .try_collect()
+            .await?
+    };
+}
+
+/// Writes layered records into the parent branch partition.
+macro_rules! write_records {
+    ($model:ident, $db_session:expr, $inserted:expr, $deleted:expr) => {
+        $model::unlogged_batch()
+            .chunked_delete($db_session, &$deleted, crate::constants::BATCH_CHUNK_SIZE)
+            .await
+            .map_err(NodecosmosError::from)?;
+        $model::unlogged_batch()
+            .chunked_insert($db_session, &$inserted, crate::constants::BATCH_CHUNK_SIZE)
+            .await
+            .map_err(NodecosmosError::from)?;
+    };
+}
+
+impl ParentBranch {
+    fn is_open(&self) -> bool {
+        self.status == Some(BranchStatus::Open.to_string())
+    }
+
+    fn deletes(&self, object_type: ObjectType, id: Uuid) -> bool {
+        let deleted_ids = match object_type {
+            ObjectType::Node => &self.deleted_nodes,
+            ObjectType::Flow => &self.deleted_flows,
+            ObjectType::FlowStep => &self.deleted_flow_steps,
+            ObjectType::Io => &self.deleted_ios,
+            ObjectType::Workflow => &None,
+        };
+
+        deleted_ids.as_ref().is_some_and(|ids| ids.contains(&id))
+    }
+}
+
+/// Parents up to the first one that is not open, as the stack ends there.
+fn open_parents(parents: Vec<ParentBranch>) -> Vec<ParentBranch> {
+    parents.into_iter().take_while(ParentBranch::is_open).collect()
+}
+
+/// Objects deleted within a parent branch, on their own or along with their node, are not read from the layers
+/// below it. `parents` are the parent branches above the layer the object was read from.
+pub fn validate_not_deleted(
+    parents: &[ParentBranch],
+    object_type: ObjectType,
+    id: Uuid,
+    node_id: Uuid,
+) -> Result<(), NodecosmosError> {
+    let parent = parents
+        .iter()
+        .find(|parent| parent.deletes(object_type, id) || parent.deletes(ObjectType::Node, node_id));
+
+    match parent {
+        Some(parent) => Err(NodecosmosError::NotFound(format!(
+            "{} {} is deleted within branch {}",
+            object_type, id, parent.id
+        ))),
+        None => Ok(()),
+    }
+}
+
+/// Record read through the branch, its parent branches and the original.
+pub trait LayerRecord {
+    fn layer_id(&self) -> Uuid;
+
+    fn set_layer_branch_id(&mut self, branch_id: Uuid);
+
+    /// Whether the object was deleted within the parent branch, on its own or along with its node.
+    fn is_deleted_within(&self, parent: &ParentBranch) -> bool;
+
+    /// Applies the lower layer record to the record of the upper layer that shadows it.
+    fn merge_lower(&mut self, _lower: &Self) {}
+
+    /// Drops references to objects deleted within the parent branch from the lower layer record.
+    fn apply_parent_deletions(&mut self, _parent: &ParentBranch) {}
+}
+
+/// Records of a single partition. `parent` is set for the partitions of parent branches.
+pub struct BranchLayer<T> {
+    pub records: Vec<T>,
+    pub parent: Option<ParentBranch>,
+}
+
+impl<T> BranchLayer<T> {
+    pub fn new(records: Vec<T>) -> Self {
+        Self { records, parent: None }
+    }
+
+    pub fn parent(records: Vec<T>, parent: ParentBranch) -> Self {
+        Self {
+            records,
+            parent: Some(parent),
+        }
+    }
+}
+
+/// Combines layers of the branch, its parent branches and the original, ordered from the branch down. Records of the
+/// upper layers shadow the lower ones and objects deleted within a parent branch are not read from its layer or
+/// the layers below it.
+pub fn combine_layers<T: LayerRecord>(layers: Vec<BranchLayer<T>>, branch_id: Uuid) -> Vec<T> {
+    let mut parents: Vec<ParentBranch> = vec![];
+    let mut index_by_id: HashMap<Uuid, usize> = HashMap::new();
+    let mut records: Vec<T> = vec![];
+
+    for layer in layers {
+        parents.extend(layer.parent);
+
+        for mut record in layer.records {
+            if parents.iter().any(|parent| record.is_deleted_within(parent)) {
+                continue;
+            }
+
+            for parent in &parents {
+                record.apply_parent_deletions(parent);
+            }
+
+            match index_by_id.get(&record.layer_id()) {
+                Some(&index) => records[index].merge_lower(&record),
+                None => {
+                    record.set_layer_branch_id(branch_id);
+                    index_by_id.insert(record.layer_id(), records.len());
+                    records.push(record);
+                }
+            }
+        }
+    }
+
+    records
+}
+
+impl LayerRecord for Flow {
+    fn layer_id(&self) -> Uuid {
+        self.id
+    }
+
+    fn set_layer_branch_id(&mut self, branch_id: Uuid) {
+        self.branch_id = branch_id;
+    }
+
+    fn is_deleted_within(&self, parent: &ParentBranch) -> bool {
+        parent.deletes(ObjectType::Flow, self.id) || parent.deletes(ObjectType::Node, self.node_id)
+    }
+}
+
+impl LayerRecord for FlowStep {
+    fn layer_id(&self) -> Uuid {
+        self.id
+    }
+
+    fn set_layer_branch_id(&mut self, branch_id: Uuid) {
+        self.branch_id = branch_id;
+    }
+
+    fn is_deleted_within(&self, parent: &ParentBranch) -> bool {
+        parent.deletes(ObjectType::FlowStep, self.id)
+            || parent.deletes(ObjectType::Flow, self.flow_id)
+            || parent.deletes(ObjectType::Node, self.node_id)
+    }
+
+    fn merge_lower(&mut self, lower: &Self) {
+        self.merge_original_inputs(lower);
+        self.merge_original_nodes(lower);
+        self.merge_original_outputs(lower);
+    }
+
+    fn apply_parent_deletions(&mut self, parent: &ParentBranch) {
+        if let Some(node_ids) = &mut self.node_ids {
+            node_ids.retain(|id| !parent.deletes(ObjectType::Node, *id));
+        }
+
+        for ids_by_node_id in [&mut self.input_ids_by_node_id, &mut self.output_ids_by_node_id]
+            .into_iter()
+            .flatten()
+        {
+            ids_by_node_id.retain(|node_id, _| !parent.deletes(ObjectType::Node, *node_id));
+
+            for ids in ids_by_node_id.values_mut() {
+                ids.retain(|id| !parent.deletes(ObjectType::Io, *id));
+            }
+        }
+    }
+}
+
+impl LayerRecord for Io {
+    fn layer_id(&self) -> Uuid {
+        self.id
+    }
+
+    fn set_layer_branch_id(&mut self, branch_id: Uuid) {
+        self.branch_id = branch_id;
+    }
+
+    fn is_deleted_within(&self, parent: &ParentBranch) -> bool {
+        parent.deletes(ObjectType::Io, self.id) || parent.deletes(ObjectType::Node, self.node_id)
+    }
+}
+
+impl LayerRecord for Workflow {
+    fn layer_id(&self) -> Uuid {
+        self.node_id
+    }
+
+    fn set_layer_branch_id(&mut self, branch_id: Uuid) {
+        self.branch_id = branch_id;
+    }
+
+    fn is_deleted_within(&self, parent: &ParentBranch) -> bool {
+        parent.deletes(ObjectType::Node, self.node_id)
+    }
+
+    fn merge_lower(&mut self, lower: &Self) {
+        let mut initial_input_ids = lower.initial_input_ids.clone();
+
+        initial_input_ids.merge_unique(self.initial_input_ids.take());
+        self.initial_input_ids = initial_input_ids;
+    }
+
+    fn apply_parent_deletions(&mut self, parent: &ParentBranch) {
+        if let Some(initial_input_ids) = &mut self.initial_input_ids {
+            initial_input_ids
+                .retain(|id| !contains(&parent.deleted_initial_inputs, id) && !parent.deletes(ObjectType::Io, *id));
+        }
+    }
+}
+
+fn extend_ids<T: Eq + Hash>(parent: &mut Option<Set<T>>, child: Option<Set<T>>) {
+    if let Some(child) = child {
+        parent.get_or_insert_with(Set::new).extend(child);
+    }
+}
+
+fn extend_ids_by_key(parent: &mut Option<Map<Uuid, Frozen<Set<Uuid>>>>, child: Option<Map<Uuid, Frozen<Set<Uuid>>>>) {
+    if let Some(child) = child {
+        let parent = parent.get_or_insert_with(Map::new);
+
+        for (key, ids) in child {
+            match parent.get_mut(&key) {
+                Some(parent_ids) => parent_ids.extend(ids),
+                None => {
+                    parent.insert(key, ids);
+                }
+            }
+        }
+    }
+}
+
+fn extend_nested_ids(
+    parent: &mut Option<Map<Uuid, Frozen<Map<Uuid, Frozen<Set<Uuid>>>>>>,
+    child: Option<Map<Uuid, Frozen<Map<Uuid, Frozen<Set<Uuid>>>>>>,
+) {
+    if let Some(child) = child {
+        let parent = parent.get_or_insert_with(Map::new);
+
+        for (key, ids_by_node_id) in child {
+            let mut parent_ids_by_node_id = parent.remove(&key);
+
+            extend_ids_by_key(&mut parent_ids_by_node_id, Some(ids_by_node_id));
+
+            if let Some(parent_ids_by_node_id) = parent_ids_by_node_id {
+                parent.insert(key, parent_ids_by_node_id);
+            }
+        }
+    }
+}
+
+/// Objects deleted within the child are deleted within the parent, unless the parent created or restored them. Then
+/// they are just dropped from the parent changes.
+fn apply_deletions(
+    parent_ids: [&mut Option<Set<Uuid>>; 2],
+    deleted: &mut Option<Set<Uuid>>,
+    child: &Option<Set<Uuid>>,
+) {
+    let [created, restored] = parent_ids;
+
+    for id in child.iter().flatten() {
+        let is_created = created.as_mut().is_some_and(|ids| ids.remove(id));
+        let is_restored = restored.as_mut().is_some_and(|ids| ids.remove(id));
+
+        if !is_created && !is_restored {
+            deleted.get_or_insert_with(Set::new).insert(*id);
+        }
+    }
+}
+
+/// Child changes are applied on top of the parent ones, so the parent keeps its base text.
+fn extend_text_changes(
+    parent: &mut Option<Frozen<Map<Uuid, Frozen<TextChange>>>>,
+    child: Option<Frozen<Map<Uuid, Frozen<TextChange>>>>,
+) {
+    if let Some(child) = child {
+        let parent = parent.get_or_insert_with(Map::new);
+
+        for (object_id, change) in child {
+            match parent.get_mut(&object_id) {
+                Some(parent_change) => parent_change.new = change.new,
+                None => {
+                    parent.insert(object_id, change);
+                }
+            }
+        }
+    }
+}
+
+/// Record of a branch partition that is layered onto the parent branch partition.
+trait StackRecord: Clone {
+    /// Id of the object that the record belongs to.
+    fn object_id(&self) -> Uuid;
+
+    fn set_branch_id(&mut self, branch_id: Uuid);
+
+    /// Whether the object was deleted within the branch, on its own or along with its node, flow or flow step.
+    fn is_deleted(&self, branch: &Branch) -> bool;
+
+    /// Applies changes made within the child branch to the parent record.
+    fn apply_child(&mut self, child: &Self, child_branch: &Branch);
+}
+
+fn contains(ids: &Option<Set<Uuid>>, id: &Uuid) -> bool {
+    ids.as_ref().is_some_and(|ids| ids.contains(id))
+}
+
+impl StackRecord for Node {
+    fn object_id(&self) -> Uuid {
+        self.id
+    }
+
+    fn set_branch_id(&mut self, branch_id: Uuid) {
+        self.branch_id = branch_id;
+    }
+
+    fn is_deleted(&self, branch: &Branch) -> bool {
+        contains(&branch.deleted_nodes, &self.id)
+    }
+
+    fn apply_child(&mut self, child: &Self, child_branch: &Branch) {
+        if contains(&child_branch.edited_title_nodes, &self.id) {
+            self.title = child.title.clone();
+        }
+
+        let is_moved = child_branch.reordered_nodes.iter().flatten().any(|reorder_data| {
+            reorder_data.id == self.id
+                || child
+                    .ancestor_ids
+                    .as_ref()
+                    .is_some_and(|ids| ids.contains(&reorder_data.id))
+        });
+
+        if is_moved {
+            self.parent_id = child.parent_id;
+            self.order_index = child.order_index;
+            self.ancestor_ids = child.ancestor_ids.clone();
+        }
+    }
+}
+
+impl StackRecord for Workflow {
+    fn object_id(&self) -> Uuid {
+        self.node_id
+    }
+
+    fn set_branch_id(&mut self, branch_id: Uuid) {
+        self.branch_id = branch_id;
+    }
+
+    fn is_deleted(&self, branch: &Branch) -> bool {
+        contains(&branch.deleted_nodes, &self.node_id)
+    }
+
+    fn apply_child(&mut self, child: &Self, child_branch: &Branch) {
+        let initial_input_ids = self.initial_input_ids.get_or_insert_with(Vec::new);
+
+        initial_input_ids.retain(|id| !contains(&child_branch.deleted_initial_inputs, id));
+
+        for id in child.initial_input_ids.iter().flatten() {
+            if contains(&child_branch.created_initial_inputs, id) && !initial_input_ids.contains(id) {
+                initial_input_ids.push(*id);
+            }
+        }
+    }
+}
+
+impl StackRecord for Flow {
+    fn object_id(&self) -> Uuid {
+        self.id
+    }
+
+    fn set_branch_id(&mut self, branch_id: Uuid) {
+        self.branch_id = branch_id;
+    }
+
+    fn is_deleted(&self, branch: &Branch) -> bool {
+        contains(&branch.deleted_flows, &self.id) || contains(&branch.deleted_nodes, &self.node_id)
+    }
+
+    fn apply_child(&mut self, child: &Self, child_branch: &Branch) {
+        if contains(&child_branch.edited_title_flows, &self.id) {
+            self.title = child.title.clone();
+        }
+    }
+}
+
+impl StackRecord for FlowStep {
+    fn object_id(&self) -> Uuid {
+        self.id
+    }
+
+    fn set_branch_id(&mut self, branch_id: Uuid) {
+        self.branch_id = branch_id;
+    }
+
+    fn is_deleted(&self, branch: &Branch) -> bool {
+        contains(&branch.deleted_flow_steps, &self.id)
+            || contains(&branch.deleted_flows, &self.flow_id)
+            || contains(&branch.deleted_nodes, &self.node_id)
+    }
+
+    fn apply_child(&mut self, _child: &Self, child_branch: &Branch) {
+        self.apply_changes(
+            child_branch.created_flow_step_changes(self.id),
+            child_branch.deleted_flow_step_changes(self.id),
+        );
+    }
+}
+
+impl StackRecord for Io {
+    fn object_id(&self) -> Uuid {
+        self.id
+    }
+
+    fn set_branch_id(&mut self, branch_id: Uuid) {
+        self.branch_id = branch_id;
+    }
+
+    fn is_deleted(&self, branch: &Branch) -> bool {
+        contains(&branch.deleted_ios, &self.id) || contains(&branch.deleted_nodes, &self.node_id)
+    }
+
+    fn apply_child(&mut self, child: &Self, child_branch: &Branch) {
+        if contains(&child_branch.edited_title_ios, &self.id) {
+            self.title = child.title.clone();
+        }
+    }
+}
+
+/// Child records that the parent doesn't have are copied as they are. Records the parent has are kept and only the
+/// changes made within the child are applied to them, so a stale child copy doesn't overwrite newer parent edits.
+/// Returns records to insert into and delete from the parent partition.
+fn layer_records<T: StackRecord>(
+    child_records: Vec<T>,
+    parent_records: Vec<T>,
+    child_branch: &Branch,
+    parent_id: Uuid,
+) -> (Vec<T>, Vec<T>) {
+    let (deleted, parent_records): (Vec<T>, Vec<T>) = parent_records
+        .into_iter()
+        .partition(|record| record.is_deleted(child_branch));
+    let mut parent_by_id: HashMap<Uuid, T> = parent_records
+        .into_iter()
+        .map(|record| (record.object_id(), record))
+        .collect();
+    let mut inserted = vec![];
+
+    for mut record in child_records {
+        if record.is_deleted(child_branch) {
+            continue;
+        }
+
+        match parent_by_id.remove(&record.object_id()) {
+            Some(mut parent_record) => {
+                parent_record.apply_child(&record, child_branch);
+                inserted.push(parent_record);
+            }
+            None => {
+                record.set_branch_id(parent_id);
+                inserted.push(record);
+            }
+        }
+    }
+
+    (inserted, deleted)
+}
+
+/// Descendant rows of nodes created, restored, retitled or moved within the child replace the parent ones. Rows of
+/// deleted nodes are deleted.
+fn layer_descendants(
+    child_descendants: Vec<NodeDescendant>,
+    parent_descendants: Vec<NodeDescendant>,
+    child_nodes: &[Node],
+    child_branch: &Branch,
+    parent_id: Uuid,
+) -> (Vec<NodeDescendant>, Vec<NodeDescendant>) {
+    let reordered_ids: HashSet<Uuid> = child_branch
+        .reordered_nodes
+        .iter()
+        .flatten()
+        .map(|data| data.id)
+        .collect();
+    let mut changed_ids: HashSet<Uuid> = child_nodes
+        .iter()
+        .filter(|node| {
+            reordered_ids.contains(&node.id)
+                || node
+                    .ancestor_ids
+                    .as_ref()
+                    .is_some_and(|ids| !ids.is_disjoint(&reordered_ids))
+        })
+        .map(|node| node.id)
+        .collect();
+
+    changed_ids.extend(child_branch.created_nodes.iter().flatten());
+    changed_ids.extend(child_branch.restored_nodes.iter().flatten());
+    changed_ids.extend(child_branch.edited_title_nodes.iter().flatten());
+
+    let deleted = parent_descendants
+        .into_iter()
+        .filter(|descendant| {
+            changed_ids.contains(&descendant.id) || contains(&child_branch.deleted_nodes, &descendant.id)
+        })
+        .collect();
+    let inserted = child_descendants
+        .into_iter()
+        .filter(|descendant| {
+            changed_ids.contains(&descendant.id) && !contains(&child_branch.deleted_nodes, &descendant.id)
+        })
+        .map(|mut descendant| {
+            descendant.branch_id = parent_id;
+            descendant
+        })
+        .collect();
+
+    (inserted, deleted)
+}
+
+/// Records of the child and of the parent branch partition.
+#[derive(Default)]
+struct StackRecords {
+    child_nodes: Vec<Node>,
+    parent_nodes: Vec<Node>,
+    child_workflows: Vec<Workflow>,
+    parent_workflows: Vec<Workflow>,
+    child_flows: Vec<Flow>,
+    parent_flows: Vec<Flow>,
+    child_flow_steps: Vec<FlowStep>,
+    parent_flow_steps: Vec<FlowStep>,
+    child_ios: Vec<Io>,
+    parent_ios: Vec<Io>,
+    child_descriptions: Vec<Description>,
+    parent_descriptions: Vec<Description>,
+    child_descendants: Vec<NodeDescendant>,
+    parent_descendants: Vec<NodeDescendant>,
+}
+
+impl StackRecords {
+    async fn find(db_session: &CachingSession, child: &Branch, parent: &Branch) -> Result<Self, NodecosmosError> {
+        Ok(Self {
+            child_nodes: find_partition!(Node, db_session, child.id),
+            parent_nodes: find_partition!(Node, db_session, parent.id),
+            child_workflows: find_partition!(Workflow, db_session, child.id),
+            parent_workflows: find_partition!(Workflow, db_session, parent.id),
+            child_flows: find_partition!(Flow, db_session, child.id),
+            parent_flows: find_partition!(Flow, db_session, parent.id),
+            child_flow_steps: find_partition!(FlowStep, db_session, child.id),
+            parent_flow_steps: find_partition!(FlowStep, db_session, parent.id),
+            child_ios: find_partition!(Io, db_session, child.id),
+            parent_ios: find_partition!(Io, db_session, parent.id),
+            child_descriptions: find_partition!(Description, db_session, child.id),
+            parent_descriptions: find_partition!(Description, db_session, parent.id),
+            child_descendants: NodeDescendant::find_by_root_id_and_branch_id(child.root_id, child.id)
+                .execute(db_session)
+                .await?
+                .try_collect()
+                .await?,
+            parent_descendants: NodeDescendant::find_by_root_id_and_branch_id(parent.root_id, parent.id)
+                .execute(db_session)
+                .await?
+                .try_collect()
+                .await?,
+        })
+    }
+
+    /// Conflicts between the child and the changes its parent made after the child was created from it. Conflicts
+    /// with the original are checked once the parent is merged.
+    fn conflict(&self, child: &Branch, parent: &Branch) -> Option<Conflict> {
+        let mut conflict = Conflict::default();
+        let intersect = |ids: HashSet<Uuid>, deleted_ids: &Option<Set<Uuid>>| -> Option<Set<Uuid>> {
+            let conflicting: Set<Uuid> = ids.into_iter().filter(|id| contains(deleted_ids, id)).collect();
+
+            (!conflicting.is_empty()).then_some(conflicting)
+        };
+
+        let created_nodes = self
+            .child_nodes
+            .iter()
+            .filter(|node| contains(&child.created_nodes, &node.id));
+        let created_flows = self
+            .child_flows
+            .iter()
+            .filter(|flow| contains(&child.created_flows, &flow.id));
+        let created_flow_steps: Vec<&FlowStep> = self
+            .child_flow_steps
+            .iter()
+            .filter(|flow_step| contains(&child.created_flow_steps, &flow_step.id))
+            .collect();
+        let created_ios: Vec<&Io> = self
+            .child_ios
+            .iter()
+            .filter(|io| contains(&child.created_ios, &io.id))
+            .collect();
+
+        let ancestor_ids: HashSet<Uuid> = created_nodes
+            .flat_map(|node| node.ancestor_ids.iter().flatten().copied())
+            .collect();
+        conflict.deleted_ancestors = intersect(ancestor_ids, &parent.deleted_nodes);
+
+        let mut edited_node_ids: HashSet<Uuid> = [
+            &child.edited_nodes,
+            &child.edited_title_nodes,
+            &child.edited_description_nodes,
+        ]
+        .into_iter()
+        .flatten()
+        .flatten()
+        .copied()
+        .collect();
+        edited_node_ids.extend(child.reordered_nodes.iter().flatten().map(|data| data.id));
+        edited_node_ids.extend(created_flows.map(|flow| flow.node_id));
+        edited_node_ids.extend(created_ios.iter().map(|io| io.node_id));
+        conflict.deleted_edited_nodes = intersect(edited_node_ids, &parent.deleted_nodes);
+
+        let mut edited_flow_ids: HashSet<Uuid> = [&child.edited_title_flows, &child.edited_description_flows]
+            .into_iter()
+            .flatten()
+            .flatten()
+            .copied()
+            .collect();
+        edited_flow_ids.extend(created_flow_steps.iter().map(|flow_step| flow_step.flow_id));
+        conflict.deleted_edited_flows = intersect(edited_flow_ids, &parent.deleted_flows);
+
+        let mut edited_flow_step_ids: HashSet<Uuid> =
+            child.edited_description_flow_steps.iter().flatten().copied().collect();
+        edited_flow_step_ids.extend(
+            [&child.created_flow_step_nodes, &child.deleted_flow_step_nodes]
+                .into_iter()
+                .flatten()
+                .flat_map(|changes| changes.keys().copied()),
+        );
+        edited_flow_step_ids.extend(
+            [
+                &child.created_flow_step_inputs_by_node,
+                &child.deleted_flow_step_inputs_by_node,
+                &child.created_flow_step_outputs_by_node,
+                &child.deleted_flow_step_outputs_by_node,
+            ]
+            .into_iter()
+            .flatten()
+            .flat_map(|changes| changes.keys().copied()),
+        );
+        edited_flow_step_ids.extend(created_ios.iter().filter_map(|io| io.flow_step_id));
+        conflict.deleted_edited_flow_steps = intersect(edited_flow_step_ids, &parent.deleted_flow_steps);
+
+        let edited_io_ids: HashSet<Uuid> = [&child.edited_title_ios, &child.edited_description_ios]
+            .into_iter()
+            .flatten()
+            .flatten()
+            .copied()
+            .collect();
+        conflict.deleted_edited_ios = intersect(edited_io_ids, &parent.deleted_ios);
+
+        let conflicting_flow_steps: Set<Uuid> = created_flow_steps
+            .iter()
+            .filter(|flow_step| {
+                self.parent_flow_steps.iter().any(|parent_flow_step| {
+                    parent_flow_step.id != flow_step.id
+                        && parent_flow_step.flow_id == flow_step.flow_id
+                        && parent_flow_step.step_index == flow_step.step_index
+                        && !parent_flow_step.is_deleted(child)
+                })
+            })
+            .map(|flow_step| flow_step.id)
+            .collect();
+        conflict.conflicting_flow_steps = (!conflicting_flow_steps.is_empty()).then_some(conflicting_flow_steps);
+
+        // title was changed within the parent since the child edited it
+        let edited_titles: Set<Uuid> = child
+            .title_change_by_object
+            .iter()
+            .flatten()
+            .filter(|(id, change)| {
+                parent
+                    .title_change_by_object
+                    .as_ref()
+                    .and_then(|changes| changes.get(id))
+                    .is_some_and(|parent_change| parent_change.new != change.old)
+            })
+            .map(|(id, _)| *id)
+            .collect();
+        conflict.edited_titles = (!edited_titles.is_empty()).then_some(edited_titles);
+
+        if conflict == Conflict::default() {
+            return None;
+        }
+
+        Some(conflict)
+    }
+
+    /// Edited descriptions are merged into the parent ones like on merge into the original.
+    async fn layer_descriptions(
+        child_descriptions: Vec<Description>,
+        parent_descriptions: Vec<Description>,
+        child_branch: &Branch,
+        parent_id: Uuid,
+    ) -> Result<(Vec<Description>, Vec<Description>), NodecosmosError> {
+        let edited_ids = child_branch.all_edited_description_ids();
+        let deleted_ids = child_branch.all_deleted_object_ids();
+        let is_deleted = |description: &Description| {
+            deleted_ids.contains(&description.object_id) || contains(&child_branch.deleted_nodes, &description.node_id)
+        };
+        let (deleted, parent_descriptions): (Vec<Description>, Vec<Description>) =
+            parent_descriptions.into_iter().partition(is_deleted);
+        let mut parent_by_id: HashMap<Uuid, Description> = parent_descriptions
+            .into_iter()
+            .map(|description| (description.object_id, description))
+            .collect();
+        let mut inserted = vec![];
+
+        for mut description in child_descriptions {
+            if is_deleted(&description) {
+                continue;
+            }
+
+            match parent_by_id.remove(&description.object_id) {
+                Some(mut parent_description) => {
+                    if edited_ids.contains(&description.object_id) {
+                        parent_description.merge(&description).await?;
+                        inserted.push(parent_description);
+                    }
+                }
+                None => {
+                    description.branch_id = parent_id;
+                    inserted.push(description);
+                }
+            }
+        }
+
+        Ok((inserted, deleted))
+    }
+}
+
+saga_steps! {
+    pub enum ParentMergeStep {
+        WriteNodes = 1,
+        WriteWorkflows = 2,
+        WriteFlows = 3,
+        WriteFlowSteps = 4,
+        WriteIos = 5,
+        WriteDescriptions = 6,
+        WriteDescendants = 7,
+        UpdateParent = 8,
+        Finish = 9,
+        AfterFinish = 10,
+    }
+}
+
+/// Records written into the parent partition. `replaced` are the parent records that the inserted ones
+/// overwrite, so they can be restored along with the deleted ones.
+#[derive(Serialize, Deserialize, Default)]
+struct ParentWrite<T> {
+    inserted: Vec<T>,
+    deleted: Vec<T>,
+    replaced: Vec<T>,
+}
+
+impl<T: StackRecord> ParentWrite<T> {
+    fn layer(child_records: Vec<T>, parent_records: Vec<T>, child_branch: &Branch, parent_id: Uuid) -> Self {
+        let (inserted, deleted) = layer_records(child_records, parent_records.clone(), child_branch, parent_id);
+        let replaced = replaced_records(&inserted, parent_records, StackRecord::object_id);
+
+        Self {
+            inserted,
+            deleted,
+            replaced,
+        }
+    }
+}
+
+fn replaced_records<T>(inserted: &[T], parent_records: Vec<T>, object_id: impl Fn(&T) -> Uuid) -> Vec<T> {
+    let inserted_ids: HashSet<Uuid> = inserted.iter().map(&object_id).collect();
+
+    parent_records
+        .into_iter()
+        .filter(|record| inserted_ids.contains(&object_id(record)))
+        .collect()
+}
+
+/// Writes layered records into the parent partition.
+macro_rules! write_parent {
+    ($model:ident, $db_session:expr, $write:expr) => {
+        write_records!($model, $db_session, $write.inserted, $write.deleted);
+    };
+}
+
+/// Deletes inserted records from the parent partition and restores the replaced and deleted ones.
+macro_rules! undo_write_parent {
+    ($model:ident, $db_session:expr, $write:expr) => {
+        $model::unlogged_batch()
+            .chunked_delete($db_session, &$write.inserted, crate::constants::BATCH_CHUNK_SIZE)
+            .await
+            .map_err(NodecosmosError::from)?;
+        $model::unlogged_batch()
+            .chunked_insert($db_session, &$write.replaced, crate::constants::BATCH_CHUNK_SIZE)
+            .await
+            .map_err(NodecosmosError::from)?;
+        $model::unlogged_batch()
+            .chunked_insert($db_session, &$write.deleted, crate::constants::BATCH_CHUNK_SIZE)
+            .await
+            .map_err(NodecosmosError::from)?;
+    };
+}
+
+/// Merge of a stacked branch into its parent branch. Records are written table by table and the parent keeps its
+/// previous changes in `parent`, so a failed merge is compensated like the merge into the original.
+#[derive(Serialize, Deserialize)]
+pub struct ParentMerge {
+    branch: Branch,
+    parent: Branch,
+    step: ParentMergeStep,
+    nodes: ParentWrite<Node>,
+    workflows: ParentWrite<Workflow>,
+    flows: ParentWrite<Flow>,
+    flow_steps: ParentWrite<FlowStep>,
+    ios: ParentWrite<Io>,
+    descriptions: ParentWrite<Description>,
+    descendants: ParentWrite<NodeDescendant>,
+}
+
+impl ParentMerge {
+    async fn new(branch: Branch, parent: Branch, records: StackRecords) -> Result<Self, Box<MergeError>> {
+        let (descendants, deleted_descendants) = layer_descendants(
+            records.child_descendants,
+            records.parent_descendants,
+            &records.child_nodes,
+            &branch,
+            parent.id,
+        );
+        let parent_descriptions = records.parent_descriptions.clone();
+        let descriptions = match StackRecords::layer_descriptions(
+            records.child_descriptions,
+            records.parent_descriptions,
+            &branch,
+            parent.id,
+        )
+        .await
+        {
+            Ok((inserted, deleted)) => {
+                let replaced = replaced_records(&inserted, parent_descriptions, |description| description.object_id);
+
+                ParentWrite {
+                    inserted,
+                    deleted,
+                    replaced,
+                }
+            }
+            Err(e) => return Err(Box::new(MergeError { inner: e, branch })),
+        };
+
+        Ok(Self {
+            nodes: ParentWrite::layer(records.child_nodes, records.parent_nodes, &branch, parent.id),
+            workflows: ParentWrite::layer(records.child_workflows, records.parent_workflows, &branch, parent.id),
+            flows: ParentWrite::layer(records.child_flows, records.parent_flows, &branch, parent.id),
+            flow_steps: ParentWrite::layer(records.child_flow_steps, records.parent_flow_steps, &branch, parent.id),
+            ios: ParentWrite::layer(records.child_ios, records.parent_ios, &branch, parent.id),
+            descriptions,
+            // descendant rows of changed nodes are deleted along with the ones of deleted nodes
+            descendants: ParentWrite {
+                inserted: descendants,
+                deleted: deleted_descendants,
+                replaced: vec![],
+            },
+            branch,
+            parent,
+            step: ParentMergeStep::Start,
+        })
+    }
+
+    async fn run(mut self, data: &RequestData) -> Result<Branch, MergeError> {
+        match self.run_saga(data).await {
+            Ok(_) => Ok(self.branch),
+            Err(e) => match self.compensate_saga(data).await {
+                Ok(_) => {
+                    warn!("ParentMerge::Recovered from error: {}", e);
+
+                    Err(MergeError {
+                        inner: e,
+                        branch: self.branch,
+                    })
+                }
+                Err(recovery_err) => {
+                    error!("ParentMerge::Failed to recover: {}", recovery_err);
+
+                    self.branch.status = Some(BranchStatus::RecoveryFailed.to_string());
+
+                    Err(MergeError {
+                        inner: NodecosmosError::FatalMergeError(format!("Failed to merge and recover: {}", e)),
+                        branch: self.branch,
+                    })
+                }
+            },
+        }
+    }
+
+    async fn unlock_resource(&mut self, data: &RequestData) -> Result<(), NodecosmosError> {
+        let node = self.branch.node(data.db_session()).await?;
+
+        data.resource_locker()
+            .unlock_resource(node.root_id, node.branch_id)
+            .await?;
+        data.resource_locker()
+            .unlock_resource_action(ActionTypes::Merge, node.root_id, node.branch_id)
+            .await?;
+
+        Ok(())
+    }
+}
+
+impl RecoveryLog<'_> for ParentMerge {
+    fn rec_id(&self) -> Uuid {
+        self.branch.id
+    }
+
+    fn rec_branch_id(&self) -> Uuid {
+        self.branch.id
+    }
+
+    fn rec_object_type(&self) -> RecoveryObjectType {
+        RecoveryObjectType::ParentMerge
+    }
+
+    fn set_step(&mut self, step: i8) -> Result<(), NodecosmosError> {
+        self.step = ParentMergeStep::try_from_value(step)?;
+
+        Ok(())
+    }
+
+    async fn recover_from_log(&mut self, data: &RequestData) -> Result<(), NodecosmosError> {
+        self.compensate_saga(data).await.map_err(|e| {
+            log::error!(
+                "Fatal ParentMerge Error: recover_from_log failed for branch: {}\n! ERROR: {:?}",
+                self.branch.id,
+                e
+            );
+            NodecosmosError::FatalMergeError(format!("Error recovering from log: {:?}", e))
+        })?;
+
+        let _ = self.unlock_resource(data).await.map_err(|e| {
+            log::error!(
+                "ParentMerge Error: unlock_resource failed for branch: {}\n! ERROR: {:?}",
+                self.branch.id,
+                e
+            );
+        });
+
+        Ok(())
+    }
+}
+
+impl Saga for ParentMerge {
+    type Step = ParentMergeStep;
+
+    fn step(&self) -> ParentMergeStep {
+        self.step
+    }
+
+    fn set_saga_step(&mut self, step: ParentMergeStep) {
+        self.step = step;
+    }
+
+    async fn execute(&mut self, data: &RequestData, step: ParentMergeStep) -> Result<(), NodecosmosError> {
+        let db_session = data.db_session();
+
+        match step {
+            ParentMergeStep::WriteNodes => {
+                write_parent!(Node, db_session, self.nodes);
+            }
+            ParentMergeStep::WriteWorkflows => {
+                write_parent!(Workflow, db_session, self.workflows);
+            }
+            ParentMergeStep::WriteFlows => {
+                write_parent!(Flow, db_session, self.flows);
+            }
+            ParentMergeStep::WriteFlowSteps => {
+                write_parent!(FlowStep, db_session, self.flow_steps);
+            }
+            ParentMergeStep::WriteIos => {
+                write_parent!(Io, db_session, self.ios);
+            }
+            ParentMergeStep::WriteDescriptions => {
+                write_parent!(Description, db_session, self.descriptions);
+            }
+            ParentMergeStep::WriteDescendants => {
+                write_parent!(NodeDescendant, db_session, self.descendants);
+            }
+            ParentMergeStep::UpdateParent => {
+                let mut parent = self.parent.clone();
+
+                self.branch.extend_parent_changes(&mut parent);
+                parent.update().execute(db_session).await?;
+            }
+            // log and placeholder steps are handled by the saga executor
+            ParentMergeStep::BeforeStart
+            | ParentMergeStep::Start
+            | ParentMergeStep::Finish
+            | ParentMergeStep::AfterFinish => (),
+        }
+
+        Ok(())
+    }
+
+    async fn compensate(&mut self, data: &RequestData, step: ParentMergeStep) -> Result<(), NodecosmosError> {
+        let db_session = data.db_session();
+
+        match step {
+            ParentMergeStep::WriteNodes => {
+                undo_write_parent!(Node, db_session, self.nodes);
+            }
+            ParentMergeStep::WriteWorkflows => {
+                undo_write_parent!(Workflow, db_session, self.workflows);
+            }
+            ParentMergeStep::WriteFlows => {
+                undo_write_parent!(Flow, db_session, self.flows);
+            }
+            ParentMergeStep::WriteFlowSteps => {
+                undo_write_parent!(FlowStep, db_session, self.flow_steps);
+            }
+            ParentMergeStep::WriteIos => {
+                undo_write_parent!(Io, db_session, self.ios);
+            }
+            ParentMergeStep::WriteDescriptions => {
+                undo_write_parent!(Description, db_session, self.descriptions);
+            }
+            ParentMergeStep::WriteDescendants => {
+                undo_write_parent!(NodeDescendant, db_session, self.descendants);
+            }
+            ParentMergeStep::UpdateParent => {
+                self.parent.update().execute(db_session).await?;
+            }
+            // log and placeholder steps are handled by the saga executor
+            ParentMergeStep::BeforeStart
+            | ParentMergeStep::Start
+            | ParentMergeStep::Finish
+            | ParentMergeStep::AfterFinish => (),
+        }
+
+        Ok(())
+    }
+}
+
+impl Branch {
+    /// Open branches this branch is stacked on, starting with its direct parent. Changes of a merged parent are
+    /// already in the original and changes of a closed one never reach it, so the walk stops at the first parent
+    /// that is not open.
+    pub async fn parent_branches(
+        db_session: &CachingSession,
+        branch_id: Uuid,
+    ) -> Result<Vec<ParentBranch>, NodecosmosError> {
+        // most branches are not stacked, so only the parent id is read before walking the parents
+        let mut next_id = ParentIdBranch {
+            id: branch_id,
+            ..Default::default()
+        }
+        .maybe_find_by_primary_key()
+        .execute(db_session)
+        .await?
+        .and_then(|branch| branch.parent_id);
+        let mut parents: Vec<ParentBranch> = vec![];
+
+        while let Some(parent_id) = next_id {
+            if parent_id == branch_id || parents.iter().any(|parent| parent.id == parent_id) {
+                break;
+            }
+
+            let parent = ParentBranch {
+                id: parent_id,
+                ..Default::default()
+            }
+            .maybe_find_by_primary_key()
+            .execute(db_session)
+            .await?;
+
+            match parent {
+                Some(parent) if parent.is_open() => {
+                    next_id = parent.parent_id;
+                    parents.push(parent);
+                }
+                Some(parent) => {
+                    parents.push(parent);
+                    break;
+                }
+                None => break,
+            }
+        }
+
+        Ok(open_parents(parents))
+    }
+
+    /// Ids of the branches this branch is stacked on, starting with its direct parent.
+    pub async fn parent_branch_ids(db_session: &CachingSession, branch_id: Uuid) -> Result<Vec<Uuid>, NodecosmosError> {
+        let parents = Self::parent_branches(db_session, branch_id).await?;
+
+        Ok(parents.into_iter().map(|parent| parent.id).collect())
+    }
+
+    /// Parent branch that the branch merges into. Once the parent is merged or closed, the branch merges
+    /// into the original.
+    pub async fn open_parent(&self, db_session: &CachingSession) -> Result<Option<Branch>, NodecosmosError> {
+        let parent_id = match self.parent_id {
+            Some(parent_id) => parent_id,
+            None => return Ok(None),
+        };
+
+        let parent = Branch {
+            id: parent_id,
+            ..Default::default()
+        }
+        .maybe_find_by_primary_key()
+        .execute(db_session)
+        .await?;
+
+        Ok(parent.filter(|parent| parent.status == Some(BranchStatus::Open.to_string())))
+    }
+
+    /// Selection merges and previews are made against the original, so they are not available for branches that
+    /// merge into their parent.
+    pub async fn validate_no_open_parent(&self, db_session: &CachingSession) -> Result<(), NodecosmosError> {
+        if self.open_parent(db_session).await?.is_some() {
+            return Err(NodecosmosError::PreconditionFailed(
+                "Branch merges into its parent branch, so only complete merge is supported",
+            ));
+        }
+
+        Ok(())
+    }
+
+    /// Conflicts with the parent branch.
+    pub async fn parent_conflict(
+        &self,
+        db_session: &CachingSession,
+        parent: &Branch,
+    ) -> Result<Option<Conflict>, NodecosmosError> {
+        let records = StackRecords::find(db_session, self, parent).await?;
+
+        Ok(records.conflict(self, parent))
+    }
+
+    /// Checks conflicts with the parent branch and persists them on the branch.
+    pub async fn check_parent_conflicts(
+        mut self,
+        db_session: &CachingSession,
+        parent: &Branch,
+    ) -> Result<Self, MergeError> {
+        self.conflict = match self.parent_conflict(db_session, parent).await {
+            Ok(conflict) => conflict,
+            Err(e) => return Err(MergeError { inner: e, branch: self }),
+        };
+
+        if let Err(e) = self.update().execute(db_session).await {
+            return Err(MergeError {
+                inner: NodecosmosError::from(e),
+                branch: self,
+            });
+        }
+
+        if self.conflict.is_some() {
+            return Err(MergeError {
+                inner: NodecosmosError::Conflict("Conflict detected".to_string()),
+                branch: self,
+            });
+        }
+
+        Ok(self)
+    }
+
+    /// Moves records and changes of the branch into its parent branch.
+    pub async fn merge_into_parent(mut self, data: &RequestData, parent: Branch) -> Result<Self, MergeError> {
+        let db_session = data.db_session();
+        let records = match StackRecords::find(db_session, &self, &parent).await {
+            Ok(records) => records,
+            Err(e) => return Err(MergeError { inner: e, branch: self }),
+        };
+
+        self.conflict = records.conflict(&self, &parent);
+
+        if self.conflict.is_some() {
+            let inner = match self.update().execute(db_session).await {
+                Ok(_) => NodecosmosError::Conflict("Conflict detected".to_string()),
+                Err(e) => NodecosmosError::from(e),
+            };
+
+            return Err(MergeError { inner, branch: self });
+        }
+
+        let parent_merge = match ParentMerge::new(self, parent, records).await {
+            Ok(parent_merge) => parent_merge,
+            Err(merge_error) => return Err(*merge_error),
+        };
+
+        let mut branch = parent_merge.run(data).await?;
+
+        branch.status = Some(BranchStatus::Merged.to_string());
+
+        if let Err(e) = branch.update().execute(db_session).await {
+            return Err(MergeError {
+                inner: NodecosmosError::from(e),
+                branch,
+            });
+        }
+
+        Ok(branch)
+    }
+
+    fn extend_parent_changes(&self, parent: &mut Branch) {
+        let child = self.clone();
+
+        extend_ids(&mut parent.created_nodes, child.created_nodes);
+        extend_ids(&mut parent.restored_nodes, child.restored_nodes);
+        extend_ids(&mut parent.edited_title_nodes, child.edited_title_nodes);
+        extend_ids(&mut parent.edited_description_nodes, child.edited_description_nodes);
+        extend_ids(&mut parent.edited_nodes, child.edited_nodes);
+        extend_ids(&mut parent.created_initial_inputs, child.created_initial_inputs);
+        extend_ids(&mut parent.deleted_initial_inputs, child.deleted_initial_inputs);
+
+        if let Some(child_reordered_nodes) = child.reordered_nodes {
+            let reordered_nodes = parent.reordered_nodes.get_or_insert_with(Vec::new);
+
+            for reorder_data in child_reordered_nodes {
+                reordered_nodes.retain(|parent_reorder_data| parent_reorder_data.id != reorder_data.id);
+                reordered_nodes.push(reorder_data);
+            }
+        }
+
+        extend_ids(&mut parent.created_flows, child.created_flows);
+        extend_ids(&mut parent.restored_flows, child.restored_flows);
+        extend_ids(&mut parent.edited_title_flows, child.edited_title_flows);
+        extend_ids(&mut parent.edited_description_flows, child.edited_description_flows);
+
+        extend_ids(&mut parent.created_flow_steps, child.created_flow_steps);
+        extend_ids(&mut parent.restored_flow_steps, child.restored_flow_steps);
+        extend_ids(&mut parent.kept_flow_steps, child.kept_flow_steps);
+        extend_ids(
+            &mut parent.edited_description_flow_steps,
+            child.edited_description_flow_steps,
+        );
+        extend_ids_by_key(&mut parent.created_flow_step_nodes, child.created_flow_step_nodes);
+        extend_ids_by_key(&mut parent.deleted_flow_step_nodes, child.deleted_flow_step_nodes);
+        extend_nested_ids(
+            &mut parent.created_flow_step_inputs_by_node,
+            child.created_flow_step_inputs_by_node,
+        );
+        extend_nested_ids(
+            &mut parent.deleted_flow_step_inputs_by_node,
+            child.deleted_flow_step_inputs_by_node,
+        );
+        extend_nested_ids(
+            &mut parent.created_flow_step_outputs_by_node,
+            child.created_flow_step_outputs_by_node,
+        );
+        extend_nested_ids(
+            &mut parent.deleted_flow_step_outputs_by_node,
+            child.deleted_flow_step_outputs_by_node,
+        );
+
+        extend_ids(&mut parent.created_ios, child.created_ios);
+        extend_ids(&mut parent.restored_ios, child.restored_ios);
+        extend_ids(&mut parent.edited_title_ios, child.edited_title_ios);
+        extend_ids(&mut parent.edited_description_ios, child.edited_description_ios);
+
+        extend_text_changes(&mut parent.title_change_by_object, child.title_change_by_object);
+        extend_text_changes(
+            &mut parent.description_change_by_object,
+            child.description_change_by_object,
+        );
+
+        apply_deletions(
+            [&mut parent.created_nodes, &mut parent.restored_nodes],
+            &mut parent.deleted_nodes,
+            &self.deleted_nodes,
+        );
+        apply_deletions(
+            [&mut parent.created_flows, &mut parent.restored_flows],
+            &mut parent.deleted_flows,
+            &self.deleted_flows,
+        );
+        apply_deletions(
+            [&mut parent.created_flow_steps, &mut parent.restored_flow_steps],
+            &mut parent.deleted_flow_steps,
+            &self.deleted_flow_steps,
+        );
+        apply_deletions(
+            [&mut parent.created_ios, &mut parent.restored_ios],
+            &mut parent.deleted_ios,
+            &self.deleted_ios,
+        );
+
+        if let Some(reordered_nodes) = &mut parent.reordered_nodes {
+            reordered_nodes.retain(|reorder_data| !contains(&parent.deleted_nodes, &reorder_data.id));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ids(ids: &[Uuid]) -> Option<Set<Uuid>> {
+        Some(ids.iter().copied().collect())
+    }
+
+    #[test]
+    fn test_conflict_with_parent_deletions_and_titles() {
+        let node_id = Uuid::new_v4();
+        let flow_id = Uuid::new_v4();
+        let created_node_id = Uuid::new_v4();
+        let child = Branch {
+            created_nodes: ids(&[created_node_id]),
+            edited_title_nodes: ids(&[node_id]),
+            created_flow_steps: ids(&[Uuid::new_v4()]),
+            title_change_by_object: Some(HashMap::from([(
+                node_id,
+                TextChange {
+                    old: "Base".to_string(),
+                    new: "Child".to_string(),
+                },
+            )])),
+            ..Default::default()
+        };
+        let parent = Branch {
+            deleted_nodes: ids(&[node_id]),
+            deleted_flows: ids(&[flow_id]),
+            title_change_by_object: Some(HashMap::from([(
+                node_id,
+                TextChange {
+                    old: "Base".to_string(),
+                    new: "Parent".to_string(),
+                },
+            )])),
+            ..Default::default()
+        };
+        let created_flow_step_id = *child.created_flow_steps.as_ref().unwrap().iter().next().unwrap();
+        let records = StackRecords {
+            child_nodes: vec![Node {
+                id: created_node_id,
+                ancestor_ids: ids(&[node_id]),
+                ..Default::default()
+            }],
+            child_flow_steps: vec![FlowStep {
+                id: created_flow_step_id,
+                flow_id,
+                ..Default::default()
+            }],
+            ..Default::default()
+        };
+
+        let conflict = records.conflict(&child, &parent).expect("conflict should be detected");
+
+        assert_eq!(conflict.deleted_ancestors, ids(&[node_id]));
+        assert_eq!(conflict.deleted_edited_nodes, ids(&[node_id]));
+        assert_eq!(conflict.deleted_edited_flows, ids(&[flow_id]));
+        assert_eq!(conflict.edited_titles, ids(&[node_id]));
+        assert!(records.conflict(&child, &Branch::default()).is_none());
+    }
+
+    #[test]
+    fn test_conflicting_flow_step_index() {
+        let flow_id = Uuid::new_v4();
+        let created_id = Uuid::new_v4();
+        let child = Branch {
+            created_flow_steps: ids(&[created_id]),
+            ..Default::default()
+        };
+        let records = StackRecords {
+            child_flow_steps: vec![FlowStep {
+                id: created_id,
+                flow_id,
+                ..Default::default()
+            }],
+            parent_flow_steps: vec![FlowStep {
+                id: Uuid::new_v4(),
+                flow_id,
+                ..Default::default()
+            }],
+            ..Default::default()
+        };
+
+        let conflict = records
+            .conflict(&child, &Branch::default())
+            .expect("conflict should be detected");
+
+        assert_eq!(conflict.conflicting_flow_steps, ids(&[created_id]));
+    }
+
+    #[test]
+    fn test_layer_records_keeps_newer_parent_edits() {
+        let id = Uuid::new_v4();
+        let parent_id = Uuid::new_v4();
+        let child_branch = Branch::default();
+        let child = Node {
+            id,
+            title: "Stale".to_string(),
+            ..Default::default()
+        };
+        let parent = Node {
+            id,
+            branch_id: parent_id,
+            title: "Newer".to_string(),
+            ..Default::default()
+        };
+
+        let (inserted, deleted) = layer_records(vec![child.clone()], vec![parent.clone()], &child_branch, parent_id);
+
+        assert_eq!(inserted[0].title, "Newer");
+        assert!(deleted.is_empty());
+
+        let child_branch = Branch {
+            edited_title_nodes: ids(&[id]),
+            ..Default::default()
+        };
+        let (inserted, _) = layer_records(vec![child], vec![parent], &child_branch, parent_id);
+
+        assert_eq!(inserted[0].title, "Stale");
+        assert_eq!(inserted[0].branch_id, parent_id);
+    }
+
+    #[test]
+    fn test_layer_records_deletes_child_deletions() {
+        let node_id = Uuid::new_v4();
+        let flow_id = Uuid::new_v4();
+        let parent_id = Uuid::new_v4();
+        let child_branch = Branch {
+            deleted_nodes: ids(&[node_id]),
+            ..Default::default()
+        };
+        let flow = Flow {
+            id: flow_id,
+            node_id,
+            branch_id: parent_id,
+            ..Default::default()
+        };
+
+        let (inserted, deleted) = layer_records(vec![flow.clone()], vec![flow], &child_branch, parent_id);
+
+        assert!(inserted.is_empty());
+        assert_eq!(deleted.len(), 1);
+        assert_eq!(deleted[0].id, flow_id);
+    }
+
+    #[test]
+    fn test_layer_flow_step_applies_child_changes() {
+        let id = Uuid::new_v4();
+        let kept_node_id = Uuid::new_v4();
+        let removed_node_id = Uuid::new_v4();
+        let added_node_id = Uuid::new_v4();
+        let child_branch = Branch {
+            created_flow_step_nodes: Some(HashMap::from([(id, ids(&[added_node_id]).unwrap())])),
+            deleted_flow_step_nodes: Some(HashMap::from([(id, ids(&[removed_node_id]).unwrap())])),
+            ..Default::default()
+        };
+        let parent = FlowStep {
+            id,
+            node_ids: Some(vec![kept_node_id, removed_node_id]),
+            ..Default::default()
+        };
+        let child = FlowStep {
+            id,
+            node_ids: Some(vec![removed_node_id, added_node_id]),
+            ..Default::default()
+        };
+
+        let (inserted, _) = layer_records(vec![child], vec![parent], &child_branch, Uuid::new_v4());
+
+        assert_eq!(inserted[0].node_ids, Some(vec![kept_node_id, added_node_id]));
+    }
+
+    #[test]
+    fn test_apply_deletions() {
+        let created_id = Uuid::new_v4();
+        let restored_id = Uuid::new_v4();
+        let original_id = Uuid::new_v4();
+        let mut created = ids(&[created_id]);
+        let mut restored = ids(&[restored_id]);
+        let mut deleted = None;
+
+        apply_deletions(
+            [&mut created, &mut restored],
+            &mut deleted,
+            &ids(&[created_id, restored_id, original_id]),
+        );
+
+        assert_eq!(created, ids(&[]));
+        assert_eq!(restored, ids(&[]));
+        assert_eq!(deleted, ids(&[original_id]));
+    }
+
+    #[test]
+    fn test_open_parents_stop_at_closed_parent() {
+        let parent = |status: BranchStatus| ParentBranch {
+            id: Uuid::new_v4(),
+            status: Some(status.to_string()),
+            ..Default::default()
+        };
+        let open_parent = parent(BranchStatus::Open);
+        let open_parent_id = open_parent.id;
+
+        let parents = open_parents(vec![
+            open_parent,
+            parent(BranchStatus::Closed),
+            parent(BranchStatus::Open),
+        ]);
+
+        assert_eq!(
+            parents.iter().map(|parent| parent.id).collect::<Vec<_>>(),
+            vec![open_parent_id]
+        );
+        assert!(open_parents(vec![parent(BranchStatus::Closed)]).is_empty());
+        assert!(open_parents(vec![parent(BranchStatus::Merged)]).is_empty());
+    }
+
+    #[test]
+    fn test_validate_not_deleted() {
+        let node_id = Uuid::new_v4();
+        let flow_id = Uuid::new_v4();
+        let parents = vec![
+            ParentBranch::default(),
+            ParentBranch {
+                deleted_flows: ids(&[flow_id]),
+                ..Default::default()
+            },
+        ];
+
+        assert!(validate_not_deleted(&parents[..1], ObjectType::Flow, flow_id, node_id).is_ok());
+        assert!(matches!(
+            validate_not_deleted(&parents, ObjectType::Flow, flow_id, node_id),
+            Err(NodecosmosError::NotFound(_))
+        ));
+
+        let parents = vec![ParentBranch {
+            deleted_nodes: ids(&[node_id]),
+            ..Default::default()
+        }];
+
+        assert!(matches!(
+            validate_not_deleted(&parents, ObjectType::Io, Uuid::new_v4(), node_id),
+            Err(NodecosmosError::NotFound(_))
+        ));
+    }
+
+    #[test]
+    fn test_combine_layers_reads_through_parents() {
+        let branch_id = Uuid::new_v4();
+        let parent_id = Uuid::new_v4();
+        let deleted_node_id = Uuid::new_v4();
+        let branched_flow_id = Uuid::new_v4();
+        let parent_flow_id = Uuid::new_v4();
+        let deleted_flow_id = Uuid::new_v4();
+        let original_flow_id = Uuid::new_v4();
+        let flow = |id: Uuid, branch_id: Uuid, node_id: Uuid, title: &str| Flow {
+            id,
+            branch_id,
+            node_id,
+            title: title.to_string(),
+            ..Default::default()
+        };
+        let node_id = Uuid::new_v4();
+        let layers = vec![
+            BranchLayer::new(vec![flow(branched_flow_id, branch_id, node_id, "Branch")]),
+            BranchLayer::parent(
+                vec![
+                    flow(branched_flow_id, parent_id, node_id, "Parent"),
+                    flow(parent_flow_id, parent_id, node_id, "Parent"),
+                ],
+                ParentBranch {
+                    id: parent_id,
+                    deleted_flows: ids(&[deleted_flow_id]),
+                    deleted_nodes: ids(&[deleted_node_id]),
+                    ..Default::default()
+                },
+            ),
+            BranchLayer::new(vec![
+                flow(deleted_flow_id, Uuid::new_v4(), node_id, "Original"),
+                flow(Uuid::new_v4(), Uuid::new_v4(), deleted_node_id, "Original"),
+                flow(original_flow_id, Uuid::new_v4(), node_id, "Original"),
+            ]),
+        ];
+
+        let flows = combine_layers(layers, branch_id);
+
+        assert_eq!(
+            flows
+                .iter()
+                .map(|flow| (flow.id, flow.title.as_str()))
+                .collect::<Vec<_>>(),
+            vec![
+                (branched_flow_id, "Branch"),
+                (parent_flow_id, "Parent"),
+                (original_flow_id, "Original")
+            ]
+        );
+        assert!(flows.iter().all(|flow| flow.branch_id == branch_id));
+    }
+
+    #[test]
+    fn test_combine_layers_applies_parent_deletions() {
+        let node_id = Uuid::new_v4();
+        let kept_input_id = Uuid::new_v4();
+        let parent_input_id = Uuid::new_v4();
+        let deleted_input_id = Uuid::new_v4();
+        let parent = ParentBranch {
+            id: Uuid::new_v4(),
+            deleted_initial_inputs: ids(&[deleted_input_id]),
+            ..Default::default()
+        };
+        let workflow = |initial_input_ids: Vec<Uuid>| Workflow {
+            node_id,
+            initial_input_ids: Some(initial_input_ids),
+            ..Default::default()
+        };
+        let layers = vec![
+            BranchLayer::new(vec![]),
+            BranchLayer::parent(vec![workflow(vec![parent_input_id])], parent),
+            BranchLayer::new(vec![workflow(vec![kept_input_id, deleted_input_id])]),
+        ];
+
+        let workflows = combine_layers(layers, Uuid::new_v4());
+
+        assert_eq!(workflows.len(), 1);
+        assert_eq!(
+            workflows[0].initial_input_ids,
+            Some(vec![kept_input_id, parent_input_id])
+        );
+    }
+
+    #[test]
+    fn test_parent_write_keeps_replaced_records() {
+        let parent_id = Uuid::new_v4();
+        let edited_io_id = Uuid::new_v4();
+        let created_io_id = Uuid::new_v4();
+        let deleted_io_id = Uuid::new_v4();
+        let io = |id: Uuid, branch_id: Uuid, title: &str| Io {
+            id,
+            branch_id,
+            title: Some(title.to_string()),
+            ..Default::default()
+        };
+        let child_branch = Branch {
+            id: Uuid::new_v4(),
+            created_ios: ids(&[created_io_id]),
+            edited_title_ios: ids(&[edited_io_id]),
+            deleted_ios: ids(&[deleted_io_id]),
+            ..Default::default()
+        };
+        let child_ios = vec![
+            io(edited_io_id, child_branch.id, "Child"),
+            io(created_io_id, child_branch.id, "Child"),
+        ];
+        let parent_ios = vec![
+            io(edited_io_id, parent_id, "Parent"),
+            io(deleted_io_id, parent_id, "Parent"),
+        ];
+
+        let write = ParentWrite::layer(child_ios, parent_ios, &child_branch, parent_id);
+
+        assert_eq!(write.inserted.len(), 2);
+        assert!(write.inserted.iter().all(|io| io.branch_id == parent_id));
+        assert_eq!(
+            write
+                .replaced
+                .iter()
+                .map(|io| (io.id, io.title.as_deref()))
+                .collect::<Vec<_>>(),
+            vec![(edited_io_id, Some("Parent"))]
+        );
+        assert_eq!(
+            write.deleted.iter().map(|io| io.id).collect::<Vec<_>>(),
+            vec![deleted_io_id]
+        );
+    }
+}
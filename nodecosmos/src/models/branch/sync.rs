@@ -0,0 +1,376 @@
+use std::collections::{HashMap, HashSet};
+
+use charybdis::operations::{Delete, Insert, Update};
+use charybdis::types::{Decimal, Set, Uuid};
+use serde::Deserialize;
+
+use crate::api::data::RequestData;
+use crate::errors::NodecosmosError;
+use crate::models::branch::update::BranchUpdate;
+use crate::models::branch::Branch;
+use crate::models::flow_step::{find_pk_flow_step, FlowStep, PkFlowStep};
+use crate::models::io::Io;
+use crate::models::node::PkNode;
+use crate::models::traits::{Branchable, FindForBranchMerge, ObjectType};
+use crate::models::udts::Conflict;
+
+/// How to resolve a branch edit of an object that was deleted in the original since the branch was created.
+#[derive(Deserialize, Clone, Copy, PartialEq)]
+pub enum StaleResolution {
+    /// Restore the object from the branch, so the edit is merged.
+    Restore,
+    /// Discard the branch edits of the object.
+    Drop,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SyncParams {
+    pub resolution: StaleResolution,
+
+    /// Overrides `resolution` for specific objects.
+    #[serde(default)]
+    pub resolution_by_object: HashMap<Uuid, StaleResolution>,
+}
+
+impl SyncParams {
+    fn resolution(&self, id: &Uuid) -> StaleResolution {
+        self.resolution_by_object.get(id).copied().unwrap_or(self.resolution)
+    }
+
+    fn split(&self, ids: &Option<Set<Uuid>>) -> (Vec<Uuid>, HashSet<Uuid>) {
+        let mut restore_ids = vec![];
+        let mut drop_ids = HashSet::new();
+
+        for id in ids.iter().flatten() {
+            match self.resolution(id) {
+                StaleResolution::Restore => restore_ids.push(*id),
+                StaleResolution::Drop => {
+                    drop_ids.insert(*id);
+                }
+            }
+        }
+
+        (restore_ids, drop_ids)
+    }
+}
+
+/// Stale objects whose branch edits are discarded.
+struct DroppedObjects {
+    ancestor_ids: HashSet<Uuid>,
+    node_ids: HashSet<Uuid>,
+    flow_ids: HashSet<Uuid>,
+    flow_step_ids: HashSet<Uuid>,
+    io_ids: HashSet<Uuid>,
+}
+
+fn pull_ids(ids: &mut Option<Set<Uuid>>, dropped_ids: &HashSet<Uuid>) {
+    if let Some(set) = ids {
+        set.retain(|id| !dropped_ids.contains(id));
+
+        if set.is_empty() {
+            *ids = None;
+        }
+    }
+}
+
+fn pull_keys<V>(map: &mut Option<HashMap<Uuid, V>>, dropped_ids: &HashSet<Uuid>) {
+    if let Some(inner) = map {
+        inner.retain(|id, _| !dropped_ids.contains(id));
+
+        if inner.is_empty() {
+            *map = None;
+        }
+    }
+}
+
+impl Branch {
+    /// Re-evaluates the branch against the current state of the branch it merges into, its open parent for
+    /// stacked branches or the original. Edits of objects that were deleted there are restored or dropped based
+    /// on `params`, conflicting flow steps are moved to a free step index and the `Conflict` is refreshed.
+    pub async fn sync_with_original(self, data: &RequestData, params: &SyncParams) -> Result<Self, NodecosmosError> {
+        let branch_id = self.id;
+        let conflict = self.clone().find_conflict(data.db_session()).await?;
+
+        if let Some(conflict) = conflict {
+            let dropped = self.restore_stale(data, &conflict, params).await?;
+            let mut branch = Branch::find_by_id(branch_id).execute(data.db_session()).await?;
+
+            branch.drop_stale(data, dropped).await?;
+            branch.reindex_conflicting_flow_steps(data, &conflict).await?;
+
+            // conflicts are recalculated below
+            branch.conflict = None;
+            branch.update().execute(data.db_session()).await?;
+        }
+
+        let branch = Branch::find_by_id(branch_id).execute(data.db_session()).await?;
+
+        match branch.check_conflicts(data.db_session()).await {
+            Ok(branch) => Ok(branch),
+            Err(merge_error) => Ok(merge_error.branch),
+        }
+    }
+
+    /// Restores stale objects that should be kept and returns the ones that should be dropped.
+    async fn restore_stale(
+        &self,
+        data: &RequestData,
+        conflict: &Conflict,
+        params: &SyncParams,
+    ) -> Result<DroppedObjects, NodecosmosError> {
+        let (restore_ancestor_ids, ancestor_ids) = params.split(&conflict.deleted_ancestors);
+        let (restore_node_ids, node_ids) = params.split(&conflict.deleted_edited_nodes);
+        let (restore_flow_ids, flow_ids) = params.split(&conflict.deleted_edited_flows);
+        let (restore_flow_step_ids, flow_step_ids) = params.split(&conflict.deleted_edited_flow_steps);
+        let (restore_io_ids, io_ids) = params.split(&conflict.deleted_edited_ios);
+
+        for id in restore_ancestor_ids.into_iter().chain(restore_node_ids) {
+            Branch::update(data, self.id, id, BranchUpdate::RestoreNode(id)).await?;
+        }
+
+        for id in restore_flow_ids {
+            let node_id = self.object_node_id(data.db_session(), id, ObjectType::Flow).await?;
+            Branch::update(data, self.id, node_id, BranchUpdate::RestoreFlow(id)).await?;
+        }
+
+        for id in restore_flow_step_ids {
+            let node_id = self.object_node_id(data.db_session(), id, ObjectType::FlowStep).await?;
+            Branch::update(data, self.id, node_id, BranchUpdate::RestoreFlowStep(id)).await?;
+        }
+
+        for id in restore_io_ids {
+            let node_id = self.object_node_id(data.db_session(), id, ObjectType::Io).await?;
+            Branch::update(data, self.id, node_id, BranchUpdate::RestoreIo(id)).await?;
+        }
+
+        Ok(DroppedObjects {
+            ancestor_ids,
+            node_ids,
+            flow_ids,
+            flow_step_ids,
+            io_ids,
+        })
+    }
+
+    async fn drop_stale(&mut self, data: &RequestData, dropped: DroppedObjects) -> Result<(), NodecosmosError> {
+        let db_session = data.db_session();
+        let DroppedObjects {
+            ancestor_ids: drop_ancestor_ids,
+            node_ids: drop_node_ids,
+            flow_ids: drop_flow_ids,
+            flow_step_ids: mut drop_flow_step_ids,
+            io_ids: mut drop_io_ids,
+        } = dropped;
+
+        // nodes created under deleted ancestors
+        if !drop_ancestor_ids.is_empty() {
+            let created_node_ids = self.created_nodes.clone().unwrap_or_default();
+            let created_nodes = PkNode::find_by_ids(
+                db_session,
+                self.id,
+                &created_node_ids.into_iter().collect::<Vec<Uuid>>(),
+            )
+            .await?;
+            let dropped_created_node_ids = created_nodes
+                .iter()
+                .filter(|node| {
+                    node.ancestor_ids
+                        .iter()
+                        .flatten()
+                        .any(|ancestor_id| drop_ancestor_ids.contains(ancestor_id))
+                })
+                .map(|node| node.id)
+                .collect::<HashSet<Uuid>>();
+
+            pull_ids(&mut self.created_nodes, &dropped_created_node_ids);
+            pull_ids(&mut self.edited_nodes, &dropped_created_node_ids);
+        }
+
+        if !drop_node_ids.is_empty() {
+            pull_ids(&mut self.edited_nodes, &drop_node_ids);
+            pull_ids(&mut self.edited_title_nodes, &drop_node_ids);
+            pull_ids(&mut self.edited_description_nodes, &drop_node_ids);
+
+            if let Some(reordered_nodes) = &mut self.reordered_nodes {
+                reordered_nodes.retain(|reorder_data| !drop_node_ids.contains(&reorder_data.id));
+            }
+        }
+
+        // flow steps and ios created within dropped flows
+        if !drop_flow_ids.is_empty() {
+            pull_ids(&mut self.edited_title_flows, &drop_flow_ids);
+            pull_ids(&mut self.edited_description_flows, &drop_flow_ids);
+
+            if let Some(created_flow_step_ids) = &self.created_flow_steps {
+                let created_flow_steps: Vec<FlowStep> =
+                    FlowStep::find_by_branch_id_and_ids(db_session, self.id, created_flow_step_ids)
+                        .await
+                        .try_collect()
+                        .await?;
+
+                drop_flow_step_ids.extend(
+                    created_flow_steps
+                        .iter()
+                        .filter(|flow_step| drop_flow_ids.contains(&flow_step.flow_id))
+                        .map(|flow_step| flow_step.id),
+                );
+            }
+
+            pull_ids(&mut self.created_flow_steps, &drop_flow_step_ids);
+        }
+
+        if !drop_flow_step_ids.is_empty() {
+            pull_ids(&mut self.edited_description_flow_steps, &drop_flow_step_ids);
+            pull_keys(&mut self.created_flow_step_nodes, &drop_flow_step_ids);
+            pull_keys(&mut self.deleted_flow_step_nodes, &drop_flow_step_ids);
+            pull_keys(&mut self.created_flow_step_inputs_by_node, &drop_flow_step_ids);
+            pull_keys(&mut self.deleted_flow_step_inputs_by_node, &drop_flow_step_ids);
+            pull_keys(&mut self.created_flow_step_outputs_by_node, &drop_flow_step_ids);
+            pull_keys(&mut self.deleted_flow_step_outputs_by_node, &drop_flow_step_ids);
+        }
+
+        if (!drop_flow_ids.is_empty() || !drop_flow_step_ids.is_empty()) && self.created_ios.is_some() {
+            let created_ios = Io::find_by_branch_id_and_root_id_and_ids(
+                db_session,
+                self.id,
+                self.root_id,
+                &self.created_ios.clone().unwrap_or_default(),
+            )
+            .await?;
+
+            drop_io_ids.extend(
+                created_ios
+                    .iter()
+                    .filter(|io| {
+                        io.flow_id.is_some_and(|flow_id| drop_flow_ids.contains(&flow_id))
+                            || io
+                                .flow_step_id
+                                .is_some_and(|flow_step_id| drop_flow_step_ids.contains(&flow_step_id))
+                    })
+                    .map(|io| io.id),
+            );
+
+            pull_ids(&mut self.created_ios, &drop_io_ids);
+        }
+
+        if !drop_io_ids.is_empty() {
+            pull_ids(&mut self.edited_title_ios, &drop_io_ids);
+            pull_ids(&mut self.edited_description_ios, &drop_io_ids);
+        }
+
+        let dropped_object_ids = drop_node_ids
+            .iter()
+            .chain(drop_flow_ids.iter())
+            .chain(drop_flow_step_ids.iter())
+            .chain(drop_io_ids.iter())
+            .copied()
+            .collect::<HashSet<Uuid>>();
+
+        pull_keys(&mut self.title_change_by_object, &dropped_object_ids);
+        pull_keys(&mut self.description_change_by_object, &dropped_object_ids);
+
+        Ok(())
+    }
+
+    /// Branch flow steps that have the same `step_index` as a flow step of the branch they merge into are spread
+    /// evenly between that step and the next step of the flow, so steps that share the index don't collide again.
+    async fn reindex_conflicting_flow_steps(
+        &self,
+        data: &RequestData,
+        conflict: &Conflict,
+    ) -> Result<(), NodecosmosError> {
+        let db_session = data.db_session();
+        let conflicting_flow_step_ids = match &conflict.conflicting_flow_steps {
+            Some(ids) => ids,
+            None => return Ok(()),
+        };
+
+        let flow_steps: Vec<FlowStep> =
+            FlowStep::find_by_branch_id_and_ids(db_session, self.id, conflicting_flow_step_ids)
+                .await
+                .try_collect()
+                .await?;
+        let mut flow_steps_by_index: HashMap<(Uuid, Uuid, Decimal), Vec<FlowStep>> = HashMap::new();
+
+        for flow_step in flow_steps {
+            flow_steps_by_index
+                .entry((flow_step.node_id, flow_step.flow_id, flow_step.step_index.clone()))
+                .or_default()
+                .push(flow_step);
+        }
+
+        // stacked branches read through their open parents
+        let mut branch_ids = vec![self.original_id()];
+        branch_ids.extend(Branch::parent_branch_ids(db_session, self.id).await?);
+        branch_ids.push(self.id);
+
+        for ((node_id, flow_id, step_index), mut flow_steps) in flow_steps_by_index {
+            let mut step_indexes = vec![];
+
+            for branch_id in &branch_ids {
+                let flow_steps: Vec<PkFlowStep> = find_pk_flow_step!(
+                    "branch_id = ? AND node_id = ? AND flow_id = ?",
+                    (*branch_id, node_id, flow_id)
+                )
+                .execute(db_session)
+                .await?
+                .try_collect()
+                .await?;
+
+                step_indexes.extend(flow_steps.into_iter().map(|flow_step| flow_step.step_index));
+            }
+
+            let next_step_index = step_indexes.into_iter().filter(|index| index > &step_index).min();
+
+            // keep the order in which the steps were created
+            flow_steps.sort_by_key(|flow_step| flow_step.created_at);
+
+            let new_step_indexes = spread_step_indexes(&step_index, next_step_index.as_ref(), flow_steps.len());
+
+            for (mut flow_step, new_step_index) in flow_steps.into_iter().zip(new_step_indexes) {
+                // as we can not update clustering keys, we need to delete and insert again with new step index
+                flow_step.delete().execute(db_session).await?;
+                flow_step.step_index = new_step_index;
+                flow_step.insert().execute(db_session).await?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Evenly spaced step indexes between `step_index` and `next_step_index`, or after `step_index` when it's the last
+/// step of the flow.
+fn spread_step_indexes(step_index: &Decimal, next_step_index: Option<&Decimal>, count: usize) -> Vec<Decimal> {
+    (1..=count)
+        .map(|position| match next_step_index {
+            Some(next_step_index) => {
+                step_index
+                    + (next_step_index - step_index) * Decimal::from(position as u64) / Decimal::from(count as u64 + 1)
+            }
+            None => step_index + Decimal::from(position as u64),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_spread_step_indexes() {
+        assert_eq!(
+            spread_step_indexes(&Decimal::from(1), Some(&Decimal::from(2)), 3),
+            vec![
+                Decimal::from(5) / Decimal::from(4),
+                Decimal::from(6) / Decimal::from(4),
+                Decimal::from(7) / Decimal::from(4)
+            ]
+        );
+        assert_eq!(
+            spread_step_indexes(&Decimal::from(1), None, 2),
+            vec![Decimal::from(2), Decimal::from(3)]
+        );
+    }
+}
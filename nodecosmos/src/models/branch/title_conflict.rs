@@ -0,0 +1,194 @@
+use charybdis::operations::{Update, UpdateWithCallbacks};
+use charybdis::types::{Set, Text, Uuid};
+use scylla::client::caching_session::CachingSession;
+use serde::Deserialize;
+use std::collections::HashMap;
+
+use crate::api::data::RequestData;
+use crate::errors::NodecosmosError;
+use crate::models::branch::{Branch, UpdateTitleChangeBranch};
+use crate::models::flow::UpdateTitleFlow;
+use crate::models::io::UpdateTitleIo;
+use crate::models::node::UpdateTitleNode;
+use crate::models::traits::{Branchable, FindForBranchMerge, ObjectType};
+use crate::models::udts::TextChange;
+
+#[derive(Deserialize)]
+pub enum TitleResolution {
+    /// Keep the title from the branch.
+    KeepBranch,
+    /// Keep the current title from the original.
+    KeepOriginal,
+    /// Use a title merged by the user.
+    Merged(Text),
+}
+
+impl TitleResolution {
+    fn title(self, branch_title: Text, original_title: Text) -> Text {
+        match self {
+            TitleResolution::KeepBranch => branch_title,
+            TitleResolution::KeepOriginal => original_title,
+            TitleResolution::Merged(title) => title,
+        }
+    }
+}
+
+async fn find_title_flow(
+    db_session: &CachingSession,
+    branch_id: Uuid,
+    id: Uuid,
+) -> Result<UpdateTitleFlow, NodecosmosError> {
+    let flows: Vec<UpdateTitleFlow> =
+        UpdateTitleFlow::find_by_branch_id_and_ids(db_session, branch_id, &Set::from([id]))
+            .await
+            .try_collect()
+            .await?;
+
+    flows
+        .into_iter()
+        .next()
+        .ok_or_else(|| NodecosmosError::NotFound(format!("Flow {} not found", id)))
+}
+
+impl Branch {
+    /// Branch partitions the branch title is compared against. Stacked branches merge into their open parent, so its
+    /// title takes precedence over the original one.
+    async fn title_base_ids(&self, db_session: &CachingSession) -> Result<Vec<Uuid>, NodecosmosError> {
+        let mut base_ids = vec![];
+
+        if let Some(parent) = self.open_parent(db_session).await? {
+            base_ids.push(parent.id);
+        }
+
+        base_ids.push(self.original_id());
+
+        Ok(base_ids)
+    }
+
+    /// Resolves `edited_titles` conflict of the given object. The branch title is updated to the resolved value
+    /// and the current base title becomes the base of the branch edit, so the conflict is not raised again.
+    pub async fn resolve_title_conflict(
+        &self,
+        data: &RequestData,
+        object_id: Uuid,
+        object_type: ObjectType,
+        resolution: TitleResolution,
+    ) -> Result<Self, NodecosmosError> {
+        let db_session = data.db_session();
+        let base_ids = self.title_base_ids(db_session).await?;
+        let not_found = || NodecosmosError::NotFound(format!("{} {} not found", object_type, object_id));
+
+        let (original_title, title) = match object_type {
+            ObjectType::Node => {
+                let mut branched = UpdateTitleNode::find_by_branch_id_and_id(self.id, object_id)
+                    .execute(db_session)
+                    .await?;
+                let mut original = None;
+
+                for base_id in &base_ids {
+                    original = UpdateTitleNode::maybe_find_first_by_branch_id_and_id(*base_id, object_id)
+                        .execute(db_session)
+                        .await?;
+
+                    if original.is_some() {
+                        break;
+                    }
+                }
+
+                let original = original.ok_or_else(not_found)?;
+                let title = resolution.title(branched.title.clone(), original.title.clone());
+
+                if branched.title != title {
+                    branched.title = title.clone();
+                    branched.update_cb(data).execute(db_session).await?;
+                }
+
+                (original.title, title)
+            }
+            ObjectType::Flow => {
+                let mut branched = find_title_flow(db_session, self.id, object_id).await?;
+                let mut original = None;
+
+                for base_id in &base_ids {
+                    match find_title_flow(db_session, *base_id, object_id).await {
+                        Ok(flow) => {
+                            original = Some(flow);
+                            break;
+                        }
+                        Err(NodecosmosError::NotFound(_)) => continue,
+                        Err(e) => return Err(e),
+                    }
+                }
+
+                let original = original.ok_or_else(not_found)?;
+                let title = resolution.title(branched.title.clone(), original.title.clone());
+
+                if branched.title != title {
+                    branched.title = title.clone();
+                    branched.update_cb(data).execute(db_session).await?;
+                }
+
+                (original.title, title)
+            }
+            ObjectType::Io => {
+                let mut branched =
+                    UpdateTitleIo::find_by_branch_id_and_root_id_and_id(self.id, self.root_id, object_id)
+                        .execute(db_session)
+                        .await?;
+                let mut original = None;
+
+                for base_id in &base_ids {
+                    original = UpdateTitleIo::maybe_find_first_by_branch_id_and_root_id_and_id(
+                        *base_id,
+                        self.root_id,
+                        object_id,
+                    )
+                    .execute(db_session)
+                    .await?;
+
+                    if original.is_some() {
+                        break;
+                    }
+                }
+
+                let original = original.ok_or_else(not_found)?;
+                let original_title = original.title.unwrap_or_default();
+                let title = resolution.title(branched.title.clone().unwrap_or_default(), original_title.clone());
+
+                if branched.title.as_ref() != Some(&title) {
+                    branched.title = Some(title.clone());
+                    branched.update_cb(data).execute(db_session).await?;
+                }
+
+                (original_title, title)
+            }
+            ObjectType::Workflow | ObjectType::FlowStep => {
+                return Err(NodecosmosError::BadRequest(format!(
+                    "Title conflicts are not supported for {}",
+                    object_type
+                )));
+            }
+        };
+
+        let mut title_change_branch = UpdateTitleChangeBranch::find_by_id(self.id).execute(db_session).await?;
+
+        title_change_branch
+            .title_change_by_object
+            .get_or_insert_with(HashMap::default)
+            .insert(
+                object_id,
+                TextChange {
+                    old: original_title,
+                    new: title,
+                },
+            );
+        title_change_branch.update().execute(db_session).await?;
+
+        let branch = Branch::find_by_id(self.id).execute(db_session).await?;
+
+        match branch.check_conflicts(db_session).await {
+            Ok(branch) => Ok(branch),
+            Err(merge_error) => Ok(merge_error.branch),
+        }
+    }
+}
@@ -1,3 +1,4 @@
+use crate::api::data::RequestData;
 use crate::errors::NodecosmosError;
 use crate::models::branch::{
     Branch, UpdateCreateFlowStepNodesBranch, UpdateCreateWorkflowInitialInputsBranch, UpdateCreatedFlowStepsBranch,
@@ -8,17 +9,19 @@ use crate::models::branch::{
     UpdateEditedNodesBranch, UpdateEditedTitleIosBranch, UpdateEditedTitleNodesBranch,
     UpdateFlowStepInputsByNodeBranch, UpdateFlowStepOutputsByNodeBranch, UpdateKeptFlowStepsBranch,
     UpdateReorderedNodes, UpdateRestoredFlowStepsBranch, UpdateRestoredFlowsBranch, UpdateRestoredIosBranch,
-    UpdateRestoredNodesBranch,
+    UpdateRestoredNodesBranch, UpdateTitleChangeBranch,
 };
+use crate::models::commit::Commit;
 use crate::models::traits::Merge;
-use crate::models::udts::BranchReorderData;
+use crate::models::udts::{BranchReorderData, TextChange};
 use charybdis::batch::{CharybdisBatch, CharybdisModelBatch};
 use charybdis::errors::CharybdisError;
 use charybdis::operations::Update;
-use charybdis::types::{Frozen, Map, Set, Uuid};
+use charybdis::types::{Frozen, Map, Set, Text, Uuid};
 use log::error;
 use scylla::client::caching_session::CachingSession;
 use scylla::response::query_result::QueryResult;
+use std::collections::HashMap;
 
 pub enum BranchUpdate {
     CreateNode((Uuid, Set<Uuid>)),
@@ -60,11 +63,15 @@ pub enum BranchUpdate {
 }
 
 impl Branch {
+    /// Applies the update to the branch change sets and records it as a commit of the given node.
     pub async fn update(
-        db_session: &CachingSession,
+        data: &RequestData,
         branch_id: Uuid,
+        node_id: Uuid,
         update: BranchUpdate,
     ) -> Result<Self, NodecosmosError> {
+        let db_session = data.db_session();
+        let commit = Commit::new(data, branch_id, node_id, &update);
         let res: Result<QueryResult, CharybdisError>;
         let mut check_conflicts = false;
 
@@ -552,8 +559,15 @@ impl Branch {
             }
         }
 
-        if let Err(err) = res {
-            error!("Failed to update branch: {}", err)
+        match res {
+            Ok(_) => {
+                if let Some(commit) = commit {
+                    if let Err(err) = commit.insert_or_coalesce(db_session).await {
+                        error!("Failed to insert commit: {}", err)
+                    }
+                }
+            }
+            Err(err) => error!("Failed to update branch: {}", err),
         }
 
         let mut branch = Branch::find_by_id(branch_id).execute(db_session).await?;
@@ -571,4 +585,28 @@ impl Branch {
 
         Ok(branch)
     }
+
+    /// Records the title change of an original object. The `old` title is kept from the first edit within the
+    /// branch, so the merge can tell whether the original title was changed in the meantime.
+    pub async fn track_title_change(
+        db_session: &CachingSession,
+        branch_id: Uuid,
+        object_id: Uuid,
+        original_title: Text,
+        new_title: Text,
+    ) -> Result<(), NodecosmosError> {
+        let mut branch = UpdateTitleChangeBranch::find_by_id(branch_id)
+            .execute(db_session)
+            .await?;
+        let title_changes = branch.title_change_by_object.get_or_insert_with(HashMap::default);
+        let old = title_changes
+            .get(&object_id)
+            .map_or(original_title, |title_change| title_change.old.clone());
+
+        title_changes.insert(object_id, TextChange { old, new: new_title });
+
+        branch.update().execute(db_session).await?;
+
+        Ok(())
+    }
 }
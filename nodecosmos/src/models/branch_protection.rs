@@ -0,0 +1,77 @@
+use charybdis::macros::charybdis_model;
+use charybdis::operations::Find;
+use charybdis::types::{Boolean, Set, Timestamp, Uuid};
+use scylla::client::caching_session::CachingSession;
+use serde::{Deserialize, Serialize};
+
+use crate::api::data::RequestData;
+use crate::errors::NodecosmosError;
+use crate::models::node::AuthNode;
+
+/// Protection of the original tree of a root node. When enabled, only the root owner and maintainers can change
+/// originals directly, while everyone else has to go through a contribution request.
+#[charybdis_model(
+    table_name = branch_protections,
+    partition_keys = [root_id],
+    clustering_keys = []
+)]
+#[derive(Serialize, Deserialize, Default, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct BranchProtection {
+    pub root_id: Uuid,
+
+    #[serde(default)]
+    pub is_enabled: Boolean,
+
+    pub maintainer_ids: Option<Set<Uuid>>,
+
+    #[serde(default = "chrono::Utc::now")]
+    pub updated_at: Timestamp,
+}
+
+impl BranchProtection {
+    pub async fn find_or_default(db_session: &CachingSession, root_id: Uuid) -> Result<Self, NodecosmosError> {
+        let protection = BranchProtection {
+            root_id,
+            ..Default::default()
+        };
+        let maybe_protection = protection.maybe_find_by_primary_key().execute(db_session).await?;
+
+        Ok(maybe_protection.unwrap_or(protection))
+    }
+
+    pub fn is_maintainer(&self, user_id: Uuid) -> bool {
+        self.maintainer_ids.as_ref().is_some_and(|ids| ids.contains(&user_id))
+    }
+
+    /// Rejects direct changes of originals within a protected root for users that are not maintainers. Changes
+    /// within branches are not restricted.
+    pub async fn validate_direct_edit(
+        data: &RequestData,
+        branch_id: Uuid,
+        root_id: Uuid,
+    ) -> Result<(), NodecosmosError> {
+        if branch_id != root_id {
+            return Ok(());
+        }
+
+        let current_user_id = data.current_user.id;
+        let protection = Self::find_or_default(data.db_session(), root_id).await?;
+
+        if !protection.is_enabled || protection.is_maintainer(current_user_id) {
+            return Ok(());
+        }
+
+        let root = AuthNode::find_by_branch_id_and_id(root_id, root_id)
+            .execute(data.db_session())
+            .await?;
+
+        if root.owner_id == current_user_id {
+            return Ok(());
+        }
+
+        Err(NodecosmosError::Forbidden(
+            "This node is protected. Changes must be submitted through a contribution request.".to_string(),
+        ))
+    }
+}
@@ -1,17 +1,21 @@
 use charybdis::callbacks::Callbacks;
 use charybdis::macros::charybdis_model;
-use charybdis::types::{Frozen, Text, Timestamp, Uuid};
+use charybdis::operations::Find;
+use charybdis::types::{Frozen, Set, Text, Timestamp, Uuid};
 use log::error;
 use scylla::client::caching_session::CachingSession;
 use serde::{Deserialize, Serialize};
 use std::collections::HashSet;
 
 use crate::api::data::RequestData;
+use crate::api::types::{ActionObject, ActionTypes};
 use crate::errors::NodecosmosError;
 use crate::models::comment_thread::{CommentThread, ThreadLocation};
+use crate::models::mention::Mentions;
 use crate::models::notification::{Notification, NotificationType};
 use crate::models::traits::Clean;
 use crate::models::udts::Profile;
+use crate::resources::sse_broadcast::ModelEvent;
 
 mod create;
 
@@ -43,6 +47,9 @@ pub struct Comment {
     pub author: Option<Frozen<Profile>>,
     pub url: Text,
 
+    #[serde(default)]
+    pub mentioned_user_ids: Option<Set<Uuid>>,
+
     #[serde(default = "chrono::Utc::now")]
     pub created_at: Timestamp,
 
@@ -52,19 +59,28 @@ pub struct Comment {
     #[charybdis(ignore)]
     #[serde(skip)]
     pub thread: Option<CommentThread>,
+
+    /// Users mentioned by the content change, notified after it's saved.
+    #[charybdis(ignore)]
+    #[serde(skip)]
+    pub new_mentions: Option<Mentions>,
 }
 
 impl Callbacks for Comment {
     type Extension = RequestData;
     type Error = NodecosmosError;
 
-    async fn before_insert(&mut self, _db_session: &CachingSession, data: &RequestData) -> Result<(), NodecosmosError> {
+    async fn before_insert(&mut self, db_session: &CachingSession, data: &RequestData) -> Result<(), NodecosmosError> {
         self.set_default_values(data).await?;
         self.validate_author(data).await?;
         self.validate_url(data).await?;
 
         self.content.clean()?;
 
+        let mentions = Mentions::find(db_session, &self.content).await?;
+        self.mentioned_user_ids = mentions.user_ids();
+        self.new_mentions = Some(mentions);
+
         Ok(())
     }
 
@@ -77,6 +93,7 @@ impl Callbacks for Comment {
                 .emmit_create_event(&data)
                 .await
                 .map_err(|e| error!("Error while emitting create event: {}", e));
+            self_clone.notify_mentions(&data).await;
             let author_id = self_clone.author_id;
             let thread_res = self_clone.thread(data.db_session()).await;
             match thread_res {
@@ -186,22 +203,66 @@ partial_comment!(
     object_id,
     id,
     content,
-    updated_at
+    mentioned_user_ids,
+    updated_at,
+    new_mentions
 );
 
 impl Callbacks for UpdateContentComment {
-    type Extension = Option<()>;
+    type Extension = RequestData;
     type Error = NodecosmosError;
 
-    async fn before_update(
-        &mut self,
-        _db_session: &CachingSession,
-        _ext: &Self::Extension,
-    ) -> Result<(), NodecosmosError> {
+    async fn before_update(&mut self, db_session: &CachingSession, _data: &RequestData) -> Result<(), NodecosmosError> {
         self.updated_at = chrono::Utc::now();
 
         self.content.clean()?;
 
+        // only users that were not mentioned before the edit are notified
+        let current = self.find_by_primary_key().execute(db_session).await?;
+        let mut mentions = Mentions::find(db_session, &self.content).await?;
+
+        self.mentioned_user_ids = mentions.user_ids();
+        mentions.retain_new(&current.mentioned_user_ids);
+        self.new_mentions = Some(mentions);
+
+        Ok(())
+    }
+
+    async fn after_update(&mut self, _db_session: &CachingSession, data: &RequestData) -> Result<(), NodecosmosError> {
+        let self_clone = self.clone();
+        let data = data.clone();
+
+        tokio::spawn(async move {
+            let thread = CommentThread::find_by_branch_id_and_object_id_and_id(
+                self_clone.branch_id,
+                self_clone.object_id,
+                self_clone.thread_id,
+            )
+            .execute(data.db_session())
+            .await;
+
+            match thread {
+                Ok(mut thread) => {
+                    ModelEvent::new(
+                        thread.root_id,
+                        self_clone.branch_id,
+                        ActionTypes::Update(ActionObject::Comment),
+                        &self_clone,
+                    )
+                    .emit(&data)
+                    .await;
+
+                    if let Some(mentions) = self_clone.new_mentions.as_ref().filter(|m| !m.is_empty()) {
+                        match thread.mention_target(&data).await {
+                            Ok(target) => mentions.notify(&data, &target).await,
+                            Err(e) => error!("Error while notifying mentioned users: {}", e),
+                        }
+                    }
+                }
+                Err(e) => error!("Error while emitting update comment event: {}", e),
+            }
+        });
+
         Ok(())
     }
 }
@@ -227,6 +288,15 @@ impl Callbacks for DeleteComment {
 
             match thread {
                 Ok(thread) => {
+                    ModelEvent::new(
+                        thread.root_id,
+                        self_clone.branch_id,
+                        ActionTypes::Delete(ActionObject::Comment),
+                        &self_clone,
+                    )
+                    .emit(&data)
+                    .await;
+
                     thread.delete_if_no_comments(data.db_session()).await;
                 }
                 Err(e) => error!("Error while deleting comment: {}", e),
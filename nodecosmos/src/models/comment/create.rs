@@ -64,6 +64,21 @@ impl Comment {
         Ok(())
     }
 
+    pub async fn notify_mentions(&mut self, data: &RequestData) {
+        let Some(mentions) = self.new_mentions.take().filter(|mentions| !mentions.is_empty()) else {
+            return;
+        };
+
+        match self.thread(data.db_session()).await {
+            Ok(Some(thread)) => match thread.mention_target(data).await {
+                Ok(target) => mentions.notify(data, &target).await,
+                Err(e) => error!("Error while notifying mentioned users: {}", e),
+            },
+            Ok(None) => error!("[notify_mentions] Thread not initialized"),
+            Err(e) => error!("Error while notifying mentioned users: {}", e),
+        }
+    }
+
     pub async fn emmit_create_event(&mut self, data: &RequestData) -> Result<(), NodecosmosError> {
         let thread = self.thread(data.db_session()).await?;
 
@@ -71,9 +86,14 @@ impl Comment {
             Some(thread) => {
                 let root_id = thread.root_id;
 
-                let res = ModelEvent::new(root_id, ActionTypes::Create(ActionObject::Comment), self)
-                    .send(data)
-                    .await;
+                let res = ModelEvent::new(
+                    root_id,
+                    self.branch_id,
+                    ActionTypes::Create(ActionObject::Comment),
+                    self,
+                )
+                .send(data)
+                .await;
 
                 if let Err(e) = res {
                     error!("Error sending message to room {}: {}", root_id, e);
@@ -11,6 +11,7 @@ use crate::api::data::RequestData;
 use crate::errors::NodecosmosError;
 use crate::models::branch::Branch;
 use crate::models::comment::{Comment, PkComment};
+use crate::models::mention::MentionTarget;
 use crate::models::node::Node;
 use crate::models::node_counter::NodeCounter;
 use crate::models::udts::Profile;
@@ -141,10 +142,7 @@ impl Callbacks for CommentThread {
     }
 
     async fn after_insert(&mut self, session: &CachingSession, data: &RequestData) -> Result<(), Self::Error> {
-        let node_id = match self.thread_location()? {
-            ThreadLocation::Thread => self.object_id,
-            ThreadLocation::ContributionRequest(..) => self.branch(session).await?.node_id,
-        };
+        let node_id = self.node_id(session).await?;
 
         NodeCounter::increment_thread_count(data, self.root_id, self.branch_id, node_id).await?;
 
@@ -163,6 +161,29 @@ impl Callbacks for CommentThread {
 }
 
 impl CommentThread {
+    /// Node of the thread. Contribution request threads belong to the node of the branch.
+    pub async fn node_id(&mut self, db_session: &CachingSession) -> Result<Uuid, NodecosmosError> {
+        match self.thread_location()? {
+            ThreadLocation::Thread => Ok(self.object_id),
+            ThreadLocation::ContributionRequest(..) => Ok(self.branch(db_session).await?.node_id),
+        }
+    }
+
+    pub async fn mention_target(&mut self, data: &RequestData) -> Result<MentionTarget, NodecosmosError> {
+        let url = format!(
+            "{}/nodes/{}/{}/threads/{}",
+            data.app.config.client_url, self.branch_id, self.object_id, self.id
+        );
+
+        Ok(MentionTarget {
+            branch_id: self.branch_id,
+            node_id: self.node_id(data.db_session()).await?,
+            root_id: self.root_id,
+            text: format!("mentioned you in thread: {}", self.title),
+            url: Some(url),
+        })
+    }
+
     pub async fn branch(&mut self, db_session: &CachingSession) -> Result<&mut Branch, NodecosmosError> {
         match self.thread_location()? {
             ThreadLocation::ContributionRequest(..) => {
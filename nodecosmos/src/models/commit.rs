@@ -1,21 +1,27 @@
-use crate::models::udts::BranchReorderData;
+use crate::api::data::RequestData;
+use crate::errors::NodecosmosError;
+use crate::models::branch::update::BranchUpdate;
+use crate::models::udts::{BranchReorderData, Profile};
 use charybdis::macros::charybdis_model;
-use charybdis::types::{Frozen, List, Map, Set, Text, Timestamp, Uuid};
+use charybdis::operations::{Insert, Update};
+use charybdis::types::{Frozen, Map, Set, Text, Timestamp, Uuid};
 use macros::Branchable;
+use scylla::client::caching_session::CachingSession;
 use serde::{Deserialize, Serialize};
 
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, strum_macros::Display)]
 pub enum CommitObject {
     /// For each ancestor we need to store node creation, deletion, title, description, and reordering
     CreateNode(Uuid),
     DeleteNodes(Vec<Uuid>),
     UndoDeleteNodes(Vec<Uuid>),
     RestoreNode(Uuid),
-    EditNodeTitle(String),
-    EditNodeDescription(String), // base64 encoded
+    EditNodeTitle(Uuid),
+    EditNodeDescription(Uuid),
     ReorderNode(BranchReorderData),
-    CreateWorkflowInitialInputs(Map<Uuid, Frozen<List<Uuid>>>),
-    DeleteWorkflowInitialInputs(Map<Uuid, Frozen<List<Uuid>>>),
+    CreateWorkflowInitialInputs(Set<Uuid>),
+    DeleteWorkflowInitialInputs(Set<Uuid>),
+    UndoDeleteWorkflowInitialInputs(Set<Uuid>),
     CreateFlow(Uuid),
     DeleteFlow(Uuid),
     UndoDeleteFlow(Uuid),
@@ -29,19 +35,124 @@ pub enum CommitObject {
     KeepFlowStep(Uuid),
     CreateFlowStepNodes(Map<Uuid, Frozen<Set<Uuid>>>),
     DeleteFlowStepNodes(Map<Uuid, Frozen<Set<Uuid>>>),
-    CreateFlowStepInputs(Map<Uuid, Frozen<Map<Uuid, Frozen<Set<Uuid>>>>>),
-    DeleteFlowStepInputs(Map<Uuid, Frozen<Map<Uuid, Frozen<Set<Uuid>>>>>),
-    CreateFlowStepOutputs(Map<Uuid, Frozen<Map<Uuid, Frozen<Set<Uuid>>>>>),
-    DeletedFlowStepOutputs(Map<Uuid, Frozen<Map<Uuid, Frozen<Set<Uuid>>>>>),
+    CreateFlowStepInputs((Uuid, Frozen<Map<Uuid, Frozen<Set<Uuid>>>>)),
+    DeleteFlowStepInputs((Uuid, Frozen<Map<Uuid, Frozen<Set<Uuid>>>>)),
+    CreateFlowStepOutputs((Uuid, Frozen<Map<Uuid, Frozen<Set<Uuid>>>>)),
+    DeletedFlowStepOutputs((Uuid, Frozen<Map<Uuid, Frozen<Set<Uuid>>>>)),
     EditFlowStepDescription(Uuid),
     CreateIo(Uuid),
     DeleteIo(Uuid),
     UndoDeleteIo(Uuid),
+    UndoDeleteOutput((Uuid, Uuid, Uuid)),
     RestoreIo(Uuid),
     EditIoTitle(Uuid),
     EditIoDescription(Uuid),
 }
 
+/// Edits of the same object by the same author within this period are collected into a single commit.
+const COMMIT_INTERVAL_MIN: i64 = 10;
+
+impl CommitObject {
+    /// `EditNode` only marks ancestors of a changed object as edited, so it is not recorded as a commit.
+    pub fn from_update(update: &BranchUpdate) -> Option<Self> {
+        let object = match update {
+            BranchUpdate::CreateNode((id, _)) => Self::CreateNode(*id),
+            BranchUpdate::DeleteNodes(ids) => Self::DeleteNodes(ids.clone()),
+            BranchUpdate::UndoDeleteNodes(ids) => Self::UndoDeleteNodes(ids.clone()),
+            BranchUpdate::RestoreNode(id) => Self::RestoreNode(*id),
+            BranchUpdate::EditNodeTitle(id) => Self::EditNodeTitle(*id),
+            BranchUpdate::EditNodeDescription(id) => Self::EditNodeDescription(*id),
+            BranchUpdate::ReorderNode(reorder_data) => Self::ReorderNode(reorder_data.clone()),
+            BranchUpdate::EditNode(_) => return None,
+            BranchUpdate::CreateWorkflowInitialInputs(ids) => Self::CreateWorkflowInitialInputs(ids.clone()),
+            BranchUpdate::DeleteWorkflowInitialInputs(ids) => Self::DeleteWorkflowInitialInputs(ids.clone()),
+            BranchUpdate::UndoDeleteWorkflowInitialInputs(ids) => Self::UndoDeleteWorkflowInitialInputs(ids.clone()),
+            BranchUpdate::CreateFlow(id) => Self::CreateFlow(*id),
+            BranchUpdate::DeleteFlow(id) => Self::DeleteFlow(*id),
+            BranchUpdate::UndoDeleteFlow(id) => Self::UndoDeleteFlow(*id),
+            BranchUpdate::RestoreFlow(id) => Self::RestoreFlow(*id),
+            BranchUpdate::EditFlowTitle(id) => Self::EditFlowTitle(*id),
+            BranchUpdate::EditFlowDescription(id) => Self::EditFlowDescription(*id),
+            BranchUpdate::CreateFlowStep(id) => Self::CreateFlowStep(*id),
+            BranchUpdate::DeleteFlowStep(id) => Self::DeleteFlowStep(*id),
+            BranchUpdate::UndoDeleteFlowStep(id) => Self::UndoDeleteFlowStep(*id),
+            BranchUpdate::RestoreFlowStep(id) => Self::RestoreFlowStep(*id),
+            BranchUpdate::KeepFlowStep(id) => Self::KeepFlowStep(*id),
+            BranchUpdate::CreateFlowStepNodes(nodes) => Self::CreateFlowStepNodes(nodes.clone()),
+            BranchUpdate::DeleteFlowStepNodes(nodes) => Self::DeleteFlowStepNodes(nodes.clone()),
+            BranchUpdate::CreateFlowStepInputs(inputs) => Self::CreateFlowStepInputs(inputs.clone()),
+            BranchUpdate::DeleteFlowStepInputs(inputs) => Self::DeleteFlowStepInputs(inputs.clone()),
+            BranchUpdate::CreateFlowStepOutputs(outputs) => Self::CreateFlowStepOutputs(outputs.clone()),
+            BranchUpdate::DeletedFlowStepOutputs(outputs) => Self::DeletedFlowStepOutputs(outputs.clone()),
+            BranchUpdate::EditFlowStepDescription(id) => Self::EditFlowStepDescription(*id),
+            BranchUpdate::CreateIo(id) => Self::CreateIo(*id),
+            BranchUpdate::DeleteIo(id) => Self::DeleteIo(*id),
+            BranchUpdate::UndoDeleteIo(id) => Self::UndoDeleteIo(*id),
+            BranchUpdate::UndoDeleteOutput(output) => Self::UndoDeleteOutput(*output),
+            BranchUpdate::RestoreIo(id) => Self::RestoreIo(*id),
+            BranchUpdate::EditIoTitle(id) => Self::EditIoTitle(*id),
+            BranchUpdate::EditIoDescription(id) => Self::EditIoDescription(*id),
+        };
+
+        Some(object)
+    }
+
+    /// Edits only reference the edited object, so repeated ones don't need separate commits.
+    pub fn is_edit(&self) -> bool {
+        matches!(
+            self,
+            Self::EditNodeTitle(_)
+                | Self::EditNodeDescription(_)
+                | Self::EditFlowTitle(_)
+                | Self::EditFlowDescription(_)
+                | Self::EditFlowStepDescription(_)
+                | Self::EditIoTitle(_)
+                | Self::EditIoDescription(_)
+        )
+    }
+
+    /// Id of the changed object. Changes that span multiple objects are recorded on the node.
+    pub fn object_id(&self, node_id: Uuid) -> Uuid {
+        match self {
+            Self::CreateNode(id)
+            | Self::RestoreNode(id)
+            | Self::EditNodeTitle(id)
+            | Self::EditNodeDescription(id)
+            | Self::CreateFlow(id)
+            | Self::DeleteFlow(id)
+            | Self::UndoDeleteFlow(id)
+            | Self::RestoreFlow(id)
+            | Self::EditFlowTitle(id)
+            | Self::EditFlowDescription(id)
+            | Self::CreateFlowStep(id)
+            | Self::DeleteFlowStep(id)
+            | Self::UndoDeleteFlowStep(id)
+            | Self::RestoreFlowStep(id)
+            | Self::KeepFlowStep(id)
+            | Self::EditFlowStepDescription(id)
+            | Self::CreateIo(id)
+            | Self::DeleteIo(id)
+            | Self::UndoDeleteIo(id)
+            | Self::RestoreIo(id)
+            | Self::EditIoTitle(id)
+            | Self::EditIoDescription(id) => *id,
+            Self::ReorderNode(reorder_data) => reorder_data.id,
+            Self::CreateFlowStepInputs((fs_id, _))
+            | Self::DeleteFlowStepInputs((fs_id, _))
+            | Self::CreateFlowStepOutputs((fs_id, _))
+            | Self::DeletedFlowStepOutputs((fs_id, _))
+            | Self::UndoDeleteOutput((fs_id, _, _)) => *fs_id,
+            Self::DeleteNodes(_)
+            | Self::UndoDeleteNodes(_)
+            | Self::CreateWorkflowInitialInputs(_)
+            | Self::DeleteWorkflowInitialInputs(_)
+            | Self::UndoDeleteWorkflowInitialInputs(_)
+            | Self::CreateFlowStepNodes(_)
+            | Self::DeleteFlowStepNodes(_) => node_id,
+        }
+    }
+}
+
 #[charybdis_model(
     table_name = commits,
     partition_keys = [branch_id],
@@ -59,7 +170,127 @@ pub struct Commit {
     pub id: Uuid,
     pub commit_type: Text,
     pub data: Text,
+    pub author_id: Uuid,
+    pub author: Option<Frozen<Profile>>,
 
     #[serde(default = "chrono::Utc::now")]
     pub created_at: Timestamp,
+
+    /// Time of the latest edit collected into the commit.
+    pub updated_at: Option<Timestamp>,
+
+    #[charybdis(ignore)]
+    #[serde(skip)]
+    pub is_edit: bool,
+}
+
+partial_commit!(
+    CoalesceCommit,
+    branch_id,
+    node_id,
+    id,
+    object_id,
+    commit_type,
+    author_id,
+    created_at,
+    updated_at
+);
+
+impl Commit {
+    pub fn new(data: &RequestData, branch_id: Uuid, node_id: Uuid, update: &BranchUpdate) -> Option<Self> {
+        let object = CommitObject::from_update(update)?;
+        let commit_data = match serde_json::to_string(&object) {
+            Ok(commit_data) => commit_data,
+            Err(err) => {
+                log::error!("Failed to serialize commit object {}: {}", object, err);
+
+                return None;
+            }
+        };
+
+        let now = chrono::Utc::now();
+
+        Some(Self {
+            node_id,
+            branch_id,
+            object_id: object.object_id(node_id),
+            id: Uuid::new_v4(),
+            commit_type: object.to_string(),
+            data: commit_data,
+            author_id: data.current_user.id,
+            author: Some((&data.current_user).into()),
+            created_at: now,
+            updated_at: Some(now),
+            is_edit: object.is_edit(),
+        })
+    }
+
+    /// Inserts the commit, unless it is an edit of an object that the same author already edited within
+    /// `COMMIT_INTERVAL_MIN`, e.g. a description persisted on every pause in typing. In that case only
+    /// `updated_at` of the existing commit is bumped.
+    pub async fn insert_or_coalesce(&self, db_session: &CachingSession) -> Result<(), NodecosmosError> {
+        if self.is_edit {
+            let latest = CoalesceCommit::find_by_branch_id_and_node_id(self.branch_id, self.node_id)
+                .execute(db_session)
+                .await?
+                .try_collect()
+                .await?
+                .into_iter()
+                .filter(|commit| {
+                    commit.object_id == self.object_id
+                        && commit.commit_type == self.commit_type
+                        && commit.author_id == self.author_id
+                })
+                .max_by_key(|commit| commit.updated_at.unwrap_or(commit.created_at));
+
+            if let Some(mut latest) = latest {
+                let last_edit = latest.updated_at.unwrap_or(latest.created_at);
+
+                if self.created_at - last_edit < chrono::Duration::minutes(COMMIT_INTERVAL_MIN) {
+                    latest.updated_at = Some(self.created_at);
+                    latest.update().execute(db_session).await?;
+
+                    return Ok(());
+                }
+            }
+        }
+
+        self.insert().execute(db_session).await?;
+
+        Ok(())
+    }
+
+    /// Commits of a node within a branch, oldest first.
+    pub async fn node_commits(
+        db_session: &CachingSession,
+        branch_id: Uuid,
+        node_id: Uuid,
+    ) -> Result<Vec<Self>, NodecosmosError> {
+        let mut commits: Vec<Self> = Self::find_by_branch_id_and_node_id(branch_id, node_id)
+            .execute(db_session)
+            .await?
+            .try_collect()
+            .await?;
+
+        commits.sort_by_key(|commit| commit.created_at);
+
+        Ok(commits)
+    }
+
+    /// Time of the latest change within a branch.
+    pub async fn last_change_at(
+        db_session: &CachingSession,
+        branch_id: Uuid,
+    ) -> Result<Option<Timestamp>, NodecosmosError> {
+        let commits: Vec<CoalesceCommit> = CoalesceCommit::find_by_branch_id(branch_id)
+            .execute(db_session)
+            .await?
+            .try_collect()
+            .await?;
+
+        Ok(commits
+            .iter()
+            .map(|commit| commit.updated_at.unwrap_or(commit.created_at))
+            .max())
+    }
 }
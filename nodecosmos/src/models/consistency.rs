@@ -0,0 +1,1239 @@
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+
+use charybdis::batch::ModelBatch;
+use charybdis::operations::{Find, Update};
+use charybdis::types::{Set, Uuid};
+use scylla::client::caching_session::CachingSession;
+use serde::Serialize;
+
+use crate::api::data::RequestData;
+use crate::errors::NodecosmosError;
+use crate::models::branch::Branch;
+use crate::models::flow_step::{FlowStep, UpdateNodeIdsFlowStep};
+use crate::models::io::{Io, UpdateFlowStepIo};
+use crate::models::node::Node;
+use crate::models::node_counter::NodeCounter;
+use crate::models::node_descendant::NodeDescendant;
+use crate::models::workflow::{UpdateInitialInputsWorkflow, Workflow};
+use crate::resources::resource_locker::ResourceLocker;
+
+/// Loads records of the model from the checked partition and the partitions it's layered on. Records of the
+/// earlier partitions win. Returns records by key and keys of the records stored in the checked partition.
+macro_rules! find_layered {
+    ($model:ident, $db_session:expr, $layer_ids:expr, $key:ident) => {{
+        let mut records: HashMap<Uuid, $model> = HashMap::new();
+        let mut owned_ids: HashSet<Uuid> = HashSet::new();
+
+        for (index, layer_id) in $layer_ids.iter().enumerate() {
+            let layer: Vec<$model> = $model::find_by_branch_id(*layer_id)
+                .execute($db_session)
+                .await?
+                .try_collect()
+                .await?;
+
+            for record in layer {
+                if index == 0 {
+                    owned_ids.insert(record.$key);
+                }
+
+                records.entry(record.$key).or_insert(record);
+            }
+        }
+
+        (records, owned_ids)
+    }};
+}
+
+#[derive(Serialize, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug)]
+pub enum InconsistencyCategory {
+    /// `Node.parent_id` points to a node that doesn't exist.
+    MissingParent,
+    /// `Node.ancestor_ids` doesn't match the parent chain.
+    AncestorIdsMismatch,
+    /// `node_descendants` row is missing for an ancestor of the node.
+    MissingDescendant,
+    /// `node_descendants` row has outdated order index, parent or title.
+    OutdatedDescendant,
+    /// `node_descendants` row of a node that doesn't exist or is not a descendant of the node.
+    StaleDescendant,
+    /// `NodeCounter.descendants_count` doesn't match the number of descendants.
+    DescendantsCountMismatch,
+    /// `FlowStep.node_ids` contains a node that doesn't exist.
+    MissingFlowStepNode,
+    /// `FlowStep.input_ids_by_node_id` contains an io that doesn't exist.
+    MissingFlowStepInput,
+    /// `FlowStep.output_ids_by_node_id` contains an io that doesn't exist.
+    MissingFlowStepOutput,
+    /// Input of a flow step is missing the flow step in `Io.inputted_by_flow_steps`.
+    InputNotLinked,
+    /// `Io.inputted_by_flow_steps` contains a flow step that doesn't use the io as an input.
+    StaleInputLink,
+    /// Output of a flow step doesn't point to the flow step with `Io.flow_step_id`.
+    OutputNotLinked,
+    /// `Io.flow_step_id` points to a flow step that doesn't have the io as an output.
+    StaleOutputLink,
+    /// `Workflow.initial_input_ids` contains an io that doesn't exist.
+    MissingInitialInput,
+    /// Initial input io is not listed in `Workflow.initial_input_ids`.
+    InitialInputNotListed,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Inconsistency {
+    pub category: InconsistencyCategory,
+
+    /// Record that holds the inconsistent data.
+    pub object_id: Uuid,
+
+    /// Record the inconsistent data points to.
+    pub related_id: Option<Uuid>,
+
+    pub message: String,
+    pub repaired: bool,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ConsistencyReport {
+    pub root_id: Uuid,
+    pub branch_id: Uuid,
+    pub nodes_count: usize,
+    pub flow_steps_count: usize,
+    pub ios_count: usize,
+    pub inconsistencies: Vec<Inconsistency>,
+}
+
+/// Writes that repair found inconsistencies. Checks only collect them, so nothing is written unless repair is
+/// requested.
+#[derive(Default)]
+struct Repairs {
+    /// Nodes with ancestor ids to pull and push.
+    ancestor_ids: Vec<(Node, Set<Uuid>, Set<Uuid>)>,
+    delete_descendants: Vec<NodeDescendant>,
+    insert_descendants: Vec<NodeDescendant>,
+
+    /// Node ids and differences to add to their descendants counts.
+    descendants_counts: Vec<(Uuid, i64)>,
+
+    flow_steps: Vec<UpdateNodeIdsFlowStep>,
+    output_ios: Vec<UpdateFlowStepIo>,
+
+    /// Flow step and io ids of inputs to link.
+    link_inputs: Vec<(Uuid, Uuid)>,
+
+    /// Flow step and io ids of inputs to unlink.
+    unlink_inputs: Vec<(Uuid, Uuid)>,
+
+    /// Node ids of workflows and initial inputs to add.
+    list_initial_inputs: Vec<(Uuid, Uuid)>,
+
+    /// Node ids of workflows and initial inputs to remove.
+    unlist_initial_inputs: Vec<(Uuid, Vec<Uuid>)>,
+}
+
+impl Repairs {
+    async fn apply(self, db_session: &CachingSession, root_id: Uuid, branch_id: Uuid) -> Result<(), NodecosmosError> {
+        for (node, extra_ids, missing_ids) in &self.ancestor_ids {
+            if !extra_ids.is_empty() {
+                node.pull_ancestor_ids(extra_ids).execute(db_session).await?;
+            }
+
+            if !missing_ids.is_empty() {
+                node.push_ancestor_ids(missing_ids).execute(db_session).await?;
+            }
+        }
+
+        NodeDescendant::unlogged_batch()
+            .chunked_delete(db_session, &self.delete_descendants, crate::constants::BATCH_CHUNK_SIZE)
+            .await
+            .map_err(NodecosmosError::from)?;
+
+        NodeDescendant::unlogged_batch()
+            .chunked_insert(db_session, &self.insert_descendants, crate::constants::BATCH_CHUNK_SIZE)
+            .await
+            .map_err(NodecosmosError::from)?;
+
+        for (id, difference) in &self.descendants_counts {
+            NodeCounter {
+                branch_id: root_id,
+                id: *id,
+                ..Default::default()
+            }
+            .increment_descendants_count(*difference)
+            .execute(db_session)
+            .await?;
+        }
+
+        for flow_step in &self.flow_steps {
+            flow_step.update().execute(db_session).await?;
+        }
+
+        for io in &self.output_ios {
+            io.update().execute(db_session).await?;
+        }
+
+        let mut io_batch = Io::statement_batch();
+
+        for (flow_step_id, io_id) in &self.link_inputs {
+            io_batch.append_statement(
+                Io::PUSH_INPUTTED_BY_FLOW_STEPS_QUERY,
+                (vec![*flow_step_id], branch_id, root_id, *io_id),
+            );
+        }
+
+        for (flow_step_id, io_id) in &self.unlink_inputs {
+            io_batch.append_statement(
+                Io::PULL_INPUTTED_BY_FLOW_STEPS_QUERY,
+                (vec![*flow_step_id], branch_id, root_id, *io_id),
+            );
+        }
+
+        io_batch.execute(db_session).await?;
+
+        for (node_id, io_id) in &self.list_initial_inputs {
+            Self::workflow(*node_id, root_id, branch_id)
+                .push_initial_input_ids(vec![*io_id])
+                .execute(db_session)
+                .await?;
+        }
+
+        for (node_id, io_ids) in self.unlist_initial_inputs {
+            Self::workflow(node_id, root_id, branch_id)
+                .pull_initial_input_ids(io_ids)
+                .execute(db_session)
+                .await?;
+        }
+
+        Ok(())
+    }
+
+    fn workflow(node_id: Uuid, root_id: Uuid, branch_id: Uuid) -> UpdateInitialInputsWorkflow {
+        UpdateInitialInputsWorkflow {
+            node_id,
+            branch_id,
+            root_id,
+            initial_input_ids: None,
+            ctx: Default::default(),
+        }
+    }
+}
+
+/// Checks denormalized data of a tree. Branches are checked on top of their parent branches and the original,
+/// but only records stored in the branch are repaired.
+pub struct TreeConsistency {
+    root_id: Uuid,
+    branch_id: Uuid,
+    repair: bool,
+    nodes: HashMap<Uuid, Node>,
+    owned_node_ids: HashSet<Uuid>,
+    workflows: HashMap<Uuid, Workflow>,
+    owned_workflow_ids: HashSet<Uuid>,
+    flow_steps: HashMap<Uuid, FlowStep>,
+    owned_flow_step_ids: HashSet<Uuid>,
+    ios: HashMap<Uuid, Io>,
+    owned_io_ids: HashSet<Uuid>,
+
+    /// `node_descendants` rows of the checked partition.
+    descendants: Vec<NodeDescendant>,
+
+    /// Descendants counts by node id. Counters are kept only for the original tree.
+    descendants_counts: Option<HashMap<Uuid, i64>>,
+
+    /// Ancestors of nodes with a complete parent chain.
+    ancestor_ids_by_node_id: HashMap<Uuid, Set<Uuid>>,
+}
+
+impl TreeConsistency {
+    pub async fn check(
+        data: &RequestData,
+        root_id: Uuid,
+        branch_id: Uuid,
+        repair: bool,
+    ) -> Result<ConsistencyReport, NodecosmosError> {
+        if repair {
+            data.resource_locker()
+                .lock_resource(root_id, branch_id, ResourceLocker::ONE_HOUR)
+                .await?;
+        }
+
+        let res = TreeConsistency::run(data.app.db_session.clone(), root_id, branch_id, repair).await;
+
+        if repair {
+            data.resource_locker().unlock_resource(root_id, branch_id).await?;
+        }
+
+        res
+    }
+
+    async fn run(
+        db_session: Arc<CachingSession>,
+        root_id: Uuid,
+        branch_id: Uuid,
+        repair: bool,
+    ) -> Result<ConsistencyReport, NodecosmosError> {
+        let consistency = TreeConsistency::load(&db_session, root_id, branch_id, repair).await?;
+        let (report, repairs) = consistency.report();
+
+        if repair {
+            repairs.apply(&db_session, root_id, branch_id).await?;
+        }
+
+        Ok(report)
+    }
+
+    async fn load(
+        db_session: &CachingSession,
+        root_id: Uuid,
+        branch_id: Uuid,
+        repair: bool,
+    ) -> Result<Self, NodecosmosError> {
+        let mut layer_ids = vec![branch_id];
+        let mut deleted_ids = HashSet::new();
+
+        if branch_id != root_id {
+            layer_ids.extend(Branch::parent_branch_ids(db_session, branch_id).await?);
+
+            for (index, layer_id) in layer_ids.iter().enumerate() {
+                let branch = Branch {
+                    id: *layer_id,
+                    ..Default::default()
+                }
+                .maybe_find_by_primary_key()
+                .execute(db_session)
+                .await?;
+
+                match branch {
+                    Some(branch) if branch.root_id == root_id => deleted_ids.extend(branch.all_deleted_object_ids()),
+                    _ if index == 0 => {
+                        return Err(NodecosmosError::NotFound(format!(
+                            "Branch {} of root {} not found",
+                            branch_id, root_id
+                        )))
+                    }
+                    _ => (),
+                }
+            }
+
+            layer_ids.push(root_id);
+        }
+
+        let (nodes, owned_node_ids) = find_layered!(Node, db_session, layer_ids, id);
+        let (workflows, owned_workflow_ids) = find_layered!(Workflow, db_session, layer_ids, node_id);
+        let (flow_steps, owned_flow_step_ids) = find_layered!(FlowStep, db_session, layer_ids, id);
+        let (ios, owned_io_ids) = find_layered!(Io, db_session, layer_ids, id);
+        let descendants: Vec<NodeDescendant> = NodeDescendant::find_by_root_id_and_branch_id(root_id, branch_id)
+            .execute(db_session)
+            .await?
+            .try_collect()
+            .await?;
+        let mut descendants_counts = None;
+
+        if root_id == branch_id {
+            let counters: Vec<NodeCounter> = NodeCounter::find_by_branch_id(root_id)
+                .execute(db_session)
+                .await?
+                .try_collect()
+                .await?;
+
+            descendants_counts = Some(
+                counters
+                    .into_iter()
+                    .map(|counter| (counter.id, counter.descendants_count.map_or(0, |count| count.0)))
+                    .collect(),
+            );
+        }
+
+        let mut consistency = Self {
+            root_id,
+            branch_id,
+            repair,
+            nodes,
+            owned_node_ids,
+            workflows,
+            owned_workflow_ids,
+            flow_steps,
+            owned_flow_step_ids,
+            ios,
+            owned_io_ids,
+            descendants,
+            descendants_counts,
+            ancestor_ids_by_node_id: HashMap::new(),
+        };
+
+        consistency.retain_tree(&deleted_ids);
+
+        if !consistency.nodes.contains_key(&root_id) {
+            return Err(NodecosmosError::NotFound(format!("Root node {} not found", root_id)));
+        }
+
+        consistency.build_ancestor_ids();
+
+        Ok(consistency)
+    }
+
+    /// Drops records of other trees and records deleted in the checked branch or its parents.
+    fn retain_tree(&mut self, deleted_ids: &HashSet<Uuid>) {
+        let root_id = self.root_id;
+
+        self.nodes
+            .retain(|id, node| node.root_id == root_id && !deleted_ids.contains(id));
+        self.owned_node_ids.retain(|id| self.nodes.contains_key(id));
+        self.workflows.retain(|node_id, _| self.nodes.contains_key(node_id));
+        self.owned_workflow_ids
+            .retain(|node_id| self.workflows.contains_key(node_id));
+        self.flow_steps
+            .retain(|id, flow_step| flow_step.root_id == root_id && !deleted_ids.contains(id));
+        self.owned_flow_step_ids.retain(|id| self.flow_steps.contains_key(id));
+        self.ios
+            .retain(|id, io| io.root_id == root_id && !deleted_ids.contains(id));
+        self.owned_io_ids.retain(|id| self.ios.contains_key(id));
+    }
+
+    fn build_ancestor_ids(&mut self) {
+        for (id, node) in &self.nodes {
+            let mut ancestor_ids = Set::new();
+            let mut current = node;
+            let mut is_complete = true;
+
+            while !current.is_root {
+                match current.parent_id.and_then(|parent_id| self.nodes.get(&parent_id)) {
+                    Some(parent) if !ancestor_ids.contains(&parent.id) => {
+                        ancestor_ids.insert(parent.id);
+                        current = parent;
+                    }
+                    _ => {
+                        is_complete = false;
+                        break;
+                    }
+                }
+            }
+
+            if is_complete {
+                self.ancestor_ids_by_node_id.insert(*id, ancestor_ids);
+            }
+        }
+    }
+
+    /// Runs all checks. Returned repairs are applied only if repair is requested.
+    fn report(self) -> (ConsistencyReport, Repairs) {
+        let mut repairs = Repairs::default();
+        let mut inconsistencies = vec![];
+
+        inconsistencies.extend(self.check_nodes(&mut repairs));
+        inconsistencies.extend(self.check_descendants(&mut repairs));
+        inconsistencies.extend(self.check_descendants_count(&mut repairs));
+        inconsistencies.extend(self.check_flow_steps(&mut repairs));
+        inconsistencies.extend(self.check_ios(&mut repairs));
+        inconsistencies.extend(self.check_workflows(&mut repairs));
+
+        inconsistencies.sort_by_key(|inconsistency| (inconsistency.category, inconsistency.object_id));
+
+        let report = ConsistencyReport {
+            root_id: self.root_id,
+            branch_id: self.branch_id,
+            nodes_count: self.nodes.len(),
+            flow_steps_count: self.flow_steps.len(),
+            ios_count: self.ios.len(),
+            inconsistencies,
+        };
+
+        (report, repairs)
+    }
+
+    fn inconsistency(
+        &self,
+        category: InconsistencyCategory,
+        object_id: Uuid,
+        related_id: Option<Uuid>,
+        message: String,
+        is_repairable: bool,
+    ) -> Inconsistency {
+        Inconsistency {
+            category,
+            object_id,
+            related_id,
+            message,
+            repaired: self.repair && is_repairable,
+        }
+    }
+
+    fn check_nodes(&self, repairs: &mut Repairs) -> Vec<Inconsistency> {
+        let mut inconsistencies = vec![];
+
+        for id in &self.owned_node_ids {
+            let node = &self.nodes[id];
+
+            let has_parent = node
+                .parent_id
+                .is_some_and(|parent_id| self.nodes.contains_key(&parent_id));
+
+            if !node.is_root && !has_parent {
+                inconsistencies.push(self.inconsistency(
+                    InconsistencyCategory::MissingParent,
+                    node.id,
+                    node.parent_id,
+                    format!("Parent of node {} does not exist", node.id),
+                    false,
+                ));
+
+                continue;
+            }
+
+            // ancestors can not be built for descendants of nodes with missing parents
+            let expected_ids = match self.ancestor_ids_by_node_id.get(id) {
+                Some(expected_ids) => expected_ids,
+                None => continue,
+            };
+            let ancestor_ids = node.ancestor_ids.clone().unwrap_or_default();
+
+            if &ancestor_ids == expected_ids {
+                continue;
+            }
+
+            let missing_ids: Set<Uuid> = expected_ids.difference(&ancestor_ids).cloned().collect();
+            let extra_ids: Set<Uuid> = ancestor_ids.difference(expected_ids).cloned().collect();
+
+            inconsistencies.push(self.inconsistency(
+                InconsistencyCategory::AncestorIdsMismatch,
+                node.id,
+                None,
+                format!(
+                    "Node {} is missing ancestors {:?} and has extra ancestors {:?}",
+                    node.id, missing_ids, extra_ids
+                ),
+                true,
+            ));
+
+            repairs.ancestor_ids.push((node.clone(), extra_ids, missing_ids));
+        }
+
+        inconsistencies
+    }
+
+    fn check_descendants(&self, repairs: &mut Repairs) -> Vec<Inconsistency> {
+        let mut inconsistencies = vec![];
+        let mut rows_by_key: HashMap<(Uuid, Uuid), Vec<&NodeDescendant>> = HashMap::new();
+
+        for row in &self.descendants {
+            rows_by_key.entry((row.node_id, row.id)).or_default().push(row);
+        }
+
+        for id in &self.owned_node_ids {
+            let node = &self.nodes[id];
+            let ancestor_ids = match self.ancestor_ids_by_node_id.get(id) {
+                Some(ancestor_ids) => ancestor_ids,
+                None => continue,
+            };
+
+            for ancestor_id in ancestor_ids {
+                let expected_row = NodeDescendant {
+                    root_id: self.root_id,
+                    branch_id: self.branch_id,
+                    node_id: *ancestor_id,
+                    order_index: node.order_index,
+                    id: node.id,
+                    parent_id: node.parent_id.unwrap_or_default(),
+                    title: node.title.clone(),
+                };
+
+                match rows_by_key.remove(&(*ancestor_id, node.id)) {
+                    None => {
+                        inconsistencies.push(self.inconsistency(
+                            InconsistencyCategory::MissingDescendant,
+                            *ancestor_id,
+                            Some(node.id),
+                            format!("Node {} is missing descendant {}", ancestor_id, node.id),
+                            true,
+                        ));
+
+                        repairs.insert_descendants.push(expected_row);
+                    }
+                    Some(rows) => {
+                        let is_current = rows.len() == 1
+                            && rows[0].order_index == expected_row.order_index
+                            && rows[0].parent_id == expected_row.parent_id
+                            && rows[0].title == expected_row.title;
+
+                        if is_current {
+                            continue;
+                        }
+
+                        inconsistencies.push(self.inconsistency(
+                            InconsistencyCategory::OutdatedDescendant,
+                            *ancestor_id,
+                            Some(node.id),
+                            format!("Descendant {} of node {} is outdated", node.id, ancestor_id),
+                            true,
+                        ));
+
+                        // rows with the same order index are overwritten by the insert
+                        repairs.delete_descendants.extend(
+                            rows.into_iter()
+                                .filter(|row| row.order_index != node.order_index)
+                                .cloned(),
+                        );
+                        repairs.insert_descendants.push(expected_row);
+                    }
+                }
+            }
+        }
+
+        for ((node_id, id), rows) in rows_by_key {
+            // rows of nodes stored in the parent partitions are checked with those partitions
+            let is_layered = !self.owned_node_ids.contains(&id)
+                && self
+                    .ancestor_ids_by_node_id
+                    .get(&id)
+                    .is_some_and(|ancestor_ids| ancestor_ids.contains(&node_id));
+
+            if is_layered {
+                continue;
+            }
+
+            inconsistencies.push(self.inconsistency(
+                InconsistencyCategory::StaleDescendant,
+                node_id,
+                Some(id),
+                format!("Node {} has stale descendant {}", node_id, id),
+                true,
+            ));
+
+            repairs.delete_descendants.extend(rows.into_iter().cloned());
+        }
+
+        inconsistencies
+    }
+
+    fn check_descendants_count(&self, repairs: &mut Repairs) -> Vec<Inconsistency> {
+        let mut inconsistencies = vec![];
+        let counts = match &self.descendants_counts {
+            Some(counts) => counts,
+            None => return inconsistencies,
+        };
+        let mut expected_counts: HashMap<Uuid, i64> = self.nodes.keys().map(|id| (*id, 0)).collect();
+
+        for ancestor_ids in self.ancestor_ids_by_node_id.values() {
+            for ancestor_id in ancestor_ids {
+                *expected_counts.entry(*ancestor_id).or_default() += 1;
+            }
+        }
+
+        for (id, expected_count) in expected_counts {
+            let count = counts.get(&id).copied().unwrap_or_default();
+
+            if count == expected_count {
+                continue;
+            }
+
+            inconsistencies.push(self.inconsistency(
+                InconsistencyCategory::DescendantsCountMismatch,
+                id,
+                None,
+                format!(
+                    "Node {} has descendants count {} instead of {}",
+                    id, count, expected_count
+                ),
+                true,
+            ));
+
+            repairs.descendants_counts.push((id, expected_count - count));
+        }
+
+        inconsistencies
+    }
+
+    fn check_flow_steps(&self, repairs: &mut Repairs) -> Vec<Inconsistency> {
+        let mut inconsistencies = vec![];
+
+        for id in &self.owned_flow_step_ids {
+            let flow_step = &self.flow_steps[id];
+            let mut node_ids = flow_step.node_ids.clone();
+            let mut input_ids_by_node_id = flow_step.input_ids_by_node_id.clone();
+            let mut output_ids_by_node_id = flow_step.output_ids_by_node_id.clone();
+            let mut is_changed = false;
+
+            if let Some(node_ids) = node_ids.as_mut() {
+                for node_id in node_ids.iter().filter(|node_id| !self.nodes.contains_key(node_id)) {
+                    inconsistencies.push(self.inconsistency(
+                        InconsistencyCategory::MissingFlowStepNode,
+                        flow_step.id,
+                        Some(*node_id),
+                        format!("Flow step {} has node {} that does not exist", flow_step.id, node_id),
+                        true,
+                    ));
+
+                    is_changed = true;
+                }
+
+                node_ids.retain(|node_id| self.nodes.contains_key(node_id));
+            }
+
+            if let Some(input_ids_by_node_id) = input_ids_by_node_id.as_mut() {
+                input_ids_by_node_id.retain(|node_id, _| self.nodes.contains_key(node_id));
+
+                for input_ids in input_ids_by_node_id.values_mut() {
+                    for input_id in input_ids.iter() {
+                        match self.ios.get(input_id) {
+                            None => {
+                                inconsistencies.push(self.inconsistency(
+                                    InconsistencyCategory::MissingFlowStepInput,
+                                    flow_step.id,
+                                    Some(*input_id),
+                                    format!("Flow step {} has input {} that does not exist", flow_step.id, input_id),
+                                    true,
+                                ));
+
+                                is_changed = true;
+                            }
+                            Some(io)
+                                if !io
+                                    .inputted_by_flow_steps
+                                    .as_ref()
+                                    .is_some_and(|ids| ids.contains(&flow_step.id)) =>
+                            {
+                                let is_owned = self.owned_io_ids.contains(input_id);
+
+                                inconsistencies.push(self.inconsistency(
+                                    InconsistencyCategory::InputNotLinked,
+                                    *input_id,
+                                    Some(flow_step.id),
+                                    format!("Input {} is not linked to flow step {}", input_id, flow_step.id),
+                                    is_owned,
+                                ));
+
+                                if is_owned {
+                                    repairs.link_inputs.push((flow_step.id, *input_id));
+                                }
+                            }
+                            Some(_) => (),
+                        }
+                    }
+
+                    input_ids.retain(|input_id| self.ios.contains_key(input_id));
+                }
+            }
+
+            if let Some(output_ids_by_node_id) = output_ids_by_node_id.as_mut() {
+                output_ids_by_node_id.retain(|node_id, _| self.nodes.contains_key(node_id));
+
+                for (node_id, output_ids) in output_ids_by_node_id.iter_mut() {
+                    for output_id in output_ids.iter() {
+                        match self.ios.get(output_id) {
+                            None => {
+                                inconsistencies.push(self.inconsistency(
+                                    InconsistencyCategory::MissingFlowStepOutput,
+                                    flow_step.id,
+                                    Some(*output_id),
+                                    format!(
+                                        "Flow step {} has output {} that does not exist",
+                                        flow_step.id, output_id
+                                    ),
+                                    true,
+                                ));
+
+                                is_changed = true;
+                            }
+                            Some(io) if io.flow_step_id != Some(flow_step.id) => {
+                                let is_owned = self.owned_io_ids.contains(output_id);
+
+                                inconsistencies.push(self.inconsistency(
+                                    InconsistencyCategory::OutputNotLinked,
+                                    *output_id,
+                                    Some(flow_step.id),
+                                    format!("Output {} is not linked to flow step {}", output_id, flow_step.id),
+                                    is_owned,
+                                ));
+
+                                if is_owned {
+                                    repairs.output_ios.push(UpdateFlowStepIo {
+                                        root_id: io.root_id,
+                                        node_id: io.node_id,
+                                        branch_id: io.branch_id,
+                                        id: io.id,
+                                        flow_step_id: Some(flow_step.id),
+                                        flow_step_node_id: Some(*node_id),
+                                    });
+                                }
+                            }
+                            Some(_) => (),
+                        }
+                    }
+
+                    output_ids.retain(|output_id| self.ios.contains_key(output_id));
+                }
+            }
+
+            if is_changed {
+                repairs.flow_steps.push(UpdateNodeIdsFlowStep {
+                    node_id: flow_step.node_id,
+                    branch_id: flow_step.branch_id,
+                    flow_id: flow_step.flow_id,
+                    step_index: flow_step.step_index.clone(),
+                    id: flow_step.id,
+                    root_id: flow_step.root_id,
+                    node_ids,
+                    output_ids_by_node_id,
+                    input_ids_by_node_id,
+                    updated_at: flow_step.updated_at,
+                    ctx: Default::default(),
+                });
+            }
+        }
+
+        inconsistencies
+    }
+
+    fn check_ios(&self, repairs: &mut Repairs) -> Vec<Inconsistency> {
+        let mut inconsistencies = vec![];
+
+        for id in &self.owned_io_ids {
+            let io = &self.ios[id];
+
+            for flow_step_id in io.inputted_by_flow_steps.iter().flatten() {
+                let is_input = self.flow_steps.get(flow_step_id).is_some_and(|flow_step| {
+                    flow_step
+                        .input_ids_by_node_id
+                        .iter()
+                        .flat_map(|input_ids_by_node_id| input_ids_by_node_id.values())
+                        .any(|input_ids| input_ids.contains(&io.id))
+                });
+
+                if is_input {
+                    continue;
+                }
+
+                inconsistencies.push(self.inconsistency(
+                    InconsistencyCategory::StaleInputLink,
+                    io.id,
+                    Some(*flow_step_id),
+                    format!("Io {} is not an input of flow step {}", io.id, flow_step_id),
+                    true,
+                ));
+
+                repairs.unlink_inputs.push((*flow_step_id, io.id));
+            }
+
+            if let Some(flow_step_id) = io.flow_step_id {
+                let is_output = self.flow_steps.get(&flow_step_id).is_some_and(|flow_step| {
+                    flow_step
+                        .output_ids_by_node_id
+                        .iter()
+                        .flat_map(|output_ids_by_node_id| output_ids_by_node_id.values())
+                        .any(|output_ids| output_ids.contains(&io.id))
+                });
+
+                // io can not be repaired without knowing which flow step it belongs to
+                if !is_output {
+                    inconsistencies.push(self.inconsistency(
+                        InconsistencyCategory::StaleOutputLink,
+                        io.id,
+                        Some(flow_step_id),
+                        format!("Io {} is not an output of flow step {}", io.id, flow_step_id),
+                        false,
+                    ));
+                }
+            }
+
+            if io.initial_input {
+                let workflow = self.workflows.get(&io.node_id);
+                let is_listed = workflow
+                    .and_then(|workflow| workflow.initial_input_ids.as_ref())
+                    .is_some_and(|initial_input_ids| initial_input_ids.contains(&io.id));
+
+                if is_listed {
+                    continue;
+                }
+
+                let is_owned = self.owned_workflow_ids.contains(&io.node_id);
+
+                inconsistencies.push(self.inconsistency(
+                    InconsistencyCategory::InitialInputNotListed,
+                    io.id,
+                    Some(io.node_id),
+                    format!(
+                        "Initial input {} is not listed in workflow of node {}",
+                        io.id, io.node_id
+                    ),
+                    is_owned,
+                ));
+
+                if is_owned {
+                    repairs.list_initial_inputs.push((io.node_id, io.id));
+                }
+            }
+        }
+
+        inconsistencies
+    }
+
+    fn check_workflows(&self, repairs: &mut Repairs) -> Vec<Inconsistency> {
+        let mut inconsistencies = vec![];
+
+        for node_id in &self.owned_workflow_ids {
+            let workflow = &self.workflows[node_id];
+            let missing_ids: Vec<Uuid> = workflow
+                .initial_input_ids
+                .iter()
+                .flatten()
+                .filter(|input_id| !self.ios.contains_key(input_id))
+                .cloned()
+                .collect();
+
+            for input_id in &missing_ids {
+                inconsistencies.push(self.inconsistency(
+                    InconsistencyCategory::MissingInitialInput,
+                    workflow.node_id,
+                    Some(*input_id),
+                    format!(
+                        "Workflow of node {} has initial input {} that does not exist",
+                        workflow.node_id, input_id
+                    ),
+                    true,
+                ));
+            }
+
+            if !missing_ids.is_empty() {
+                repairs.unlist_initial_inputs.push((workflow.node_id, missing_ids));
+            }
+        }
+
+        inconsistencies
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct Tree {
+        root_id: Uuid,
+        child_id: Uuid,
+        grandchild_id: Uuid,
+    }
+
+    impl Tree {
+        fn new() -> Self {
+            Self {
+                root_id: Uuid::new_v4(),
+                child_id: Uuid::new_v4(),
+                grandchild_id: Uuid::new_v4(),
+            }
+        }
+
+        fn node(&self, id: Uuid, parent_id: Option<Uuid>, ancestor_ids: &[Uuid]) -> Node {
+            Node {
+                branch_id: self.root_id,
+                id,
+                root_id: self.root_id,
+                is_root: parent_id.is_none(),
+                parent_id,
+                ancestor_ids: Some(ancestor_ids.iter().cloned().collect()),
+                title: id.to_string(),
+                ..Default::default()
+            }
+        }
+
+        fn descendant(&self, node_id: Uuid, node: &Node) -> NodeDescendant {
+            NodeDescendant {
+                root_id: self.root_id,
+                branch_id: self.root_id,
+                node_id,
+                order_index: node.order_index,
+                id: node.id,
+                parent_id: node.parent_id.unwrap_or_default(),
+                title: node.title.clone(),
+            }
+        }
+
+        /// Consistent tree of root, child and grandchild nodes.
+        fn consistency(&self, repair: bool) -> TreeConsistency {
+            let nodes = vec![
+                self.node(self.root_id, None, &[]),
+                self.node(self.child_id, Some(self.root_id), &[self.root_id]),
+                self.node(self.grandchild_id, Some(self.child_id), &[self.root_id, self.child_id]),
+            ];
+            let descendants = vec![
+                self.descendant(self.root_id, &nodes[1]),
+                self.descendant(self.root_id, &nodes[2]),
+                self.descendant(self.child_id, &nodes[2]),
+            ];
+
+            let mut consistency = TreeConsistency {
+                root_id: self.root_id,
+                branch_id: self.root_id,
+                repair,
+                owned_node_ids: nodes.iter().map(|node| node.id).collect(),
+                nodes: nodes.into_iter().map(|node| (node.id, node)).collect(),
+                workflows: HashMap::new(),
+                owned_workflow_ids: HashSet::new(),
+                flow_steps: HashMap::new(),
+                owned_flow_step_ids: HashSet::new(),
+                ios: HashMap::new(),
+                owned_io_ids: HashSet::new(),
+                descendants,
+                descendants_counts: Some(HashMap::from([(self.root_id, 2), (self.child_id, 1)])),
+                ancestor_ids_by_node_id: HashMap::new(),
+            };
+
+            consistency.build_ancestor_ids();
+
+            consistency
+        }
+    }
+
+    fn categories(report: &ConsistencyReport) -> Vec<InconsistencyCategory> {
+        report
+            .inconsistencies
+            .iter()
+            .map(|inconsistency| inconsistency.category)
+            .collect()
+    }
+
+    #[test]
+    fn test_consistent_tree() {
+        let tree = Tree::new();
+        let (report, repairs) = tree.consistency(true).report();
+
+        assert!(report.inconsistencies.is_empty());
+        assert_eq!(report.nodes_count, 3);
+        assert!(repairs.ancestor_ids.is_empty());
+        assert!(repairs.insert_descendants.is_empty());
+        assert!(repairs.delete_descendants.is_empty());
+        assert!(repairs.descendants_counts.is_empty());
+    }
+
+    #[test]
+    fn test_descendants_count_mismatch() {
+        let tree = Tree::new();
+        let mut consistency = tree.consistency(true);
+
+        consistency.descendants_counts = Some(HashMap::from([(tree.root_id, 5), (tree.grandchild_id, 1)]));
+
+        let (report, repairs) = consistency.report();
+
+        assert_eq!(
+            categories(&report),
+            vec![InconsistencyCategory::DescendantsCountMismatch; 3]
+        );
+        assert!(report
+            .inconsistencies
+            .iter()
+            .all(|inconsistency| inconsistency.repaired));
+
+        let mut differences = repairs.descendants_counts.clone();
+        let mut expected_differences = vec![(tree.root_id, -3), (tree.child_id, 1), (tree.grandchild_id, -1)];
+
+        differences.sort();
+        expected_differences.sort();
+
+        assert_eq!(differences, expected_differences);
+    }
+
+    #[test]
+    fn test_descendants_count_is_not_checked_for_branches() {
+        let tree = Tree::new();
+        let mut consistency = tree.consistency(true);
+
+        consistency.descendants_counts = None;
+
+        let (report, repairs) = consistency.report();
+
+        assert!(report.inconsistencies.is_empty());
+        assert!(repairs.descendants_counts.is_empty());
+    }
+
+    #[test]
+    fn test_ancestor_ids_mismatch() {
+        let tree = Tree::new();
+        let extra_id = Uuid::new_v4();
+        let mut consistency = tree.consistency(true);
+
+        consistency.nodes.get_mut(&tree.grandchild_id).unwrap().ancestor_ids =
+            Some([tree.root_id, extra_id].into_iter().collect());
+
+        let (report, repairs) = consistency.report();
+
+        assert_eq!(categories(&report), vec![InconsistencyCategory::AncestorIdsMismatch]);
+        assert!(report.inconsistencies[0].repaired);
+
+        let (node, extra_ids, missing_ids) = &repairs.ancestor_ids[0];
+
+        assert_eq!(node.id, tree.grandchild_id);
+        assert_eq!(extra_ids, &[extra_id].into_iter().collect::<Set<Uuid>>());
+        assert_eq!(missing_ids, &[tree.child_id].into_iter().collect::<Set<Uuid>>());
+    }
+
+    #[test]
+    fn test_missing_parent_is_not_repairable() {
+        let tree = Tree::new();
+        let mut consistency = tree.consistency(true);
+
+        consistency.nodes.get_mut(&tree.child_id).unwrap().parent_id = Some(Uuid::new_v4());
+        consistency.ancestor_ids_by_node_id.clear();
+        consistency.build_ancestor_ids();
+
+        let (report, repairs) = consistency.report();
+        let missing_parent = report
+            .inconsistencies
+            .iter()
+            .find(|inconsistency| inconsistency.category == InconsistencyCategory::MissingParent)
+            .unwrap();
+
+        assert_eq!(missing_parent.object_id, tree.child_id);
+        assert!(!missing_parent.repaired);
+        assert!(repairs.ancestor_ids.is_empty());
+    }
+
+    #[test]
+    fn test_descendants() {
+        let tree = Tree::new();
+        let stale_id = Uuid::new_v4();
+        let mut consistency = tree.consistency(true);
+
+        // missing grandchild of root, outdated grandchild of child and stale row
+        consistency
+            .descendants
+            .retain(|row| !(row.node_id == tree.root_id && row.id == tree.grandchild_id));
+        consistency
+            .descendants
+            .iter_mut()
+            .find(|row| row.node_id == tree.child_id)
+            .unwrap()
+            .order_index = 5.0;
+        consistency.descendants.push(NodeDescendant {
+            root_id: tree.root_id,
+            branch_id: tree.root_id,
+            node_id: tree.child_id,
+            id: stale_id,
+            ..Default::default()
+        });
+
+        let (report, repairs) = consistency.report();
+
+        assert_eq!(
+            categories(&report),
+            vec![
+                InconsistencyCategory::MissingDescendant,
+                InconsistencyCategory::OutdatedDescendant,
+                InconsistencyCategory::StaleDescendant,
+            ]
+        );
+
+        let mut inserted: Vec<(Uuid, Uuid)> = repairs
+            .insert_descendants
+            .iter()
+            .map(|row| (row.node_id, row.id))
+            .collect();
+        let mut deleted: Vec<(Uuid, Uuid)> = repairs
+            .delete_descendants
+            .iter()
+            .map(|row| (row.node_id, row.id))
+            .collect();
+
+        inserted.sort();
+        deleted.sort();
+
+        let mut expected_inserted = vec![(tree.root_id, tree.grandchild_id), (tree.child_id, tree.grandchild_id)];
+        let mut expected_deleted = vec![(tree.child_id, tree.grandchild_id), (tree.child_id, stale_id)];
+
+        expected_inserted.sort();
+        expected_deleted.sort();
+
+        assert_eq!(inserted, expected_inserted);
+        assert_eq!(deleted, expected_deleted);
+    }
+
+    #[test]
+    fn test_flow_step_references() {
+        let tree = Tree::new();
+        let flow_step_id = Uuid::new_v4();
+        let io_id = Uuid::new_v4();
+        let missing_io_id = Uuid::new_v4();
+        let missing_node_id = Uuid::new_v4();
+        let mut consistency = tree.consistency(true);
+
+        consistency.flow_steps.insert(
+            flow_step_id,
+            FlowStep {
+                branch_id: tree.root_id,
+                root_id: tree.root_id,
+                id: flow_step_id,
+                node_ids: Some(vec![tree.child_id, missing_node_id]),
+                input_ids_by_node_id: Some([(tree.child_id, vec![io_id, missing_io_id])].into_iter().collect()),
+                ..Default::default()
+            },
+        );
+        consistency.owned_flow_step_ids.insert(flow_step_id);
+        consistency.ios.insert(
+            io_id,
+            Io {
+                branch_id: tree.root_id,
+                root_id: tree.root_id,
+                id: io_id,
+                ..Default::default()
+            },
+        );
+        consistency.owned_io_ids.insert(io_id);
+
+        let (report, repairs) = consistency.report();
+
+        assert_eq!(
+            categories(&report),
+            vec![
+                InconsistencyCategory::MissingFlowStepNode,
+                InconsistencyCategory::MissingFlowStepInput,
+                InconsistencyCategory::InputNotLinked,
+            ]
+        );
+        assert_eq!(repairs.link_inputs, vec![(flow_step_id, io_id)]);
+
+        let flow_step = &repairs.flow_steps[0];
+
+        assert_eq!(flow_step.node_ids, Some(vec![tree.child_id]));
+        assert_eq!(
+            flow_step.input_ids_by_node_id,
+            Some([(tree.child_id, vec![io_id])].into_iter().collect())
+        );
+    }
+
+    #[test]
+    fn test_check_without_repair() {
+        let tree = Tree::new();
+        let mut consistency = tree.consistency(false);
+
+        consistency.descendants.clear();
+
+        let (report, _) = consistency.report();
+
+        assert_eq!(report.inconsistencies.len(), 3);
+        assert!(report
+            .inconsistencies
+            .iter()
+            .all(|inconsistency| !inconsistency.repaired));
+    }
+
+    #[test]
+    fn test_retain_tree() {
+        let tree = Tree::new();
+        let mut consistency = tree.consistency(false);
+        let other_root_node = Node {
+            id: Uuid::new_v4(),
+            root_id: Uuid::new_v4(),
+            ..Default::default()
+        };
+
+        consistency.owned_node_ids.insert(other_root_node.id);
+        consistency.nodes.insert(other_root_node.id, other_root_node);
+        consistency.retain_tree(&[tree.grandchild_id].into_iter().collect());
+
+        let mut node_ids: Vec<Uuid> = consistency.owned_node_ids.into_iter().collect();
+        let mut expected_ids = vec![tree.root_id, tree.child_id];
+
+        node_ids.sort();
+        expected_ids.sort();
+
+        assert_eq!(node_ids, expected_ids);
+    }
+}
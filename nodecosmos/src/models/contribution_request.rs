@@ -11,20 +11,24 @@ use serde::{Deserialize, Serialize};
 
 use crate::api::data::RequestData;
 use crate::errors::NodecosmosError;
+use crate::models::branch::merge::selection::MergeSelection;
 use crate::models::branch::Branch;
 use crate::models::description::Description;
+use crate::models::description_version::DescriptionVersion;
 use crate::models::flow::PkFlow;
 use crate::models::flow_step::PkFlowStep;
 use crate::models::io::DeleteIo;
 use crate::models::node::{Node, PkNode};
 use crate::models::node_counter::NodeCounter;
 use crate::models::notification::{Notification, NotificationType};
+use crate::models::review_settings::ReviewSettings;
 use crate::models::traits::Branchable;
 use crate::models::udts::Profile;
 use crate::models::utils::{impl_updated_at_cb, sanitize_description_cb, updated_at_cb_fn};
 use crate::models::workflow::DeleteWorkflow;
 
 pub mod create;
+pub mod revert;
 pub mod update;
 
 pub enum ContributionRequestStatus {
@@ -90,6 +94,14 @@ pub struct ContributionRequest {
 
     pub owner: Option<Frozen<Profile>>,
 
+    /// Users requested to review the contribution request.
+    pub reviewer_ids: Option<Set<Uuid>>,
+
+    /// Open branch that the contribution request is stacked on.
+    #[charybdis(ignore)]
+    #[serde(default)]
+    pub parent_id: Option<Uuid>,
+
     #[charybdis(ignore)]
     #[serde(skip)]
     pub branch: Option<Branch>,
@@ -163,7 +175,10 @@ impl Callbacks for ContributionRequest {
     updated_at_cb_fn!();
 
     async fn after_delete(&mut self, db_session: &CachingSession, data: &RequestData) -> Result<(), Self::Error> {
-        self.branch(db_session).await?.delete().execute(db_session).await?;
+        let branch = self.branch(db_session).await?;
+        let edited_description_ids = branch.all_edited_description_ids();
+
+        branch.delete().execute(db_session).await?;
 
         // delete branch data
         let nodes = PkNode {
@@ -211,6 +226,12 @@ impl Callbacks for ContributionRequest {
                 log::error!("Error deleting branch data: {:?}", e);
             });
 
+        let _ = DescriptionVersion::delete_branch_versions(db_session, self.id, edited_description_ids)
+            .await
+            .map_err(|e| {
+                log::error!("Error deleting branch description versions: {:?}", e);
+            });
+
         let _ = NodeCounter::decrement_cr_count(data, self.root_id, self.root_id, self.node_id)
             .await
             .map_err(|e| {
@@ -250,22 +271,37 @@ impl ContributionRequest {
             .ok_or_else(|| NodecosmosError::NotFound("Branch not found".to_string()))
     }
 
-    pub async fn merge(&mut self, data: &RequestData) -> Result<(), NodecosmosError> {
+    /// Merges the branch of the contribution request. With a selection only the selected objects are merged and
+    /// the contribution request stays open for the rest of the changes.
+    pub async fn merge(
+        &mut self,
+        data: &RequestData,
+        selection: Option<MergeSelection>,
+    ) -> Result<(), NodecosmosError> {
         if self.status == Some(ContributionRequestStatus::Merged.to_string()) {
             return Err(NodecosmosError::PreconditionFailed(
                 "Contribution request is already merged",
             ));
         }
 
+        ReviewSettings::validate_approvals(data.db_session(), self.root_id, self.id).await?;
+
         let branch = self.branch(data.db_session()).await?.clone();
+        let is_partial = selection.is_some();
 
-        let updated_branch = branch.merge(data).await;
+        let updated_branch = match selection {
+            Some(selection) => branch.merge_selection(data, selection).await,
+            None => branch.merge(data).await,
+        };
 
         match updated_branch {
             Ok(updated_branch) => {
                 self.branch.replace(updated_branch);
-                self.update_status(data, ContributionRequestStatus::Merged).await?;
-                self.create_merge_notification(data).await?;
+
+                if !is_partial {
+                    self.update_status(data, ContributionRequestStatus::Merged).await?;
+                    self.create_merge_notification(data).await?;
+                }
             }
             Err(merge_error) => {
                 self.branch.replace(merge_error.branch);
@@ -350,6 +386,14 @@ partial_contribution_request!(
 
 sanitize_description_cb!(UpdateContributionRequestDescription);
 
+partial_contribution_request!(
+    UpdateContributionRequestReviewers,
+    node_id,
+    id,
+    reviewer_ids,
+    updated_at
+);
+
 #[cfg(test)]
 mod tests {
     use super::*;
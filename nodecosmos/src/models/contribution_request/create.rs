@@ -1,12 +1,14 @@
-use charybdis::operations::{Insert, InsertWithCallbacks};
+use charybdis::operations::{Find, Insert, InsertWithCallbacks};
 use charybdis::types::Uuid;
 use std::collections::HashSet;
 
 use crate::api::data::RequestData;
+use crate::api::request::current_user::OptCurrentUser;
 use crate::errors::NodecosmosError;
-use crate::models::branch::Branch;
+use crate::models::branch::{Branch, BranchStatus};
 use crate::models::contribution_request::ContributionRequest;
-use crate::models::traits::ModelContext;
+use crate::models::node::Node;
+use crate::models::traits::{Authorization, FindBranchedOrOriginalNode, ModelContext, NodeBranchParams};
 
 impl ContributionRequest {
     pub fn set_defaults(&mut self, data: &RequestData) {
@@ -21,7 +23,21 @@ impl ContributionRequest {
     }
 
     pub async fn create_branch_node(&mut self, data: &RequestData) -> Result<(), NodecosmosError> {
-        let mut node = self.node(data.db_session()).await?.clone();
+        let mut node = match self.parent_id {
+            // branch is already created, so the node is read through the parent branches
+            Some(_) => {
+                Node::find_branched_or_original(
+                    data.db_session(),
+                    NodeBranchParams {
+                        root_id: self.root_id,
+                        branch_id: self.id,
+                        node_id: self.node_id,
+                    },
+                )
+                .await?
+            }
+            None => self.node(data.db_session()).await?.clone(),
+        };
 
         node.branch_id = self.id;
 
@@ -44,6 +60,10 @@ impl ContributionRequest {
 
         editor_ids.insert(node.owner_id);
 
+        if let Some(parent_id) = self.parent_id {
+            self.validate_parent_branch(data, parent_id, root_id).await?;
+        }
+
         let branch = Branch {
             id: self.id, //
             node_id: self.node_id,
@@ -54,6 +74,7 @@ impl ContributionRequest {
             owner: self.owner.clone(),
             is_public,
             is_contribution_request: Some(true),
+            parent_id: self.parent_id,
             editor_ids: Some(editor_ids),
             ..Default::default()
         };
@@ -62,4 +83,40 @@ impl ContributionRequest {
 
         Ok(())
     }
+
+    /// Contribution requests can be stacked only on open branches of the same root that the user can view.
+    async fn validate_parent_branch(
+        &self,
+        data: &RequestData,
+        parent_id: Uuid,
+        root_id: Uuid,
+    ) -> Result<(), NodecosmosError> {
+        let mut parent = Branch {
+            id: parent_id,
+            ..Default::default()
+        }
+        .maybe_find_by_primary_key()
+        .execute(data.db_session())
+        .await?
+        .ok_or_else(|| NodecosmosError::NotFound(format!("Parent branch {} not found", parent_id)))?;
+
+        if parent.root_id != root_id {
+            return Err(NodecosmosError::BadRequest(
+                "Parent branch must belong to the same root".to_string(),
+            ));
+        }
+
+        if parent.status != Some(BranchStatus::Open.to_string()) {
+            return Err(NodecosmosError::PreconditionFailed("Parent branch is not open"));
+        }
+
+        parent
+            .auth_view(
+                data.db_session(),
+                &OptCurrentUser(Option::from(data.current_user.clone())),
+            )
+            .await?;
+
+        Ok(())
+    }
 }
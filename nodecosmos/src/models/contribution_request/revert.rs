@@ -0,0 +1,952 @@
+use std::collections::{HashMap, HashSet};
+
+use charybdis::batch::ModelBatch;
+use charybdis::operations::{DeleteWithCallbacks, InsertWithCallbacks, Update};
+use charybdis::types::{Set, Uuid};
+use scylla::client::caching_session::CachingSession;
+
+use crate::api::data::RequestData;
+use crate::constants::BATCH_CHUNK_SIZE;
+use crate::errors::NodecosmosError;
+use crate::models::archived_description::{find_archived_description, ArchivedDescription};
+use crate::models::archived_flow::{find_archived_flow, ArchivedFlow};
+use crate::models::archived_flow_step::{find_archived_flow_step, ArchivedFlowStep};
+use crate::models::archived_io::{find_archived_io, ArchivedIo};
+use crate::models::archived_node::{find_archived_node, ArchivedNode};
+use crate::models::archived_workflow::{find_archived_workflow, ArchivedWorkflow};
+use crate::models::branch::Branch;
+use crate::models::contribution_request::{ContributionRequest, ContributionRequestStatus};
+use crate::models::description::Description;
+use crate::models::flow::Flow;
+use crate::models::flow_step::FlowStep;
+use crate::models::io::Io;
+use crate::models::node::{Node, PkNode};
+use crate::models::node_descendant::NodeDescendant;
+use crate::models::traits::WhereInChunksExec;
+use crate::models::traits::{Branchable, Descendants, FindForBranchMerge, ObjectType, Pluck, PluckFromStream};
+use crate::models::udts::BranchReorderData;
+
+/// Builds the branch of a revert contribution request. Merging it undoes the changes of the merged branch:
+/// created objects are deleted, deleted objects are restored from archives, titles are set back to their
+/// previous values, nodes, inputs and outputs of edited flow steps are set back and reordered nodes are moved back
+/// to their previous parent.
+struct BranchRevert<'a> {
+    merged: &'a Branch,
+    branch: Branch,
+    nodes: Vec<Node>,
+    flows: Vec<Flow>,
+    flow_steps: Vec<FlowStep>,
+    ios: Vec<Io>,
+    descriptions: Vec<Description>,
+    initial_input_ids: HashSet<Uuid>,
+}
+
+impl<'a> BranchRevert<'a> {
+    fn new(merged: &'a Branch, branch: Branch) -> Self {
+        Self {
+            merged,
+            branch,
+            nodes: vec![],
+            flows: vec![],
+            flow_steps: vec![],
+            ios: vec![],
+            descriptions: vec![],
+            initial_input_ids: merged.deleted_initial_inputs.clone().unwrap_or_default(),
+        }
+    }
+
+    /// Ids from the merged change set without the ones that were both created and deleted within the branch.
+    fn merged_ids(ids: &Option<Set<Uuid>>, excluded_ids: &Option<Set<Uuid>>) -> Set<Uuid> {
+        ids.iter()
+            .flatten()
+            .filter(|id| {
+                !excluded_ids
+                    .as_ref()
+                    .is_some_and(|excluded_ids| excluded_ids.contains(id))
+            })
+            .copied()
+            .collect()
+    }
+
+    fn non_empty(ids: Set<Uuid>) -> Option<Set<Uuid>> {
+        if ids.is_empty() {
+            None
+        } else {
+            Some(ids)
+        }
+    }
+
+    async fn run(mut self, db_session: &CachingSession) -> Result<Branch, NodecosmosError> {
+        self.delete_created(db_session).await?;
+        self.restore_deleted(db_session).await?;
+        self.revert_flow_step_changes(db_session).await?;
+        self.restore_ios(db_session).await?;
+        self.restore_descriptions(db_session).await?;
+        self.revert_titles(db_session).await?;
+        self.revert_reorders(db_session).await?;
+        self.insert_records(db_session).await?;
+
+        // conflicts are persisted on the branch, so they can be resolved like for any other contribution request
+        let branch = match self.branch.check_conflicts(db_session).await {
+            Ok(branch) => branch,
+            Err(merge_error) => merge_error.branch,
+        };
+
+        Ok(branch)
+    }
+
+    /// Objects created by the merged branch that still exist in the original are marked as deleted.
+    async fn delete_created(&mut self, db_session: &CachingSession) -> Result<(), NodecosmosError> {
+        let original_id = self.merged.original_id();
+
+        let node_ids = Self::merged_ids(&self.merged.created_nodes, &self.merged.deleted_nodes);
+        if !node_ids.is_empty() {
+            let nodes =
+                PkNode::find_by_ids(db_session, original_id, &node_ids.into_iter().collect::<Vec<Uuid>>()).await?;
+            self.branch.deleted_nodes = Self::non_empty(nodes.pluck_id_set());
+        }
+
+        let flow_ids = Self::merged_ids(&self.merged.created_flows, &self.merged.deleted_flows);
+        if !flow_ids.is_empty() {
+            let ids = Flow::find_by_branch_id_and_ids(db_session, original_id, &flow_ids)
+                .await
+                .pluck_id_set()
+                .await?;
+            self.branch.deleted_flows = Self::non_empty(ids);
+        }
+
+        let flow_step_ids = Self::merged_ids(&self.merged.created_flow_steps, &self.merged.deleted_flow_steps);
+        if !flow_step_ids.is_empty() {
+            let ids = FlowStep::find_by_branch_id_and_ids(db_session, original_id, &flow_step_ids)
+                .await
+                .pluck_id_set()
+                .await?;
+            self.branch.deleted_flow_steps = Self::non_empty(ids);
+        }
+
+        let io_ids = Self::merged_ids(&self.merged.created_ios, &self.merged.deleted_ios);
+        if !io_ids.is_empty() {
+            let ios = Io::find_by_branch_id_and_root_id_and_ids(db_session, original_id, self.merged.root_id, &io_ids)
+                .await?;
+            self.branch.deleted_ios = Self::non_empty(ios.pluck_id_set());
+        }
+
+        Ok(())
+    }
+
+    /// Nodes, flows and flow steps deleted by the merged branch are restored from archives, together with flows
+    /// and flow steps that were deleted along with a restored node or flow.
+    async fn restore_deleted(&mut self, db_session: &CachingSession) -> Result<(), NodecosmosError> {
+        self.restore_nodes(db_session).await?;
+        self.restore_flows(db_session).await?;
+        self.restore_flow_steps(db_session).await?;
+
+        let merged_flow_ids = Self::merged_ids(&self.merged.deleted_flows, &self.merged.created_flows);
+        let merged_flow_step_ids = Self::merged_ids(&self.merged.deleted_flow_steps, &self.merged.created_flow_steps);
+        let restored_flow_ids = self.flows.pluck_id_set();
+        let restored_flow_step_ids = self.flow_steps.pluck_id_set();
+
+        merged_flow_ids
+            .iter()
+            .filter(|id| !restored_flow_ids.contains(id))
+            .for_each(|id| log::warn!("[revert] Flow {} of branch {} has no archive", id, self.merged.id));
+        merged_flow_step_ids
+            .iter()
+            .filter(|id| !restored_flow_step_ids.contains(id))
+            .for_each(|id| log::warn!("[revert] Flow step {} of branch {} has no archive", id, self.merged.id));
+
+        self.branch.restored_nodes = Self::non_empty(self.nodes.pluck_id_set());
+        self.branch.restored_flows = Self::non_empty(restored_flow_ids);
+        self.branch.restored_flow_steps = Self::non_empty(restored_flow_step_ids);
+
+        Ok(())
+    }
+
+    async fn restore_nodes(&mut self, db_session: &CachingSession) -> Result<(), NodecosmosError> {
+        let original_id = self.merged.original_id();
+        let node_ids = Self::merged_ids(&self.merged.deleted_nodes, &self.merged.created_nodes);
+
+        if node_ids.is_empty() {
+            return Ok(());
+        }
+
+        let existing_ids = PkNode::find_by_ids(
+            db_session,
+            original_id,
+            &node_ids.iter().copied().collect::<Vec<Uuid>>(),
+        )
+        .await?
+        .pluck_id_set();
+        let archived_nodes: Vec<ArchivedNode> = node_ids
+            .where_in_chunked_query(db_session, |ids_chunk| {
+                find_archived_node!("branch_id = ? AND id IN ?", (original_id, ids_chunk))
+            })
+            .await
+            .try_collect()
+            .await?;
+
+        for archived_node in archived_nodes.iter().filter(|node| !existing_ids.contains(&node.id)) {
+            let mut node = Node::from(archived_node);
+            node.branch_id = self.branch.id;
+
+            self.nodes.push(node);
+        }
+
+        let restored_node_ids = self.nodes.pluck_id_set();
+
+        if restored_node_ids.is_empty() {
+            return Ok(());
+        }
+
+        let archived_workflows: Vec<ArchivedWorkflow> = restored_node_ids
+            .where_in_chunked_query(db_session, |ids_chunk| {
+                find_archived_workflow!("branch_id = ? AND node_id IN ?", (original_id, ids_chunk))
+            })
+            .await
+            .try_collect()
+            .await?;
+
+        self.initial_input_ids.extend(
+            archived_workflows
+                .iter()
+                .flat_map(|workflow| workflow.initial_input_ids.iter().flatten().copied()),
+        );
+
+        let archived_flows: Vec<ArchivedFlow> = restored_node_ids
+            .where_in_chunked_query(db_session, |ids_chunk| {
+                find_archived_flow!("branch_id = ? AND node_id IN ?", (original_id, ids_chunk))
+            })
+            .await
+            .try_collect()
+            .await?;
+
+        self.push_archived_flows(archived_flows);
+
+        let archived_flow_steps: Vec<ArchivedFlowStep> = restored_node_ids
+            .where_in_chunked_query(db_session, |ids_chunk| {
+                find_archived_flow_step!("branch_id = ? AND node_id IN ?", (original_id, ids_chunk))
+            })
+            .await
+            .try_collect()
+            .await?;
+
+        self.push_archived_flow_steps(archived_flow_steps);
+
+        Ok(())
+    }
+
+    /// Flows deleted on their own, i.e. not through their node. Their flow steps were deleted along with them.
+    async fn restore_flows(&mut self, db_session: &CachingSession) -> Result<(), NodecosmosError> {
+        let original_id = self.merged.original_id();
+        let restored_flow_ids = self.flows.pluck_id_set();
+        let flow_ids: Set<Uuid> = Self::merged_ids(&self.merged.deleted_flows, &self.merged.created_flows)
+            .into_iter()
+            .filter(|id| !restored_flow_ids.contains(id))
+            .collect();
+
+        if flow_ids.is_empty() {
+            return Ok(());
+        }
+
+        let existing_ids = Flow::find_by_branch_id_and_ids(db_session, original_id, &flow_ids)
+            .await
+            .pluck_id_set()
+            .await?;
+        let archived_flows: Vec<ArchivedFlow> = flow_ids
+            .where_in_chunked_query(db_session, |ids_chunk| {
+                find_archived_flow!("branch_id = ? AND id IN ? ALLOW FILTERING", (original_id, ids_chunk))
+            })
+            .await
+            .try_collect()
+            .await?;
+        let archived_flows: Vec<ArchivedFlow> = archived_flows
+            .into_iter()
+            .filter(|flow| !existing_ids.contains(&flow.id))
+            .collect();
+
+        if archived_flows.is_empty() {
+            return Ok(());
+        }
+
+        let node_ids: Set<Uuid> = archived_flows.iter().map(|flow| flow.node_id).collect();
+        let flow_ids: Set<Uuid> = archived_flows.iter().map(|flow| flow.id).collect();
+
+        self.push_archived_flows(archived_flows);
+
+        let archived_flow_steps: Vec<ArchivedFlowStep> = node_ids
+            .where_in_chunked_query(db_session, |ids_chunk| {
+                find_archived_flow_step!("branch_id = ? AND node_id IN ?", (original_id, ids_chunk))
+            })
+            .await
+            .try_collect()
+            .await?;
+
+        self.push_archived_flow_steps(
+            archived_flow_steps
+                .into_iter()
+                .filter(|flow_step| flow_ids.contains(&flow_step.flow_id))
+                .collect(),
+        );
+
+        Ok(())
+    }
+
+    /// Flow steps deleted on their own, i.e. not through their node or flow.
+    async fn restore_flow_steps(&mut self, db_session: &CachingSession) -> Result<(), NodecosmosError> {
+        let original_id = self.merged.original_id();
+        let restored_flow_step_ids = self.flow_steps.pluck_id_set();
+        let flow_step_ids: Set<Uuid> =
+            Self::merged_ids(&self.merged.deleted_flow_steps, &self.merged.created_flow_steps)
+                .into_iter()
+                .filter(|id| !restored_flow_step_ids.contains(id))
+                .collect();
+
+        if flow_step_ids.is_empty() {
+            return Ok(());
+        }
+
+        let existing_ids = FlowStep::find_by_branch_id_and_ids(db_session, original_id, &flow_step_ids)
+            .await
+            .pluck_id_set()
+            .await?;
+        let archived_flow_steps: Vec<ArchivedFlowStep> = flow_step_ids
+            .where_in_chunked_query(db_session, |ids_chunk| {
+                find_archived_flow_step!("branch_id = ? AND id IN ? ALLOW FILTERING", (original_id, ids_chunk))
+            })
+            .await
+            .try_collect()
+            .await?;
+
+        self.push_archived_flow_steps(
+            archived_flow_steps
+                .into_iter()
+                .filter(|flow_step| !existing_ids.contains(&flow_step.id))
+                .collect(),
+        );
+
+        Ok(())
+    }
+
+    /// Object can be archived more than once, e.g. if it was restored and deleted again, so only the latest archive
+    /// is restored.
+    fn push_archived_flows(&mut self, mut archived_flows: Vec<ArchivedFlow>) {
+        let mut restored_ids = self.flows.pluck_id_set();
+
+        archived_flows.sort_by_key(|archived_flow| std::cmp::Reverse(archived_flow.updated_at));
+
+        for archived_flow in archived_flows.iter() {
+            if restored_ids.insert(archived_flow.id) {
+                let mut flow = Flow::from(archived_flow);
+                flow.branch_id = self.branch.id;
+
+                self.flows.push(flow);
+            }
+        }
+    }
+
+    fn push_archived_flow_steps(&mut self, mut archived_flow_steps: Vec<ArchivedFlowStep>) {
+        let mut restored_ids = self.flow_steps.pluck_id_set();
+
+        archived_flow_steps.sort_by_key(|archived_flow_step| std::cmp::Reverse(archived_flow_step.updated_at));
+
+        for archived_flow_step in archived_flow_steps.iter() {
+            if restored_ids.insert(archived_flow_step.id) {
+                let mut flow_step = FlowStep::from(archived_flow_step);
+                flow_step.branch_id = self.branch.id;
+
+                self.flow_steps.push(flow_step);
+            }
+        }
+    }
+
+    /// Nodes, inputs and outputs added to flow steps by the merged branch are removed and the removed ones are added
+    /// back. Flow steps created or deleted by the merged branch are reverted as a whole, so they are skipped.
+    async fn revert_flow_step_changes(&mut self, db_session: &CachingSession) -> Result<(), NodecosmosError> {
+        let excluded_ids: Set<Uuid> = self
+            .merged
+            .created_flow_steps
+            .iter()
+            .chain(self.merged.deleted_flow_steps.iter())
+            .flatten()
+            .copied()
+            .collect();
+        let merged = self.merged;
+
+        self.branch.created_flow_step_nodes =
+            Self::edited_flow_step_changes(&merged.deleted_flow_step_nodes, &excluded_ids);
+        self.branch.deleted_flow_step_nodes =
+            Self::edited_flow_step_changes(&merged.created_flow_step_nodes, &excluded_ids);
+        self.branch.created_flow_step_inputs_by_node =
+            Self::edited_flow_step_changes(&merged.deleted_flow_step_inputs_by_node, &excluded_ids);
+        self.branch.deleted_flow_step_inputs_by_node =
+            Self::edited_flow_step_changes(&merged.created_flow_step_inputs_by_node, &excluded_ids);
+        self.branch.created_flow_step_outputs_by_node =
+            Self::edited_flow_step_changes(&merged.deleted_flow_step_outputs_by_node, &excluded_ids);
+        self.branch.deleted_flow_step_outputs_by_node =
+            Self::edited_flow_step_changes(&merged.created_flow_step_outputs_by_node, &excluded_ids);
+
+        let flow_step_ids: Set<Uuid> = [
+            &self.branch.created_flow_step_nodes,
+            &self.branch.deleted_flow_step_nodes,
+        ]
+        .into_iter()
+        .flatten()
+        .flat_map(|changes| changes.keys().copied())
+        .chain(
+            [
+                &self.branch.created_flow_step_inputs_by_node,
+                &self.branch.deleted_flow_step_inputs_by_node,
+                &self.branch.created_flow_step_outputs_by_node,
+                &self.branch.deleted_flow_step_outputs_by_node,
+            ]
+            .into_iter()
+            .flatten()
+            .flat_map(|changes| changes.keys().copied()),
+        )
+        .collect();
+
+        if flow_step_ids.is_empty() {
+            return Ok(());
+        }
+
+        // merge reads added nodes and inputs from the branched flow step
+        let flow_steps: Vec<FlowStep> =
+            FlowStep::find_by_branch_id_and_ids(db_session, self.merged.original_id(), &flow_step_ids)
+                .await
+                .try_collect()
+                .await?;
+
+        for mut flow_step in flow_steps {
+            flow_step.branch_id = self.branch.id;
+            flow_step.apply_changes(
+                self.merged.deleted_flow_step_changes(flow_step.id),
+                self.merged.created_flow_step_changes(flow_step.id),
+            );
+
+            self.flow_steps.push(flow_step);
+        }
+
+        Ok(())
+    }
+
+    fn edited_flow_step_changes<V: Clone>(
+        changes: &Option<HashMap<Uuid, V>>,
+        excluded_ids: &Set<Uuid>,
+    ) -> Option<HashMap<Uuid, V>> {
+        let changes: HashMap<Uuid, V> = changes
+            .iter()
+            .flatten()
+            .filter(|(id, _)| !excluded_ids.contains(id))
+            .map(|(id, change)| (*id, change.clone()))
+            .collect();
+
+        if changes.is_empty() {
+            None
+        } else {
+            Some(changes)
+        }
+    }
+
+    /// Ios deleted by the merged branch, and the ones deleted along with restored nodes or removed from flow steps,
+    /// are restored from archives.
+    async fn restore_ios(&mut self, db_session: &CachingSession) -> Result<(), NodecosmosError> {
+        let original_id = self.merged.original_id();
+        let root_id = self.merged.root_id;
+        let restored_node_ids = self.nodes.pluck_id_set();
+
+        if !restored_node_ids.is_empty() {
+            let archived_ios: Vec<ArchivedIo> = restored_node_ids
+                .where_in_chunked_query(db_session, |ids_chunk| {
+                    find_archived_io!(
+                        "branch_id = ? AND root_id = ? AND node_id IN ? ALLOW FILTERING",
+                        (original_id, root_id, ids_chunk)
+                    )
+                })
+                .await
+                .try_collect()
+                .await?;
+
+            self.ios.extend(archived_ios.iter().map(Io::from));
+        }
+
+        let io_ids: Set<Uuid> = Self::merged_ids(&self.merged.deleted_ios, &self.merged.created_ios)
+            .into_iter()
+            .chain(
+                self.flow_steps
+                    .iter()
+                    .flat_map(|flow_step| flow_step.output_ids_by_node_id.iter().flatten())
+                    .flat_map(|(_, output_ids)| output_ids.iter().copied()),
+            )
+            .collect();
+
+        if !io_ids.is_empty() {
+            let existing_ids = Io::find_by_branch_id_and_root_id_and_ids(db_session, original_id, root_id, &io_ids)
+                .await?
+                .pluck_id_set();
+            let restored_io_ids = self.ios.pluck_id_set();
+            let archived_ios: Vec<ArchivedIo> = io_ids
+                .where_in_chunked_query(db_session, |ids_chunk| {
+                    find_archived_io!(
+                        "branch_id = ? AND root_id = ? AND id IN ?",
+                        (original_id, root_id, ids_chunk)
+                    )
+                })
+                .await
+                .try_collect()
+                .await?;
+
+            self.ios.extend(
+                archived_ios
+                    .iter()
+                    .filter(|io| !existing_ids.contains(&io.id) && !restored_io_ids.contains(&io.id))
+                    .map(Io::from),
+            );
+        }
+
+        self.set_restored_ios_context(db_session).await?;
+        self.branch.restored_ios = Self::non_empty(self.ios.pluck_id_set());
+
+        Ok(())
+    }
+
+    /// Archived ios don't keep workflow and flow step context, so it is resolved from archived workflows and from
+    /// outputs of restored or original flow steps.
+    async fn set_restored_ios_context(&mut self, db_session: &CachingSession) -> Result<(), NodecosmosError> {
+        let restored_flow_step_ids = self.flow_steps.pluck_id_set();
+        let original_flow_step_ids: Set<Uuid> = self
+            .ios
+            .iter()
+            .filter_map(|io| io.flow_step_id)
+            .filter(|id| !restored_flow_step_ids.contains(id))
+            .collect();
+        let mut flow_steps: Vec<FlowStep> = vec![];
+
+        if !original_flow_step_ids.is_empty() {
+            flow_steps =
+                FlowStep::find_by_branch_id_and_ids(db_session, self.merged.original_id(), &original_flow_step_ids)
+                    .await
+                    .try_collect()
+                    .await?;
+        }
+
+        let mut flow_step_node_id_by_io_id = HashMap::new();
+
+        for flow_step in flow_steps.iter().chain(self.flow_steps.iter()) {
+            for (node_id, output_ids) in flow_step.output_ids_by_node_id.iter().flatten() {
+                for output_id in output_ids {
+                    flow_step_node_id_by_io_id.insert(*output_id, *node_id);
+                }
+            }
+        }
+
+        for io in self.ios.iter_mut() {
+            io.branch_id = self.branch.id;
+            io.initial_input = self.initial_input_ids.contains(&io.id);
+            io.flow_step_node_id = flow_step_node_id_by_io_id.get(&io.id).copied();
+        }
+
+        Ok(())
+    }
+
+    async fn restore_descriptions(&mut self, db_session: &CachingSession) -> Result<(), NodecosmosError> {
+        let original_id = self.merged.original_id();
+        let object_ids: Set<Uuid> = [
+            &self.branch.restored_nodes,
+            &self.branch.restored_flows,
+            &self.branch.restored_flow_steps,
+            &self.branch.restored_ios,
+        ]
+        .into_iter()
+        .flatten()
+        .flatten()
+        .copied()
+        .collect();
+
+        if object_ids.is_empty() {
+            return Ok(());
+        }
+
+        let archived_descriptions: Vec<ArchivedDescription> = object_ids
+            .where_in_chunked_query(db_session, |ids_chunk| {
+                find_archived_description!("branch_id = ? AND object_id IN ?", (original_id, ids_chunk))
+            })
+            .await
+            .try_collect()
+            .await?;
+
+        for archived_description in archived_descriptions {
+            let mut description = Description::from(archived_description);
+            description.branch_id = self.branch.id;
+
+            let edited_description_ids = match description.object_type.parse::<ObjectType>()? {
+                ObjectType::Node => &mut self.branch.edited_description_nodes,
+                ObjectType::Flow => &mut self.branch.edited_description_flows,
+                ObjectType::FlowStep => &mut self.branch.edited_description_flow_steps,
+                ObjectType::Io => &mut self.branch.edited_description_ios,
+                ObjectType::Workflow => continue,
+            };
+
+            edited_description_ids
+                .get_or_insert_with(HashSet::new)
+                .insert(description.object_id);
+            self.descriptions.push(description);
+        }
+
+        Ok(())
+    }
+
+    /// Titles edited by the merged branch are set back to their previous values.
+    async fn revert_titles(&mut self, db_session: &CachingSession) -> Result<(), NodecosmosError> {
+        let title_changes = match &self.merged.title_change_by_object {
+            Some(title_changes) => title_changes,
+            None => return Ok(()),
+        };
+        let original_id = self.merged.original_id();
+        let changed_ids = |edited_ids: &Option<Set<Uuid>>| -> Set<Uuid> {
+            title_changes
+                .keys()
+                .filter(|id| edited_ids.as_ref().is_some_and(|edited_ids| edited_ids.contains(id)))
+                .copied()
+                .collect()
+        };
+
+        let node_ids = changed_ids(&self.merged.edited_title_nodes);
+        if !node_ids.is_empty() {
+            let nodes: Vec<Node> = Node::find_by_ids(db_session, original_id, &node_ids.into_iter().collect())
+                .await
+                .try_collect()
+                .await?;
+            self.branch.edited_title_nodes = Self::non_empty(nodes.pluck_id_set());
+
+            for mut node in nodes {
+                node.branch_id = self.branch.id;
+                node.title = title_changes[&node.id].old.clone();
+
+                self.nodes.push(node);
+            }
+        }
+
+        let flow_ids = changed_ids(&self.merged.edited_title_flows);
+        if !flow_ids.is_empty() {
+            let flows: Vec<Flow> = Flow::find_by_branch_id_and_ids(db_session, original_id, &flow_ids)
+                .await
+                .try_collect()
+                .await?;
+            self.branch.edited_title_flows = Self::non_empty(flows.pluck_id_set());
+
+            for mut flow in flows {
+                flow.branch_id = self.branch.id;
+                flow.title = title_changes[&flow.id].old.clone();
+
+                self.flows.push(flow);
+            }
+        }
+
+        let io_ids = changed_ids(&self.merged.edited_title_ios);
+        if !io_ids.is_empty() {
+            let ios = Io::find_by_branch_id_and_root_id_and_ids(db_session, original_id, self.merged.root_id, &io_ids)
+                .await?;
+            self.branch.edited_title_ios = Self::non_empty(ios.pluck_id_set());
+
+            for mut io in ios {
+                io.branch_id = self.branch.id;
+                io.title = Some(title_changes[&io.id].old.clone());
+
+                self.ios.push(io);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Reordered nodes are moved back under their previous parent, next to the siblings that currently surround
+    /// their previous order index.
+    async fn revert_reorders(&mut self, db_session: &CachingSession) -> Result<(), NodecosmosError> {
+        let original_id = self.merged.original_id();
+        let mut reordered_nodes = vec![];
+
+        for reorder_data in self.merged.reordered_nodes.iter().flatten() {
+            let is_created = self
+                .merged
+                .created_nodes
+                .as_ref()
+                .is_some_and(|ids| ids.contains(&reorder_data.id));
+            let is_deleted = self
+                .merged
+                .deleted_nodes
+                .as_ref()
+                .is_some_and(|ids| ids.contains(&reorder_data.id));
+
+            if is_created || is_deleted {
+                continue;
+            }
+
+            let node = Node::maybe_find_first_by_branch_id_and_id(original_id, reorder_data.id)
+                .execute(db_session)
+                .await?;
+            let old_parent = Node::maybe_find_first_by_branch_id_and_id(original_id, reorder_data.old_parent_id)
+                .execute(db_session)
+                .await?;
+
+            let (node, old_parent) = match (node, old_parent) {
+                (Some(node), Some(old_parent)) => (node, old_parent),
+                _ => {
+                    log::warn!(
+                        "[revert] Skipping reorder of node {}: node or its previous parent no longer exists",
+                        reorder_data.id
+                    );
+                    continue;
+                }
+            };
+
+            let siblings: Vec<NodeDescendant> = old_parent
+                .descendants(db_session)
+                .await?
+                .try_collect()
+                .await?
+                .into_iter()
+                .filter(|descendant| descendant.parent_id == old_parent.id && descendant.id != node.id)
+                .collect();
+
+            let new_upper_sibling_id = siblings
+                .iter()
+                .filter(|sibling| sibling.order_index < reorder_data.old_order_index)
+                .max_by(|a, b| a.order_index.total_cmp(&b.order_index))
+                .map(|sibling| sibling.id);
+            let new_lower_sibling_id = siblings
+                .iter()
+                .filter(|sibling| sibling.order_index > reorder_data.old_order_index)
+                .min_by(|a, b| a.order_index.total_cmp(&b.order_index))
+                .map(|sibling| sibling.id);
+
+            reordered_nodes.push(BranchReorderData {
+                id: node.id,
+                new_parent_id: old_parent.id,
+                new_upper_sibling_id,
+                new_lower_sibling_id,
+                old_parent_id: reorder_data.new_parent_id,
+                old_order_index: node.order_index,
+            });
+        }
+
+        if !reordered_nodes.is_empty() {
+            self.branch.reordered_nodes = Some(reordered_nodes);
+        }
+
+        Ok(())
+    }
+
+    async fn insert_records(&mut self, db_session: &CachingSession) -> Result<(), NodecosmosError> {
+        Node::unlogged_batch()
+            .chunked_insert(db_session, &self.nodes, BATCH_CHUNK_SIZE)
+            .await
+            .map_err(NodecosmosError::from)?;
+        Flow::unlogged_batch()
+            .chunked_insert(db_session, &self.flows, BATCH_CHUNK_SIZE)
+            .await
+            .map_err(NodecosmosError::from)?;
+        FlowStep::unlogged_batch()
+            .chunked_insert(db_session, &self.flow_steps, BATCH_CHUNK_SIZE)
+            .await
+            .map_err(NodecosmosError::from)?;
+        Io::unlogged_batch()
+            .chunked_insert(db_session, &self.ios, BATCH_CHUNK_SIZE)
+            .await
+            .map_err(NodecosmosError::from)?;
+        Description::unlogged_batch()
+            .chunked_insert(db_session, &self.descriptions, BATCH_CHUNK_SIZE)
+            .await
+            .map_err(NodecosmosError::from)?;
+
+        self.branch.update().execute(db_session).await?;
+
+        Ok(())
+    }
+}
+
+impl ContributionRequest {
+    /// Creates a new contribution request whose branch undoes the changes of this merged one. The revert goes
+    /// through the regular review and merge flow.
+    pub async fn revert(&mut self, data: &RequestData) -> Result<ContributionRequest, NodecosmosError> {
+        if self.status != Some(ContributionRequestStatus::Merged.to_string()) {
+            return Err(NodecosmosError::PreconditionFailed(
+                "Only merged contribution requests can be reverted",
+            ));
+        }
+
+        let merged = self.branch(data.db_session()).await?.clone();
+
+        let mut revert_cr = ContributionRequest {
+            node_id: self.node_id,
+            root_id: self.root_id,
+            title: format!("Revert \"{}\"", self.title),
+            status: ContributionRequestStatus::default(),
+            ..Default::default()
+        };
+
+        revert_cr.insert_cb(data).execute(data.db_session()).await?;
+
+        let branch = revert_cr.branch(data.db_session()).await?.clone();
+
+        match BranchRevert::new(&merged, branch).run(data.db_session()).await {
+            Ok(branch) => {
+                revert_cr.branch.replace(branch);
+            }
+            Err(e) => {
+                let _ = revert_cr.delete_cb(data).execute(data.db_session()).await.map_err(|e| {
+                    log::error!("[revert] Failed to delete revert contribution request: {:?}", e);
+                });
+
+                return Err(e);
+            }
+        }
+
+        Ok(revert_cr)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::traits::{FindBranchedOrOriginal, ModelBranchParams, NodeBranchParams, Reload};
+
+    #[test]
+    fn test_invert_flow_step_changes() {
+        let flow_step_id = Uuid::new_v4();
+        let (kept_node_id, created_node_id, deleted_node_id) = (Uuid::new_v4(), Uuid::new_v4(), Uuid::new_v4());
+        let (created_input_id, deleted_input_id) = (Uuid::new_v4(), Uuid::new_v4());
+        let (created_output_id, deleted_output_id) = (Uuid::new_v4(), Uuid::new_v4());
+        let merged = Branch {
+            created_flow_step_nodes: Some(HashMap::from([(flow_step_id, HashSet::from([created_node_id]))])),
+            deleted_flow_step_nodes: Some(HashMap::from([(flow_step_id, HashSet::from([deleted_node_id]))])),
+            created_flow_step_inputs_by_node: Some(HashMap::from([(
+                flow_step_id,
+                HashMap::from([(created_node_id, HashSet::from([created_input_id]))]),
+            )])),
+            deleted_flow_step_inputs_by_node: Some(HashMap::from([(
+                flow_step_id,
+                HashMap::from([(deleted_node_id, HashSet::from([deleted_input_id]))]),
+            )])),
+            created_flow_step_outputs_by_node: Some(HashMap::from([(
+                flow_step_id,
+                HashMap::from([(created_node_id, HashSet::from([created_output_id]))]),
+            )])),
+            deleted_flow_step_outputs_by_node: Some(HashMap::from([(
+                flow_step_id,
+                HashMap::from([(deleted_node_id, HashSet::from([deleted_output_id]))]),
+            )])),
+            ..Default::default()
+        };
+        let mut flow_step = FlowStep {
+            id: flow_step_id,
+            node_ids: Some(vec![kept_node_id, created_node_id]),
+            input_ids_by_node_id: Some(HashMap::from([(created_node_id, vec![created_input_id])])),
+            output_ids_by_node_id: Some(HashMap::from([(created_node_id, vec![created_output_id])])),
+            ..Default::default()
+        };
+
+        flow_step.apply_changes(
+            merged.deleted_flow_step_changes(flow_step_id),
+            merged.created_flow_step_changes(flow_step_id),
+        );
+
+        assert_eq!(flow_step.node_ids, Some(vec![kept_node_id, deleted_node_id]));
+        assert_eq!(
+            flow_step.input_ids_by_node_id,
+            Some(HashMap::from([
+                (created_node_id, vec![]),
+                (deleted_node_id, vec![deleted_input_id])
+            ]))
+        );
+        assert_eq!(
+            flow_step.output_ids_by_node_id,
+            Some(HashMap::from([
+                (created_node_id, vec![]),
+                (deleted_node_id, vec![deleted_output_id])
+            ]))
+        );
+    }
+
+    #[test]
+    fn test_edited_flow_step_changes_skip_created_and_deleted_flow_steps() {
+        let (edited_id, created_id) = (Uuid::new_v4(), Uuid::new_v4());
+        let node_ids = HashSet::from([Uuid::new_v4()]);
+        let changes = Some(HashMap::from([
+            (edited_id, node_ids.clone()),
+            (created_id, node_ids.clone()),
+        ]));
+
+        assert_eq!(
+            BranchRevert::edited_flow_step_changes(&changes, &HashSet::from([created_id])),
+            Some(HashMap::from([(edited_id, node_ids)]))
+        );
+        assert_eq!(
+            BranchRevert::edited_flow_step_changes(&changes, &HashSet::from([edited_id, created_id])),
+            None
+        );
+    }
+
+    #[tokio::test]
+    async fn test_revert_restores_standalone_deleted_flow_and_flow_step() {
+        let data = RequestData::new(None).await;
+        let root = Node::sample_node_tree(&data).await;
+        let original_params = NodeBranchParams {
+            root_id: root.root_id,
+            branch_id: root.root_id,
+            node_id: root.root_id,
+        };
+        let deleted_flow = Flow::create_test_flow(&data, &original_params).await;
+        let kept_flow = Flow::create_test_flow(&data, &original_params).await;
+        let deleted_flow_step = FlowStep::create_test_flow_step(&data, &original_params, kept_flow.id).await;
+
+        let mut cr = ContributionRequest::create_test_cr(&data, &root).await;
+
+        let mut branch_flow = deleted_flow.clone();
+        branch_flow.branch_id = cr.id;
+        branch_flow
+            .delete_cb(&data)
+            .execute(data.db_session())
+            .await
+            .expect("Failed to delete branch flow");
+
+        let mut branch_flow_step = FlowStep::find_branched_or_original(
+            data.db_session(),
+            ModelBranchParams {
+                original_id: root.root_id,
+                branch_id: cr.id,
+                id: deleted_flow_step.id,
+            },
+        )
+        .await
+        .expect("Failed to find branch flow step");
+        branch_flow_step.branch_id = cr.id;
+        branch_flow_step
+            .delete_cb(&data)
+            .execute(data.db_session())
+            .await
+            .expect("Failed to delete branch flow step");
+
+        let mut branch = cr.branch(data.db_session()).await.unwrap().clone();
+        branch.reload(data.db_session()).await.unwrap();
+        cr.branch.replace(branch);
+        cr.merge(&data, None).await.expect("Failed to merge");
+
+        let revert_cr = cr.revert(&data).await.expect("Failed to revert");
+        let revert_branch = revert_cr.branch.as_ref().expect("Revert branch should be loaded");
+
+        assert!(
+            revert_branch
+                .restored_flows
+                .as_ref()
+                .is_some_and(|ids| ids.contains(&deleted_flow.id)),
+            "Revert should restore the deleted flow"
+        );
+        assert!(
+            revert_branch
+                .restored_flow_steps
+                .as_ref()
+                .is_some_and(|ids| ids.contains(&deleted_flow_step.id)),
+            "Revert should restore the deleted flow step"
+        );
+    }
+}
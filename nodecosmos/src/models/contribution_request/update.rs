@@ -1,8 +1,16 @@
-use charybdis::operations::UpdateWithCallbacks;
+use std::collections::HashSet;
+
+use charybdis::operations::{Update, UpdateWithCallbacks};
+use charybdis::types::{Set, Uuid};
 
 use crate::api::data::RequestData;
+use crate::api::types::{ActionObject, ActionTypes};
 use crate::errors::NodecosmosError;
-use crate::models::contribution_request::{ContributionRequest, ContributionRequestStatus};
+use crate::models::contribution_request::{
+    ContributionRequest, ContributionRequestStatus, UpdateContributionRequestReviewers,
+};
+use crate::models::notification::{Notification, NotificationType};
+use crate::resources::sse_broadcast::ModelEvent;
 
 impl ContributionRequest {
     pub async fn publish(&mut self, data: &RequestData) -> Result<(), NodecosmosError> {
@@ -20,6 +28,81 @@ impl ContributionRequest {
 
         self.update_cb(data).execute(data.db_session()).await?;
 
+        // contribution requests belong to the original tree
+        ModelEvent::new(
+            self.root_id,
+            self.root_id,
+            ActionTypes::Update(ActionObject::ContributionRequest),
+            self,
+        )
+        .emit(data)
+        .await;
+
+        Ok(())
+    }
+
+    /// Sets the requested reviewers and notifies the ones that were not requested before.
+    pub async fn update_reviewers(
+        &mut self,
+        data: &RequestData,
+        reviewer_ids: Set<Uuid>,
+    ) -> Result<(), NodecosmosError> {
+        let added_reviewer_ids = match &self.reviewer_ids {
+            Some(current_ids) => reviewer_ids.difference(current_ids).copied().collect(),
+            None => reviewer_ids.clone(),
+        };
+
+        self.reviewer_ids = if reviewer_ids.is_empty() {
+            None
+        } else {
+            Some(reviewer_ids)
+        };
+        self.updated_at = chrono::Utc::now();
+
+        UpdateContributionRequestReviewers {
+            node_id: self.node_id,
+            id: self.id,
+            reviewer_ids: self.reviewer_ids.clone(),
+            updated_at: self.updated_at,
+        }
+        .update()
+        .execute(data.db_session())
+        .await?;
+
+        if !added_reviewer_ids.is_empty() {
+            self.create_review_request_notification(data, added_reviewer_ids);
+        }
+
         Ok(())
     }
+
+    fn create_review_request_notification(&self, data: &RequestData, receiver_ids: HashSet<Uuid>) {
+        let id = self.id;
+        let root_id = self.root_id;
+        let node_id = self.node_id;
+        let title = self.title.clone();
+        let data = data.clone();
+
+        tokio::spawn(async move {
+            let notification = Notification::new(
+                NotificationType::ReviewRequest,
+                format!("requested your review on contribution request - {}", title),
+                format!(
+                    "{client_url}/nodes/{original_id}/{node_id}/contribution_requests/{id}",
+                    client_url = &data.app.config.client_url,
+                    original_id = root_id,
+                    node_id = node_id,
+                    id = id
+                ),
+                Some((&data.current_user).into()),
+            );
+
+            let _ = notification
+                .create_for_receivers(&data, receiver_ids)
+                .await
+                .map_err(|e| {
+                    log::error!("Error creating notification for review request: {:?}", e);
+                });
+        });
+    }
 }
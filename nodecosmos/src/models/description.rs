@@ -1,21 +1,28 @@
 use crate::api::data::RequestData;
 use crate::errors::NodecosmosError;
 use crate::models::archived_description::ArchivedDescription;
-use crate::models::traits::Branchable;
+use crate::models::branch::stack::validate_not_deleted;
+use crate::models::branch::Branch;
+use crate::models::description_version::DescriptionVersion;
+use crate::models::mention::{MentionTarget, Mentions};
 use crate::models::traits::Clean;
-use crate::models::utils::DescriptionYDocParser;
+use crate::models::traits::{Branchable, ObjectType};
+use crate::models::udts::Profile;
+use crate::models::utils::{DescriptionHtmlToXml, DescriptionYDocParser};
 use base64::engine::general_purpose::STANDARD;
 use base64::Engine;
 use charybdis::callbacks::Callbacks;
 use charybdis::macros::charybdis_model;
 use charybdis::operations::{Find, Insert};
-use charybdis::types::{Text, Timestamp, Uuid};
+use charybdis::types::{Set, Text, Timestamp, Uuid};
 use chrono::Utc;
 use macros::{Branchable, ObjectId};
 use scylla::client::caching_session::CachingSession;
 use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::str::FromStr;
 use yrs::updates::decoder::Decode;
-use yrs::{Doc, Transact, Update};
+use yrs::{Doc, GetString, Transact, TransactionMut, Update, Xml, XmlElementRef, XmlFragment, XmlOut};
 
 mod update;
 
@@ -42,8 +49,21 @@ pub struct Description {
     pub markdown: Option<Text>,
     pub base64: Option<Text>,
 
+    #[serde(default)]
+    pub mentioned_user_ids: Option<Set<Uuid>>,
+
     #[serde(default = "chrono::Utc::now")]
     pub updated_at: Timestamp,
+
+    /// Users mentioned since the last save, notified after insert.
+    #[charybdis(ignore)]
+    #[serde(skip)]
+    pub new_mentions: Option<Mentions>,
+
+    /// Collaborators whose room updates are persisted by this save, recorded as authors of the version.
+    #[charybdis(ignore)]
+    #[serde(skip)]
+    pub editors: Option<Vec<Profile>>,
 }
 
 impl Callbacks for Description {
@@ -63,16 +83,23 @@ impl Callbacks for Description {
             let branch_id = self.branch_id;
 
             current.merge(self).await?;
+            current.editors = self.editors.take();
 
             *self = current;
             self.branch_id = branch_id;
+
+            if self.is_branch() {
+                self.commit_edit(data).await?;
+            }
         } else if self.is_branch() {
             self.update_branch(data).await?;
+            self.mentioned_user_ids = self.layered_mentioned_user_ids(session).await?;
         }
 
         self.updated_at = Utc::now();
 
         self.html.clean()?;
+        self.set_mentions(session).await?;
 
         Ok(())
     }
@@ -83,6 +110,12 @@ impl Callbacks for Description {
             e
         });
 
+        let _ = DescriptionVersion::snapshot(data, self).await.map_err(|e| {
+            log::error!("[after_insert] Failed to snapshot description version: {:?}", e);
+        });
+
+        self.notify_mentions(data);
+
         Ok(())
     }
 
@@ -109,6 +142,13 @@ impl Callbacks for Description {
                 e
             });
 
+        let object_ids = HashSet::from([self.object_id]);
+        let _ = DescriptionVersion::delete_branch_versions(db_session, self.branch_id, object_ids)
+            .await
+            .map_err(|e| {
+                log::error!("[after_delete] Failed to delete description versions: {:?}", e);
+            });
+
         Ok(())
     }
 }
@@ -126,12 +166,13 @@ impl From<ArchivedDescription> for Description {
             markdown: description.markdown,
             base64: description.base64,
             updated_at: description.updated_at,
+            ..Default::default()
         }
     }
 }
 
 impl Description {
-    const DESCRIPTION_ROOT: &'static str = "prosemirror";
+    pub(crate) const DESCRIPTION_ROOT: &'static str = "prosemirror";
 
     pub async fn merge(&mut self, other: &Self) -> Result<(), NodecosmosError> {
         let current_base64 = match &self.base64 {
@@ -174,6 +215,164 @@ impl Description {
 
         Ok(())
     }
+
+    /// Finds branched description. Branched descriptions are merged with the original, so they are kept in sync
+    /// with changes made to the original after branching.
+    pub async fn find_branched_merged(&mut self, db_session: &CachingSession) -> Result<&mut Self, NodecosmosError> {
+        self.find_branched(db_session).await?;
+
+        if self.is_branch() {
+            let original = Description::find_by_branch_id_and_object_id(self.original_id(), self.object_id)
+                .execute(db_session)
+                .await;
+
+            if let Ok(mut original) = original {
+                match self.base64 {
+                    Some(_) => {
+                        if self.base64 != original.base64 {
+                            original.merge(self).await?;
+                            self.html = original.html;
+                            self.markdown = original.markdown;
+                            self.base64 = original.base64;
+                        }
+                    }
+
+                    None => {
+                        self.base64 = original.base64;
+                    }
+                }
+            }
+        }
+
+        Ok(self)
+    }
+
+    /// Resolves users mentioned in the description. Users that were not mentioned before are notified after insert.
+    async fn set_mentions(&mut self, db_session: &CachingSession) -> Result<(), NodecosmosError> {
+        let mut mentions = Mentions::find(db_session, self.markdown.as_deref().unwrap_or_default()).await?;
+        let prev_user_ids = std::mem::replace(&mut self.mentioned_user_ids, mentions.user_ids());
+
+        mentions.retain_new(&prev_user_ids);
+        self.new_mentions = Some(mentions);
+
+        Ok(())
+    }
+
+    /// Users mentioned in the description that the branch reads through, i.e. of the nearest parent branch or
+    /// the original, so the first edit within a branch doesn't notify them again.
+    async fn layered_mentioned_user_ids(
+        &self,
+        db_session: &CachingSession,
+    ) -> Result<Option<Set<Uuid>>, NodecosmosError> {
+        let mut layer_ids = Branch::parent_branch_ids(db_session, self.branch_id).await?;
+        layer_ids.push(self.original_id());
+
+        for layer_id in layer_ids {
+            let layer = Self::maybe_find_first_by_branch_id_and_object_id(layer_id, self.object_id)
+                .execute(db_session)
+                .await?;
+
+            if let Some(layer) = layer {
+                return Ok(layer.mentioned_user_ids);
+            }
+        }
+
+        Ok(None)
+    }
+
+    fn notify_mentions(&mut self, data: &RequestData) {
+        let Some(mentions) = self.new_mentions.take().filter(|mentions| !mentions.is_empty()) else {
+            return;
+        };
+        let target = MentionTarget {
+            branch_id: self.branch_id,
+            node_id: self.node_id,
+            root_id: self.root_id,
+            text: "mentioned you in a description".to_string(),
+            url: None,
+        };
+        let data = data.clone();
+
+        tokio::spawn(async move {
+            mentions.notify(&data, &target).await;
+        });
+    }
+
+    /// Returns description in `<description>` xml format that is consumed by the node import.
+    pub fn to_xml(&self) -> Result<Option<String>, NodecosmosError> {
+        if let Some(base64) = &self.base64 {
+            let buf = STANDARD.decode(base64)?;
+            let update = Update::decode_v2(&buf)?;
+            let doc = Doc::new();
+            let xml = doc.get_or_insert_xml_fragment(Self::DESCRIPTION_ROOT);
+
+            let mut transaction = doc.transact_mut();
+            transaction.apply_update(update)?;
+
+            return Ok(Some(format!(
+                "<description>{}</description>",
+                xml.get_string(&transaction)
+            )));
+        }
+
+        match &self.html {
+            Some(html) => Ok(Some(DescriptionHtmlToXml::new(html).run()?.xml)),
+            None => Ok(None),
+        }
+    }
+
+    /// Replaces image urls within the description content. Used when attachments are copied to the new S3 keys.
+    pub fn replace_image_urls(&mut self, urls: &HashMap<String, String>) -> Result<(), NodecosmosError> {
+        if urls.is_empty() {
+            return Ok(());
+        }
+
+        for text in [&mut self.html, &mut self.markdown].into_iter().flatten() {
+            for (old_url, new_url) in urls {
+                if text.contains(old_url.as_str()) {
+                    *text = text.replace(old_url.as_str(), new_url);
+                }
+            }
+        }
+
+        if let Some(base64) = &self.base64 {
+            let buf = STANDARD.decode(base64)?;
+            let update = Update::decode_v2(&buf)?;
+            let doc = Doc::new();
+            let xml = doc.get_or_insert_xml_fragment(Self::DESCRIPTION_ROOT);
+
+            let mut transaction = doc.transact_mut();
+            transaction.apply_update(update)?;
+
+            for image in Self::image_elements(&transaction, &xml) {
+                let src = image.get_attribute::<TransactionMut>(&transaction, "src");
+
+                if let Some(new_url) = src.and_then(|src| urls.get(&src)) {
+                    image.insert_attribute(&mut transaction, "src", new_url.clone());
+                }
+            }
+
+            self.base64 = Some(STANDARD.encode(transaction.encode_update_v2()));
+        }
+
+        Ok(())
+    }
+
+    fn image_elements<T: XmlFragment>(txn: &TransactionMut, fragment: &T) -> Vec<XmlElementRef> {
+        let mut images = vec![];
+
+        for child in fragment.children(txn) {
+            if let XmlOut::Element(element) = child {
+                if element.tag().as_ref() == "image" {
+                    images.push(element.clone());
+                }
+
+                images.extend(Self::image_elements(txn, &element));
+            }
+        }
+
+        images
+    }
 }
 
 partial_description!(
@@ -220,11 +419,31 @@ macro_rules! find_branched {
                     }
                     None => {
                         let branch_id = self.branch_id;
+                        let mut layered = None;
 
-                        if let Some(desc) = Self::maybe_find_by_primary_key_value((self.original_id(), self.object_id))
-                            .execute(db_session)
-                            .await?
-                        {
+                        // stacked branches read through their parents before the original
+                        let parents = Branch::parent_branches(db_session, branch_id).await?;
+                        let mut layer_ids = parents.iter().map(|parent| parent.id).collect::<Vec<Uuid>>();
+                        layer_ids.push(self.original_id());
+
+                        for (index, layer_id) in layer_ids.into_iter().enumerate() {
+                            if let Some(desc) = Self::maybe_find_by_primary_key_value((layer_id, self.object_id))
+                                .execute(db_session)
+                                .await?
+                            {
+                                let parents = &parents[..parents.len().min(index + 1)];
+                                let object_type = ObjectType::from_str(&desc.object_type)?;
+
+                                // description of an object deleted within a parent is read from the archive
+                                if validate_not_deleted(parents, object_type, desc.object_id, desc.node_id).is_ok() {
+                                    layered = Some(desc);
+                                }
+
+                                break;
+                            }
+                        }
+
+                        if let Some(desc) = layered {
                             *self = desc;
                         } else if let Some(desc) =
                             ArchivedDescription::maybe_find_by_primary_key_value((self.branch_id, self.object_id))
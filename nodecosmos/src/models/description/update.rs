@@ -2,6 +2,7 @@ use crate::api::data::RequestData;
 use crate::errors::NodecosmosError;
 use crate::models::branch::update::BranchUpdate;
 use crate::models::branch::Branch;
+use crate::models::commit::Commit;
 use crate::models::description::Description;
 use crate::models::flow::Flow;
 use crate::models::flow_step::FlowStep;
@@ -25,8 +26,9 @@ impl Description {
                     Node::find_or_insert_branched(data, params).await?;
 
                     Branch::update(
-                        data.db_session(),
+                        data,
                         self.branch_id,
+                        self.node_id,
                         BranchUpdate::EditNodeDescription(self.object_id),
                     )
                     .await?;
@@ -35,8 +37,9 @@ impl Description {
                     Flow::find_or_insert_branched(data, params).await?;
 
                     Branch::update(
-                        data.db_session(),
+                        data,
                         self.branch_id,
+                        self.node_id,
                         BranchUpdate::EditFlowDescription(self.object_id),
                     )
                     .await?;
@@ -45,8 +48,9 @@ impl Description {
                     FlowStep::find_or_insert_branched(data, params).await?;
 
                     Branch::update(
-                        data.db_session(),
+                        data,
                         self.branch_id,
+                        self.node_id,
                         BranchUpdate::EditFlowStepDescription(self.object_id),
                     )
                     .await?;
@@ -55,8 +59,9 @@ impl Description {
                     Io::find_or_insert_branched_main(data, params.original_id, params.branch_id, params.id).await?;
 
                     Branch::update(
-                        data.db_session(),
+                        data,
                         self.branch_id,
+                        self.node_id,
                         BranchUpdate::EditIoDescription(self.object_id),
                     )
                     .await?;
@@ -68,6 +73,23 @@ impl Description {
         Ok(())
     }
 
+    /// Later edits of a branched description only need the commit, as the branch already tracks the edit.
+    pub async fn commit_edit(&self, data: &RequestData) -> Result<(), NodecosmosError> {
+        let update = match self.object_type.parse::<ObjectType>()? {
+            ObjectType::Node => BranchUpdate::EditNodeDescription(self.object_id),
+            ObjectType::Flow => BranchUpdate::EditFlowDescription(self.object_id),
+            ObjectType::FlowStep => BranchUpdate::EditFlowStepDescription(self.object_id),
+            ObjectType::Io => BranchUpdate::EditIoDescription(self.object_id),
+            _ => return Ok(()),
+        };
+
+        if let Some(commit) = Commit::new(data, self.branch_id, self.node_id, &update) {
+            commit.insert_or_coalesce(data.db_session()).await?;
+        }
+
+        Ok(())
+    }
+
     pub async fn update_elastic_index(&self, data: &RequestData) -> Result<(), NodecosmosError> {
         if self.is_original() && self.object_type.parse::<ObjectType>()? == ObjectType::Node {
             let _ = UpdateNodeDescriptionElasticIdx {
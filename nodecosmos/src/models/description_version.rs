@@ -0,0 +1,637 @@
+use crate::api::data::RequestData;
+use crate::errors::NodecosmosError;
+use crate::models::branch::stack::validate_not_deleted;
+use crate::models::branch::{Branch, ParentBranch};
+use crate::models::description::Description;
+use crate::models::flow::Flow;
+use crate::models::flow_step::FlowStep;
+use crate::models::io::Io;
+use crate::models::node::PkNode;
+use crate::models::traits::ObjectType;
+use crate::models::udts::Profile;
+use crate::resources::description_room::DescriptionRoom;
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use charybdis::batch::CharybdisBatch;
+use charybdis::errors::CharybdisError;
+use charybdis::macros::charybdis_model;
+use charybdis::operations::{Delete, Find, Insert, InsertWithCallbacks};
+use charybdis::types::{Frozen, List, Set, Text, Timestamp, Uuid};
+use chrono::Utc;
+use scylla::client::caching_session::CachingSession;
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::str::FromStr;
+use yrs::types::text::YChange;
+use yrs::updates::decoder::Decode;
+use yrs::updates::encoder::Encode;
+use yrs::{
+    Any, Doc, Out, ReadTxn, StateVector, Text as YText, Transact, TransactionMut, Update, Xml, XmlElementPrelim,
+    XmlFragment, XmlOut, XmlTextPrelim,
+};
+
+/// Saves within this period after the latest version was created are collected into it.
+const VERSION_INTERVAL_MIN: i64 = 10;
+
+/// Oldest versions of the description are removed above this count.
+const MAX_VERSIONS: usize = 100;
+
+/// Max cells of the LCS table built by the line diff.
+const MAX_DIFF_CELLS: usize = 1_000_000;
+
+/// Periodic snapshot of the description. Versions are created on description save, so edits of the
+/// collaboration session end up in a few versions instead of one per persisted update. Ids are time based,
+/// so versions are clustered newest first.
+#[charybdis_model(
+    table_name = description_versions,
+    partition_keys = [branch_id, object_id],
+    clustering_keys = [id],
+    global_secondary_indexes = [],
+    table_options = r#"
+        CLUSTERING ORDER BY (id DESC) AND
+        compression = {
+            'sstable_compression': 'ZstdCompressor',
+            'chunk_length_in_kb': 128
+        }
+    "#
+)]
+#[derive(Default, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DescriptionVersion {
+    pub branch_id: Uuid,
+    pub object_id: Uuid,
+    pub id: Uuid,
+    pub node_id: Uuid,
+    pub root_id: Uuid,
+    pub object_type: Text,
+
+    /// Encoded Yjs state vector of the version.
+    pub state_vector: Option<Text>,
+
+    /// Yjs document of the version encoded as v2 update.
+    pub base64: Option<Text>,
+
+    pub html: Option<Text>,
+    pub markdown: Option<Text>,
+    pub author_ids: Option<Set<Uuid>>,
+    pub authors: Option<Frozen<List<Frozen<Profile>>>>,
+
+    #[serde(default = "chrono::Utc::now")]
+    pub created_at: Timestamp,
+
+    #[serde(default = "chrono::Utc::now")]
+    pub updated_at: Timestamp,
+}
+
+partial_description_version!(
+    BaseDescriptionVersion,
+    branch_id,
+    object_id,
+    id,
+    node_id,
+    root_id,
+    author_ids,
+    authors,
+    created_at,
+    updated_at
+);
+
+partial_description_version!(PkDescriptionVersion, branch_id, object_id, id);
+
+#[derive(Serialize, Clone, Copy, PartialEq, Debug)]
+#[serde(rename_all = "camelCase")]
+pub enum LineDiffOp {
+    Equal,
+    Insert,
+    Delete,
+}
+
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct LineDiff {
+    pub op: LineDiffOp,
+    pub line: String,
+}
+
+impl DescriptionVersion {
+    /// Versions of the description, newest first.
+    pub async fn versions(
+        db_session: &CachingSession,
+        branch_id: Uuid,
+        object_id: Uuid,
+    ) -> Result<Vec<BaseDescriptionVersion>, NodecosmosError> {
+        let mut versions: Vec<BaseDescriptionVersion> =
+            BaseDescriptionVersion::find_by_branch_id_and_object_id(branch_id, object_id)
+                .execute(db_session)
+                .await?
+                .try_collect()
+                .await?;
+
+        versions.sort_by_key(|version| std::cmp::Reverse(version.created_at));
+
+        Ok(versions)
+    }
+
+    /// Records the saved description. Latest version is updated if it is created within the version interval,
+    /// otherwise new version is created.
+    pub async fn snapshot(data: &RequestData, description: &Description) -> Result<(), NodecosmosError> {
+        let Some(base64) = &description.base64 else {
+            return Ok(());
+        };
+
+        let db_session = data.db_session();
+        let latest = Self::maybe_find_first_by_branch_id_and_object_id(description.branch_id, description.object_id)
+            .execute(db_session)
+            .await?;
+        let state_vector = Some(Self::encode_state_vector(base64)?);
+
+        match latest {
+            Some(mut latest) if Utc::now() - latest.created_at < chrono::Duration::minutes(VERSION_INTERVAL_MIN) => {
+                latest.state_vector = state_vector;
+                latest.base64 = description.base64.clone();
+                latest.html = description.html.clone();
+                latest.markdown = description.markdown.clone();
+                latest.add_authors(data, description);
+                latest.updated_at = Utc::now();
+
+                latest.insert().execute(db_session).await?;
+            }
+            Some(latest) if latest.html == description.html => (),
+            _ => {
+                let mut version = Self {
+                    branch_id: description.branch_id,
+                    object_id: description.object_id,
+                    id: Uuid::now_v1(&rand::random()),
+                    node_id: description.node_id,
+                    root_id: description.root_id,
+                    object_type: description.object_type.clone(),
+                    state_vector,
+                    base64: description.base64.clone(),
+                    html: description.html.clone(),
+                    markdown: description.markdown.clone(),
+                    created_at: Utc::now(),
+                    updated_at: Utc::now(),
+                    ..Default::default()
+                };
+
+                version.add_authors(data, description);
+                version.insert().execute(db_session).await?;
+
+                let data = data.clone();
+
+                tokio::spawn(async move {
+                    let _ = Self::prune(data.db_session(), version.branch_id, version.object_id)
+                        .await
+                        .map_err(|e| log::error!("Failed to prune description versions: {}", e));
+                });
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Removes versions above `MAX_VERSIONS`. As versions are clustered newest first, the ones older than the last
+    /// kept version are removed with a single range delete.
+    async fn prune(db_session: &CachingSession, branch_id: Uuid, object_id: Uuid) -> Result<(), NodecosmosError> {
+        let kept: Vec<PkDescriptionVersion> = find_pk_description_version!(
+            "branch_id = ? AND object_id = ? LIMIT ?",
+            (branch_id, object_id, MAX_VERSIONS as i32)
+        )
+        .execute(db_session)
+        .await?
+        .try_collect()
+        .await?;
+
+        if let (MAX_VERSIONS, Some(oldest_kept)) = (kept.len(), kept.last()) {
+            delete_description_version!(
+                "branch_id = ? AND object_id = ? AND id < ?",
+                (branch_id, object_id, oldest_kept.id)
+            )
+            .execute(db_session)
+            .await?;
+        }
+
+        Ok(())
+    }
+
+    /// Deletes versions of descriptions edited within the branch.
+    pub async fn delete_branch_versions(
+        db_session: &CachingSession,
+        branch_id: Uuid,
+        object_ids: HashSet<Uuid>,
+    ) -> Result<(), NodecosmosError> {
+        let versions: Vec<PkDescriptionVersion> = object_ids
+            .into_iter()
+            .map(|object_id| PkDescriptionVersion {
+                branch_id,
+                object_id,
+                ..Default::default()
+            })
+            .collect();
+        let mut batch = CharybdisBatch::new();
+
+        for version in &versions {
+            batch.append(version.delete_by_partition_key());
+        }
+
+        batch.execute(db_session).await?;
+
+        Ok(())
+    }
+
+    /// Markdown lines changed from this version to the other one. Common prefix and suffix are skipped before the
+    /// LCS is built. Changed ranges above `MAX_DIFF_CELLS` are diffed as deleted old lines followed by inserted new
+    /// ones, so the LCS table stays bounded.
+    pub fn diff(&self, other: &Self) -> Vec<LineDiff> {
+        let old: Vec<&str> = self.markdown.as_deref().unwrap_or_default().lines().collect();
+        let new: Vec<&str> = other.markdown.as_deref().unwrap_or_default().lines().collect();
+
+        let prefix = old.iter().zip(&new).take_while(|(old, new)| old == new).count();
+        let suffix = old[prefix..]
+            .iter()
+            .rev()
+            .zip(new[prefix..].iter().rev())
+            .take_while(|(old, new)| old == new)
+            .count();
+        let changed_old = &old[prefix..old.len() - suffix];
+        let changed_new = &new[prefix..new.len() - suffix];
+
+        let line_diff = |op, line: &str| LineDiff {
+            op,
+            line: line.to_string(),
+        };
+        let mut diff: Vec<LineDiff> = old[..prefix]
+            .iter()
+            .map(|line| line_diff(LineDiffOp::Equal, line))
+            .collect();
+
+        if (changed_old.len() + 1).saturating_mul(changed_new.len() + 1) > MAX_DIFF_CELLS {
+            diff.extend(changed_old.iter().map(|line| line_diff(LineDiffOp::Delete, line)));
+            diff.extend(changed_new.iter().map(|line| line_diff(LineDiffOp::Insert, line)));
+        } else {
+            diff.extend(Self::lcs_diff(changed_old, changed_new));
+        }
+
+        diff.extend(
+            old[old.len() - suffix..]
+                .iter()
+                .map(|line| line_diff(LineDiffOp::Equal, line)),
+        );
+
+        diff
+    }
+
+    fn lcs_diff(old: &[&str], new: &[&str]) -> Vec<LineDiff> {
+        // lengths of the longest common subsequences of the line suffixes
+        let mut lcs = vec![vec![0u32; new.len() + 1]; old.len() + 1];
+
+        for i in (0..old.len()).rev() {
+            for j in (0..new.len()).rev() {
+                lcs[i][j] = if old[i] == new[j] {
+                    lcs[i + 1][j + 1] + 1
+                } else {
+                    lcs[i + 1][j].max(lcs[i][j + 1])
+                };
+            }
+        }
+
+        let mut diff = vec![];
+        let (mut i, mut j) = (0, 0);
+
+        while i < old.len() || j < new.len() {
+            let (op, line) = if i < old.len() && j < new.len() && old[i] == new[j] {
+                i += 1;
+                j += 1;
+
+                (LineDiffOp::Equal, old[i - 1])
+            } else if j < new.len() && (i == old.len() || lcs[i][j + 1] >= lcs[i + 1][j]) {
+                j += 1;
+
+                (LineDiffOp::Insert, new[j - 1])
+            } else {
+                i += 1;
+
+                (LineDiffOp::Delete, old[i - 1])
+            };
+
+            diff.push(LineDiff {
+                op,
+                line: line.to_string(),
+            });
+        }
+
+        diff
+    }
+
+    /// Restores the version as a new update on top of the current description, so collaborators converge to the
+    /// restored content instead of having their documents overwritten. If the room of the description is held by
+    /// this instance, update is applied to the room document and persisted by the room. Update is published on the
+    /// room channel either way, so rooms held by other instances apply it as well.
+    pub async fn restore(&self, data: &RequestData) -> Result<(), NodecosmosError> {
+        self.validate_object_exists(data.db_session()).await?;
+
+        let room_id = format!("{}{}", self.branch_id, self.object_id);
+        let broadcast = data.ws_broadcast();
+        let room = broadcast.rooms.get(&room_id).map(|room| room.clone());
+
+        let update = match room {
+            Some(room) => {
+                let current = DescriptionRoom::lock(&room).state();
+
+                self.restore_update(Some(&current))?.0
+            }
+            None => {
+                let mut description = Description {
+                    branch_id: self.branch_id,
+                    object_id: self.object_id,
+                    node_id: self.node_id,
+                    root_id: self.root_id,
+                    object_type: self.object_type.clone(),
+                    ..Default::default()
+                };
+
+                match description.find_branched_merged(data.db_session()).await {
+                    Ok(_) | Err(NodecosmosError::CharybdisError(CharybdisError::NotFoundError(_))) => (),
+                    Err(e) => return Err(e),
+                }
+
+                let current = description.base64.as_deref().map(|b| STANDARD.decode(b)).transpose()?;
+                let (update, state) = self.restore_update(current.as_deref())?;
+                let restored = Description {
+                    base64: Some(STANDARD.encode(state)),
+                    html: self.html.clone(),
+                    markdown: self.markdown.clone(),
+                    ..Default::default()
+                };
+
+                description.merge(&restored).await?;
+                description.insert_cb(data).execute(data.db_session()).await?;
+
+                update
+            }
+        };
+
+        broadcast.apply_update(data, &room_id, update).await
+    }
+
+    /// Restoring the version of a deleted object would recreate its description. Object is looked up in the
+    /// branch, its parent branches and the original, unless it is deleted within one of the branches.
+    async fn validate_object_exists(&self, db_session: &CachingSession) -> Result<(), NodecosmosError> {
+        let object_type = ObjectType::from_str(&self.object_type)?;
+        let mut branches = vec![];
+
+        if self.branch_id != self.root_id {
+            let branch = ParentBranch {
+                id: self.branch_id,
+                ..Default::default()
+            }
+            .find_by_primary_key()
+            .execute(db_session)
+            .await?;
+
+            branches.push(branch);
+            branches.extend(Branch::parent_branches(db_session, self.branch_id).await?);
+        }
+
+        validate_not_deleted(&branches, object_type, self.object_id, self.node_id)?;
+
+        let mut layer_ids: Vec<Uuid> = branches.iter().map(|branch| branch.id).collect();
+        layer_ids.push(self.root_id);
+
+        for layer_id in layer_ids {
+            let exists = match object_type {
+                ObjectType::Node | ObjectType::Workflow => {
+                    PkNode::maybe_find_first_by_branch_id_and_id(layer_id, self.node_id)
+                        .execute(db_session)
+                        .await?
+                        .is_some()
+                }
+                ObjectType::Flow => Flow::maybe_find_first_by_branch_id_and_id(layer_id, self.object_id)
+                    .execute(db_session)
+                    .await?
+                    .is_some(),
+                ObjectType::FlowStep => FlowStep::maybe_find_first_by_branch_id_and_id(layer_id, self.object_id)
+                    .execute(db_session)
+                    .await?
+                    .is_some(),
+                ObjectType::Io => {
+                    Io::maybe_find_first_by_branch_id_and_root_id_and_id(layer_id, self.root_id, self.object_id)
+                        .execute(db_session)
+                        .await?
+                        .is_some()
+                }
+            };
+
+            if exists {
+                return Ok(());
+            }
+        }
+
+        Err(NodecosmosError::NotFound(format!(
+            "{} {} of the description no longer exists",
+            object_type, self.object_id
+        )))
+    }
+
+    /// Update that replaces content of the current document with the version content. Returns update encoded
+    /// as v1, as used by y-sync, and the resulting document state encoded as v2.
+    fn restore_update(&self, current: Option<&[u8]>) -> Result<(Vec<u8>, Vec<u8>), NodecosmosError> {
+        let version_doc = Doc::new();
+        let version_xml = version_doc.get_or_insert_xml_fragment(Description::DESCRIPTION_ROOT);
+
+        if let Some(base64) = &self.base64 {
+            let buf = STANDARD.decode(base64)?;
+
+            version_doc.transact_mut().apply_update(Update::decode_v2(&buf)?)?;
+        }
+
+        let doc = Doc::new();
+        let xml = doc.get_or_insert_xml_fragment(Description::DESCRIPTION_ROOT);
+
+        if let Some(current) = current {
+            doc.transact_mut().apply_update(Update::decode_v2(current)?)?;
+        }
+
+        let update = {
+            let version_txn = version_doc.transact();
+            let mut txn = doc.transact_mut();
+            let len = xml.len(&txn);
+
+            xml.remove_range(&mut txn, 0, len);
+            Self::copy_children(&version_txn, &version_xml, &mut txn, &xml);
+
+            txn.encode_update_v1()
+        };
+
+        let state = doc.transact().encode_state_as_update_v2(&StateVector::default());
+
+        Ok((update, state))
+    }
+
+    fn copy_children<T: ReadTxn, S: XmlFragment, D: XmlFragment>(
+        source_txn: &T,
+        source: &S,
+        txn: &mut TransactionMut,
+        target: &D,
+    ) {
+        for child in source.children(source_txn) {
+            match child {
+                XmlOut::Element(element) => {
+                    let copy = target.push_back(txn, XmlElementPrelim::empty(element.tag().as_ref()));
+
+                    for (key, value) in element.attributes(source_txn) {
+                        copy.insert_attribute(txn, key, value);
+                    }
+
+                    Self::copy_children(source_txn, &element, txn, &copy);
+                }
+                XmlOut::Text(text) => {
+                    let copy = target.push_back(txn, XmlTextPrelim::new(""));
+
+                    // formatting marks are held in attributes of text chunks
+                    for chunk in text.diff(source_txn, YChange::identity) {
+                        if let Out::Any(Any::String(insert)) = chunk.insert {
+                            let index = copy.len(txn);
+
+                            match chunk.attributes {
+                                Some(attributes) => copy.insert_with_attributes(txn, index, &insert, *attributes),
+                                None => copy.insert(txn, index, &insert),
+                            };
+                        }
+                    }
+                }
+                XmlOut::Fragment(_) => (),
+            }
+        }
+    }
+
+    fn encode_state_vector(base64: &str) -> Result<String, NodecosmosError> {
+        let buf = STANDARD.decode(base64)?;
+        let doc = Doc::new();
+        let mut txn = doc.transact_mut();
+
+        txn.apply_update(Update::decode_v2(&buf)?)?;
+
+        Ok(STANDARD.encode(txn.state_vector().encode_v1()))
+    }
+
+    /// Records the saving user and collaborators whose room updates are persisted by the save.
+    fn add_authors(&mut self, data: &RequestData, description: &Description) {
+        let current_user = Profile::from(&data.current_user);
+        let editors = description.editors.iter().flatten().cloned();
+
+        for author in std::iter::once(current_user).chain(editors) {
+            if self.author_ids.get_or_insert_with(Set::new).insert(author.id) {
+                self.authors.get_or_insert_with(Vec::new).push(author);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::utils::DescriptionYDocParser;
+
+    fn paragraphs_state(doc: &Doc, paragraphs: &[&str]) -> Vec<u8> {
+        let xml = doc.get_or_insert_xml_fragment(Description::DESCRIPTION_ROOT);
+        let mut txn = doc.transact_mut();
+        let len = xml.len(&txn);
+
+        xml.remove_range(&mut txn, 0, len);
+
+        for text in paragraphs {
+            let paragraph = xml.push_back(&mut txn, XmlElementPrelim::empty("paragraph"));
+            paragraph.push_back(&mut txn, XmlTextPrelim::new(*text));
+        }
+
+        drop(txn);
+
+        doc.transact().encode_state_as_update_v2(&StateVector::default())
+    }
+
+    #[test]
+    fn test_restore_update_converges_to_version() {
+        let version_doc = Doc::new();
+        let version_state = paragraphs_state(&version_doc, &["First", "Second"]);
+        let version_xml = version_doc.get_or_insert_xml_fragment(Description::DESCRIPTION_ROOT);
+        let expected = DescriptionYDocParser::new()
+            .run(&version_doc.transact_mut(), version_xml)
+            .unwrap();
+        let version = DescriptionVersion {
+            base64: Some(STANDARD.encode(version_state)),
+            ..Default::default()
+        };
+
+        let current_doc = Doc::new();
+        let current_state = paragraphs_state(&current_doc, &["Changed"]);
+        let (update, state) = version.restore_update(Some(&current_state)).unwrap();
+
+        // collaborator that holds the current document receives the update
+        let xml = current_doc.get_or_insert_xml_fragment(Description::DESCRIPTION_ROOT);
+        let mut txn = current_doc.transact_mut();
+
+        txn.apply_update(Update::decode_v1(&update).unwrap()).unwrap();
+
+        let prose_doc = DescriptionYDocParser::new().run(&txn, xml).unwrap();
+
+        assert_eq!(prose_doc.html, expected.html);
+
+        let restored_doc = Doc::new();
+        let restored_xml = restored_doc.get_or_insert_xml_fragment(Description::DESCRIPTION_ROOT);
+        let mut restored_txn = restored_doc.transact_mut();
+
+        restored_txn.apply_update(Update::decode_v2(&state).unwrap()).unwrap();
+
+        let restored = DescriptionYDocParser::new().run(&restored_txn, restored_xml).unwrap();
+
+        assert_eq!(restored.html, prose_doc.html);
+    }
+
+    #[test]
+    fn test_diff_lines() {
+        let version = DescriptionVersion {
+            markdown: Some("# Title\nFirst\nSecond".to_string()),
+            ..Default::default()
+        };
+        let other = DescriptionVersion {
+            markdown: Some("# Title\nSecond\nThird".to_string()),
+            ..Default::default()
+        };
+
+        let diff = version.diff(&other);
+        let ops: Vec<(LineDiffOp, &str)> = diff.iter().map(|diff| (diff.op, diff.line.as_str())).collect();
+
+        assert_eq!(
+            ops,
+            vec![
+                (LineDiffOp::Equal, "# Title"),
+                (LineDiffOp::Delete, "First"),
+                (LineDiffOp::Equal, "Second"),
+                (LineDiffOp::Insert, "Third"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_diff_large_change_without_lcs() {
+        let old_lines: Vec<String> = (0..1500).map(|i| format!("old {}", i)).collect();
+        let new_lines: Vec<String> = (0..1500).map(|i| format!("new {}", i)).collect();
+        let version = DescriptionVersion {
+            markdown: Some(format!("# Title\n{}\nEnd", old_lines.join("\n"))),
+            ..Default::default()
+        };
+        let other = DescriptionVersion {
+            markdown: Some(format!("# Title\n{}\nEnd", new_lines.join("\n"))),
+            ..Default::default()
+        };
+
+        let diff = version.diff(&other);
+
+        assert_eq!(diff.len(), 3002);
+        assert_eq!((diff[0].op, diff[0].line.as_str()), (LineDiffOp::Equal, "# Title"));
+        assert!(diff[1..1501].iter().all(|diff| diff.op == LineDiffOp::Delete));
+        assert!(diff[1501..3001].iter().all(|diff| diff.op == LineDiffOp::Insert));
+        assert_eq!((diff[3001].op, diff[3001].line.as_str()), (LineDiffOp::Equal, "End"));
+    }
+}
@@ -1,20 +1,21 @@
-use std::collections::HashSet;
-
 use charybdis::callbacks::Callbacks;
 use charybdis::macros::charybdis_model;
 use charybdis::operations::{DeleteWithCallbacks, Insert};
 use charybdis::types::{Double, Int, Text, Timestamp, Uuid};
-use futures::StreamExt;
 use scylla::client::caching_session::CachingSession;
 use serde::{Deserialize, Serialize};
 
 use macros::{Branchable, Id};
 
 use crate::api::data::RequestData;
+use crate::api::types::{ActionObject, ActionTypes};
 use crate::errors::NodecosmosError;
 use crate::models::archived_flow::ArchivedFlow;
+use crate::models::branch::stack::{combine_layers, BranchLayer};
+use crate::models::branch::Branch;
 use crate::models::flow_step::FlowStep;
 use crate::models::traits::{Branchable, Context, Descriptionable, ModelContext, NodeBranchParams};
+use crate::resources::sse_broadcast::ModelEvent;
 
 pub mod create;
 mod update_title;
@@ -76,6 +77,21 @@ impl Callbacks for Flow {
         Ok(())
     }
 
+    async fn after_insert(&mut self, _db_session: &CachingSession, data: &RequestData) -> Result<(), NodecosmosError> {
+        if self.is_default_context() {
+            ModelEvent::new(
+                self.root_id,
+                self.branch_id,
+                ActionTypes::Create(ActionObject::Flow),
+                self,
+            )
+            .emit(data)
+            .await;
+        }
+
+        Ok(())
+    }
+
     async fn before_delete(&mut self, db_session: &CachingSession, data: &RequestData) -> Result<(), NodecosmosError> {
         self.update_branch_with_deletion(data).await?;
         self.preserve_branch_node(data).await?;
@@ -106,42 +122,57 @@ impl Callbacks for Flow {
                 e
             });
 
+        if self.is_default_context() {
+            ModelEvent::new(
+                self.root_id,
+                self.branch_id,
+                ActionTypes::Delete(ActionObject::Flow),
+                self,
+            )
+            .emit(data)
+            .await;
+        }
+
         Ok(())
     }
 }
 
 impl Flow {
-    /// merges original and branched flows
+    /// merges flows of the branch, its parent branches and the original
     pub async fn branched(
         db_session: &CachingSession,
         params: &NodeBranchParams,
     ) -> Result<Vec<Self>, NodecosmosError> {
-        let mut flows = Self::find_by_branch_id_and_node_id(params.branch_id, params.node_id)
+        let flows = Self::find_by_branch_id_and_node_id(params.branch_id, params.node_id)
             .execute(db_session)
+            .await?
+            .try_collect()
             .await?;
 
         if params.is_original() {
-            Ok(flows.try_collect().await?)
+            Ok(flows)
         } else {
-            let mut original_flows = Self::find_by_branch_id_and_node_id(params.original_id(), params.node_id)
+            let mut layers = vec![BranchLayer::new(flows)];
+
+            for parent in Branch::parent_branches(db_session, params.branch_id).await? {
+                let parent_flows = Self::find_by_branch_id_and_node_id(parent.id, params.node_id)
+                    .execute(db_session)
+                    .await?
+                    .try_collect()
+                    .await?;
+
+                layers.push(BranchLayer::parent(parent_flows, parent));
+            }
+
+            let original_flows = Self::find_by_branch_id_and_node_id(params.original_id(), params.node_id)
                 .execute(db_session)
+                .await?
+                .try_collect()
                 .await?;
-            let mut branched_flows_set = HashSet::new();
-            let mut branch_flows = vec![];
 
-            while let Some(flow) = flows.next().await {
-                let flow = flow?;
-                branched_flows_set.insert(flow.id);
-                branch_flows.push(flow);
-            }
+            layers.push(BranchLayer::new(original_flows));
 
-            while let Some(flow) = original_flows.next().await {
-                let mut flow = flow?;
-                if !branched_flows_set.contains(&flow.id) {
-                    flow.branch_id = params.branch_id;
-                    branch_flows.push(flow);
-                }
-            }
+            let mut branch_flows = combine_layers(layers, params.branch_id);
 
             branch_flows.sort_by(|a, b| {
                 a.vertical_index
@@ -169,6 +200,23 @@ impl Flow {
     }
 }
 
+impl From<&ArchivedFlow> for Flow {
+    fn from(flow: &ArchivedFlow) -> Self {
+        Self {
+            branch_id: flow.branch_id,
+            node_id: flow.node_id,
+            root_id: flow.branch_id,
+            vertical_index: flow.vertical_index,
+            start_index: flow.start_index,
+            id: flow.id,
+            title: flow.title.clone(),
+            created_at: flow.created_at,
+            updated_at: flow.updated_at,
+            ..Default::default()
+        }
+    }
+}
+
 partial_flow!(
     UpdateTitleFlow,
     node_id,
@@ -191,6 +239,21 @@ impl Callbacks for UpdateTitleFlow {
 
         Ok(())
     }
+
+    async fn after_update(&mut self, _db_session: &CachingSession, data: &RequestData) -> Result<(), NodecosmosError> {
+        if self.is_default_context() {
+            ModelEvent::new(
+                self.root_id,
+                self.branch_id,
+                ActionTypes::Update(ActionObject::Flow),
+                self,
+            )
+            .emit(data)
+            .await;
+        }
+
+        Ok(())
+    }
 }
 
 partial_flow!(PkFlow, node_id, branch_id, start_index, vertical_index, id, root_id);
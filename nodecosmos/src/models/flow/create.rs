@@ -63,8 +63,8 @@ impl Flow {
 
     pub async fn update_branch_with_creation(&self, data: &RequestData) -> Result<(), NodecosmosError> {
         if self.is_branch() {
-            Branch::update(data.db_session(), self.branch_id, BranchUpdate::EditNode(self.node_id)).await?;
-            Branch::update(data.db_session(), self.branch_id, BranchUpdate::CreateFlow(self.id)).await?;
+            Branch::update(data, self.branch_id, self.node_id, BranchUpdate::EditNode(self.node_id)).await?;
+            Branch::update(data, self.branch_id, self.node_id, BranchUpdate::CreateFlow(self.id)).await?;
         }
 
         Ok(())
@@ -72,8 +72,8 @@ impl Flow {
 
     pub async fn update_branch_with_deletion(&self, data: &RequestData) -> Result<(), NodecosmosError> {
         if self.is_branch() {
-            Branch::update(data.db_session(), self.branch_id, BranchUpdate::EditNode(self.node_id)).await?;
-            Branch::update(data.db_session(), self.branch_id, BranchUpdate::DeleteFlow(self.id)).await?;
+            Branch::update(data, self.branch_id, self.node_id, BranchUpdate::EditNode(self.node_id)).await?;
+            Branch::update(data, self.branch_id, self.node_id, BranchUpdate::DeleteFlow(self.id)).await?;
         }
 
         Ok(())
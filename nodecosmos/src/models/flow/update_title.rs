@@ -1,3 +1,5 @@
+use charybdis::operations::Find;
+
 use crate::api::data::RequestData;
 use crate::errors::NodecosmosError;
 use crate::models::branch::update::BranchUpdate;
@@ -8,7 +10,7 @@ use crate::models::traits::{Branchable, FindOrInsertBranched, ModelBranchParams}
 impl UpdateTitleFlow {
     pub async fn update_branch(&self, data: &RequestData) -> Result<(), NodecosmosError> {
         if self.is_branch() {
-            Branch::update(data.db_session(), self.branch_id, BranchUpdate::EditNode(self.node_id)).await?;
+            Branch::update(data, self.branch_id, self.node_id, BranchUpdate::EditNode(self.node_id)).await?;
             Flow::find_or_insert_branched(
                 data,
                 ModelBranchParams {
@@ -18,7 +20,31 @@ impl UpdateTitleFlow {
                 },
             )
             .await?;
-            Branch::update(data.db_session(), self.branch_id, BranchUpdate::EditFlowTitle(self.id)).await?;
+            Branch::update(data, self.branch_id, self.node_id, BranchUpdate::EditFlowTitle(self.id)).await?;
+            self.track_title_change(data).await?;
+        }
+
+        Ok(())
+    }
+
+    async fn track_title_change(&self, data: &RequestData) -> Result<(), NodecosmosError> {
+        let maybe_original = UpdateTitleFlow {
+            branch_id: self.original_id(),
+            ..self.clone()
+        }
+        .maybe_find_by_primary_key()
+        .execute(data.db_session())
+        .await?;
+
+        if let Some(original) = maybe_original {
+            Branch::track_title_change(
+                data.db_session(),
+                self.branch_id,
+                self.id,
+                original.title,
+                self.title.clone(),
+            )
+            .await?;
         }
 
         Ok(())
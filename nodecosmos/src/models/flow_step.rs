@@ -1,19 +1,21 @@
 use crate::api::data::RequestData;
+use crate::api::types::{ActionObject, ActionTypes};
 use crate::errors::NodecosmosError;
 use crate::models::archived_flow_step::ArchivedFlowStep;
+use crate::models::branch::stack::{combine_layers, BranchLayer};
+use crate::models::branch::Branch;
 use crate::models::io::UpdateFlowStepIo;
 use crate::models::traits::{
-    Branchable, Descriptionable, FindOrInsertBranched, GroupById, Merge, ModelBranchParams, NodeBranchParams,
-    WhereInChunksExec,
+    Branchable, Descriptionable, FindOrInsertBranched, Merge, ModelBranchParams, NodeBranchParams, WhereInChunksExec,
 };
 use crate::models::traits::{Context, ModelContext};
 use crate::models::utils::updated_at_cb_fn;
+use crate::resources::sse_broadcast::ModelEvent;
 use charybdis::batch::ModelBatch;
 use charybdis::callbacks::Callbacks;
 use charybdis::macros::charybdis_model;
 use charybdis::operations::{Find, Insert, UpdateWithCallbacks};
 use charybdis::types::{Decimal, Frozen, List, Map, Set, Timestamp, Uuid};
-use futures::StreamExt;
 use macros::{Branchable, FlowId, Id, NodeId};
 use scylla::client::caching_session::CachingSession;
 use serde::{Deserialize, Serialize};
@@ -81,6 +83,21 @@ impl Callbacks for FlowStep {
         Ok(())
     }
 
+    async fn after_insert(&mut self, _db_session: &CachingSession, data: &RequestData) -> Result<(), NodecosmosError> {
+        if self.is_default_context() {
+            ModelEvent::new(
+                self.root_id,
+                self.branch_id,
+                ActionTypes::Create(ActionObject::FlowStep),
+                self,
+            )
+            .emit(data)
+            .await;
+        }
+
+        Ok(())
+    }
+
     updated_at_cb_fn!();
 
     async fn before_delete(&mut self, _db_session: &CachingSession, data: &RequestData) -> Result<(), NodecosmosError> {
@@ -108,6 +125,17 @@ impl Callbacks for FlowStep {
                 e
             });
 
+        if self.is_default_context() {
+            ModelEvent::new(
+                self.root_id,
+                self.branch_id,
+                ActionTypes::Delete(ActionObject::FlowStep),
+                self,
+            )
+            .emit(data)
+            .await;
+        }
+
         Ok(())
     }
 }
@@ -122,29 +150,35 @@ impl FlowStep {
     ) -> Result<Vec<FlowStep>, NodecosmosError> {
         let flow_steps = Self::find_by_branch_id_and_node_id(params.branch_id, params.node_id)
             .execute(db_session)
+            .await?
+            .try_collect()
             .await?;
 
         if params.is_original() {
-            Ok(flow_steps.try_collect().await?)
+            Ok(flow_steps)
         } else {
-            let mut original_flow_steps = Self::find_by_branch_id_and_node_id(params.original_id(), params.node_id)
+            let mut layers = vec![BranchLayer::new(flow_steps)];
+
+            for parent in Branch::parent_branches(db_session, params.branch_id).await? {
+                let parent_flow_steps = Self::find_by_branch_id_and_node_id(parent.id, params.node_id)
+                    .execute(db_session)
+                    .await?
+                    .try_collect()
+                    .await?;
+
+                layers.push(BranchLayer::parent(parent_flow_steps, parent));
+            }
+
+            let original_flow_steps = Self::find_by_branch_id_and_node_id(params.original_id(), params.node_id)
                 .execute(db_session)
+                .await?
+                .try_collect()
                 .await?;
-            let mut branch_flow_steps = flow_steps.group_by_id().await?;
-
-            while let Some(original_flow_step) = original_flow_steps.next().await {
-                let mut original_flow_step = original_flow_step?;
-                if let Some(branched_flow_step) = branch_flow_steps.get_mut(&original_flow_step.id) {
-                    branched_flow_step.merge_original_inputs(&original_flow_step);
-                    branched_flow_step.merge_original_nodes(&original_flow_step);
-                    branched_flow_step.merge_original_outputs(&original_flow_step);
-                } else {
-                    original_flow_step.branch_id = params.branch_id;
-                    branch_flow_steps.insert(original_flow_step.id, original_flow_step);
-                }
-            }
 
-            let mut branch_flow_steps = branch_flow_steps.into_values().collect::<Vec<FlowStep>>();
+            layers.push(BranchLayer::new(original_flow_steps));
+
+            // branched flow steps keep original inputs, nodes and outputs they didn't remove
+            let mut branch_flow_steps = combine_layers(layers, params.branch_id);
 
             branch_flow_steps.sort_by(|a, b| {
                 a.step_index
@@ -289,6 +323,90 @@ impl FlowStep {
     }
 }
 
+/// Nodes, inputs and outputs added to or removed from a flow step within a branch.
+#[derive(Default, Clone, Copy)]
+pub struct FlowStepChanges<'a> {
+    pub node_ids: Option<&'a Set<Uuid>>,
+    pub input_ids_by_node_id: Option<&'a Map<Uuid, Frozen<Set<Uuid>>>>,
+    pub output_ids_by_node_id: Option<&'a Map<Uuid, Frozen<Set<Uuid>>>>,
+}
+
+impl FlowStep {
+    pub fn apply_changes(&mut self, added: FlowStepChanges, removed: FlowStepChanges) {
+        if let (Some(node_ids), Some(removed_ids)) = (&mut self.node_ids, removed.node_ids) {
+            node_ids.retain(|node_id| !removed_ids.contains(node_id));
+        }
+
+        if let Some(added_ids) = added.node_ids {
+            let node_ids = self.node_ids.get_or_insert_with(Vec::new);
+
+            for node_id in added_ids {
+                if !node_ids.contains(node_id) {
+                    node_ids.push(*node_id);
+                }
+            }
+        }
+
+        Self::apply_io_changes(
+            &mut self.input_ids_by_node_id,
+            added.input_ids_by_node_id,
+            removed.input_ids_by_node_id,
+        );
+        Self::apply_io_changes(
+            &mut self.output_ids_by_node_id,
+            added.output_ids_by_node_id,
+            removed.output_ids_by_node_id,
+        );
+    }
+
+    fn apply_io_changes(
+        io_ids_by_node_id: &mut Option<Frozen<Map<Uuid, Frozen<List<Uuid>>>>>,
+        added: Option<&Map<Uuid, Frozen<Set<Uuid>>>>,
+        removed: Option<&Map<Uuid, Frozen<Set<Uuid>>>>,
+    ) {
+        if let (Some(io_ids_by_node_id), Some(removed)) = (io_ids_by_node_id.as_mut(), removed) {
+            for (node_id, io_ids) in io_ids_by_node_id.iter_mut() {
+                if let Some(removed_ids) = removed.get(node_id) {
+                    io_ids.retain(|io_id| !removed_ids.contains(io_id));
+                }
+            }
+        }
+
+        if let Some(added) = added {
+            let io_ids_by_node_id = io_ids_by_node_id.get_or_insert_with(HashMap::new);
+
+            for (node_id, added_ids) in added {
+                let io_ids = io_ids_by_node_id.entry(*node_id).or_default();
+
+                for io_id in added_ids {
+                    if !io_ids.contains(io_id) {
+                        io_ids.push(*io_id);
+                    }
+                }
+            }
+        }
+    }
+}
+
+impl From<&ArchivedFlowStep> for FlowStep {
+    fn from(flow_step: &ArchivedFlowStep) -> Self {
+        Self {
+            branch_id: flow_step.branch_id,
+            node_id: flow_step.node_id,
+            flow_id: flow_step.flow_id,
+            step_index: flow_step.step_index.clone(),
+            id: flow_step.id,
+            root_id: flow_step.root_id,
+            node_ids: flow_step.node_ids.clone(),
+            input_ids_by_node_id: flow_step.input_ids_by_node_id.clone(),
+            output_ids_by_node_id: flow_step.output_ids_by_node_id.clone(),
+            created_at: flow_step.created_at,
+            updated_at: flow_step.updated_at,
+            ..Default::default()
+        }
+    }
+}
+
 partial_flow_step!(
     UpdateInputIdsFlowStep,
     node_id,
@@ -317,6 +435,21 @@ impl Callbacks for UpdateInputIdsFlowStep {
 
         Ok(())
     }
+
+    async fn after_update(&mut self, _db_session: &CachingSession, data: &RequestData) -> Result<(), NodecosmosError> {
+        if self.is_default_context() {
+            ModelEvent::new(
+                self.root_id,
+                self.branch_id,
+                ActionTypes::Update(ActionObject::FlowStep),
+                self,
+            )
+            .emit(data)
+            .await;
+        }
+
+        Ok(())
+    }
 }
 
 impl UpdateInputIdsFlowStep {
@@ -380,6 +513,21 @@ impl Callbacks for UpdateNodeIdsFlowStep {
 
         Ok(())
     }
+
+    async fn after_update(&mut self, _db_session: &CachingSession, data: &RequestData) -> Result<(), NodecosmosError> {
+        if self.is_default_context() {
+            ModelEvent::new(
+                self.root_id,
+                self.branch_id,
+                ActionTypes::Update(ActionObject::FlowStep),
+                self,
+            )
+            .emit(data)
+            .await;
+        }
+
+        Ok(())
+    }
 }
 
 impl UpdateNodeIdsFlowStep {
@@ -420,6 +568,21 @@ impl Callbacks for UpdateOutputIdsFlowStep {
 
         Ok(())
     }
+
+    async fn after_update(&mut self, _db_session: &CachingSession, data: &RequestData) -> Result<(), NodecosmosError> {
+        if self.is_default_context() {
+            ModelEvent::new(
+                self.root_id,
+                self.branch_id,
+                ActionTypes::Update(ActionObject::FlowStep),
+                self,
+            )
+            .emit(data)
+            .await;
+        }
+
+        Ok(())
+    }
 }
 
 impl UpdateOutputIdsFlowStep {
@@ -158,8 +158,14 @@ impl FlowStep {
 
     pub async fn update_branch_with_creation(&self, data: &RequestData) -> Result<(), NodecosmosError> {
         if self.is_branch() {
-            Branch::update(data.db_session(), self.branch_id, BranchUpdate::EditNode(self.node_id)).await?;
-            Branch::update(data.db_session(), self.branch_id, BranchUpdate::CreateFlowStep(self.id)).await?;
+            Branch::update(data, self.branch_id, self.node_id, BranchUpdate::EditNode(self.node_id)).await?;
+            Branch::update(
+                data,
+                self.branch_id,
+                self.node_id,
+                BranchUpdate::CreateFlowStep(self.id),
+            )
+            .await?;
         }
 
         Ok(())
@@ -167,8 +173,14 @@ impl FlowStep {
 
     pub async fn update_branch_with_deletion(&self, data: &RequestData) -> Result<(), NodecosmosError> {
         if self.is_branch() {
-            Branch::update(data.db_session(), self.branch_id, BranchUpdate::EditNode(self.node_id)).await?;
-            Branch::update(data.db_session(), self.branch_id, BranchUpdate::DeleteFlowStep(self.id)).await?;
+            Branch::update(data, self.branch_id, self.node_id, BranchUpdate::EditNode(self.node_id)).await?;
+            Branch::update(
+                data,
+                self.branch_id,
+                self.node_id,
+                BranchUpdate::DeleteFlowStep(self.id),
+            )
+            .await?;
         }
 
         Ok(())
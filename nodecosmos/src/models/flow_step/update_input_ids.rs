@@ -14,7 +14,7 @@ use crate::models::traits::{Branchable, FindOriginalOrBranched, Merge, ModelBran
 
 impl UpdateInputIdsFlowStep {
     pub async fn update_branch(&mut self, data: &RequestData) -> Result<(), NodecosmosError> {
-        Branch::update(data.db_session(), self.branch_id, BranchUpdate::EditNode(self.node_id)).await?;
+        Branch::update(data, self.branch_id, self.node_id, BranchUpdate::EditNode(self.node_id)).await?;
 
         let current = FlowStep::find_original_or_branched(
             data.db_session(),
@@ -30,15 +30,17 @@ impl UpdateInputIdsFlowStep {
             FlowStep::ios_diff(current.input_ids_by_node_id.clone(), &self.input_ids_by_node_id);
 
         Branch::update(
-            data.db_session(),
+            data,
             self.branch_id,
+            self.node_id,
             BranchUpdate::CreateFlowStepInputs((self.id, created_ids_by_node_id)),
         )
         .await?;
 
         Branch::update(
-            data.db_session(),
+            data,
             self.branch_id,
+            self.node_id,
             BranchUpdate::DeleteFlowStepInputs((self.id, removed_ids_by_node_id.clone())),
         )
         .await?;
@@ -54,7 +54,7 @@ impl UpdateNodeIdsFlowStep {
     }
 
     pub async fn update_branch(&mut self, data: &RequestData) -> Result<(), NodecosmosError> {
-        Branch::update(data.db_session(), self.branch_id, BranchUpdate::EditNode(self.node_id)).await?;
+        Branch::update(data, self.branch_id, self.node_id, BranchUpdate::EditNode(self.node_id)).await?;
 
         // we always compare against original if it exists
         let current = FlowStep::find_original_or_branched(
@@ -113,15 +113,17 @@ impl UpdateNodeIdsFlowStep {
         deleted_flow_step_nodes.insert(self.id, deleted_node_ids.clone());
 
         Branch::update(
-            data.db_session(),
+            data,
             self.branch_id,
+            self.node_id,
             BranchUpdate::CreateFlowStepNodes(created_flow_step_nodes),
         )
         .await?;
 
         Branch::update(
-            data.db_session(),
+            data,
             self.branch_id,
+            self.node_id,
             BranchUpdate::DeleteFlowStepNodes(deleted_flow_step_nodes),
         )
         .await?;
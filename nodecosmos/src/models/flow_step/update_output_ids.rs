@@ -7,7 +7,7 @@ use crate::models::traits::{Branchable, FindOriginalOrBranched, Merge, ModelBran
 
 impl UpdateOutputIdsFlowStep {
     pub async fn update_branch(&mut self, data: &RequestData) -> Result<(), NodecosmosError> {
-        Branch::update(data.db_session(), self.branch_id, BranchUpdate::EditNode(self.node_id)).await?;
+        Branch::update(data, self.branch_id, self.node_id, BranchUpdate::EditNode(self.node_id)).await?;
 
         // we always compare against original if it exists
         let current = FlowStep::find_original_or_branched(
@@ -24,15 +24,17 @@ impl UpdateOutputIdsFlowStep {
             FlowStep::ios_diff(current.output_ids_by_node_id.clone(), &self.output_ids_by_node_id);
 
         Branch::update(
-            data.db_session(),
+            data,
             self.branch_id,
+            self.node_id,
             BranchUpdate::CreateFlowStepOutputs((self.id, created_ids_by_node_id)),
         )
         .await?;
 
         Branch::update(
-            data.db_session(),
+            data,
             self.branch_id,
+            self.node_id,
             BranchUpdate::DeletedFlowStepOutputs((self.id, removed_ids_by_node_id)),
         )
         .await?;
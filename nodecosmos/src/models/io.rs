@@ -1,23 +1,24 @@
-use std::collections::HashSet;
-
 use charybdis::callbacks::Callbacks;
 use charybdis::macros::charybdis_model;
 use charybdis::operations::Insert;
 use charybdis::types::{Boolean, Set, Text, Timestamp, Uuid};
-use futures::StreamExt;
 use scylla::client::caching_session::CachingSession;
 use serde::{Deserialize, Serialize};
 
 use macros::{Branchable, Id, MaybeFlowId, MaybeFlowStepId};
 
 use crate::api::data::RequestData;
+use crate::api::types::{ActionObject, ActionTypes};
 use crate::errors::NodecosmosError;
 use crate::models::archived_io::ArchivedIo;
+use crate::models::branch::stack::{combine_layers, BranchLayer};
+use crate::models::branch::Branch;
 use crate::models::node::Node;
 use crate::models::traits::{
     Branchable, Descriptionable, FindBranchedOrOriginalNode, NodeBranchParams, WhereInChunksExec,
 };
 use crate::models::traits::{Context, ModelContext};
+use crate::resources::sse_broadcast::ModelEvent;
 use crate::stream::MergedModelStream;
 
 mod create;
@@ -99,6 +100,17 @@ impl Callbacks for Io {
         self.push_to_initial_input_ids(data).await?;
         self.push_to_flow_step_outputs(data).await?;
 
+        if self.is_default_context() {
+            ModelEvent::new(
+                self.root_id,
+                self.branch_id,
+                ActionTypes::Create(ActionObject::Io),
+                self,
+            )
+            .emit(data)
+            .await;
+        }
+
         Ok(())
     }
 
@@ -133,6 +145,17 @@ impl Callbacks for Io {
                 e
             });
 
+        if self.is_default_context() {
+            ModelEvent::new(
+                self.root_id,
+                self.branch_id,
+                ActionTypes::Delete(ActionObject::Io),
+                self,
+            )
+            .emit(data)
+            .await;
+        }
+
         Ok(())
     }
 }
@@ -140,34 +163,36 @@ impl Callbacks for Io {
 impl Io {
     pub async fn branched(db_session: &CachingSession, params: &NodeBranchParams) -> Result<Vec<Io>, NodecosmosError> {
         // root_id == params.original_id
-        let mut ios = Self::find_by_branch_id_and_root_id(params.branch_id, params.root_id)
+        let ios = Self::find_by_branch_id_and_root_id(params.branch_id, params.root_id)
             .execute(db_session)
+            .await?
+            .try_collect()
             .await?;
 
         if params.is_original() {
-            Ok(ios.try_collect().await?)
+            Ok(ios)
         } else {
-            let mut original_ios = Self::find_by_branch_id_and_root_id(params.original_id(), params.root_id)
-                .execute(db_session)
-                .await?;
-            let mut branched_ios_set = HashSet::new();
-            let mut branch_ios = vec![];
+            let mut layers = vec![BranchLayer::new(ios)];
 
-            while let Some(io) = ios.next().await {
-                let io = io?;
-                branched_ios_set.insert(io.id);
-                branch_ios.push(io);
-            }
+            for parent in Branch::parent_branches(db_session, params.branch_id).await? {
+                let parent_ios = Self::find_by_branch_id_and_root_id(parent.id, params.root_id)
+                    .execute(db_session)
+                    .await?
+                    .try_collect()
+                    .await?;
 
-            while let Some(io) = original_ios.next().await {
-                let mut io = io?;
-                if !branched_ios_set.contains(&io.id) {
-                    io.branch_id = params.branch_id;
-                    branch_ios.push(io);
-                }
+                layers.push(BranchLayer::parent(parent_ios, parent));
             }
 
-            Ok(branch_ios)
+            let original_ios = Self::find_by_branch_id_and_root_id(params.original_id(), params.root_id)
+                .execute(db_session)
+                .await?
+                .try_collect()
+                .await?;
+
+            layers.push(BranchLayer::new(original_ios));
+
+            Ok(combine_layers(layers, params.branch_id))
         }
     }
 
@@ -334,6 +359,28 @@ impl Io {
     }
 }
 
+impl From<&ArchivedIo> for Io {
+    fn from(io: &ArchivedIo) -> Self {
+        Self {
+            branch_id: io.branch_id,
+            root_id: io.root_id,
+            node_id: io.node_id,
+            id: io.id,
+            main_id: io.main_id,
+            flow_id: io.flow_id,
+            flow_step_id: io.flow_step_id,
+            inputted_by_flow_steps: io.inputted_by_flow_steps.clone(),
+            title: io.title.clone(),
+            unit: io.unit.clone(),
+            data_type: io.data_type.clone(),
+            value: io.value.clone(),
+            created_at: io.created_at,
+            updated_at: io.updated_at,
+            ..Default::default()
+        }
+    }
+}
+
 partial_io!(
     UpdateTitleIo,
     root_id,
@@ -356,6 +403,21 @@ impl Callbacks for UpdateTitleIo {
 
         Ok(())
     }
+
+    async fn after_update(&mut self, _db_session: &CachingSession, data: &RequestData) -> Result<(), Self::Error> {
+        if self.is_default_context() {
+            ModelEvent::new(
+                self.root_id,
+                self.branch_id,
+                ActionTypes::Update(ActionObject::Io),
+                self,
+            )
+            .emit(data)
+            .await;
+        }
+
+        Ok(())
+    }
 }
 
 impl UpdateTitleIo {
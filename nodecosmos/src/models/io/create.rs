@@ -21,7 +21,7 @@ impl Io {
                 root_id: self.root_id,
                 ..Default::default()
             }
-            .push_initial_input(data.db_session(), self.id)
+            .push_initial_input(data, self.id)
             .await?;
         }
 
@@ -157,8 +157,8 @@ impl Io {
 
     pub async fn update_branch_with_creation(&self, data: &RequestData) -> Result<(), NodecosmosError> {
         if self.is_branch() {
-            Branch::update(data.db_session(), self.branch_id, BranchUpdate::EditNode(self.node_id)).await?;
-            Branch::update(data.db_session(), self.branch_id, BranchUpdate::CreateIo(self.id)).await?;
+            Branch::update(data, self.branch_id, self.node_id, BranchUpdate::EditNode(self.node_id)).await?;
+            Branch::update(data, self.branch_id, self.node_id, BranchUpdate::CreateIo(self.id)).await?;
         }
 
         Ok(())
@@ -166,8 +166,8 @@ impl Io {
 
     pub async fn update_branch_with_deletion(&self, data: &RequestData) -> Result<(), NodecosmosError> {
         if self.is_branch() && !self.is_parent_delete_context() {
-            Branch::update(data.db_session(), self.branch_id, BranchUpdate::EditNode(self.node_id)).await?;
-            Branch::update(data.db_session(), self.branch_id, BranchUpdate::DeleteIo(self.id)).await?;
+            Branch::update(data, self.branch_id, self.node_id, BranchUpdate::EditNode(self.node_id)).await?;
+            Branch::update(data, self.branch_id, self.node_id, BranchUpdate::DeleteIo(self.id)).await?;
         }
 
         Ok(())
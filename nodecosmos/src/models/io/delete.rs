@@ -15,7 +15,7 @@ impl Io {
                 root_id: self.root_id,
                 ..Default::default()
             }
-            .pull_initial_input(data.db_session(), self.id)
+            .pull_initial_input(data, self.id)
             .await?;
         }
 
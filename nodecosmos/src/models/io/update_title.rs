@@ -1,5 +1,6 @@
 use charybdis::batch::ModelBatch;
 use charybdis::model::AsNative;
+use charybdis::operations::Find;
 use scylla::client::caching_session::CachingSession;
 
 use crate::api::data::RequestData;
@@ -40,8 +41,32 @@ impl UpdateTitleIo {
     pub async fn update_branch(&self, data: &RequestData) -> Result<(), NodecosmosError> {
         if self.is_branch() {
             self.as_native().create_branched_if_original_exists(data).await?;
-            Branch::update(data.db_session(), self.branch_id, BranchUpdate::EditNode(self.node_id)).await?;
-            Branch::update(data.db_session(), self.branch_id, BranchUpdate::EditIoTitle(self.id)).await?;
+            Branch::update(data, self.branch_id, self.node_id, BranchUpdate::EditNode(self.node_id)).await?;
+            Branch::update(data, self.branch_id, self.node_id, BranchUpdate::EditIoTitle(self.id)).await?;
+            self.track_title_change(data).await?;
+        }
+
+        Ok(())
+    }
+
+    async fn track_title_change(&self, data: &RequestData) -> Result<(), NodecosmosError> {
+        let maybe_original = UpdateTitleIo {
+            branch_id: self.original_id(),
+            ..self.clone()
+        }
+        .maybe_find_by_primary_key()
+        .execute(data.db_session())
+        .await?;
+
+        if let Some(original) = maybe_original {
+            Branch::track_title_change(
+                data.db_session(),
+                self.branch_id,
+                self.id,
+                original.title.unwrap_or_default(),
+                self.title.clone().unwrap_or_default(),
+            )
+            .await?;
         }
 
         Ok(())
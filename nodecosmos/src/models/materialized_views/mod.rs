@@ -1,3 +1,4 @@
 pub mod likes_by_user;
 pub mod nodes_by_creator;
 pub mod nodes_by_owner;
+pub mod recoveries_by_status;
@@ -0,0 +1,20 @@
+use charybdis::macros::charybdis_view_model;
+use charybdis::types::{Text, Timestamp, TinyInt, Uuid};
+use serde::{Deserialize, Serialize};
+
+/// Recovery log keys by status, so the recovery task reads only pending entries. Saga data is loaded
+/// from the base table for the entries that are due.
+#[charybdis_view_model(
+    table_name = recoveries_by_status,
+    base_table = recoveries,
+    partition_keys = [status],
+    clustering_keys = [branch_id, object_type, id]
+)]
+#[derive(Serialize, Deserialize, Default)]
+pub struct RecoveriesByStatus {
+    pub status: Text,
+    pub branch_id: Uuid,
+    pub object_type: TinyInt,
+    pub id: Uuid,
+    pub updated_at: Timestamp,
+}
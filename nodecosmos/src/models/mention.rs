@@ -0,0 +1,263 @@
+use std::collections::HashSet;
+
+use charybdis::types::{Set, Uuid};
+use log::error;
+use scylla::client::caching_session::CachingSession;
+
+use crate::api::current_user::OptCurrentUser;
+use crate::api::data::RequestData;
+use crate::errors::NodecosmosError;
+use crate::models::branch::GetNodeIdBranch;
+use crate::models::node::AuthNode;
+use crate::models::notification::{Notification, NotificationType};
+use crate::models::user::{CurrentUser, User};
+
+/// Max number of usernames resolved from a single content.
+const MAX_MENTIONS: usize = 20;
+
+/// Html elements whose content is code.
+const CODE_TAGS: [&str; 2] = ["pre", "code"];
+
+/// Users mentioned with `@username` within comment or description content.
+#[derive(Default, Clone)]
+pub struct Mentions {
+    pub users: Vec<User>,
+}
+
+impl Mentions {
+    /// Extracts mentioned usernames. `@` preceded by alphanumeric character, e.g. within email, is not a mention.
+    /// Neither is `@` within markdown or html code.
+    pub fn usernames(text: &str) -> HashSet<String> {
+        let text = Self::without_code(text);
+        let text = text.as_str();
+        let mut usernames = HashSet::new();
+        let mut prev_char = None;
+
+        for (index, ch) in text.char_indices() {
+            if ch == '@' && !prev_char.is_some_and(|prev: char| prev.is_alphanumeric()) {
+                let rest = &text[index + 1..];
+                let end = rest
+                    .find(|c: char| !(c.is_alphanumeric() || c == '_' || c == '-' || c == '.'))
+                    .unwrap_or(rest.len());
+                let username = rest[..end].trim_end_matches(['.', '-']);
+
+                if !username.is_empty() {
+                    usernames.insert(username.to_string());
+                }
+            }
+
+            prev_char = Some(ch);
+        }
+
+        usernames
+    }
+
+    /// Replaces code spans and blocks by a space. Content is markdown for descriptions and html for comments.
+    fn without_code(text: &str) -> String {
+        let mut result = String::with_capacity(text.len());
+        let mut rest = text;
+
+        while let Some(start) = rest.find(['`', '<']) {
+            result.push_str(&rest[..start]);
+            let tail = &rest[start..];
+
+            let code_len = if tail.starts_with('`') {
+                // closing fence has the same number of backticks, e.g. `code` or ```block```
+                let ticks = tail.len() - tail.trim_start_matches('`').len();
+                let fence = &tail[..ticks];
+
+                tail[ticks..].find(fence).map(|end| ticks + end + ticks)
+            } else {
+                CODE_TAGS.iter().find_map(|tag| {
+                    let is_open_tag = tail[1..].starts_with(tag)
+                        && tail[1 + tag.len()..].starts_with(|c: char| c == '>' || c.is_whitespace());
+
+                    if !is_open_tag {
+                        return None;
+                    }
+
+                    let close_tag = format!("</{}>", tag);
+
+                    tail.find(&close_tag).map(|end| end + close_tag.len())
+                })
+            };
+
+            match code_len {
+                Some(len) => {
+                    result.push(' ');
+                    rest = &tail[len..];
+                }
+                None => {
+                    // unclosed code or non code tag is regular text
+                    let len = if tail.starts_with('`') {
+                        tail.len() - tail.trim_start_matches('`').len()
+                    } else {
+                        1
+                    };
+                    result.push_str(&tail[..len]);
+                    rest = &tail[len..];
+                }
+            }
+        }
+
+        result.push_str(rest);
+
+        result
+    }
+
+    /// Resolves mentioned usernames against users. Unknown usernames are ignored.
+    pub async fn find(db_session: &CachingSession, text: &str) -> Result<Self, NodecosmosError> {
+        let mut users = vec![];
+
+        for username in Self::usernames(text).into_iter().take(MAX_MENTIONS) {
+            let user = User::maybe_find_first_by_username(username).execute(db_session).await?;
+
+            if let Some(user) = user {
+                users.push(user);
+            }
+        }
+
+        Ok(Self { users })
+    }
+
+    pub fn user_ids(&self) -> Option<Set<Uuid>> {
+        if self.users.is_empty() {
+            return None;
+        }
+
+        Some(self.users.iter().map(|user| user.id).collect())
+    }
+
+    /// Keeps users that are not within previously mentioned users, so updated content notifies only new mentions.
+    pub fn retain_new(&mut self, prev_user_ids: &Option<Set<Uuid>>) {
+        if let Some(prev_user_ids) = prev_user_ids {
+            self.users.retain(|user| !prev_user_ids.contains(&user.id));
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.users.is_empty()
+    }
+
+    /// Sends notification and email to mentioned users that can view the node of the target.
+    pub async fn notify(&self, data: &RequestData, target: &MentionTarget) {
+        let url = match target.url(data.db_session(), &data.app.config.client_url).await {
+            Ok(url) => url,
+            Err(e) => {
+                error!("Error resolving mention url: {}", e);
+                return;
+            }
+        };
+        let mut receiver_ids = HashSet::new();
+
+        for user in &self.users {
+            if user.id == data.current_user.id || user.is_blocked {
+                continue;
+            }
+
+            let opt_cu = OptCurrentUser(Some(CurrentUser::from_user(user.clone())));
+            let auth = AuthNode::auth_view(
+                data.db_session(),
+                &opt_cu,
+                target.branch_id,
+                target.node_id,
+                target.root_id,
+            )
+            .await;
+
+            if auth.is_err() {
+                continue;
+            }
+
+            receiver_ids.insert(user.id);
+
+            let _ = data
+                .mailer()
+                .send_mention_email(user.email.clone(), &data.current_user.username, &target.text, &url)
+                .await
+                .map_err(|e| {
+                    error!("Error sending mention email to user {}: {}", user.id, e);
+                });
+        }
+
+        if receiver_ids.is_empty() {
+            return;
+        }
+
+        let _ = Notification::new(
+            NotificationType::Mention,
+            target.text.clone(),
+            url,
+            Some((&data.current_user).into()),
+        )
+        .create_for_receivers(data, receiver_ids)
+        .await
+        .map_err(|e| {
+            error!("Error creating mention notification: {}", e);
+        });
+    }
+}
+
+/// Object where users are mentioned. View permissions of its node are required to receive the mention.
+pub struct MentionTarget {
+    pub branch_id: Uuid,
+    pub node_id: Uuid,
+    pub root_id: Uuid,
+    pub text: String,
+
+    /// Url of the object within original tree, e.g. comment thread. Defaults to the node url.
+    pub url: Option<String>,
+}
+
+impl MentionTarget {
+    /// Mentions inside branches link to the branch view of the contribution request, which lives under the
+    /// node of the branch rather than the node of the mentioned object.
+    async fn url(&self, db_session: &CachingSession, client_url: &str) -> Result<String, NodecosmosError> {
+        if self.branch_id != self.root_id {
+            let branch = GetNodeIdBranch::find_by_id(self.branch_id).execute(db_session).await?;
+
+            return Ok(format!(
+                "{}/nodes/{}/{}/contribution_requests/{}",
+                client_url, self.root_id, branch.node_id, self.branch_id
+            ));
+        }
+
+        Ok(self
+            .url
+            .clone()
+            .unwrap_or_else(|| format!("{}/nodes/{}/{}", client_url, self.branch_id, self.node_id)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn usernames(text: &str) -> Vec<String> {
+        let mut usernames: Vec<String> = Mentions::usernames(text).into_iter().collect();
+        usernames.sort();
+
+        usernames
+    }
+
+    #[test]
+    fn test_usernames() {
+        assert_eq!(usernames("hi @alice and @bob_1."), vec!["alice", "bob_1"]);
+        assert_eq!(usernames("mail me at alice@example.com"), Vec::<String>::new());
+    }
+
+    #[test]
+    fn test_usernames_skip_markdown_code() {
+        let text = "@alice see `@bob` and\n```\nlet x = @carol;\n```\nthen @dave";
+
+        assert_eq!(usernames(text), vec!["alice", "dave"]);
+        assert_eq!(usernames("unclosed `@alice"), vec!["alice"]);
+    }
+
+    #[test]
+    fn test_usernames_skip_html_code() {
+        let text = "<p>@alice <code>@bob</code></p><pre><code>@carol</code></pre><p><b>@dave</b></p>";
+
+        assert_eq!(usernames(text), vec!["alice", "dave"]);
+    }
+}
@@ -0,0 +1,349 @@
+use std::collections::HashSet;
+
+use charybdis::macros::charybdis_model;
+use charybdis::operations::{Delete, Find, Insert, Update};
+use charybdis::types::{Int, Text, Timestamp, Uuid};
+use futures::StreamExt;
+use serde::{Deserialize, Serialize};
+
+use crate::api::data::RequestData;
+use crate::api::types::ActionTypes;
+use crate::errors::NodecosmosError;
+use crate::models::contribution_request::{ContributionRequest, ContributionRequestStatus};
+use crate::models::notification::{Notification, NotificationType};
+use crate::models::user::CurrentUser;
+use crate::resources::resource_locker::ResourceLocker;
+
+pub const MERGE_QUEUE_INTERVAL_SEC: u64 = 10;
+
+/// Merges that fail with other errors than conflicts are retried until this number of attempts.
+const MAX_MERGE_ATTEMPTS: i32 = 3;
+
+/// Contribution request waiting to be merged. Each root has its own queue that is processed in order of
+/// enqueueing by the merge queue task.
+#[charybdis_model(
+    table_name = merge_queue,
+    partition_keys = [root_id],
+    clustering_keys = [created_at, branch_id],
+)]
+#[derive(Serialize, Deserialize, Default, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct MergeQueueItem {
+    pub root_id: Uuid,
+
+    #[serde(default = "chrono::Utc::now")]
+    pub created_at: Timestamp,
+
+    /// Id of the contribution request.
+    pub branch_id: Uuid,
+
+    /// Node of the contribution request.
+    pub node_id: Uuid,
+
+    #[serde(default)]
+    pub enqueued_by_id: Uuid,
+
+    #[serde(default)]
+    pub attempts: Int,
+
+    pub last_error: Option<Text>,
+}
+
+/// Root with queued merges. The merge queue task iterates over these, so it doesn't have to scan the queue
+/// itself. Rows are removed by the task once the queue of the root is empty.
+#[charybdis_model(
+    table_name = merge_queue_roots,
+    partition_keys = [root_id],
+    clustering_keys = []
+)]
+#[derive(Serialize, Deserialize, Default, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct MergeQueueRoot {
+    pub root_id: Uuid,
+}
+
+impl MergeQueueRoot {
+    async fn head(&self, data: &RequestData) -> Result<Option<MergeQueueItem>, NodecosmosError> {
+        MergeQueueItem::maybe_find_first_by_root_id(self.root_id)
+            .execute(data.db_session())
+            .await
+            .map_err(NodecosmosError::from)
+    }
+
+    /// Items are enqueued before their root, so if an item is enqueued while the root is being removed, it's
+    /// either found by the second check or its root is inserted after the removal.
+    async fn remove_if_empty(&self, data: &RequestData) -> Result<(), NodecosmosError> {
+        self.delete().execute(data.db_session()).await?;
+
+        if self.head(data).await?.is_some() {
+            self.insert().execute(data.db_session()).await?;
+        }
+
+        Ok(())
+    }
+}
+
+impl MergeQueueItem {
+    pub async fn enqueue(
+        data: &RequestData,
+        contribution_request: &ContributionRequest,
+    ) -> Result<MergeQueueItem, NodecosmosError> {
+        if contribution_request.status == Some(ContributionRequestStatus::Merged.to_string())
+            || contribution_request.status == Some(ContributionRequestStatus::Closed.to_string())
+        {
+            return Err(NodecosmosError::PreconditionFailed(
+                "Only open contribution requests can be queued for merge",
+            ));
+        }
+
+        if Self::find_queued(data, contribution_request.root_id, contribution_request.id)
+            .await?
+            .is_some()
+        {
+            return Err(NodecosmosError::Conflict(
+                "Contribution request is already queued for merge".to_string(),
+            ));
+        }
+
+        let item = MergeQueueItem {
+            root_id: contribution_request.root_id,
+            created_at: chrono::Utc::now(),
+            branch_id: contribution_request.id,
+            node_id: contribution_request.node_id,
+            enqueued_by_id: data.current_user.id,
+            attempts: 0,
+            last_error: None,
+        };
+
+        item.insert().execute(data.db_session()).await?;
+
+        MergeQueueRoot { root_id: item.root_id }
+            .insert()
+            .execute(data.db_session())
+            .await?;
+
+        Ok(item)
+    }
+
+    pub async fn find_queued(
+        data: &RequestData,
+        root_id: Uuid,
+        branch_id: Uuid,
+    ) -> Result<Option<MergeQueueItem>, NodecosmosError> {
+        let items: Vec<MergeQueueItem> = MergeQueueItem::find_by_root_id(root_id)
+            .execute(data.db_session())
+            .await?
+            .try_collect()
+            .await?;
+
+        Ok(items.into_iter().find(|item| item.branch_id == branch_id))
+    }
+
+    /// Merges the first queued contribution request of each root.
+    pub async fn run_merge_queue_task(data: &RequestData) -> Result<(), NodecosmosError> {
+        let mut roots = MergeQueueRoot::find_all().execute(data.db_session()).await?;
+
+        while let Some(root) = roots.next().await {
+            let root = root?;
+
+            let res = match root.head(data).await {
+                Ok(Some(head)) => head.process(data).await,
+                Ok(None) => root.remove_if_empty(data).await,
+                Err(e) => Err(e),
+            };
+
+            let _ = res.map_err(|e| {
+                log::error!("Merge queue of root {} failed: {:?}", root.root_id, e);
+            });
+        }
+
+        Ok(())
+    }
+
+    async fn process(self, data: &RequestData) -> Result<(), NodecosmosError> {
+        let root_id = self.root_id;
+        let is_claimed = data
+            .resource_locker()
+            .try_lock_resource_action(ActionTypes::MergeQueue, root_id, root_id, ResourceLocker::ONE_HOUR)
+            .await?;
+
+        // queue is processed by another instance
+        if !is_claimed {
+            return Ok(());
+        }
+
+        let res = self.merge_claimed(data).await;
+
+        data.resource_locker()
+            .unlock_resource_action(ActionTypes::MergeQueue, root_id, root_id)
+            .await?;
+
+        res
+    }
+
+    async fn merge_claimed(self, data: &RequestData) -> Result<(), NodecosmosError> {
+        let db_session = data.db_session();
+
+        // item could have been processed by another instance before we claimed the queue
+        let mut item = match self.maybe_find_by_primary_key().execute(db_session).await? {
+            Some(item) => item,
+            None => return Ok(()),
+        };
+
+        let contribution_request = ContributionRequest {
+            node_id: item.node_id,
+            id: item.branch_id,
+            ..Default::default()
+        }
+        .maybe_find_by_primary_key()
+        .execute(db_session)
+        .await?;
+
+        let mut contribution_request = match contribution_request {
+            Some(contribution_request) => contribution_request,
+            None => {
+                item.delete().execute(db_session).await?;
+                return Ok(());
+            }
+        };
+
+        if contribution_request.status == Some(ContributionRequestStatus::Merged.to_string())
+            || contribution_request.status == Some(ContributionRequestStatus::Closed.to_string())
+        {
+            item.delete().execute(db_session).await?;
+            return Ok(());
+        }
+
+        // merge runs on behalf of the user that queued it
+        let current_user = CurrentUser::find_by_id(item.enqueued_by_id).execute(db_session).await?;
+        let enqueuer_data = RequestData {
+            app: data.app.clone(),
+            current_user,
+        };
+
+        // root can be locked by a merge or a reorder outside of the queue, so we try again on the next run
+        if data
+            .resource_locker()
+            .lock_resource(item.root_id, item.root_id, ResourceLocker::ONE_HOUR)
+            .await
+            .is_err()
+        {
+            return Ok(());
+        }
+
+        // same as for the merge endpoint, root and branch can have the merge action locked by node creation,
+        // deletion, reorder or clone, so we try again on the next run
+        for id in [item.root_id, item.branch_id] {
+            if data
+                .resource_locker()
+                .validate_resource_action_unlocked(ActionTypes::Merge, item.root_id, id, true)
+                .await
+                .is_err()
+            {
+                data.resource_locker()
+                    .unlock_resource(item.root_id, item.root_id)
+                    .await?;
+
+                return Ok(());
+            }
+        }
+
+        let res = contribution_request.merge(&enqueuer_data, None).await;
+
+        data.resource_locker()
+            .unlock_resource(item.root_id, item.root_id)
+            .await?;
+
+        match res {
+            Ok(_) => {
+                item.delete().execute(db_session).await?;
+
+                // merge notification reaches the author, but skips the user that merged, so the user that
+                // queued the request is notified here
+                notify(
+                    data,
+                    &contribution_request,
+                    NotificationType::MergeContributionRequest,
+                    HashSet::from([item.enqueued_by_id]),
+                )
+                .await;
+            }
+            Err(NodecosmosError::Conflict(_)) => {
+                item.delete().execute(db_session).await?;
+
+                notify(
+                    data,
+                    &contribution_request,
+                    NotificationType::MergeConflict,
+                    HashSet::from([contribution_request.owner_id, item.enqueued_by_id]),
+                )
+                .await;
+            }
+            Err(NodecosmosError::FatalMergeError(e)) => {
+                log::error!("Fatal merge error for contribution request {}: {}", item.branch_id, e);
+
+                item.delete().execute(db_session).await?;
+
+                notify(
+                    data,
+                    &contribution_request,
+                    NotificationType::MergeFailed,
+                    HashSet::from([contribution_request.owner_id, item.enqueued_by_id]),
+                )
+                .await;
+            }
+            Err(e) => {
+                item.attempts += 1;
+
+                if item.attempts >= MAX_MERGE_ATTEMPTS {
+                    item.delete().execute(db_session).await?;
+
+                    notify(
+                        data,
+                        &contribution_request,
+                        NotificationType::MergeFailed,
+                        HashSet::from([contribution_request.owner_id, item.enqueued_by_id]),
+                    )
+                    .await;
+                } else {
+                    item.last_error = Some(e.to_string());
+                    item.update().execute(db_session).await?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+async fn notify(
+    data: &RequestData,
+    contribution_request: &ContributionRequest,
+    notification_type: NotificationType,
+    receiver_ids: HashSet<Uuid>,
+) {
+    let text = match notification_type {
+        NotificationType::MergeContributionRequest => "merged contribution request",
+        NotificationType::MergeConflict => "merge conflict in contribution request",
+        _ => "failed to merge contribution request",
+    };
+    let notification = Notification::new(
+        notification_type,
+        format!("{} - {}", text, contribution_request.title),
+        format!(
+            "{client_url}/nodes/{original_id}/{node_id}/contribution_requests/{id}",
+            client_url = &data.app.config.client_url,
+            original_id = contribution_request.root_id,
+            node_id = contribution_request.node_id,
+            id = contribution_request.id
+        ),
+        None,
+    );
+
+    let _ = notification
+        .create_for_receivers(data, receiver_ids)
+        .await
+        .map_err(|e| {
+            log::error!("Error creating merge queue notification: {:?}", e);
+        });
+}
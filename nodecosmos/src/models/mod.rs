@@ -6,23 +6,31 @@ pub mod archived_node;
 pub mod archived_workflow;
 pub mod attachment;
 pub mod branch;
+pub mod branch_protection;
 pub mod comment;
 pub mod comment_thread;
 pub mod commit;
+pub mod consistency;
 pub mod contact;
 pub mod contribution_request;
 pub mod description;
+pub mod description_version;
 pub mod flow;
 pub mod flow_step;
 pub mod invitation;
 pub mod io;
 pub mod like;
 pub mod materialized_views;
+pub mod mention;
+pub mod merge_queue;
 pub mod node;
 pub mod node_counter;
 pub mod node_descendant;
 pub mod notification;
 pub mod recovery;
+pub mod review;
+pub mod review_settings;
+pub mod saga;
 pub mod subscription;
 pub mod task;
 pub mod task_section;
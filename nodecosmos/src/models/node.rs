@@ -1,15 +1,19 @@
 use crate::api::data::RequestData;
+use crate::api::types::{ActionObject, ActionTypes};
 use crate::errors::NodecosmosError;
+use crate::models::archived_node::ArchivedNode;
 use crate::models::branch::AuthBranch;
 use crate::models::node::delete::NodeDelete;
 use crate::models::node_descendant::NodeDescendant;
 use crate::models::subscription::Subscription;
 use crate::models::traits::{
-    AuthorizationFields, Branchable, ElasticDocument, FindBranchedOrOriginalNode, NodeBranchParams, WhereInChunksExec,
+    AuthorizationFields, Branchable, Descendants, ElasticDocument, FindBranchedOrOriginalNode, NodeBranchParams,
+    WhereInChunksExec,
 };
 use crate::models::traits::{Context as Ctx, ModelContext};
 use crate::models::udts::Profile;
 use crate::models::user::User;
+use crate::resources::sse_broadcast::ModelEvent;
 use crate::stream::MergedModelStream;
 use anyhow::Context;
 use charybdis::batch::{CharybdisModelBatch, ModelBatch};
@@ -25,10 +29,13 @@ use serde::{Deserialize, Serialize};
 use std::collections::HashSet;
 
 mod auth;
+pub mod clone;
 mod create;
 pub mod delete;
+pub mod export;
 pub mod import;
 pub mod reorder;
+pub mod root_move;
 pub mod search;
 pub mod sort;
 mod subscription;
@@ -147,6 +154,17 @@ impl Callbacks for Node {
             delete_data.recover(data).await?;
         }
 
+        if self.is_default_context() {
+            ModelEvent::new(
+                self.root_id,
+                self.branch_id,
+                ActionTypes::Create(ActionObject::Node),
+                self,
+            )
+            .emit(data)
+            .await;
+        }
+
         let self_clone = self.clone();
         let data = data.clone();
 
@@ -180,6 +198,17 @@ impl Callbacks for Node {
         // TODO: see nodecosmos/src/models/node/create.rs:258
         self.create_branched_if_original_exist(data).await?;
 
+        if self.is_default_context() {
+            ModelEvent::new(
+                self.root_id,
+                self.branch_id,
+                ActionTypes::Delete(ActionObject::Node),
+                self,
+            )
+            .emit(data)
+            .await;
+        }
+
         Ok(())
     }
 }
@@ -204,6 +233,51 @@ impl Node {
         })
         .await
     }
+
+    /// Order index that places a new child after the existing children of the node.
+    pub async fn next_child_order_index(&self, db_session: &CachingSession) -> Result<Double, NodecosmosError> {
+        let descendants: Vec<NodeDescendant> = if self.is_branch() {
+            self.branch_descendants(db_session).await?
+        } else {
+            self.descendants(db_session).await?.try_collect().await?
+        };
+
+        let order_index = descendants
+            .iter()
+            .filter(|descendant| descendant.parent_id == self.id)
+            .map(|descendant| descendant.order_index)
+            .fold(None, |max: Option<Double>, order_index| {
+                Some(max.map_or(order_index, |max| max.max(order_index)))
+            })
+            .map_or(0.0, |max| max + 1.0);
+
+        Ok(order_index)
+    }
+}
+
+impl From<&ArchivedNode> for Node {
+    fn from(node: &ArchivedNode) -> Self {
+        Self {
+            branch_id: node.branch_id,
+            id: node.id,
+            root_id: node.root_id,
+            is_public: node.is_public,
+            is_root: node.is_root,
+            order_index: node.order_index,
+            title: node.title.clone(),
+            parent_id: node.parent_id,
+            ancestor_ids: node.ancestor_ids.clone(),
+            owner_id: node.owner_id,
+            owner: node.owner.clone(),
+            editor_ids: node.editor_ids.clone(),
+            viewer_ids: node.viewer_ids.clone(),
+            cover_image_filename: node.cover_image_filename.clone(),
+            cover_image_url: node.cover_image_url.clone(),
+            created_at: node.created_at,
+            updated_at: node.updated_at,
+            ..Default::default()
+        }
+    }
 }
 
 partial_node!(PkNode, branch_id, id, root_id, owner_id, editor_ids, ancestor_ids);
@@ -321,6 +395,17 @@ impl Callbacks for UpdateTitleNode {
     }
 
     async fn after_update(&mut self, _: &CachingSession, data: &Self::Extension) -> Result<(), NodecosmosError> {
+        if self.is_default_context() {
+            ModelEvent::new(
+                self.root_id,
+                self.branch_id,
+                ActionTypes::Update(ActionObject::Node),
+                self,
+            )
+            .emit(data)
+            .await;
+        }
+
         let self_clone = self.clone();
         let data = data.clone();
 
@@ -383,6 +468,15 @@ impl Callbacks for UpdateCoverImageNode {
             let _ = self.update_elastic_document(data.elastic_client()).await;
         }
 
+        ModelEvent::new(
+            self.root_id,
+            self.branch_id,
+            ActionTypes::Update(ActionObject::Node),
+            self,
+        )
+        .emit(data)
+        .await;
+
         Ok(())
     }
 }
@@ -28,6 +28,25 @@ impl AuthNode {
         Ok(())
     }
 
+    /// Edit access without branch protection, see `Authorization::auth_edit_access`.
+    pub async fn auth_edit_access(
+        data: &RequestData,
+        branch_id: Uuid,
+        node_id: Uuid,
+        root_id: Uuid,
+    ) -> Result<(), NodecosmosError> {
+        let mut node = AuthNode {
+            branch_id,
+            id: node_id,
+            root_id,
+            ..Default::default()
+        };
+
+        node.auth_edit_access(data).await?;
+
+        Ok(())
+    }
+
     pub async fn auth_view(
         db_session: &CachingSession,
         opt_cu: &OptCurrentUser,
@@ -0,0 +1,991 @@
+use std::collections::{HashMap, HashSet};
+
+use charybdis::batch::{CharybdisModelBatch, ModelBatch};
+use charybdis::types::{List, Set, Uuid};
+use scylla::client::caching_session::CachingSession;
+use serde::{Deserialize, Serialize};
+
+use macros::Branchable;
+
+use crate::api::data::RequestData;
+use crate::errors::NodecosmosError;
+use crate::models::attachment::Attachment;
+use crate::models::branch::update::BranchUpdate;
+use crate::models::branch::{
+    Branch, UpdateCreateWorkflowInitialInputsBranch, UpdateCreatedFlowStepsBranch, UpdateCreatedFlowsBranch,
+    UpdateCreatedIosBranch, UpdateCreatedNodesBranch, UpdateEditedDescriptionFlowStepsBranch,
+    UpdateEditedDescriptionIosBranch, UpdateEditedDescriptionNodesBranch, UpdateEditedFlowDescriptionBranch,
+    UpdateEditedNodesBranch,
+};
+use crate::models::description::Description;
+use crate::models::flow::Flow;
+use crate::models::flow_step::FlowStep;
+use crate::models::io::Io;
+use crate::models::node::Node;
+use crate::models::node_descendant::NodeDescendant;
+use crate::models::recovery::{RecoveryLog, RecoveryObjectType};
+use crate::models::saga::{saga_steps, Saga, SagaStep};
+use crate::models::traits::s3::S3;
+use crate::models::traits::{Branchable, Descendants, ElasticDocument, FindBranchedOrOriginalNode, NodeBranchParams};
+use crate::models::traits::{FindForBranchMerge, GroupById, GroupByObjectId, ObjectType};
+use crate::models::workflow::Workflow;
+
+saga_steps! {
+    pub enum NodeCloneStep {
+        InsertNodes = 1,
+        InsertDescendants = 2,
+        InsertWorkflows = 3,
+        InsertFlows = 4,
+        InsertFlowSteps = 5,
+        InsertIos = 6,
+        InsertDescriptions = 7,
+        InsertAttachments = 8,
+        UpdateBranch = 9,
+        InsertElasticData = 10,
+        Finish = 11,
+        AfterFinish = 12,
+    }
+}
+
+/// `root_id`, `branch_id` and `id` identify the source node, while `new_*` fields identify the parent
+/// under which the subtree is cloned. Target may be within different root or branch.
+#[derive(Serialize, Deserialize, Branchable)]
+#[serde(rename_all = "camelCase")]
+pub struct CloneParams {
+    #[branch(original_id)]
+    pub root_id: Uuid,
+    pub branch_id: Uuid,
+    pub id: Uuid,
+    pub new_root_id: Uuid,
+    pub new_branch_id: Uuid,
+    pub new_parent_id: Uuid,
+}
+
+/// Deep copy of the node subtree together with its workflows, flows, flow steps, ios, descriptions and
+/// attachments. Every cloned record gets a fresh id, and all references between cloned records are remapped.
+#[derive(Serialize, Deserialize)]
+pub struct NodeClone {
+    params: CloneParams,
+    clone_step: NodeCloneStep,
+    nodes: Vec<Node>,
+    descendants: Vec<NodeDescendant>,
+    workflows: Vec<Workflow>,
+    flows: Vec<Flow>,
+    flow_steps: Vec<FlowStep>,
+    ios: Vec<Io>,
+    descriptions: Vec<Description>,
+    attachments: Vec<Attachment>,
+    // new attachment id -> source s3 key
+    attachment_source_keys: HashMap<Uuid, String>,
+}
+
+impl NodeClone {
+    pub async fn new(data: &RequestData, params: CloneParams) -> Result<Self, NodecosmosError> {
+        let db_session = data.db_session();
+        let node = Node::find_branched_or_original(
+            db_session,
+            NodeBranchParams {
+                root_id: params.root_id,
+                branch_id: params.branch_id,
+                node_id: params.id,
+            },
+        )
+        .await?;
+        let new_parent = Node::find_branched_or_original(
+            db_session,
+            NodeBranchParams {
+                root_id: params.new_root_id,
+                branch_id: params.new_branch_id,
+                node_id: params.new_parent_id,
+            },
+        )
+        .await?;
+
+        let deleted_ids = if params.is_branch() {
+            Branch::find_by_id(params.branch_id)
+                .execute(db_session)
+                .await?
+                .all_deleted_object_ids()
+        } else {
+            HashSet::new()
+        };
+
+        let mut node_ids = vec![node.id];
+        let descendants: Vec<NodeDescendant> = if node.is_branch() {
+            node.branch_descendants(db_session).await?
+        } else {
+            node.descendants(db_session).await?.try_collect().await?
+        };
+        node_ids.extend(
+            descendants
+                .iter()
+                .filter(|descendant| !deleted_ids.contains(&descendant.id))
+                .map(|descendant| descendant.id),
+        );
+
+        let mut source_nodes = Self::source_nodes(db_session, &params, &node_ids).await?;
+        // skip descendants of deleted nodes
+        source_nodes.retain(|source_node| {
+            source_node.id == node.id
+                || !source_node
+                    .ancestor_ids
+                    .as_ref()
+                    .is_some_and(|ids| ids.iter().any(|id| deleted_ids.contains(id)))
+        });
+        // parents first, so cloned ancestors are inserted before their descendants
+        source_nodes.sort_by_key(|source_node| source_node.ancestor_ids.as_ref().map_or(0, |ids| ids.len()));
+
+        let node_id_set = source_nodes
+            .iter()
+            .map(|source_node| source_node.id)
+            .collect::<Set<Uuid>>();
+        let mut source_flows = vec![];
+        let mut source_flow_steps = vec![];
+
+        for node_id in &node_id_set {
+            let source_params = NodeBranchParams {
+                root_id: params.root_id,
+                branch_id: params.branch_id,
+                node_id: *node_id,
+            };
+
+            source_flows.extend(
+                Flow::branched(db_session, &source_params)
+                    .await?
+                    .into_iter()
+                    .filter(|flow| !deleted_ids.contains(&flow.id)),
+            );
+            source_flow_steps.extend(
+                FlowStep::branched(db_session, &source_params)
+                    .await?
+                    .into_iter()
+                    .filter(|flow_step| !deleted_ids.contains(&flow_step.id)),
+            );
+        }
+
+        let source_ios = Io::branched(
+            db_session,
+            &NodeBranchParams {
+                root_id: params.root_id,
+                branch_id: params.branch_id,
+                node_id: params.id,
+            },
+        )
+        .await?
+        .into_iter()
+        .filter(|io| node_id_set.contains(&io.node_id) && !deleted_ids.contains(&io.id))
+        .collect::<Vec<Io>>();
+
+        let source_initial_input_ids = Self::source_initial_input_ids(db_session, &params, &node_id_set).await?;
+
+        let mut object_ids = node_id_set.clone();
+        object_ids.extend(source_flows.iter().map(|flow| flow.id));
+        object_ids.extend(source_flow_steps.iter().map(|flow_step| flow_step.id));
+        object_ids.extend(source_ios.iter().map(|io| io.main_id.unwrap_or(io.id)));
+
+        let source_descriptions = Self::source_descriptions(db_session, &params, &object_ids).await?;
+        let source_attachments = Self::source_attachments(db_session, &params, &node_id_set).await?;
+
+        let mut node_clone = Self {
+            params,
+            clone_step: NodeCloneStep::Start,
+            nodes: vec![],
+            descendants: vec![],
+            workflows: vec![],
+            flows: vec![],
+            flow_steps: vec![],
+            ios: vec![],
+            descriptions: vec![],
+            attachments: vec![],
+            attachment_source_keys: HashMap::new(),
+        };
+
+        let id_map = Self::build_id_map(&source_nodes, &source_flows, &source_flow_steps, &source_ios);
+        let order_index = new_parent.next_child_order_index(db_session).await?;
+        let source_node_ids = source_nodes
+            .iter()
+            .map(|source_node| source_node.id)
+            .collect::<Vec<Uuid>>();
+
+        node_clone.build_nodes(data, &new_parent, source_nodes, &id_map, order_index);
+        node_clone.build_descendants()?;
+        node_clone.build_workflows(&source_node_ids, &source_initial_input_ids, &id_map);
+        node_clone.build_flows(source_flows, &id_map);
+        node_clone.build_flow_steps(source_flow_steps, &id_map);
+        node_clone.build_ios(source_ios, &id_map);
+
+        let urls = node_clone.build_attachments(data, source_attachments, &id_map);
+        node_clone.build_descriptions(source_descriptions, &id_map, &urls)?;
+
+        Ok(node_clone)
+    }
+
+    async fn source_nodes(
+        db_session: &CachingSession,
+        params: &CloneParams,
+        ids: &Vec<Uuid>,
+    ) -> Result<Vec<Node>, NodecosmosError> {
+        let mut nodes = Node::find_by_ids(db_session, params.original_id(), ids)
+            .await
+            .group_by_id()
+            .await?;
+
+        if params.is_branch() {
+            let branched_nodes = Node::find_by_ids(db_session, params.branch_id, ids)
+                .await
+                .group_by_id()
+                .await?;
+
+            nodes.extend(branched_nodes);
+        }
+
+        Ok(nodes.into_values().collect())
+    }
+
+    async fn source_initial_input_ids(
+        db_session: &CachingSession,
+        params: &CloneParams,
+        node_ids: &Set<Uuid>,
+    ) -> Result<HashMap<Uuid, List<Uuid>>, NodecosmosError> {
+        let node_ids = node_ids.iter().cloned().collect::<Vec<Uuid>>();
+        let mut initial_input_ids = HashMap::new();
+
+        let mut workflows: Vec<Workflow> = Workflow::find_by_node_ids(db_session, params.original_id(), &node_ids)
+            .await
+            .try_collect()
+            .await?;
+
+        if params.is_branch() {
+            workflows.extend(
+                Workflow::find_by_node_ids(db_session, params.branch_id, &node_ids)
+                    .await
+                    .try_collect()
+                    .await?,
+            );
+        }
+
+        // original workflows come first, so branched initial inputs are appended to the original ones
+        for workflow in workflows {
+            let ids: &mut List<Uuid> = initial_input_ids.entry(workflow.node_id).or_default();
+
+            for id in workflow.initial_input_ids.unwrap_or_default() {
+                if !ids.contains(&id) {
+                    ids.push(id);
+                }
+            }
+        }
+
+        Ok(initial_input_ids)
+    }
+
+    async fn source_descriptions(
+        db_session: &CachingSession,
+        params: &CloneParams,
+        object_ids: &Set<Uuid>,
+    ) -> Result<Vec<Description>, NodecosmosError> {
+        let mut descriptions = Description::find_by_branch_id_and_ids(db_session, params.original_id(), object_ids)
+            .await
+            .group_by_object_id()
+            .await?;
+
+        if params.is_branch() {
+            let branched_descriptions =
+                Description::find_by_branch_id_and_ids(db_session, params.branch_id, object_ids)
+                    .await
+                    .group_by_object_id()
+                    .await?;
+
+            descriptions.extend(branched_descriptions);
+        }
+
+        Ok(descriptions.into_values().collect())
+    }
+
+    async fn source_attachments(
+        db_session: &CachingSession,
+        params: &CloneParams,
+        node_ids: &Set<Uuid>,
+    ) -> Result<Vec<Attachment>, NodecosmosError> {
+        let node_ids = node_ids.iter().cloned().collect::<Vec<Uuid>>();
+        let mut attachments = Attachment::find_by_node_ids(db_session, params.original_id(), &node_ids).await?;
+
+        if params.is_branch() {
+            let mut attachment_ids = attachments.iter().map(|a| a.id).collect::<HashSet<Uuid>>();
+
+            for attachment in Attachment::find_by_node_ids(db_session, params.branch_id, &node_ids).await? {
+                if attachment_ids.insert(attachment.id) {
+                    attachments.push(attachment);
+                }
+            }
+        }
+
+        Ok(attachments)
+    }
+
+    fn build_id_map(nodes: &[Node], flows: &[Flow], flow_steps: &[FlowStep], ios: &[Io]) -> HashMap<Uuid, Uuid> {
+        let mut id_map = HashMap::new();
+
+        let source_ids = nodes
+            .iter()
+            .map(|node| node.id)
+            .chain(flows.iter().map(|flow| flow.id))
+            .chain(flow_steps.iter().map(|flow_step| flow_step.id))
+            .chain(ios.iter().map(|io| io.id));
+
+        for id in source_ids {
+            id_map.insert(id, Uuid::new_v4());
+        }
+
+        // If main io is not within the cloned subtree, the first cloned io that references it becomes the new main.
+        for io in ios {
+            let main_id = io.main_id.unwrap_or(io.id);
+
+            if !id_map.contains_key(&main_id) {
+                id_map.insert(main_id, id_map[&io.id]);
+            }
+        }
+
+        id_map
+    }
+
+    fn build_nodes(
+        &mut self,
+        data: &RequestData,
+        new_parent: &Node,
+        source_nodes: Vec<Node>,
+        id_map: &HashMap<Uuid, Uuid>,
+        order_index: f64,
+    ) {
+        let now = chrono::Utc::now();
+        let mut top_ancestor_ids = new_parent.ancestor_ids.clone().unwrap_or_default();
+        top_ancestor_ids.insert(new_parent.id);
+
+        for source_node in source_nodes {
+            let id = id_map[&source_node.id];
+            let is_top = source_node.id == self.params.id;
+            let mut ancestor_ids = top_ancestor_ids.clone();
+
+            if !is_top {
+                ancestor_ids.extend(
+                    source_node
+                        .ancestor_ids
+                        .iter()
+                        .flatten()
+                        .filter_map(|ancestor_id| id_map.get(ancestor_id)),
+                );
+            }
+
+            let parent_id = if is_top {
+                new_parent.id
+            } else {
+                source_node
+                    .parent_id
+                    .and_then(|parent_id| id_map.get(&parent_id).copied())
+                    .unwrap_or(new_parent.id)
+            };
+
+            self.nodes.push(Node {
+                branch_id: self.params.new_branch_id,
+                id,
+                root_id: self.params.new_root_id,
+                is_public: new_parent.is_public,
+                is_subscription_active: new_parent.is_subscription_active,
+                is_root: false,
+                order_index: if is_top { order_index } else { source_node.order_index },
+                owner_id: new_parent.owner_id,
+                creator_id: Some(data.current_user.id),
+                title: source_node.title,
+                parent_id: Some(parent_id),
+                ancestor_ids: Some(ancestor_ids),
+                owner: new_parent.owner.clone(),
+                creator: Some((&data.current_user).into()),
+                editor_ids: new_parent.editor_ids.clone(),
+                viewer_ids: new_parent.viewer_ids.clone(),
+                created_at: now,
+                updated_at: now,
+                ..Default::default()
+            });
+        }
+    }
+
+    fn build_descendants(&mut self) -> Result<(), NodecosmosError> {
+        for node in &self.nodes {
+            let parent_id = node.parent_id.ok_or_else(|| {
+                NodecosmosError::InternalServerError(format!("Cloned node {} has no parent", node.id))
+            })?;
+
+            for ancestor_id in node.ancestor_ids.iter().flatten() {
+                self.descendants.push(NodeDescendant {
+                    root_id: node.root_id,
+                    branch_id: node.branch_id,
+                    node_id: *ancestor_id,
+                    order_index: node.order_index,
+                    id: node.id,
+                    parent_id,
+                    title: node.title.clone(),
+                });
+            }
+        }
+
+        Ok(())
+    }
+
+    fn build_workflows(
+        &mut self,
+        source_node_ids: &[Uuid],
+        source_initial_input_ids: &HashMap<Uuid, List<Uuid>>,
+        id_map: &HashMap<Uuid, Uuid>,
+    ) {
+        let now = chrono::Utc::now();
+
+        for source_node_id in source_node_ids {
+            let initial_input_ids = source_initial_input_ids
+                .get(source_node_id)
+                .map(|ids| {
+                    ids.iter()
+                        .filter_map(|id| id_map.get(id).copied())
+                        .collect::<List<Uuid>>()
+                })
+                .filter(|ids| !ids.is_empty());
+
+            self.workflows.push(Workflow {
+                root_id: self.params.new_root_id,
+                node_id: id_map[source_node_id],
+                branch_id: self.params.new_branch_id,
+                title: Some("Flows".to_string()),
+                created_at: now,
+                updated_at: now,
+                initial_input_ids,
+                ctx: Default::default(),
+            });
+        }
+    }
+
+    fn build_flows(&mut self, source_flows: Vec<Flow>, id_map: &HashMap<Uuid, Uuid>) {
+        for mut flow in source_flows {
+            flow.id = id_map[&flow.id];
+            flow.node_id = id_map[&flow.node_id];
+            flow.root_id = self.params.new_root_id;
+            flow.branch_id = self.params.new_branch_id;
+
+            self.flows.push(flow);
+        }
+    }
+
+    fn build_flow_steps(&mut self, source_flow_steps: Vec<FlowStep>, id_map: &HashMap<Uuid, Uuid>) {
+        let map_ids = |ids: &List<Uuid>| {
+            ids.iter()
+                .filter_map(|id| id_map.get(id).copied())
+                .collect::<List<Uuid>>()
+        };
+        let map_ios_by_node = |ios_by_node: &HashMap<Uuid, List<Uuid>>| {
+            ios_by_node
+                .iter()
+                .filter_map(|(node_id, io_ids)| id_map.get(node_id).map(|node_id| (*node_id, map_ids(io_ids))))
+                .collect::<HashMap<Uuid, List<Uuid>>>()
+        };
+
+        for mut flow_step in source_flow_steps {
+            // flows of deleted nodes are not cloned, so we skip their steps as well
+            let flow_id = match id_map.get(&flow_step.flow_id) {
+                Some(flow_id) => *flow_id,
+                None => continue,
+            };
+
+            flow_step.id = id_map[&flow_step.id];
+            flow_step.flow_id = flow_id;
+            flow_step.node_id = id_map[&flow_step.node_id];
+            flow_step.root_id = self.params.new_root_id;
+            flow_step.branch_id = self.params.new_branch_id;
+            flow_step.node_ids = flow_step.node_ids.as_ref().map(map_ids);
+            flow_step.input_ids_by_node_id = flow_step.input_ids_by_node_id.as_ref().map(map_ios_by_node);
+            flow_step.output_ids_by_node_id = flow_step.output_ids_by_node_id.as_ref().map(map_ios_by_node);
+
+            self.flow_steps.push(flow_step);
+        }
+    }
+
+    fn build_ios(&mut self, source_ios: Vec<Io>, id_map: &HashMap<Uuid, Uuid>) {
+        let map_id = |id: Option<Uuid>| id.and_then(|id| id_map.get(&id).copied());
+
+        for mut io in source_ios {
+            io.id = id_map[&io.id];
+            io.node_id = id_map[&io.node_id];
+            io.root_id = self.params.new_root_id;
+            io.branch_id = self.params.new_branch_id;
+            io.main_id = Some(id_map[&io.main_id.unwrap_or(io.id)]);
+            io.flow_id = map_id(io.flow_id);
+            io.flow_step_id = map_id(io.flow_step_id);
+            io.flow_step_node_id = map_id(io.flow_step_node_id);
+            io.inputted_by_flow_steps = io.inputted_by_flow_steps.map(|ids| {
+                ids.iter()
+                    .filter_map(|id| id_map.get(id).copied())
+                    .collect::<Set<Uuid>>()
+            });
+
+            self.ios.push(io);
+        }
+    }
+
+    /// Returns map of source attachment url -> new attachment url that is used to update description images.
+    fn build_attachments(
+        &mut self,
+        data: &RequestData,
+        source_attachments: Vec<Attachment>,
+        id_map: &HashMap<Uuid, Uuid>,
+    ) -> HashMap<String, String> {
+        let mut urls = HashMap::new();
+
+        for source_attachment in source_attachments {
+            let (node_id, object_id) = match (
+                id_map.get(&source_attachment.node_id),
+                id_map.get(&source_attachment.object_id),
+            ) {
+                (Some(node_id), Some(object_id)) => (*node_id, *object_id),
+                _ => continue,
+            };
+
+            // s3 keys are scoped to object id
+            let filename = source_attachment
+                .key
+                .split_once('/')
+                .map_or(source_attachment.key.as_str(), |(_, filename)| filename);
+
+            let mut attachment = Attachment {
+                node_id,
+                branch_id: self.params.new_branch_id,
+                object_id,
+                root_id: self.params.new_root_id,
+                id: Uuid::new_v4(),
+                key: format!("{}/{}", object_id, filename),
+                url: None,
+                user_id: source_attachment.user_id,
+                created_at: chrono::Utc::now(),
+                updated_at: chrono::Utc::now(),
+            };
+            attachment.url = Some(attachment.s3_url(data));
+
+            if let (Some(source_url), Some(url)) = (source_attachment.url, attachment.url.clone()) {
+                urls.insert(source_url, url);
+            }
+
+            self.attachment_source_keys.insert(attachment.id, source_attachment.key);
+            self.attachments.push(attachment);
+        }
+
+        urls
+    }
+
+    fn build_descriptions(
+        &mut self,
+        source_descriptions: Vec<Description>,
+        id_map: &HashMap<Uuid, Uuid>,
+        urls: &HashMap<String, String>,
+    ) -> Result<(), NodecosmosError> {
+        for mut description in source_descriptions {
+            let (object_id, node_id) = match (id_map.get(&description.object_id), id_map.get(&description.node_id)) {
+                (Some(object_id), Some(node_id)) => (*object_id, *node_id),
+                _ => continue,
+            };
+
+            description.object_id = object_id;
+            description.node_id = node_id;
+            description.root_id = self.params.new_root_id;
+            description.branch_id = self.params.new_branch_id;
+            description.updated_at = chrono::Utc::now();
+            description.replace_image_urls(urls)?;
+
+            self.descriptions.push(description);
+        }
+
+        Ok(())
+    }
+
+    fn top_node(&self) -> Result<&Node, NodecosmosError> {
+        self.nodes.first().ok_or_else(|| {
+            NodecosmosError::InternalServerError(format!("Clone of node {} has no top node", self.params.id))
+        })
+    }
+
+    pub async fn run(&mut self, data: &RequestData) -> Result<Node, NodecosmosError> {
+        // branched ancestors of the new parent are required for the merge conflict resolution
+        self.top_node()?.preserve_branch_ancestors(data).await?;
+
+        let clone = self.clone_subtree(data).await;
+
+        if let Err(e) = clone {
+            log::error!("Clone failed for node: {}\n! ERROR: {:?}", self.params.id, e);
+
+            self.recover(data)
+                .await
+                .map_err(|e| NodecosmosError::FatalCloneError(format!("Error cloning node: {:?}", e)))?;
+
+            return Err(e);
+        }
+
+        Ok(self.top_node()?.clone())
+    }
+
+    pub async fn clone_subtree(&mut self, data: &RequestData) -> Result<(), NodecosmosError> {
+        self.run_saga(data).await
+    }
+
+    pub async fn recover(&mut self, data: &RequestData) -> Result<(), NodecosmosError> {
+        self.compensate_saga(data).await
+    }
+
+    async fn insert_nodes(&self, db_session: &CachingSession) -> Result<(), NodecosmosError> {
+        Node::unlogged_batch()
+            .chunked_insert(db_session, &self.nodes, crate::constants::BATCH_CHUNK_SIZE)
+            .await
+            .map_err(NodecosmosError::from)
+    }
+
+    async fn undo_insert_nodes(&self, db_session: &CachingSession) -> Result<(), NodecosmosError> {
+        Node::unlogged_batch()
+            .chunked_delete(db_session, &self.nodes, crate::constants::BATCH_CHUNK_SIZE)
+            .await
+            .map_err(NodecosmosError::from)
+    }
+
+    async fn insert_descendants(&self, db_session: &CachingSession) -> Result<(), NodecosmosError> {
+        NodeDescendant::unlogged_batch()
+            .chunked_insert(db_session, &self.descendants, crate::constants::BATCH_CHUNK_SIZE)
+            .await
+            .map_err(NodecosmosError::from)
+    }
+
+    async fn undo_insert_descendants(&self, db_session: &CachingSession) -> Result<(), NodecosmosError> {
+        NodeDescendant::unlogged_batch()
+            .chunked_delete(db_session, &self.descendants, crate::constants::BATCH_CHUNK_SIZE)
+            .await
+            .map_err(NodecosmosError::from)
+    }
+
+    async fn insert_workflows(&self, db_session: &CachingSession) -> Result<(), NodecosmosError> {
+        Workflow::unlogged_batch()
+            .chunked_insert(db_session, &self.workflows, crate::constants::BATCH_CHUNK_SIZE)
+            .await
+            .map_err(NodecosmosError::from)
+    }
+
+    async fn undo_insert_workflows(&self, db_session: &CachingSession) -> Result<(), NodecosmosError> {
+        Workflow::unlogged_batch()
+            .chunked_delete(db_session, &self.workflows, crate::constants::BATCH_CHUNK_SIZE)
+            .await
+            .map_err(NodecosmosError::from)
+    }
+
+    async fn insert_flows(&self, db_session: &CachingSession) -> Result<(), NodecosmosError> {
+        Flow::unlogged_batch()
+            .chunked_insert(db_session, &self.flows, crate::constants::BATCH_CHUNK_SIZE)
+            .await
+            .map_err(NodecosmosError::from)
+    }
+
+    async fn undo_insert_flows(&self, db_session: &CachingSession) -> Result<(), NodecosmosError> {
+        Flow::unlogged_batch()
+            .chunked_delete(db_session, &self.flows, crate::constants::BATCH_CHUNK_SIZE)
+            .await
+            .map_err(NodecosmosError::from)
+    }
+
+    async fn insert_flow_steps(&self, db_session: &CachingSession) -> Result<(), NodecosmosError> {
+        FlowStep::unlogged_batch()
+            .chunked_insert(db_session, &self.flow_steps, crate::constants::BATCH_CHUNK_SIZE)
+            .await
+            .map_err(NodecosmosError::from)
+    }
+
+    async fn undo_insert_flow_steps(&self, db_session: &CachingSession) -> Result<(), NodecosmosError> {
+        FlowStep::unlogged_batch()
+            .chunked_delete(db_session, &self.flow_steps, crate::constants::BATCH_CHUNK_SIZE)
+            .await
+            .map_err(NodecosmosError::from)
+    }
+
+    async fn insert_ios(&self, db_session: &CachingSession) -> Result<(), NodecosmosError> {
+        Io::unlogged_batch()
+            .chunked_insert(db_session, &self.ios, crate::constants::BATCH_CHUNK_SIZE)
+            .await
+            .map_err(NodecosmosError::from)
+    }
+
+    async fn undo_insert_ios(&self, db_session: &CachingSession) -> Result<(), NodecosmosError> {
+        Io::unlogged_batch()
+            .chunked_delete(db_session, &self.ios, crate::constants::BATCH_CHUNK_SIZE)
+            .await
+            .map_err(NodecosmosError::from)
+    }
+
+    async fn insert_descriptions(&self, db_session: &CachingSession) -> Result<(), NodecosmosError> {
+        Description::unlogged_batch()
+            .chunked_insert(db_session, &self.descriptions, crate::constants::BATCH_CHUNK_SIZE)
+            .await
+            .map_err(NodecosmosError::from)
+    }
+
+    async fn undo_insert_descriptions(&self, db_session: &CachingSession) -> Result<(), NodecosmosError> {
+        Description::unlogged_batch()
+            .chunked_delete(db_session, &self.descriptions, crate::constants::BATCH_CHUNK_SIZE)
+            .await
+            .map_err(NodecosmosError::from)
+    }
+
+    async fn insert_attachments(&self, data: &RequestData) -> Result<(), NodecosmosError> {
+        for attachment in &self.attachments {
+            if let Some(source_key) = self.attachment_source_keys.get(&attachment.id) {
+                attachment.copy_s3_object(data, source_key).await?;
+            }
+        }
+
+        Attachment::unlogged_batch()
+            .chunked_insert(data.db_session(), &self.attachments, crate::constants::BATCH_CHUNK_SIZE)
+            .await
+            .map_err(NodecosmosError::from)
+    }
+
+    async fn undo_insert_attachments(&self, data: &RequestData) -> Result<(), NodecosmosError> {
+        Attachment::unlogged_batch()
+            .chunked_delete(data.db_session(), &self.attachments, crate::constants::BATCH_CHUNK_SIZE)
+            .await?;
+
+        Attachment::delete_s3_objects(data, &self.attachments).await;
+
+        Ok(())
+    }
+
+    async fn update_branch(&self, data: &RequestData) -> Result<(), NodecosmosError> {
+        let branch_id = self.params.new_branch_id;
+
+        if branch_id == self.params.new_root_id {
+            return Ok(());
+        }
+
+        for node in &self.nodes {
+            Branch::update(
+                data,
+                branch_id,
+                node.id,
+                BranchUpdate::CreateNode((node.id, node.ancestor_ids.clone().unwrap_or_default())),
+            )
+            .await?;
+        }
+
+        for workflow in &self.workflows {
+            if let Some(initial_input_ids) = workflow.initial_input_ids.as_ref().filter(|ids| !ids.is_empty()) {
+                Branch::update(
+                    data,
+                    branch_id,
+                    workflow.node_id,
+                    BranchUpdate::CreateWorkflowInitialInputs(initial_input_ids.iter().copied().collect()),
+                )
+                .await?;
+            }
+        }
+
+        for flow in &self.flows {
+            Branch::update(data, branch_id, flow.node_id, BranchUpdate::CreateFlow(flow.id)).await?;
+        }
+
+        for flow_step in &self.flow_steps {
+            Branch::update(
+                data,
+                branch_id,
+                flow_step.node_id,
+                BranchUpdate::CreateFlowStep(flow_step.id),
+            )
+            .await?;
+        }
+
+        for io in &self.ios {
+            Branch::update(data, branch_id, io.node_id, BranchUpdate::CreateIo(io.id)).await?;
+        }
+
+        for description in &self.descriptions {
+            let update = match description.object_type.parse::<ObjectType>()? {
+                ObjectType::Node => BranchUpdate::EditNodeDescription(description.object_id),
+                ObjectType::Flow => BranchUpdate::EditFlowDescription(description.object_id),
+                ObjectType::FlowStep => BranchUpdate::EditFlowStepDescription(description.object_id),
+                ObjectType::Io => BranchUpdate::EditIoDescription(description.object_id),
+                ObjectType::Workflow => continue,
+            };
+
+            Branch::update(data, branch_id, description.node_id, update).await?;
+        }
+
+        Ok(())
+    }
+
+    async fn undo_update_branch(&self, db_session: &CachingSession) -> Result<(), NodecosmosError> {
+        let branch_id = self.params.new_branch_id;
+
+        if branch_id == self.params.new_root_id {
+            return Ok(());
+        }
+
+        let description_ids = |object_type: ObjectType| {
+            self.descriptions
+                .iter()
+                .filter(|description| description.object_type == object_type.to_string())
+                .map(|description| description.object_id)
+                .collect::<Vec<Uuid>>()
+        };
+
+        let node_params = (self.nodes.iter().map(|node| node.id).collect::<Vec<Uuid>>(), branch_id);
+        let initial_input_params = (
+            self.workflows
+                .iter()
+                .flat_map(|workflow| workflow.initial_input_ids.iter().flatten().copied())
+                .collect::<Vec<Uuid>>(),
+            branch_id,
+        );
+        let flow_params = (self.flows.iter().map(|flow| flow.id).collect::<Vec<Uuid>>(), branch_id);
+        let flow_step_params = (
+            self.flow_steps
+                .iter()
+                .map(|flow_step| flow_step.id)
+                .collect::<Vec<Uuid>>(),
+            branch_id,
+        );
+        let io_params = (self.ios.iter().map(|io| io.id).collect::<Vec<Uuid>>(), branch_id);
+        let node_description_params = (description_ids(ObjectType::Node), branch_id);
+        let flow_description_params = (description_ids(ObjectType::Flow), branch_id);
+        let flow_step_description_params = (description_ids(ObjectType::FlowStep), branch_id);
+        let io_description_params = (description_ids(ObjectType::Io), branch_id);
+
+        let mut batch: CharybdisModelBatch<&(Vec<Uuid>, Uuid), Branch> = CharybdisModelBatch::new();
+
+        batch
+            .append_statement(UpdateCreatedNodesBranch::PULL_CREATED_NODES_QUERY, &node_params)
+            .append_statement(UpdateEditedNodesBranch::PULL_EDITED_NODES_QUERY, &node_params)
+            .append_statement(
+                UpdateCreateWorkflowInitialInputsBranch::PULL_CREATED_INITIAL_INPUTS_QUERY,
+                &initial_input_params,
+            )
+            .append_statement(UpdateCreatedFlowsBranch::PULL_CREATED_FLOWS_QUERY, &flow_params)
+            .append_statement(
+                UpdateCreatedFlowStepsBranch::PULL_CREATED_FLOW_STEPS_QUERY,
+                &flow_step_params,
+            )
+            .append_statement(UpdateCreatedIosBranch::PULL_CREATED_IOS_QUERY, &io_params)
+            .append_statement(
+                UpdateEditedDescriptionNodesBranch::PULL_EDITED_DESCRIPTION_NODES_QUERY,
+                &node_description_params,
+            )
+            .append_statement(
+                UpdateEditedFlowDescriptionBranch::PULL_EDITED_DESCRIPTION_FLOWS_QUERY,
+                &flow_description_params,
+            )
+            .append_statement(
+                UpdateEditedDescriptionFlowStepsBranch::PULL_EDITED_DESCRIPTION_FLOW_STEPS_QUERY,
+                &flow_step_description_params,
+            )
+            .append_statement(
+                UpdateEditedDescriptionIosBranch::PULL_EDITED_DESCRIPTION_IOS_QUERY,
+                &io_description_params,
+            )
+            .execute(db_session)
+            .await?;
+
+        Ok(())
+    }
+
+    async fn insert_elastic_data(&self, data: &RequestData) {
+        if self.params.new_branch_id == self.params.new_root_id {
+            let _ = Node::bulk_insert_elastic_documents(data.elastic_client(), &self.nodes).await;
+        }
+    }
+
+    async fn undo_insert_elastic_data(&self, data: &RequestData) {
+        if self.params.new_branch_id == self.params.new_root_id {
+            let ids = self.nodes.iter().map(|node| node.id).collect::<Vec<Uuid>>();
+
+            let _ = Node::bulk_delete_elastic_documents(data.elastic_client(), &ids).await;
+        }
+    }
+}
+
+impl Node {
+    pub async fn clone_subtree(data: &RequestData, params: CloneParams) -> Result<Node, NodecosmosError> {
+        NodeClone::new(data, params).await?.run(data).await
+    }
+}
+
+impl RecoveryLog<'_> for NodeClone {
+    // clone is not run without the top node, so the source id is never used in practice
+    fn rec_id(&self) -> Uuid {
+        self.top_node().map_or(self.params.id, |node| node.id)
+    }
+
+    fn rec_branch_id(&self) -> Uuid {
+        self.params.new_branch_id
+    }
+
+    fn rec_object_type(&self) -> RecoveryObjectType {
+        RecoveryObjectType::NodeClone
+    }
+
+    fn set_step(&mut self, step: i8) -> Result<(), NodecosmosError> {
+        self.clone_step = NodeCloneStep::try_from_value(step)?;
+
+        Ok(())
+    }
+
+    async fn recover_from_log(&mut self, data: &RequestData) -> Result<(), NodecosmosError> {
+        self.recover(data).await.map_err(|e| {
+            log::error!("FatalCloneError recovering from log: {:?}", e);
+            NodecosmosError::FatalCloneError(format!("Error recovering from log: {:?}", e))
+        })
+    }
+}
+
+impl Saga for NodeClone {
+    type Step = NodeCloneStep;
+
+    fn step(&self) -> NodeCloneStep {
+        self.clone_step
+    }
+
+    fn set_saga_step(&mut self, step: NodeCloneStep) {
+        self.clone_step = step;
+    }
+
+    async fn execute(&mut self, data: &RequestData, step: NodeCloneStep) -> Result<(), NodecosmosError> {
+        match step {
+            NodeCloneStep::InsertNodes => self.insert_nodes(data.db_session()).await?,
+            NodeCloneStep::InsertDescendants => self.insert_descendants(data.db_session()).await?,
+            NodeCloneStep::InsertWorkflows => self.insert_workflows(data.db_session()).await?,
+            NodeCloneStep::InsertFlows => self.insert_flows(data.db_session()).await?,
+            NodeCloneStep::InsertFlowSteps => self.insert_flow_steps(data.db_session()).await?,
+            NodeCloneStep::InsertIos => self.insert_ios(data.db_session()).await?,
+            NodeCloneStep::InsertDescriptions => self.insert_descriptions(data.db_session()).await?,
+            NodeCloneStep::InsertAttachments => self.insert_attachments(data).await?,
+            NodeCloneStep::UpdateBranch => self.update_branch(data).await?,
+            NodeCloneStep::InsertElasticData => self.insert_elastic_data(data).await,
+            // log and placeholder steps are handled by the saga executor
+            NodeCloneStep::BeforeStart | NodeCloneStep::Start | NodeCloneStep::Finish | NodeCloneStep::AfterFinish => {}
+        }
+
+        Ok(())
+    }
+
+    async fn compensate(&mut self, data: &RequestData, step: NodeCloneStep) -> Result<(), NodecosmosError> {
+        match step {
+            NodeCloneStep::InsertNodes => self.undo_insert_nodes(data.db_session()).await?,
+            NodeCloneStep::InsertDescendants => self.undo_insert_descendants(data.db_session()).await?,
+            NodeCloneStep::InsertWorkflows => self.undo_insert_workflows(data.db_session()).await?,
+            NodeCloneStep::InsertFlows => self.undo_insert_flows(data.db_session()).await?,
+            NodeCloneStep::InsertFlowSteps => self.undo_insert_flow_steps(data.db_session()).await?,
+            NodeCloneStep::InsertIos => self.undo_insert_ios(data.db_session()).await?,
+            NodeCloneStep::InsertDescriptions => self.undo_insert_descriptions(data.db_session()).await?,
+            NodeCloneStep::InsertAttachments => self.undo_insert_attachments(data).await?,
+            NodeCloneStep::UpdateBranch => self.undo_update_branch(data.db_session()).await?,
+            NodeCloneStep::InsertElasticData => self.undo_insert_elastic_data(data).await,
+            // log and placeholder steps are handled by the saga executor
+            NodeCloneStep::BeforeStart | NodeCloneStep::Start | NodeCloneStep::Finish | NodeCloneStep::AfterFinish => {}
+        }
+
+        Ok(())
+    }
+}
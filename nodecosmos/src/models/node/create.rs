@@ -363,8 +363,9 @@ impl Node {
     pub async fn update_branch_with_creation(&self, data: &RequestData) -> Result<(), NodecosmosError> {
         if self.is_branch() {
             Branch::update(
-                data.db_session(),
+                data,
                 self.branch_id,
+                self.id,
                 BranchUpdate::CreateNode((self.id, self.ancestor_ids.clone().unwrap_or_default())),
             )
             .await?;
@@ -16,6 +16,7 @@ use crate::models::attachment::{Attachment, AttachmentsDelete};
 use crate::models::branch::update::BranchUpdate;
 use crate::models::branch::Branch;
 use crate::models::description::Description;
+use crate::models::description_version::DescriptionVersion;
 use crate::models::flow::Flow;
 use crate::models::flow_step::FlowStep;
 use crate::models::io::Io;
@@ -23,6 +24,7 @@ use crate::models::like::Like;
 use crate::models::node::Node;
 use crate::models::node_descendant::NodeDescendant;
 use crate::models::recovery::{RecoveryLog, RecoveryObjectType};
+use crate::models::saga::{saga_steps, Saga, SagaStep};
 use crate::models::traits::{Branchable, ElasticDocument, ModelContext, Pluck};
 use crate::models::traits::{Descendants, FindForBranchMerge};
 use crate::models::workflow::Workflow;
@@ -57,7 +59,7 @@ impl Node {
                 .pluck_id();
             node_ids.extend(descendant_ids);
 
-            Branch::update(data.db_session(), self.branch_id, BranchUpdate::DeleteNodes(node_ids)).await?;
+            Branch::update(data, self.branch_id, self.id, BranchUpdate::DeleteNodes(node_ids)).await?;
         }
 
         Ok(())
@@ -65,66 +67,27 @@ impl Node {
 }
 
 // we don't delete counter data as counter row can not be recreated
-#[derive(Clone, Copy, Default, Serialize, Deserialize, PartialOrd, PartialEq, Debug)]
-pub enum NodeDeleteStep {
-    BeforeStart = -1,
-    #[default]
-    Start = 0,
-    ArchiveNodes = 1,
-    ArchiveWorkflows = 2,
-    ArchiveFlows = 3,
-    ArchiveFlowSteps = 4,
-    ArchiveIos = 5,
-    ArchiveDescriptions = 6,
-    DeleteNodes = 7,
-    DeleteDescendants = 8,
-    DeleteWorkflows = 9,
-    DeleteFlows = 10,
-    DeleteFlowSteps = 11,
-    DeleteIos = 12,
-    DeleteDescriptions = 13,
-    DeleteLikes = 14,
-    DeleteAttachments = 15,
-    DeleteElasticData = 16,
-    Finish = 17,
-    AfterFinish = 18,
-}
-
-impl NodeDeleteStep {
-    pub fn increment(&mut self) {
-        *self = NodeDeleteStep::from(*self as i8 + 1);
-    }
-
-    pub fn decrement(&mut self) {
-        *self = NodeDeleteStep::from(*self as i8 - 1);
-    }
-}
-
-impl From<i8> for NodeDeleteStep {
-    fn from(value: i8) -> Self {
-        match value {
-            -1 => NodeDeleteStep::BeforeStart,
-            0 => NodeDeleteStep::Start,
-            1 => NodeDeleteStep::ArchiveNodes,
-            2 => NodeDeleteStep::ArchiveWorkflows,
-            3 => NodeDeleteStep::ArchiveFlows,
-            4 => NodeDeleteStep::ArchiveFlowSteps,
-            5 => NodeDeleteStep::ArchiveIos,
-            6 => NodeDeleteStep::ArchiveDescriptions,
-            7 => NodeDeleteStep::DeleteNodes,
-            8 => NodeDeleteStep::DeleteDescendants,
-            9 => NodeDeleteStep::DeleteWorkflows,
-            10 => NodeDeleteStep::DeleteFlows,
-            11 => NodeDeleteStep::DeleteFlowSteps,
-            12 => NodeDeleteStep::DeleteIos,
-            13 => NodeDeleteStep::DeleteDescriptions,
-            14 => NodeDeleteStep::DeleteLikes,
-            15 => NodeDeleteStep::DeleteAttachments,
-            16 => NodeDeleteStep::DeleteElasticData,
-            17 => NodeDeleteStep::Finish,
-            18 => NodeDeleteStep::AfterFinish,
-            _ => panic!("Invalid NodeDeleteStep value: {}", value),
-        }
+saga_steps! {
+    pub enum NodeDeleteStep {
+        ArchiveNodes = 1,
+        ArchiveWorkflows = 2,
+        ArchiveFlows = 3,
+        ArchiveFlowSteps = 4,
+        ArchiveIos = 5,
+        ArchiveDescriptions = 6,
+        DeleteNodes = 7,
+        DeleteDescendants = 8,
+        DeleteWorkflows = 9,
+        DeleteFlows = 10,
+        DeleteFlowSteps = 11,
+        DeleteIos = 12,
+        DeleteDescriptions = 13,
+        DeleteLikes = 14,
+        DeleteAttachments = 15,
+        DeleteElasticData = 16,
+        DeleteDescriptionVersions = 17,
+        Finish = 18,
+        AfterFinish = 19,
     }
 }
 
@@ -303,93 +266,11 @@ impl NodeDelete {
     }
 
     pub async fn delete(&mut self, data: &RequestData) -> Result<(), NodecosmosError> {
-        while self.delete_step <= NodeDeleteStep::Finish {
-            // log current step
-            if self.delete_step > NodeDeleteStep::Start && self.delete_step < NodeDeleteStep::Finish {
-                self.update_recovery_log_step(data.db_session(), self.delete_step as i8)
-                    .await?;
-            }
-
-            match self.delete_step {
-                NodeDeleteStep::BeforeStart => {
-                    log::error!("should not hit before placeholder");
-                }
-                NodeDeleteStep::Start => {
-                    self.create_recovery_log(data.db_session()).await?;
-                }
-                NodeDeleteStep::ArchiveNodes => self.archive_nodes(data.db_session()).await?,
-                NodeDeleteStep::ArchiveWorkflows => self.archive_workflows(data.db_session()).await?,
-                NodeDeleteStep::ArchiveFlows => self.archive_flows(data.db_session()).await?,
-                NodeDeleteStep::ArchiveFlowSteps => self.archive_flow_steps(data.db_session()).await?,
-                NodeDeleteStep::ArchiveIos => self.archive_ios(data.db_session()).await?,
-                NodeDeleteStep::ArchiveDescriptions => self.archive_descriptions(data.db_session()).await?,
-                NodeDeleteStep::DeleteNodes => self.delete_nodes(data.db_session()).await?,
-                NodeDeleteStep::DeleteDescendants => self.delete_descendants(data.db_session()).await?,
-                NodeDeleteStep::DeleteWorkflows => self.delete_workflows(data.db_session()).await?,
-                NodeDeleteStep::DeleteFlows => self.delete_flows(data.db_session()).await?,
-                NodeDeleteStep::DeleteFlowSteps => self.delete_flow_steps(data.db_session()).await?,
-                NodeDeleteStep::DeleteIos => self.delete_ios(data.db_session()).await?,
-                NodeDeleteStep::DeleteDescriptions => self.delete_descriptions(data.db_session()).await?,
-                NodeDeleteStep::DeleteLikes => self.delete_likes(data.db_session()).await?,
-                NodeDeleteStep::DeleteAttachments => self.delete_attachments(data).await?,
-                NodeDeleteStep::DeleteElasticData => self.delete_elastic_data(data).await,
-                NodeDeleteStep::Finish => {
-                    self.delete_recovery_log(data.db_session()).await?;
-                }
-                NodeDeleteStep::AfterFinish => {
-                    log::error!("should not hit after placeholder");
-                }
-            }
-
-            self.delete_step.increment();
-        }
-
-        Ok(())
+        self.run_saga(data).await
     }
 
     pub async fn recover(&mut self, data: &RequestData) -> Result<(), NodecosmosError> {
-        while self.delete_step >= NodeDeleteStep::Start {
-            // log current step
-            if self.delete_step > NodeDeleteStep::Start && self.delete_step < NodeDeleteStep::Finish {
-                self.update_recovery_log_step(data.db_session(), self.delete_step as i8)
-                    .await?;
-            }
-
-            match self.delete_step {
-                NodeDeleteStep::BeforeStart => {
-                    log::error!("should not hit before placeholder");
-                }
-                NodeDeleteStep::Start => {
-                    self.delete_recovery_log(data.db_session()).await?;
-                }
-                NodeDeleteStep::ArchiveNodes => self.undo_archive_nodes(data.db_session()).await?,
-                NodeDeleteStep::ArchiveWorkflows => self.undo_archive_workflows(data.db_session()).await?,
-                NodeDeleteStep::ArchiveFlows => self.undo_archive_flows(data.db_session()).await?,
-                NodeDeleteStep::ArchiveFlowSteps => self.undo_archive_flow_steps(data.db_session()).await?,
-                NodeDeleteStep::ArchiveIos => self.undo_archive_ios(data.db_session()).await?,
-                NodeDeleteStep::ArchiveDescriptions => self.undo_archive_descriptions(data.db_session()).await?,
-                NodeDeleteStep::DeleteNodes => self.undo_delete_nodes(data.db_session()).await?,
-                NodeDeleteStep::DeleteDescendants => self.undo_delete_descendants(data.db_session()).await?,
-                NodeDeleteStep::DeleteWorkflows => self.undo_delete_workflows(data.db_session()).await?,
-                NodeDeleteStep::DeleteFlows => self.undo_delete_flows(data.db_session()).await?,
-                NodeDeleteStep::DeleteFlowSteps => self.undo_delete_flow_steps(data.db_session()).await?,
-                NodeDeleteStep::DeleteIos => self.undo_delete_ios(data.db_session()).await?,
-                NodeDeleteStep::DeleteDescriptions => self.undo_delete_descriptions(data.db_session()).await?,
-                NodeDeleteStep::DeleteLikes => self.undo_delete_likes().await?,
-                NodeDeleteStep::DeleteAttachments => self.undo_delete_attachments().await?,
-                NodeDeleteStep::DeleteElasticData => self.undo_delete_elastic_data(data).await,
-                NodeDeleteStep::Finish => {
-                    log::error!("should not recover finished process");
-                }
-                NodeDeleteStep::AfterFinish => {
-                    log::error!("should not hit after placeholder");
-                }
-            }
-
-            self.delete_step.decrement();
-        }
-
-        Ok(())
+        self.compensate_saga(data).await
     }
 
     async fn archive_nodes(&self, db_session: &CachingSession) -> Result<(), NodecosmosError> {
@@ -719,6 +600,21 @@ impl NodeDelete {
     pub async fn undo_delete_attachments(&mut self) -> Result<(), NodecosmosError> {
         Ok(())
     }
+
+    async fn delete_description_versions(&self, db_session: &CachingSession) -> Result<(), NodecosmosError> {
+        let object_ids = self
+            .deleted_descriptions
+            .iter()
+            .map(|description| description.object_id)
+            .collect();
+
+        DescriptionVersion::delete_branch_versions(db_session, self.node.branch_id, object_ids).await
+    }
+
+    // versions are history of the archived descriptions, so as likes they are not restored
+    async fn undo_delete_description_versions(&self) -> Result<(), NodecosmosError> {
+        Ok(())
+    }
 }
 
 impl RecoveryLog<'_> for NodeDelete {
@@ -734,8 +630,10 @@ impl RecoveryLog<'_> for NodeDelete {
         RecoveryObjectType::NodeDelete
     }
 
-    fn set_step(&mut self, step: i8) {
-        self.delete_step = NodeDeleteStep::from(step);
+    fn set_step(&mut self, step: i8) -> Result<(), NodecosmosError> {
+        self.delete_step = NodeDeleteStep::try_from_value(step)?;
+
+        Ok(())
     }
 
     async fn recover_from_log(&mut self, data: &RequestData) -> Result<(), NodecosmosError> {
@@ -745,3 +643,73 @@ impl RecoveryLog<'_> for NodeDelete {
         })
     }
 }
+
+impl Saga for NodeDelete {
+    type Step = NodeDeleteStep;
+
+    fn step(&self) -> NodeDeleteStep {
+        self.delete_step
+    }
+
+    fn set_saga_step(&mut self, step: NodeDeleteStep) {
+        self.delete_step = step;
+    }
+
+    async fn execute(&mut self, data: &RequestData, step: NodeDeleteStep) -> Result<(), NodecosmosError> {
+        match step {
+            NodeDeleteStep::ArchiveNodes => self.archive_nodes(data.db_session()).await?,
+            NodeDeleteStep::ArchiveWorkflows => self.archive_workflows(data.db_session()).await?,
+            NodeDeleteStep::ArchiveFlows => self.archive_flows(data.db_session()).await?,
+            NodeDeleteStep::ArchiveFlowSteps => self.archive_flow_steps(data.db_session()).await?,
+            NodeDeleteStep::ArchiveIos => self.archive_ios(data.db_session()).await?,
+            NodeDeleteStep::ArchiveDescriptions => self.archive_descriptions(data.db_session()).await?,
+            NodeDeleteStep::DeleteNodes => self.delete_nodes(data.db_session()).await?,
+            NodeDeleteStep::DeleteDescendants => self.delete_descendants(data.db_session()).await?,
+            NodeDeleteStep::DeleteWorkflows => self.delete_workflows(data.db_session()).await?,
+            NodeDeleteStep::DeleteFlows => self.delete_flows(data.db_session()).await?,
+            NodeDeleteStep::DeleteFlowSteps => self.delete_flow_steps(data.db_session()).await?,
+            NodeDeleteStep::DeleteIos => self.delete_ios(data.db_session()).await?,
+            NodeDeleteStep::DeleteDescriptions => self.delete_descriptions(data.db_session()).await?,
+            NodeDeleteStep::DeleteLikes => self.delete_likes(data.db_session()).await?,
+            NodeDeleteStep::DeleteAttachments => self.delete_attachments(data).await?,
+            NodeDeleteStep::DeleteElasticData => self.delete_elastic_data(data).await,
+            NodeDeleteStep::DeleteDescriptionVersions => self.delete_description_versions(data.db_session()).await?,
+            // log and placeholder steps are handled by the saga executor
+            NodeDeleteStep::BeforeStart
+            | NodeDeleteStep::Start
+            | NodeDeleteStep::Finish
+            | NodeDeleteStep::AfterFinish => (),
+        }
+
+        Ok(())
+    }
+
+    async fn compensate(&mut self, data: &RequestData, step: NodeDeleteStep) -> Result<(), NodecosmosError> {
+        match step {
+            NodeDeleteStep::ArchiveNodes => self.undo_archive_nodes(data.db_session()).await?,
+            NodeDeleteStep::ArchiveWorkflows => self.undo_archive_workflows(data.db_session()).await?,
+            NodeDeleteStep::ArchiveFlows => self.undo_archive_flows(data.db_session()).await?,
+            NodeDeleteStep::ArchiveFlowSteps => self.undo_archive_flow_steps(data.db_session()).await?,
+            NodeDeleteStep::ArchiveIos => self.undo_archive_ios(data.db_session()).await?,
+            NodeDeleteStep::ArchiveDescriptions => self.undo_archive_descriptions(data.db_session()).await?,
+            NodeDeleteStep::DeleteNodes => self.undo_delete_nodes(data.db_session()).await?,
+            NodeDeleteStep::DeleteDescendants => self.undo_delete_descendants(data.db_session()).await?,
+            NodeDeleteStep::DeleteWorkflows => self.undo_delete_workflows(data.db_session()).await?,
+            NodeDeleteStep::DeleteFlows => self.undo_delete_flows(data.db_session()).await?,
+            NodeDeleteStep::DeleteFlowSteps => self.undo_delete_flow_steps(data.db_session()).await?,
+            NodeDeleteStep::DeleteIos => self.undo_delete_ios(data.db_session()).await?,
+            NodeDeleteStep::DeleteDescriptions => self.undo_delete_descriptions(data.db_session()).await?,
+            NodeDeleteStep::DeleteLikes => self.undo_delete_likes().await?,
+            NodeDeleteStep::DeleteAttachments => self.undo_delete_attachments().await?,
+            NodeDeleteStep::DeleteElasticData => self.undo_delete_elastic_data(data).await,
+            NodeDeleteStep::DeleteDescriptionVersions => self.undo_delete_description_versions().await?,
+            // log and placeholder steps are handled by the saga executor
+            NodeDeleteStep::BeforeStart
+            | NodeDeleteStep::Start
+            | NodeDeleteStep::Finish
+            | NodeDeleteStep::AfterFinish => (),
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,586 @@
+use crate::errors::NodecosmosError;
+use crate::models::branch::Branch;
+use crate::models::description::Description;
+use crate::models::flow::Flow;
+use crate::models::flow_step::FlowStep;
+use crate::models::io::Io;
+use crate::models::node::import::TMP_ROOT_ID;
+use crate::models::node::Node;
+use crate::models::node_descendant::NodeDescendant;
+use crate::models::traits::{Branchable, Descendants, FindForBranchMerge, GroupByObjectId, NodeBranchParams};
+use charybdis::types::Uuid;
+use scylla::client::caching_session::CachingSession;
+use serde::Serialize;
+use std::collections::{HashMap, HashSet};
+
+/// Export structures mirror the `import` ones, so the exported file can be imported as it is.
+/// Real ids are used as tmp ids and descriptions are serialized to `<description>` xml.
+#[derive(Serialize)]
+pub struct ExportIo {
+    pub id: String,
+    pub title: String,
+    pub description: Option<String>,
+}
+
+#[derive(Serialize)]
+pub struct ExportFlowStep {
+    pub id: String,
+    pub node_ids: Vec<String>,
+    pub input_ids_by_node: Option<HashMap<String, Vec<String>>>,
+    pub outputs_by_node: Option<HashMap<String, Vec<ExportIo>>>,
+    pub description: Option<String>,
+}
+
+#[derive(Serialize)]
+pub struct ExportFlow {
+    pub title: String,
+    pub description: Option<String>,
+    pub flow_steps: Vec<ExportFlowStep>,
+    pub initial_inputs: Option<Vec<ExportIo>>,
+    pub start_index: Option<i32>,
+}
+
+#[derive(Serialize)]
+pub struct ExportNode {
+    pub id: String,
+    pub title: String,
+    pub order_index: Option<i32>,
+    pub description: Option<String>,
+    pub parent_id: String,
+    pub flows: Option<Vec<ExportFlow>>,
+}
+
+#[derive(Serialize, Default)]
+pub struct ExportNodes {
+    pub nodes: Vec<ExportNode>,
+}
+
+pub struct Export<'a> {
+    db_session: &'a CachingSession,
+    node: &'a Node,
+    deleted_ids: HashSet<Uuid>,
+    records: ExportRecords,
+}
+
+/// Records loaded for the export, from which the export structures are built.
+#[derive(Default)]
+struct ExportRecords {
+    node_ids: Vec<Uuid>,
+    ios_by_id: HashMap<Uuid, Io>,
+    descriptions: HashMap<Uuid, Description>,
+    exported_io_descriptions: HashSet<Uuid>,
+}
+
+impl<'a> Export<'a> {
+    /// `node` should be the branched node in case of the branch export.
+    pub async fn new(db_session: &'a CachingSession, node: &'a Node) -> Result<Export<'a>, NodecosmosError> {
+        let mut deleted_ids = HashSet::new();
+
+        if node.is_branch() {
+            let branch = Branch::find_by_id(node.branch_id).execute(db_session).await?;
+
+            deleted_ids = branch.all_deleted_object_ids();
+        }
+
+        Ok(Self {
+            db_session,
+            node,
+            deleted_ids,
+            records: ExportRecords::default(),
+        })
+    }
+
+    pub async fn run(mut self) -> Result<ExportNodes, NodecosmosError> {
+        let mut export_nodes = self.build_nodes().await?;
+
+        self.records.ios_by_id = Io::branched(self.db_session, &self.params(self.node.root_id))
+            .await?
+            .into_iter()
+            .filter(|io| !self.deleted_ids.contains(&io.id))
+            .map(|io| (io.id, io))
+            .collect();
+
+        let mut flows_by_node_id = HashMap::new();
+        let mut flow_steps_by_flow_id: HashMap<Uuid, Vec<FlowStep>> = HashMap::new();
+        let mut object_ids = self.records.node_ids.iter().copied().collect::<HashSet<Uuid>>();
+
+        for node_id in &self.records.node_ids {
+            let params = self.params(*node_id);
+            let mut flows = Flow::branched(self.db_session, &params).await?;
+            flows.retain(|flow| !self.deleted_ids.contains(&flow.id));
+            flows.sort_by(|a, b| {
+                a.vertical_index
+                    .partial_cmp(&b.vertical_index)
+                    .unwrap_or(std::cmp::Ordering::Equal)
+                    .then(a.start_index.cmp(&b.start_index))
+            });
+
+            for flow_step in FlowStep::branched(self.db_session, &params).await? {
+                if !self.deleted_ids.contains(&flow_step.id) {
+                    object_ids.insert(flow_step.id);
+                    flow_steps_by_flow_id
+                        .entry(flow_step.flow_id)
+                        .or_default()
+                        .push(flow_step);
+                }
+            }
+
+            object_ids.extend(flows.iter().map(|flow| flow.id));
+            flows_by_node_id.insert(*node_id, flows);
+        }
+
+        object_ids.extend(self.records.ios_by_id.values().map(|io| io.main_id.unwrap_or(io.id)));
+
+        self.records.descriptions = self.descriptions(&object_ids).await?;
+
+        for (export_node, node_id) in export_nodes.nodes.iter_mut().zip(self.records.node_ids.clone()) {
+            export_node.description = self.records.description_xml(node_id);
+
+            if let Some(flows) = flows_by_node_id.remove(&node_id) {
+                let mut export_flows = Vec::with_capacity(flows.len());
+
+                for (index, flow) in flows.into_iter().enumerate() {
+                    let flow_steps = flow_steps_by_flow_id.remove(&flow.id).unwrap_or_default();
+
+                    export_flows.push(self.records.build_flow(node_id, flow, flow_steps, index == 0));
+                }
+
+                export_node.flows = Some(export_flows);
+            }
+        }
+
+        Ok(export_nodes)
+    }
+
+    fn params(&self, node_id: Uuid) -> NodeBranchParams {
+        NodeBranchParams {
+            root_id: self.node.root_id,
+            branch_id: self.node.branch_id,
+            node_id,
+        }
+    }
+
+    /// Builds nodes top down, so the exported node is the top-level node of the import.
+    async fn build_nodes(&mut self) -> Result<ExportNodes, NodecosmosError> {
+        let descendants: Vec<NodeDescendant> = if self.node.is_branch() {
+            self.node.branch_descendants(self.db_session).await?
+        } else {
+            self.node.descendants(self.db_session).await?.try_collect().await?
+        };
+
+        let mut children_by_parent_id: HashMap<Uuid, Vec<NodeDescendant>> = HashMap::new();
+
+        for descendant in descendants {
+            if !self.deleted_ids.contains(&descendant.id) {
+                children_by_parent_id
+                    .entry(descendant.parent_id)
+                    .or_default()
+                    .push(descendant);
+            }
+        }
+
+        let mut export_nodes = ExportNodes::default();
+
+        export_nodes.nodes.push(ExportNode {
+            id: self.node.id.to_string(),
+            title: self.node.title.clone(),
+            order_index: Some(0),
+            description: None,
+            parent_id: TMP_ROOT_ID.to_string(),
+            flows: None,
+        });
+        self.records.node_ids.push(self.node.id);
+
+        let mut parent_ids = vec![self.node.id];
+
+        while let Some(parent_id) = parent_ids.pop() {
+            if let Some(mut children) = children_by_parent_id.remove(&parent_id) {
+                children.sort_by(|a, b| {
+                    a.order_index
+                        .partial_cmp(&b.order_index)
+                        .unwrap_or(std::cmp::Ordering::Equal)
+                });
+
+                for (order_index, child) in children.into_iter().enumerate() {
+                    export_nodes.nodes.push(ExportNode {
+                        id: child.id.to_string(),
+                        title: child.title,
+                        order_index: Some(order_index as i32),
+                        description: None,
+                        parent_id: parent_id.to_string(),
+                        flows: None,
+                    });
+
+                    self.records.node_ids.push(child.id);
+                    parent_ids.push(child.id);
+                }
+            }
+        }
+
+        Ok(export_nodes)
+    }
+
+    async fn descriptions(&self, object_ids: &HashSet<Uuid>) -> Result<HashMap<Uuid, Description>, NodecosmosError> {
+        let mut descriptions =
+            Description::find_by_branch_id_and_ids(self.db_session, self.node.original_id(), object_ids)
+                .await
+                .group_by_object_id()
+                .await?;
+
+        if self.node.is_branch() {
+            let branched_descriptions =
+                Description::find_by_branch_id_and_ids(self.db_session, self.node.branch_id, object_ids)
+                    .await
+                    .group_by_object_id()
+                    .await?;
+
+            descriptions.extend(branched_descriptions);
+        }
+
+        Ok(descriptions)
+    }
+}
+
+impl ExportRecords {
+    fn description_xml(&self, object_id: Uuid) -> Option<String> {
+        self.descriptions.get(&object_id).and_then(|description| {
+            description.to_xml().unwrap_or_else(|e| {
+                log::error!("Failed to export description for object {}: {:?}", object_id, e);
+
+                None
+            })
+        })
+    }
+
+    fn build_io(&mut self, io: &Io) -> ExportIo {
+        let object_id = io.main_id.unwrap_or(io.id);
+
+        // ios with the same title share the description, so it's enough to export it once
+        let description = if self.exported_io_descriptions.insert(object_id) {
+            self.description_xml(object_id)
+        } else {
+            None
+        };
+
+        ExportIo {
+            id: io.id.to_string(),
+            title: io.title.clone().unwrap_or_default(),
+            description,
+        }
+    }
+
+    fn build_flow(
+        &mut self,
+        node_id: Uuid,
+        flow: Flow,
+        flow_steps: Vec<FlowStep>,
+        with_initial_inputs: bool,
+    ) -> ExportFlow {
+        let mut initial_inputs = vec![];
+
+        // initial inputs belong to the node's workflow, so we export them within the first flow
+        if with_initial_inputs {
+            let mut ios = self
+                .ios_by_id
+                .values()
+                .filter(|io| io.node_id == node_id && io.initial_input && io.flow_id.is_none())
+                .cloned()
+                .collect::<Vec<Io>>();
+            ios.sort_by(|a, b| a.created_at.cmp(&b.created_at).then(a.id.cmp(&b.id)));
+
+            for io in ios.iter() {
+                initial_inputs.push(self.build_io(io));
+            }
+        }
+
+        let mut flow_steps = flow_steps;
+        flow_steps.sort_by(|a, b| {
+            a.step_index
+                .partial_cmp(&b.step_index)
+                .unwrap_or(std::cmp::Ordering::Equal)
+        });
+
+        let export_flow_steps = flow_steps
+            .iter()
+            .map(|flow_step| self.build_flow_step(flow_step))
+            .collect();
+
+        ExportFlow {
+            title: flow.title.clone(),
+            description: self.description_xml(flow.id),
+            flow_steps: export_flow_steps,
+            initial_inputs: Some(initial_inputs),
+            start_index: Some(flow.start_index),
+        }
+    }
+
+    fn build_flow_step(&mut self, flow_step: &FlowStep) -> ExportFlowStep {
+        let exported_node_ids = self.node_ids.iter().copied().collect::<HashSet<Uuid>>();
+
+        let node_ids = flow_step
+            .node_ids
+            .iter()
+            .flatten()
+            .filter(|id| exported_node_ids.contains(*id))
+            .map(|id| id.to_string())
+            .collect();
+
+        let input_ids_by_node = flow_step.input_ids_by_node_id.as_ref().map(|input_ids_by_node_id| {
+            input_ids_by_node_id
+                .iter()
+                .filter(|(node_id, _)| exported_node_ids.contains(*node_id))
+                .map(|(node_id, io_ids)| {
+                    let io_ids = io_ids
+                        .iter()
+                        .filter(|io_id| self.ios_by_id.contains_key(*io_id))
+                        .map(|io_id| io_id.to_string())
+                        .collect();
+
+                    (node_id.to_string(), io_ids)
+                })
+                .collect()
+        });
+
+        let mut outputs_by_node = HashMap::new();
+
+        if let Some(output_ids_by_node_id) = &flow_step.output_ids_by_node_id {
+            for (node_id, io_ids) in output_ids_by_node_id {
+                if !exported_node_ids.contains(node_id) {
+                    continue;
+                }
+
+                let ios = io_ids
+                    .iter()
+                    .filter_map(|io_id| self.ios_by_id.get(io_id).cloned())
+                    .collect::<Vec<Io>>();
+
+                let outputs = ios.iter().map(|io| self.build_io(io)).collect();
+
+                outputs_by_node.insert(node_id.to_string(), outputs);
+            }
+        }
+
+        ExportFlowStep {
+            id: flow_step.id.to_string(),
+            node_ids,
+            input_ids_by_node,
+            outputs_by_node: Some(outputs_by_node),
+            description: self.description_xml(flow_step.id),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::data::RequestData;
+    use crate::models::node::import::{Import, ImportNodes};
+    use charybdis::operations::InsertWithCallbacks;
+    use charybdis::types::Decimal;
+    use serde_json::Value;
+
+    const IMPORT_JSON: &str = r#"{
+        "nodes": [
+            {
+                "id": "a",
+                "title": "Factory",
+                "flows": [
+                    {
+                        "title": "Production",
+                        "start_index": 0,
+                        "initial_inputs": [{ "id": "ore", "title": "Ore" }],
+                        "flow_steps": [
+                            {
+                                "id": "s1",
+                                "node_ids": ["b"],
+                                "input_ids_by_node": { "b": ["ore"] },
+                                "outputs_by_node": { "b": [{ "id": "iron", "title": "Iron" }] }
+                            },
+                            {
+                                "id": "s2",
+                                "node_ids": ["c"],
+                                "input_ids_by_node": { "c": ["iron"] },
+                                "outputs_by_node": { "c": [{ "id": "steel", "title": "Steel" }] }
+                            }
+                        ]
+                    }
+                ]
+            },
+            { "id": "b", "title": "Furnace", "parent_id": "a", "order_index": 0 },
+            { "id": "c", "title": "Forge", "parent_id": "a", "order_index": 1 }
+        ]
+    }"#;
+
+    async fn import_into_new_root(data: &RequestData, json: &str) -> Node {
+        let root_id = Uuid::new_v4();
+        let mut root = Node {
+            id: root_id,
+            root_id,
+            is_root: true,
+            title: "Import Root".into(),
+            owner_id: data.current_user.id,
+            owner: Some((&data.current_user).into()),
+            ..Default::default()
+        };
+
+        root.insert_cb(data)
+            .execute(data.db_session())
+            .await
+            .expect("Failed to insert root node");
+
+        let mut import = Import::from_json(root, json).expect("Failed to parse import");
+        import.run(data).await.expect("Failed to run import");
+
+        Node::maybe_find_first_by_branch_id_and_id(import.current_root.branch_id, import.node_id_by_tmp_id["a"])
+            .execute(data.db_session())
+            .await
+            .expect("Failed to find imported node")
+            .expect("Imported node not found")
+    }
+
+    /// Replaces ids within the export by titles, so exports of different copies can be compared.
+    fn structure(export_nodes: &ExportNodes) -> Value {
+        let mut titles = HashMap::new();
+
+        for node in &export_nodes.nodes {
+            titles.insert(node.id.clone(), node.title.clone());
+
+            for flow in node.flows.iter().flatten() {
+                for io in flow.initial_inputs.iter().flatten() {
+                    titles.insert(io.id.clone(), io.title.clone());
+                }
+
+                for (step_index, flow_step) in flow.flow_steps.iter().enumerate() {
+                    titles.insert(flow_step.id.clone(), format!("{} {}", flow.title, step_index));
+
+                    for io in flow_step
+                        .outputs_by_node
+                        .iter()
+                        .flat_map(|outputs| outputs.values().flatten())
+                    {
+                        titles.insert(io.id.clone(), io.title.clone());
+                    }
+                }
+            }
+        }
+
+        fn replace_ids(value: Value, titles: &HashMap<String, String>) -> Value {
+            match value {
+                Value::String(s) => Value::String(titles.get(&s).cloned().unwrap_or(s)),
+                Value::Array(items) => Value::Array(items.into_iter().map(|item| replace_ids(item, titles)).collect()),
+                Value::Object(fields) => Value::Object(
+                    fields
+                        .into_iter()
+                        .map(|(key, value)| (titles.get(&key).cloned().unwrap_or(key), replace_ids(value, titles)))
+                        .collect(),
+                ),
+                value => value,
+            }
+        }
+
+        replace_ids(
+            serde_json::to_value(export_nodes).expect("Failed to serialize export"),
+            &titles,
+        )
+    }
+
+    #[tokio::test]
+    async fn test_export_of_reimported_tree_is_equal() {
+        let data = RequestData::new(None).await;
+
+        let node = import_into_new_root(&data, IMPORT_JSON).await;
+        let export = Export::new(data.db_session(), &node)
+            .await
+            .expect("Failed to init export")
+            .run()
+            .await
+            .expect("Failed to export");
+
+        let json = serde_json::to_string(&export).expect("Failed to serialize export");
+        let copy = import_into_new_root(&data, &json).await;
+        let copy_export = Export::new(data.db_session(), &copy)
+            .await
+            .expect("Failed to init export")
+            .run()
+            .await
+            .expect("Failed to export");
+
+        let exported = structure(&export);
+
+        assert_eq!(exported, structure(&copy_export));
+        assert_eq!(exported["nodes"].as_array().map(Vec::len), Some(3));
+        assert_eq!(exported["nodes"][0]["flows"][0]["initial_inputs"][0]["title"], "Ore");
+        assert_eq!(
+            exported["nodes"][0]["flows"][0]["flow_steps"][1]["input_ids_by_node"]["Forge"][0],
+            "Iron"
+        );
+    }
+
+    #[test]
+    fn test_export_round_trips_through_import() {
+        let node_id = Uuid::new_v4();
+        let flow = Flow {
+            id: Uuid::new_v4(),
+            node_id,
+            title: "Flow".to_string(),
+            ..Default::default()
+        };
+        let flow_steps = [3, 1, 2]
+            .into_iter()
+            .map(|step_index| FlowStep {
+                id: Uuid::new_v4(),
+                node_id,
+                flow_id: flow.id,
+                step_index: Decimal::from(step_index),
+                node_ids: Some(vec![node_id]),
+                ..Default::default()
+            })
+            .collect::<Vec<FlowStep>>();
+        let mut sorted_ids = flow_steps
+            .iter()
+            .map(|flow_step| flow_step.id.to_string())
+            .collect::<Vec<_>>();
+        sorted_ids.rotate_left(1);
+
+        let mut records = ExportRecords {
+            node_ids: vec![node_id],
+            ..Default::default()
+        };
+        records.descriptions.insert(
+            flow.id,
+            Description {
+                object_id: flow.id,
+                html: Some("<pre><code class=\"language-rust\">fn main() {}</code></pre>".to_string()),
+                ..Default::default()
+            },
+        );
+
+        let export_nodes = ExportNodes {
+            nodes: vec![ExportNode {
+                id: node_id.to_string(),
+                title: "Node".to_string(),
+                order_index: Some(0),
+                description: None,
+                parent_id: TMP_ROOT_ID.to_string(),
+                flows: Some(vec![records.build_flow(node_id, flow, flow_steps, true)]),
+            }],
+        };
+
+        let json = serde_json::to_string(&export_nodes).expect("Failed to serialize export");
+        let import_nodes: ImportNodes = serde_json::from_str(&json).expect("Failed to deserialize export");
+        let import_flow = &import_nodes.nodes[0].flows.as_ref().expect("Missing flows")[0];
+        let imported_ids = import_flow
+            .flow_steps
+            .iter()
+            .map(|flow_step| flow_step.id.clone())
+            .collect::<Vec<_>>();
+
+        assert_eq!(imported_ids, sorted_ids);
+
+        let description = import_flow.description.as_ref().expect("Missing flow description");
+
+        assert!(description.html.contains("data-code-block-language=\"rust\""));
+        assert!(description.html.contains("fn main() {}"));
+        assert!(description.markdown.contains("```rust"));
+    }
+}
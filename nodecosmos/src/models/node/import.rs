@@ -14,7 +14,7 @@ use futures::StreamExt;
 use serde::{Deserialize, Deserializer};
 use std::collections::{HashMap, HashSet};
 
-const TMP_ROOT_ID: &str = "root";
+pub const TMP_ROOT_ID: &str = "root";
 
 pub struct ImportDescription {
     pub html: String,
@@ -187,31 +187,35 @@ impl Import {
                 })?);
             }
 
-            let import_nodes: ImportNodes = serde_json::from_str::<ImportNodes>(&json)
-                .map_err(|e| NodecosmosError::BadRequest(format!("Failed to parse JSON: {:?}", e)))?
-                .sort();
-
-            let mut node_id_by_tmp_id: HashMap<String, Uuid> = HashMap::new();
-            node_id_by_tmp_id.insert(TMP_ROOT_ID.to_string(), current_root.id);
-
-            Ok(Import {
-                current_root,
-                import_nodes,
-                node_id_by_tmp_id,
-                tmp_ids_by_node_id: HashMap::new(),
-                io_id_by_tmp_id: HashMap::new(),
-                io_id_by_title: HashMap::new(),
-                io_main_id_has_desc: HashMap::new(),
-                io_node_id_by_id: HashMap::new(),
-                created_flow_steps_tmp_ids: HashSet::new(),
-                descendant_ids_by_node_id: HashMap::new(),
-                created_nodes: Vec::new(),
-            })
+            Self::from_json(current_root, &json)
         } else {
             Err(NodecosmosError::ImportError("No JSON file provided".to_string()))
         }
     }
 
+    pub fn from_json(current_root: Node, json: &str) -> Result<Import, NodecosmosError> {
+        let import_nodes: ImportNodes = serde_json::from_str::<ImportNodes>(json)
+            .map_err(|e| NodecosmosError::BadRequest(format!("Failed to parse JSON: {:?}", e)))?
+            .sort();
+
+        let mut node_id_by_tmp_id: HashMap<String, Uuid> = HashMap::new();
+        node_id_by_tmp_id.insert(TMP_ROOT_ID.to_string(), current_root.id);
+
+        Ok(Import {
+            current_root,
+            import_nodes,
+            node_id_by_tmp_id,
+            tmp_ids_by_node_id: HashMap::new(),
+            io_id_by_tmp_id: HashMap::new(),
+            io_id_by_title: HashMap::new(),
+            io_main_id_has_desc: HashMap::new(),
+            io_node_id_by_id: HashMap::new(),
+            created_flow_steps_tmp_ids: HashSet::new(),
+            descendant_ids_by_node_id: HashMap::new(),
+            created_nodes: Vec::new(),
+        })
+    }
+
     pub async fn run(&mut self, data: &RequestData) -> Result<(), NodecosmosError> {
         if let Err(e) = self.execute(data).await {
             let mut error = format!("Failed to execute import: {:?}", e);
@@ -311,6 +315,7 @@ impl Import {
                     markdown: Some(i_desc.markdown.clone()),
                     base64: None,
                     updated_at: chrono::Utc::now(),
+                    ..Default::default()
                 };
 
                 description.insert_cb(data).execute(data.db_session()).await?;
@@ -385,6 +390,7 @@ impl Import {
                             markdown: Some(i_desc.markdown.clone()),
                             base64: None,
                             updated_at: chrono::Utc::now(),
+                            ..Default::default()
                         };
 
                         description.insert_cb(data).execute(data.db_session()).await?;
@@ -511,6 +517,7 @@ impl Import {
                 markdown: Some(desc.markdown.clone()),
                 base64: None,
                 updated_at: chrono::Utc::now(),
+                ..Default::default()
             };
 
             description.insert_cb(data).execute(data.db_session()).await?;
@@ -575,6 +582,7 @@ impl Import {
                 markdown: Some(import_description.markdown.clone()),
                 base64: None,
                 updated_at: chrono::Utc::now(),
+                ..Default::default()
             };
 
             description.insert_cb(data).execute(data.db_session()).await?;
@@ -19,61 +19,28 @@ use crate::models::node::reorder::validator::ReorderValidator;
 use crate::models::node::{Node, UpdateOrderNode};
 use crate::models::node_descendant::NodeDescendant;
 use crate::models::recovery::{RecoveryLog, RecoveryObjectType};
+use crate::models::saga::{saga_steps, Saga, SagaStep};
 use crate::models::traits::{Branchable, FindOrInsertBranched, ModelBranchParams};
 use crate::models::udts::BranchReorderData;
 
 pub mod data;
 mod validator;
 
-#[derive(Clone, Copy, Serialize, Deserialize, PartialOrd, PartialEq, Debug)]
-pub enum ReorderStep {
-    BeforeStart = -1,
-    Start = 0,
-    UpdateNodeOrderIndex = 1,
-    RemoveNodeFromOldAncestors = 2,
-    AddNodeToNewAncestors = 3,
-    PullRemovedAncestorsFromNode = 4,
-    PullRemovedAncestorsFromDescendants = 5,
-    DeleteNodeDescendantsFromRemovedAncestors = 6,
-    PushAddedAncestorsToNode = 7,
-    PushAddedAncestorsToDescendants = 8,
-    InsertNodeDescendantsToAddedAncestors = 9,
-    UpdateBranch = 10,
-    PushNewEditors = 11,
-    Finish = 12,
-    AfterFinish = 13,
-}
-
-impl ReorderStep {
-    pub fn increment(&mut self) {
-        *self = ReorderStep::from(*self as i8 + 1);
-    }
-
-    pub fn decrement(&mut self) {
-        *self = ReorderStep::from(*self as i8 - 1);
-    }
-}
-
-impl From<i8> for ReorderStep {
-    fn from(value: i8) -> Self {
-        match value {
-            -1 => ReorderStep::BeforeStart,
-            0 => ReorderStep::Start,
-            1 => ReorderStep::UpdateNodeOrderIndex,
-            2 => ReorderStep::RemoveNodeFromOldAncestors,
-            3 => ReorderStep::AddNodeToNewAncestors,
-            4 => ReorderStep::PullRemovedAncestorsFromNode,
-            5 => ReorderStep::PullRemovedAncestorsFromDescendants,
-            6 => ReorderStep::DeleteNodeDescendantsFromRemovedAncestors,
-            7 => ReorderStep::PushAddedAncestorsToNode,
-            8 => ReorderStep::PushAddedAncestorsToDescendants,
-            9 => ReorderStep::InsertNodeDescendantsToAddedAncestors,
-            10 => ReorderStep::UpdateBranch,
-            11 => ReorderStep::PushNewEditors,
-            12 => ReorderStep::Finish,
-            13 => ReorderStep::AfterFinish,
-            _ => panic!("Invalid value for ReorderStep"),
-        }
+saga_steps! {
+    pub enum ReorderStep {
+        UpdateNodeOrderIndex = 1,
+        RemoveNodeFromOldAncestors = 2,
+        AddNodeToNewAncestors = 3,
+        PullRemovedAncestorsFromNode = 4,
+        PullRemovedAncestorsFromDescendants = 5,
+        DeleteNodeDescendantsFromRemovedAncestors = 6,
+        PushAddedAncestorsToNode = 7,
+        PushAddedAncestorsToDescendants = 8,
+        InsertNodeDescendantsToAddedAncestors = 9,
+        UpdateBranch = 10,
+        PushNewEditors = 11,
+        Finish = 12,
+        AfterFinish = 13,
     }
 }
 
@@ -123,7 +90,7 @@ impl Reorder {
     pub async fn run(&mut self, data: &RequestData) -> Result<(), NodecosmosError> {
         self.create_branch_nodes(data).await?;
 
-        let res = self.execute_reorder(data.db_session()).await;
+        let res = self.execute_reorder(data).await;
 
         if let Err(err) = res {
             log::error!(
@@ -132,7 +99,7 @@ impl Reorder {
                 err
             );
 
-            self.recover(data.db_session()).await.map_err(|recover_err| {
+            self.recover(data).await.map_err(|recover_err| {
                 log::error!(
                     "Fatal Reorder recovery failed for node: {}\n! ERROR: {:?}",
                     self.reorder_data.node.id,
@@ -213,107 +180,12 @@ impl Reorder {
         Ok(())
     }
 
-    async fn execute_reorder(&mut self, db_session: &CachingSession) -> Result<(), NodecosmosError> {
-        while self.reorder_step <= ReorderStep::Finish {
-            // log current step
-            if self.reorder_step > ReorderStep::Start && self.reorder_step < ReorderStep::Finish {
-                self.update_recovery_log_step(db_session, self.reorder_step as i8)
-                    .await?;
-            }
-
-            match self.reorder_step {
-                ReorderStep::BeforeStart => {
-                    log::error!("should not execute before placeholder");
-                }
-                ReorderStep::Start => {
-                    self.create_recovery_log(db_session).await?;
-                }
-                ReorderStep::UpdateNodeOrderIndex => self.update_node_order(db_session).await?,
-                ReorderStep::RemoveNodeFromOldAncestors => self.remove_node_from_old_ancestors(db_session).await?,
-                ReorderStep::AddNodeToNewAncestors => self.add_node_to_new_ancestors(db_session).await?,
-                ReorderStep::PullRemovedAncestorsFromNode => self.pull_removed_ancestors_from_node(db_session).await?,
-                ReorderStep::PullRemovedAncestorsFromDescendants => {
-                    self.pull_removed_ancestors_from_descendants(db_session).await?
-                }
-                ReorderStep::DeleteNodeDescendantsFromRemovedAncestors => {
-                    self.delete_node_descendants_from_removed_ancestors(db_session).await?
-                }
-                ReorderStep::PushAddedAncestorsToNode => self.push_added_ancestors_to_node(db_session).await?,
-                ReorderStep::PushAddedAncestorsToDescendants => {
-                    self.push_added_ancestors_to_descendants(db_session).await?
-                }
-                ReorderStep::InsertNodeDescendantsToAddedAncestors => {
-                    self.insert_node_descendants_to_added_ancestors(db_session).await?
-                }
-                ReorderStep::UpdateBranch => self.update_branch(db_session).await?,
-                ReorderStep::PushNewEditors => {
-                    self.push_new_editors(db_session).await?;
-                }
-                ReorderStep::Finish => {
-                    self.delete_recovery_log(db_session).await?;
-                }
-                ReorderStep::AfterFinish => {
-                    log::error!("should not execute after placeholder");
-                }
-            }
-
-            self.reorder_step.increment();
-        }
-
-        Ok(())
+    async fn execute_reorder(&mut self, data: &RequestData) -> Result<(), NodecosmosError> {
+        self.run_saga(data).await
     }
 
-    async fn recover(&mut self, db_session: &CachingSession) -> Result<(), NodecosmosError> {
-        while self.reorder_step >= ReorderStep::Start {
-            // log current step
-            if self.reorder_step > ReorderStep::Start && self.reorder_step < ReorderStep::Finish {
-                self.update_recovery_log_step(db_session, self.reorder_step as i8)
-                    .await?;
-            }
-
-            match self.reorder_step {
-                ReorderStep::BeforeStart => {
-                    log::error!("should not hit before placeholder");
-                }
-                ReorderStep::Start => {
-                    self.delete_recovery_log(db_session).await?;
-                }
-                ReorderStep::UpdateNodeOrderIndex => self.undo_update_node_order(db_session).await?,
-                ReorderStep::RemoveNodeFromOldAncestors => self.undo_remove_node_from_old_ancestors(db_session).await?,
-                ReorderStep::AddNodeToNewAncestors => self.undo_add_node_to_new_ancestors(db_session).await?,
-                ReorderStep::PullRemovedAncestorsFromNode => {
-                    self.undo_pull_removed_ancestors_from_node(db_session).await?
-                }
-                ReorderStep::PullRemovedAncestorsFromDescendants => {
-                    self.undo_pull_removed_ancestors_from_descendants(db_session).await?
-                }
-                ReorderStep::DeleteNodeDescendantsFromRemovedAncestors => {
-                    self.undo_delete_node_descendants_from_removed_ancestors(db_session)
-                        .await?
-                }
-                ReorderStep::PushAddedAncestorsToNode => self.undo_push_added_ancestors_to_node(db_session).await?,
-                ReorderStep::PushAddedAncestorsToDescendants => {
-                    self.undo_push_added_ancestors_to_descendants(db_session).await?
-                }
-                ReorderStep::InsertNodeDescendantsToAddedAncestors => {
-                    self.undo_insert_node_descendants_to_added_ancestors(db_session).await?
-                }
-                ReorderStep::UpdateBranch => self.undo_update_branch(db_session).await?,
-                ReorderStep::PushNewEditors => {
-                    self.undo_push_new_editors().await?;
-                }
-                ReorderStep::Finish => {
-                    log::error!("should not recover finished process");
-                }
-                ReorderStep::AfterFinish => {
-                    log::error!("should not hit after placeholder");
-                }
-            }
-
-            self.reorder_step.decrement();
-        }
-
-        Ok(())
+    async fn recover(&mut self, data: &RequestData) -> Result<(), NodecosmosError> {
+        self.compensate_saga(data).await
     }
 
     async fn update_node_order(&mut self, db_session: &CachingSession) -> Result<(), NodecosmosError> {
@@ -787,11 +659,12 @@ impl Reorder {
         Ok(())
     }
 
-    async fn update_branch(&mut self, db_session: &CachingSession) -> Result<(), NodecosmosError> {
+    async fn update_branch(&mut self, data: &RequestData) -> Result<(), NodecosmosError> {
         if self.reorder_data.is_branch() {
             Branch::update(
-                db_session,
+                data,
                 self.reorder_data.branch_id,
+                self.reorder_data.node.id,
                 BranchUpdate::ReorderNode(BranchReorderData {
                     id: self.reorder_data.node.id,
                     new_parent_id: self.reorder_data.new_parent_id,
@@ -807,11 +680,12 @@ impl Reorder {
         Ok(())
     }
 
-    async fn undo_update_branch(&mut self, db_session: &CachingSession) -> Result<(), NodecosmosError> {
+    async fn undo_update_branch(&mut self, data: &RequestData) -> Result<(), NodecosmosError> {
         if self.reorder_data.is_branch() {
             Branch::update(
-                db_session,
+                data,
                 self.reorder_data.branch_id,
+                self.reorder_data.node.id,
                 BranchUpdate::ReorderNode(BranchReorderData {
                     id: self.reorder_data.node.id,
                     new_parent_id: self.reorder_data.old_parent_id,
@@ -872,12 +746,14 @@ impl RecoveryLog<'_> for Reorder {
         RecoveryObjectType::Reorder
     }
 
-    fn set_step(&mut self, step: i8) {
-        self.reorder_step = ReorderStep::from(step);
+    fn set_step(&mut self, step: i8) -> Result<(), NodecosmosError> {
+        self.reorder_step = ReorderStep::try_from_value(step)?;
+
+        Ok(())
     }
 
     async fn recover_from_log(&mut self, data: &RequestData) -> Result<(), NodecosmosError> {
-        self.recover(data.db_session()).await.map_err(|recover_err| {
+        self.recover(data).await.map_err(|recover_err| {
             log::error!(
                 "Fatal Reorder Error: recover_from_log failed for node: {}\n! ERROR: {:?}",
                 self.reorder_data.node.id,
@@ -900,6 +776,83 @@ impl RecoveryLog<'_> for Reorder {
     }
 }
 
+impl Saga for Reorder {
+    type Step = ReorderStep;
+
+    fn step(&self) -> ReorderStep {
+        self.reorder_step
+    }
+
+    fn set_saga_step(&mut self, step: ReorderStep) {
+        self.reorder_step = step;
+    }
+
+    async fn execute(&mut self, data: &RequestData, step: ReorderStep) -> Result<(), NodecosmosError> {
+        let db_session = data.db_session();
+
+        match step {
+            ReorderStep::UpdateNodeOrderIndex => self.update_node_order(db_session).await?,
+            ReorderStep::RemoveNodeFromOldAncestors => self.remove_node_from_old_ancestors(db_session).await?,
+            ReorderStep::AddNodeToNewAncestors => self.add_node_to_new_ancestors(db_session).await?,
+            ReorderStep::PullRemovedAncestorsFromNode => self.pull_removed_ancestors_from_node(db_session).await?,
+            ReorderStep::PullRemovedAncestorsFromDescendants => {
+                self.pull_removed_ancestors_from_descendants(db_session).await?
+            }
+            ReorderStep::DeleteNodeDescendantsFromRemovedAncestors => {
+                self.delete_node_descendants_from_removed_ancestors(db_session).await?
+            }
+            ReorderStep::PushAddedAncestorsToNode => self.push_added_ancestors_to_node(db_session).await?,
+            ReorderStep::PushAddedAncestorsToDescendants => {
+                self.push_added_ancestors_to_descendants(db_session).await?
+            }
+            ReorderStep::InsertNodeDescendantsToAddedAncestors => {
+                self.insert_node_descendants_to_added_ancestors(db_session).await?
+            }
+            ReorderStep::UpdateBranch => self.update_branch(data).await?,
+            ReorderStep::PushNewEditors => {
+                self.push_new_editors(db_session).await?;
+            }
+            // log and placeholder steps are handled by the saga executor
+            ReorderStep::BeforeStart | ReorderStep::Start | ReorderStep::Finish | ReorderStep::AfterFinish => (),
+        }
+
+        Ok(())
+    }
+
+    async fn compensate(&mut self, data: &RequestData, step: ReorderStep) -> Result<(), NodecosmosError> {
+        let db_session = data.db_session();
+
+        match step {
+            ReorderStep::UpdateNodeOrderIndex => self.undo_update_node_order(db_session).await?,
+            ReorderStep::RemoveNodeFromOldAncestors => self.undo_remove_node_from_old_ancestors(db_session).await?,
+            ReorderStep::AddNodeToNewAncestors => self.undo_add_node_to_new_ancestors(db_session).await?,
+            ReorderStep::PullRemovedAncestorsFromNode => self.undo_pull_removed_ancestors_from_node(db_session).await?,
+            ReorderStep::PullRemovedAncestorsFromDescendants => {
+                self.undo_pull_removed_ancestors_from_descendants(db_session).await?
+            }
+            ReorderStep::DeleteNodeDescendantsFromRemovedAncestors => {
+                self.undo_delete_node_descendants_from_removed_ancestors(db_session)
+                    .await?
+            }
+            ReorderStep::PushAddedAncestorsToNode => self.undo_push_added_ancestors_to_node(db_session).await?,
+            ReorderStep::PushAddedAncestorsToDescendants => {
+                self.undo_push_added_ancestors_to_descendants(db_session).await?
+            }
+            ReorderStep::InsertNodeDescendantsToAddedAncestors => {
+                self.undo_insert_node_descendants_to_added_ancestors(db_session).await?
+            }
+            ReorderStep::UpdateBranch => self.undo_update_branch(data).await?,
+            ReorderStep::PushNewEditors => {
+                self.undo_push_new_editors().await?;
+            }
+            // log and placeholder steps are handled by the saga executor
+            ReorderStep::BeforeStart | ReorderStep::Start | ReorderStep::Finish | ReorderStep::AfterFinish => (),
+        }
+
+        Ok(())
+    }
+}
+
 impl Node {
     pub async fn reorder(data: &RequestData, params: &ReorderParams) -> Result<(), NodecosmosError> {
         let reorder_data = ReorderData::from_params(params, data).await?;
@@ -0,0 +1,1620 @@
+use std::collections::{HashMap, HashSet};
+
+use charybdis::batch::ModelBatch;
+use charybdis::types::{Counter, Frozen, List, Map, Set, Uuid};
+use scylla::client::caching_session::CachingSession;
+use serde::{Deserialize, Serialize};
+
+use crate::api::data::RequestData;
+use crate::errors::NodecosmosError;
+use crate::models::attachment::Attachment;
+use crate::models::description::Description;
+use crate::models::flow::Flow;
+use crate::models::flow_step::FlowStep;
+use crate::models::io::Io;
+use crate::models::like::Like;
+use crate::models::node::delete::NodeDelete;
+use crate::models::node::Node;
+use crate::models::node_counter::{find_node_counter, NodeCounter};
+use crate::models::node_descendant::NodeDescendant;
+use crate::models::recovery::{RecoveryLog, RecoveryObjectType};
+use crate::models::saga::{saga_steps, Saga, SagaStep};
+use crate::models::traits::{Descendants, ElasticDocument, FindForBranchMerge, GroupByObjectId, WhereInChunksExec};
+use crate::models::workflow::Workflow;
+
+saga_steps! {
+    pub enum RootMoveStep {
+        InsertNodes = 1,
+        InsertDescendants = 2,
+        InsertWorkflows = 3,
+        InsertFlows = 4,
+        InsertFlowSteps = 5,
+        InsertIos = 6,
+        UpdateRemainingIos = 7,
+        InsertDescriptions = 8,
+        InsertAttachments = 9,
+        InsertLikes = 10,
+        MoveCounters = 11,
+        DeleteDescendants = 12,
+        DeleteNodes = 13,
+        DeleteWorkflows = 14,
+        DeleteFlows = 15,
+        DeleteFlowSteps = 16,
+        DeleteIos = 17,
+        DeleteDescriptions = 18,
+        DeleteAttachments = 19,
+        DeleteLikes = 20,
+        UpdateElasticData = 21,
+        PruneOldRoot = 22,
+        Finish = 23,
+        AfterFinish = 24,
+    }
+}
+
+/// Move is allowed only between original trees, so `branch_id` is always equal to the `root_id`.
+#[derive(Serialize, Deserialize, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct RootMoveParams {
+    pub root_id: Uuid,
+    pub id: Uuid,
+    pub new_root_id: Uuid,
+    pub new_parent_id: Uuid,
+}
+
+/// Moves node subtree to another root. As original records are partitioned by `branch_id == root_id`,
+/// every record is inserted under the new root first, and only then removed from the old one.
+/// Ids are preserved, so links between moved records stay valid.
+///
+/// Only ids and the move params are logged. Records are read again on recovery, see `recover_from_log`.
+#[derive(Serialize, Deserialize, Default)]
+pub struct RootMove {
+    params: RootMoveParams,
+    move_step: RootMoveStep,
+    node_ids: Vec<Uuid>,
+    // order index of the moved node under the new parent
+    order_index: f64,
+    // original main ids of ios that stay in the old root, but had their main io moved
+    remaining_io_main_ids: HashMap<Uuid, Uuid>,
+    #[serde(skip)]
+    old_nodes: Vec<Node>,
+    #[serde(skip)]
+    new_nodes: Vec<Node>,
+    #[serde(skip)]
+    old_descendants: Vec<NodeDescendant>,
+    #[serde(skip)]
+    new_descendants: Vec<NodeDescendant>,
+    #[serde(skip)]
+    old_workflows: Vec<Workflow>,
+    #[serde(skip)]
+    new_workflows: Vec<Workflow>,
+    #[serde(skip)]
+    old_flows: Vec<Flow>,
+    #[serde(skip)]
+    new_flows: Vec<Flow>,
+    #[serde(skip)]
+    old_flow_steps: Vec<FlowStep>,
+    #[serde(skip)]
+    new_flow_steps: Vec<FlowStep>,
+    #[serde(skip)]
+    old_ios: Vec<Io>,
+    #[serde(skip)]
+    new_ios: Vec<Io>,
+    // ios that stay in the old root, but had their main io moved
+    #[serde(skip)]
+    old_remaining_ios: Vec<Io>,
+    #[serde(skip)]
+    updated_remaining_ios: Vec<Io>,
+    // records that stay in the old root, but reference moved nodes, ios or flow steps
+    #[serde(skip)]
+    old_root_flow_steps: Vec<FlowStep>,
+    #[serde(skip)]
+    pruned_root_flow_steps: Vec<FlowStep>,
+    #[serde(skip)]
+    old_root_ios: Vec<Io>,
+    #[serde(skip)]
+    pruned_root_ios: Vec<Io>,
+    #[serde(skip)]
+    old_root_workflows: Vec<Workflow>,
+    #[serde(skip)]
+    pruned_root_workflows: Vec<Workflow>,
+    #[serde(skip)]
+    old_descriptions: Vec<Description>,
+    #[serde(skip)]
+    new_descriptions: Vec<Description>,
+    #[serde(skip)]
+    old_attachments: Vec<Attachment>,
+    #[serde(skip)]
+    new_attachments: Vec<Attachment>,
+    #[serde(skip)]
+    old_likes: Vec<Like>,
+    #[serde(skip)]
+    new_likes: Vec<Like>,
+    #[serde(skip)]
+    counters: Vec<NodeCounter>,
+}
+
+impl RootMove {
+    pub async fn new(data: &RequestData, params: RootMoveParams) -> Result<Self, NodecosmosError> {
+        if params.root_id == params.new_root_id {
+            return Err(NodecosmosError::BadRequest(
+                "Node is already within the given root. Use reorder instead.".to_string(),
+            ));
+        }
+
+        let new_parent = Node::find_by_branch_id_and_id(params.new_root_id, params.new_parent_id)
+            .execute(data.db_session())
+            .await?;
+        let order_index = new_parent.next_child_order_index(data.db_session()).await?;
+
+        let mut root_move = Self {
+            params,
+            order_index,
+            ..Default::default()
+        };
+
+        root_move.load(data.db_session(), &new_parent).await?;
+
+        Ok(root_move)
+    }
+
+    /// Reads the moved records from the old root and builds their copies within the new root.
+    async fn load(&mut self, db_session: &CachingSession, new_parent: &Node) -> Result<(), NodecosmosError> {
+        let params = &self.params;
+        let node = Node::find_by_branch_id_and_id(params.root_id, params.id)
+            .execute(db_session)
+            .await?;
+
+        if node.is_root {
+            return Err(NodecosmosError::BadRequest("Root node can not be moved".to_string()));
+        }
+
+        let mut node_ids = vec![node.id];
+        let descendants: Vec<NodeDescendant> = node.descendants(db_session).await?.try_collect().await?;
+        node_ids.extend(descendants.iter().map(|descendant| descendant.id));
+
+        let node_id_set = node_ids.iter().cloned().collect::<Set<Uuid>>();
+
+        let mut old_nodes: Vec<Node> = Node::find_by_ids(db_session, params.root_id, &node_ids)
+            .await
+            .try_collect()
+            .await?;
+        // parents first
+        old_nodes.sort_by_key(|old_node| old_node.ancestor_ids.as_ref().map_or(0, |ids| ids.len()));
+
+        let old_descendants = NodeDelete::deleted_descendants(db_session, &node, &node_id_set).await?;
+        let old_workflows = Workflow::find_by_node_ids(db_session, params.root_id, &node_ids)
+            .await
+            .try_collect()
+            .await?;
+        let old_flows: Vec<Flow> = Flow::find_by_branch_id_and_node_ids(db_session, params.root_id, &node_id_set)
+            .await
+            .try_collect()
+            .await?;
+        let old_flow_steps: Vec<FlowStep> =
+            FlowStep::find_by_branch_id_and_node_ids(db_session, params.root_id, &node_id_set)
+                .await
+                .try_collect()
+                .await?;
+        let (old_ios, mut root_ios): (Vec<Io>, Vec<Io>) =
+            Io::find_by_branch_id_and_root_id(params.root_id, params.root_id)
+                .execute(db_session)
+                .await?
+                .try_collect()
+                .await?
+                .into_iter()
+                .partition(|io| node_id_set.contains(&io.node_id));
+        let root_flow_steps: Vec<FlowStep> = FlowStep::find_by_branch_id(params.root_id)
+            .execute(db_session)
+            .await?
+            .try_collect()
+            .await?;
+        let root_workflows: Vec<Workflow> = Workflow::find_by_branch_id(params.root_id)
+            .execute(db_session)
+            .await?
+            .try_collect()
+            .await?;
+
+        let mut object_ids = node_id_set.clone();
+        object_ids.extend(old_flows.iter().map(|flow| flow.id));
+        object_ids.extend(old_flow_steps.iter().map(|flow_step| flow_step.id));
+        object_ids.extend(old_ios.iter().map(|io| io.id));
+
+        let mut description_ids = object_ids.clone();
+        description_ids.extend(old_ios.iter().filter_map(|io| io.main_id));
+
+        let descriptions_by_object_id =
+            Description::find_by_branch_id_and_ids(db_session, params.root_id, &description_ids)
+                .await
+                .group_by_object_id()
+                .await?;
+        let old_attachments = Attachment::find_by_node_ids(db_session, params.root_id, &node_ids).await?;
+        let old_likes = Self::find_likes(db_session, params.root_id, &node_ids).await?;
+        let counters = node_ids
+            .where_in_chunked_query(db_session, |ids_chunk| {
+                find_node_counter!("branch_id = ? AND id IN ?", (params.root_id, ids_chunk))
+            })
+            .await
+            .try_collect()
+            .await?;
+
+        // remaining ios could already point to the new main, if the log is recovered after they were updated
+        for io in &mut root_ios {
+            if let Some(main_id) = self.remaining_io_main_ids.get(&io.id) {
+                io.main_id = Some(*main_id);
+            }
+        }
+
+        self.node_ids = node_ids;
+        self.old_nodes = old_nodes;
+        self.old_descendants = old_descendants;
+        self.old_workflows = old_workflows;
+        self.old_flows = old_flows;
+        self.old_flow_steps = old_flow_steps;
+        self.old_ios = old_ios;
+        self.old_attachments = old_attachments;
+        self.old_likes = old_likes;
+        self.counters = counters;
+
+        self.build_nodes(new_parent, self.order_index);
+        self.build_descendants()?;
+        self.build_workflows();
+        self.build_flows();
+        self.build_flow_steps();
+        self.build_attachments();
+        self.build_likes();
+
+        let main_ids = self.build_ios(root_ios.clone());
+        self.build_descriptions(descriptions_by_object_id, &object_ids, main_ids);
+        self.build_pruned_root_flow_steps(root_flow_steps);
+        self.build_pruned_root_workflows(root_workflows);
+        self.build_pruned_root_ios(root_ios);
+
+        self.remaining_io_main_ids = self
+            .old_remaining_ios
+            .iter()
+            .filter_map(|io| io.main_id.map(|main_id| (io.id, main_id)))
+            .collect();
+
+        Ok(())
+    }
+
+    /// Once deletion from the old root has started, the old records can't be read anymore, but their copies
+    /// within the new root are complete. Primary keys of the records only differ by the root, so the old
+    /// records to delete are read from the copies.
+    async fn load_moved(&mut self, db_session: &CachingSession) -> Result<(), NodecosmosError> {
+        let root_id = self.params.root_id;
+        let new_root_id = self.params.new_root_id;
+        let node_id_set = self.node_ids.iter().cloned().collect::<Set<Uuid>>();
+
+        self.new_nodes = Node::find_by_ids(db_session, new_root_id, &self.node_ids)
+            .await
+            .try_collect()
+            .await?;
+        self.new_workflows = Workflow::find_by_node_ids(db_session, new_root_id, &self.node_ids)
+            .await
+            .try_collect()
+            .await?;
+        self.new_flows = Flow::find_by_branch_id_and_node_ids(db_session, new_root_id, &node_id_set)
+            .await
+            .try_collect()
+            .await?;
+        self.new_flow_steps = FlowStep::find_by_branch_id_and_node_ids(db_session, new_root_id, &node_id_set)
+            .await
+            .try_collect()
+            .await?;
+        self.new_ios = Io::find_by_branch_id_and_root_id(new_root_id, new_root_id)
+            .execute(db_session)
+            .await?
+            .try_collect()
+            .await?
+            .into_iter()
+            .filter(|io| node_id_set.contains(&io.node_id))
+            .collect();
+
+        let mut object_ids = node_id_set.clone();
+        object_ids.extend(self.new_flows.iter().map(|flow| flow.id));
+        object_ids.extend(self.new_flow_steps.iter().map(|flow_step| flow_step.id));
+        object_ids.extend(self.new_ios.iter().map(|io| io.id));
+
+        self.new_descriptions = Description::find_by_branch_id_and_ids(db_session, new_root_id, &object_ids)
+            .await
+            .group_by_object_id()
+            .await?
+            .into_values()
+            .collect();
+        self.new_attachments = Attachment::find_by_node_ids(db_session, new_root_id, &self.node_ids).await?;
+        self.new_likes = Self::find_likes(db_session, new_root_id, &self.node_ids).await?;
+
+        // descendants are deleted first, so the old node is still present
+        if self.move_step == RootMoveStep::DeleteDescendants {
+            let node = Node::find_by_branch_id_and_id(root_id, self.params.id)
+                .execute(db_session)
+                .await?;
+
+            self.old_descendants = NodeDelete::deleted_descendants(db_session, &node, &node_id_set).await?;
+        }
+
+        self.old_nodes = self
+            .new_nodes
+            .iter()
+            .map(|node| Node {
+                branch_id: root_id,
+                root_id,
+                ..node.clone()
+            })
+            .collect();
+        self.old_workflows = self
+            .new_workflows
+            .iter()
+            .map(|workflow| Workflow {
+                branch_id: root_id,
+                root_id,
+                ..workflow.clone()
+            })
+            .collect();
+        self.old_flows = self
+            .new_flows
+            .iter()
+            .map(|flow| Flow {
+                branch_id: root_id,
+                root_id,
+                ..flow.clone()
+            })
+            .collect();
+        self.old_flow_steps = self
+            .new_flow_steps
+            .iter()
+            .map(|flow_step| FlowStep {
+                branch_id: root_id,
+                root_id,
+                ..flow_step.clone()
+            })
+            .collect();
+        self.old_ios = self
+            .new_ios
+            .iter()
+            .map(|io| Io {
+                branch_id: root_id,
+                root_id,
+                ..io.clone()
+            })
+            .collect();
+        self.old_descriptions = self
+            .new_descriptions
+            .iter()
+            .map(|description| Description {
+                branch_id: root_id,
+                root_id,
+                ..description.clone()
+            })
+            .collect();
+        self.old_attachments = self
+            .new_attachments
+            .iter()
+            .map(|attachment| Attachment {
+                node_id: attachment.node_id,
+                branch_id: root_id,
+                object_id: attachment.object_id,
+                root_id,
+                id: attachment.id,
+                key: attachment.key.clone(),
+                url: attachment.url.clone(),
+                user_id: attachment.user_id,
+                created_at: attachment.created_at,
+                updated_at: attachment.updated_at,
+            })
+            .collect();
+        self.old_likes = self
+            .new_likes
+            .iter()
+            .map(|like| Like {
+                branch_id: root_id,
+                root_id: Some(root_id),
+                ..like.clone()
+            })
+            .collect();
+
+        // remaining ios are updated before the deletion, so only the references to moved records are pruned
+        let root_ios = Io::find_by_branch_id_and_root_id(root_id, root_id)
+            .execute(db_session)
+            .await?
+            .try_collect()
+            .await?
+            .into_iter()
+            .filter(|io| !node_id_set.contains(&io.node_id))
+            .collect();
+        let root_flow_steps = FlowStep::find_by_branch_id(root_id)
+            .execute(db_session)
+            .await?
+            .try_collect()
+            .await?;
+        let root_workflows = Workflow::find_by_branch_id(root_id)
+            .execute(db_session)
+            .await?
+            .try_collect()
+            .await?;
+
+        self.build_pruned_root_flow_steps(root_flow_steps);
+        self.build_pruned_root_workflows(root_workflows);
+        self.build_pruned_root_ios(root_ios);
+
+        Ok(())
+    }
+
+    async fn find_likes(
+        db_session: &CachingSession,
+        root_id: Uuid,
+        node_ids: &[Uuid],
+    ) -> Result<Vec<Like>, NodecosmosError> {
+        let mut likes = vec![];
+
+        for node_id in node_ids {
+            let node_likes: Vec<Like> = Like::find_by_object_id_and_branch_id(*node_id, root_id)
+                .execute(db_session)
+                .await?
+                .try_collect()
+                .await?;
+
+            likes.extend(node_likes);
+        }
+
+        Ok(likes)
+    }
+
+    fn build_nodes(&mut self, new_parent: &Node, order_index: f64) {
+        let moved_ids = self.node_ids.iter().cloned().collect::<HashSet<Uuid>>();
+        let mut top_ancestor_ids = new_parent.ancestor_ids.clone().unwrap_or_default();
+        top_ancestor_ids.insert(new_parent.id);
+
+        for old_node in &self.old_nodes {
+            let mut new_node = old_node.clone();
+            let mut ancestor_ids = top_ancestor_ids.clone();
+
+            if old_node.id == self.params.id {
+                new_node.parent_id = Some(new_parent.id);
+                new_node.order_index = order_index;
+            } else {
+                // keep ancestors that are moved together with the node
+                ancestor_ids.extend(
+                    old_node
+                        .ancestor_ids
+                        .iter()
+                        .flatten()
+                        .filter(|ancestor_id| moved_ids.contains(*ancestor_id)),
+                );
+            }
+
+            // moved nodes inherit ownership and access from the new root
+            new_node.branch_id = self.params.new_root_id;
+            new_node.root_id = self.params.new_root_id;
+            new_node.ancestor_ids = Some(ancestor_ids);
+            new_node.owner_id = new_parent.owner_id;
+            new_node.owner = new_parent.owner.clone();
+            new_node.editor_ids = new_parent.editor_ids.clone();
+            new_node.viewer_ids = new_parent.viewer_ids.clone();
+            new_node.is_public = new_parent.is_public;
+            new_node.is_subscription_active = new_parent.is_subscription_active;
+            new_node.updated_at = chrono::Utc::now();
+
+            self.new_nodes.push(new_node);
+        }
+    }
+
+    fn build_descendants(&mut self) -> Result<(), NodecosmosError> {
+        for node in &self.new_nodes {
+            let parent_id = node
+                .parent_id
+                .ok_or_else(|| NodecosmosError::InternalServerError(format!("Moved node {} has no parent", node.id)))?;
+
+            for ancestor_id in node.ancestor_ids.iter().flatten() {
+                self.new_descendants.push(NodeDescendant {
+                    root_id: node.root_id,
+                    branch_id: node.branch_id,
+                    node_id: *ancestor_id,
+                    order_index: node.order_index,
+                    id: node.id,
+                    parent_id,
+                    title: node.title.clone(),
+                });
+            }
+        }
+
+        Ok(())
+    }
+
+    fn moved_io_ids(&self) -> HashSet<Uuid> {
+        self.old_ios.iter().map(|io| io.id).collect()
+    }
+
+    fn moved_flow_step_ids(&self) -> HashSet<Uuid> {
+        self.old_flow_steps.iter().map(|flow_step| flow_step.id).collect()
+    }
+
+    /// Keeps only nodes and ios that end up in the same root as the flow step, as records of the other root
+    /// don't exist within it. `moved` tells whether the flow step is moved or stays in the old root.
+    fn retain_root_ios(
+        ids_by_node_id: &mut Option<Frozen<Map<Uuid, Frozen<List<Uuid>>>>>,
+        moved_node_ids: &HashSet<Uuid>,
+        moved_io_ids: &HashSet<Uuid>,
+        moved: bool,
+    ) {
+        if let Some(ids_by_node_id) = ids_by_node_id {
+            ids_by_node_id.retain(|node_id, _| moved_node_ids.contains(node_id) == moved);
+
+            for ids in ids_by_node_id.values_mut() {
+                ids.retain(|id| moved_io_ids.contains(id) == moved);
+            }
+
+            ids_by_node_id.retain(|_, ids| !ids.is_empty());
+        }
+    }
+
+    fn retain_root_flow_step(
+        flow_step: &mut FlowStep,
+        moved_node_ids: &HashSet<Uuid>,
+        moved_io_ids: &HashSet<Uuid>,
+        moved: bool,
+    ) {
+        if let Some(node_ids) = &mut flow_step.node_ids {
+            node_ids.retain(|id| moved_node_ids.contains(id) == moved);
+        }
+
+        Self::retain_root_ios(&mut flow_step.input_ids_by_node_id, moved_node_ids, moved_io_ids, moved);
+        Self::retain_root_ios(
+            &mut flow_step.output_ids_by_node_id,
+            moved_node_ids,
+            moved_io_ids,
+            moved,
+        );
+    }
+
+    fn build_workflows(&mut self) {
+        let moved_io_ids = self.moved_io_ids();
+
+        for workflow in &self.old_workflows {
+            let mut new_workflow = workflow.clone();
+            new_workflow.branch_id = self.params.new_root_id;
+            new_workflow.root_id = self.params.new_root_id;
+
+            if let Some(initial_input_ids) = &mut new_workflow.initial_input_ids {
+                initial_input_ids.retain(|id| moved_io_ids.contains(id));
+            }
+
+            self.new_workflows.push(new_workflow);
+        }
+    }
+
+    fn build_flows(&mut self) {
+        for flow in &self.old_flows {
+            let mut new_flow = flow.clone();
+            new_flow.branch_id = self.params.new_root_id;
+            new_flow.root_id = self.params.new_root_id;
+
+            self.new_flows.push(new_flow);
+        }
+    }
+
+    fn build_flow_steps(&mut self) {
+        let moved_node_ids = self.node_ids.iter().cloned().collect::<HashSet<Uuid>>();
+        let moved_io_ids = self.moved_io_ids();
+
+        for flow_step in &self.old_flow_steps {
+            let mut new_flow_step = flow_step.clone();
+            new_flow_step.branch_id = self.params.new_root_id;
+            new_flow_step.root_id = self.params.new_root_id;
+
+            Self::retain_root_flow_step(&mut new_flow_step, &moved_node_ids, &moved_io_ids, true);
+
+            self.new_flow_steps.push(new_flow_step);
+        }
+    }
+
+    /// Flow steps that stay in the old root drop moved nodes and ios.
+    fn build_pruned_root_flow_steps(&mut self, root_flow_steps: Vec<FlowStep>) {
+        let moved_node_ids = self.node_ids.iter().cloned().collect::<HashSet<Uuid>>();
+        let moved_io_ids = self.moved_io_ids();
+        let moved_flow_step_ids = self.moved_flow_step_ids();
+
+        for flow_step in root_flow_steps {
+            if moved_flow_step_ids.contains(&flow_step.id) {
+                continue;
+            }
+
+            let mut pruned_flow_step = flow_step.clone();
+
+            Self::retain_root_flow_step(&mut pruned_flow_step, &moved_node_ids, &moved_io_ids, false);
+
+            if pruned_flow_step.node_ids != flow_step.node_ids
+                || pruned_flow_step.input_ids_by_node_id != flow_step.input_ids_by_node_id
+                || pruned_flow_step.output_ids_by_node_id != flow_step.output_ids_by_node_id
+            {
+                self.old_root_flow_steps.push(flow_step);
+                self.pruned_root_flow_steps.push(pruned_flow_step);
+            }
+        }
+    }
+
+    /// Workflows that stay in the old root drop moved initial inputs.
+    fn build_pruned_root_workflows(&mut self, root_workflows: Vec<Workflow>) {
+        let moved_io_ids = self.moved_io_ids();
+
+        for workflow in root_workflows {
+            let moved = workflow
+                .initial_input_ids
+                .iter()
+                .flatten()
+                .any(|id| moved_io_ids.contains(id));
+
+            if !moved || self.node_ids.contains(&workflow.node_id) {
+                continue;
+            }
+
+            let mut pruned_workflow = workflow.clone();
+
+            if let Some(initial_input_ids) = &mut pruned_workflow.initial_input_ids {
+                initial_input_ids.retain(|id| !moved_io_ids.contains(id));
+            }
+
+            self.old_root_workflows.push(workflow);
+            self.pruned_root_workflows.push(pruned_workflow);
+        }
+    }
+
+    /// Ios that stay in the old root drop moved flow steps that input them. As they are pruned after the
+    /// remaining ios are updated, the updated main id is kept.
+    fn build_pruned_root_ios(&mut self, root_ios: Vec<Io>) {
+        let moved_flow_step_ids = self.moved_flow_step_ids();
+        let updated_ios_by_id = self
+            .updated_remaining_ios
+            .iter()
+            .map(|io| (io.id, io))
+            .collect::<HashMap<Uuid, &Io>>();
+
+        for io in root_ios {
+            let moved = io
+                .inputted_by_flow_steps
+                .iter()
+                .flatten()
+                .any(|id| moved_flow_step_ids.contains(id));
+
+            if !moved {
+                continue;
+            }
+
+            let io = updated_ios_by_id
+                .get(&io.id)
+                .map_or(io, |updated_io| (*updated_io).clone());
+            let mut pruned_io = io.clone();
+
+            if let Some(inputted_by_flow_steps) = &mut pruned_io.inputted_by_flow_steps {
+                inputted_by_flow_steps.retain(|id| !moved_flow_step_ids.contains(id));
+            }
+
+            self.old_root_ios.push(io);
+            self.pruned_root_ios.push(pruned_io);
+        }
+    }
+
+    fn build_attachments(&mut self) {
+        // s3 keys are scoped to object id, so we only need to move the records
+        for attachment in &self.old_attachments {
+            self.new_attachments.push(Attachment {
+                node_id: attachment.node_id,
+                branch_id: self.params.new_root_id,
+                object_id: attachment.object_id,
+                root_id: self.params.new_root_id,
+                id: attachment.id,
+                key: attachment.key.clone(),
+                url: attachment.url.clone(),
+                user_id: attachment.user_id,
+                created_at: attachment.created_at,
+                updated_at: attachment.updated_at,
+            });
+        }
+    }
+
+    fn build_likes(&mut self) {
+        for like in &self.old_likes {
+            let mut new_like = like.clone();
+            new_like.branch_id = self.params.new_root_id;
+            new_like.root_id = Some(self.params.new_root_id);
+
+            self.new_likes.push(new_like);
+        }
+    }
+
+    /// Ios that share the title share the main io within the root. When the group is split by the move,
+    /// first io of each side becomes the new main. Returns map of `(branch_id, old main id) -> new main id`,
+    /// so the main description can be copied to the new main.
+    fn build_ios(&mut self, root_ios: Vec<Io>) -> HashMap<(Uuid, Uuid), Uuid> {
+        let moved_io_ids = self.old_ios.iter().map(|io| io.id).collect::<HashSet<Uuid>>();
+        let moved_flow_step_ids = self.moved_flow_step_ids();
+        let mut main_ids = HashMap::new();
+
+        for io in &self.old_ios {
+            let mut new_io = io.clone();
+            let main_id = io.main_id.unwrap_or(io.id);
+
+            // flow steps of the old root don't exist within the new root
+            if let Some(inputted_by_flow_steps) = &mut new_io.inputted_by_flow_steps {
+                inputted_by_flow_steps.retain(|id| moved_flow_step_ids.contains(id));
+            }
+
+            if !moved_io_ids.contains(&main_id) {
+                let new_main_id = *main_ids.entry((self.params.new_root_id, main_id)).or_insert(io.id);
+                new_io.main_id = Some(new_main_id);
+            }
+
+            new_io.branch_id = self.params.new_root_id;
+            new_io.root_id = self.params.new_root_id;
+
+            self.new_ios.push(new_io);
+        }
+
+        for io in root_ios {
+            let main_id = io.main_id.unwrap_or(io.id);
+
+            if moved_io_ids.contains(&main_id) {
+                let new_main_id = *main_ids.entry((self.params.root_id, main_id)).or_insert(io.id);
+                let mut updated_io = io.clone();
+                updated_io.main_id = Some(new_main_id);
+
+                self.old_remaining_ios.push(io);
+                self.updated_remaining_ios.push(updated_io);
+            }
+        }
+
+        main_ids
+    }
+
+    fn build_descriptions(
+        &mut self,
+        mut descriptions_by_object_id: HashMap<Uuid, Description>,
+        object_ids: &Set<Uuid>,
+        main_ids: HashMap<(Uuid, Uuid), Uuid>,
+    ) {
+        let node_id_by_io_id = self
+            .new_ios
+            .iter()
+            .chain(self.updated_remaining_ios.iter())
+            .map(|io| (io.id, io.node_id))
+            .collect::<HashMap<Uuid, Uuid>>();
+
+        for ((branch_id, old_main_id), new_main_id) in main_ids {
+            if let Some(description) = descriptions_by_object_id.get(&old_main_id) {
+                let mut main_description = description.clone();
+                main_description.branch_id = branch_id;
+                main_description.root_id = branch_id;
+                main_description.object_id = new_main_id;
+                main_description.node_id = node_id_by_io_id[&new_main_id];
+
+                self.new_descriptions.push(main_description);
+            }
+        }
+
+        descriptions_by_object_id.retain(|object_id, _| object_ids.contains(object_id));
+
+        for description in descriptions_by_object_id.into_values() {
+            let mut new_description = description.clone();
+            new_description.branch_id = self.params.new_root_id;
+            new_description.root_id = self.params.new_root_id;
+
+            self.new_descriptions.push(new_description);
+            self.old_descriptions.push(description);
+        }
+    }
+
+    pub async fn run(&mut self, data: &RequestData) -> Result<(), NodecosmosError> {
+        let res = self.move_to_root(data).await;
+
+        if let Err(e) = res {
+            log::error!("Root move failed for node: {}\n! ERROR: {:?}", self.params.id, e);
+
+            self.recover(data)
+                .await
+                .map_err(|e| NodecosmosError::FatalRootMoveError(format!("Error moving node: {:?}", e)))?;
+
+            return Err(e);
+        }
+
+        Ok(())
+    }
+
+    pub async fn move_to_root(&mut self, data: &RequestData) -> Result<(), NodecosmosError> {
+        self.run_saga(data).await
+    }
+
+    pub async fn recover(&mut self, data: &RequestData) -> Result<(), NodecosmosError> {
+        self.compensate_saga(data).await
+    }
+
+    /// Until the deletion from the old root starts, the old root is intact, so the move is undone from the
+    /// records read again. After that, copies within the new root are complete, so the move is finished instead.
+    async fn resume_from_log(&mut self, data: &RequestData) -> Result<(), NodecosmosError> {
+        if self.move_step < RootMoveStep::DeleteDescendants {
+            let new_parent = Node::find_by_branch_id_and_id(self.params.new_root_id, self.params.new_parent_id)
+                .execute(data.db_session())
+                .await?;
+
+            self.load(data.db_session(), &new_parent).await?;
+            self.recover(data).await
+        } else {
+            self.load_moved(data.db_session()).await?;
+            self.move_to_root(data).await
+        }
+    }
+
+    async fn insert_nodes(&self, db_session: &CachingSession) -> Result<(), NodecosmosError> {
+        Node::unlogged_batch()
+            .chunked_insert(db_session, &self.new_nodes, crate::constants::BATCH_CHUNK_SIZE)
+            .await
+            .map_err(NodecosmosError::from)
+    }
+
+    async fn undo_insert_nodes(&self, db_session: &CachingSession) -> Result<(), NodecosmosError> {
+        Node::unlogged_batch()
+            .chunked_delete(db_session, &self.new_nodes, crate::constants::BATCH_CHUNK_SIZE)
+            .await
+            .map_err(NodecosmosError::from)
+    }
+
+    async fn delete_nodes(&self, db_session: &CachingSession) -> Result<(), NodecosmosError> {
+        Node::unlogged_batch()
+            .chunked_delete(db_session, &self.old_nodes, crate::constants::BATCH_CHUNK_SIZE)
+            .await
+            .map_err(NodecosmosError::from)
+    }
+
+    async fn undo_delete_nodes(&self, db_session: &CachingSession) -> Result<(), NodecosmosError> {
+        Node::unlogged_batch()
+            .chunked_insert(db_session, &self.old_nodes, crate::constants::BATCH_CHUNK_SIZE)
+            .await
+            .map_err(NodecosmosError::from)
+    }
+
+    async fn insert_descendants(&self, db_session: &CachingSession) -> Result<(), NodecosmosError> {
+        NodeDescendant::unlogged_batch()
+            .chunked_insert(db_session, &self.new_descendants, crate::constants::BATCH_CHUNK_SIZE)
+            .await
+            .map_err(NodecosmosError::from)
+    }
+
+    async fn undo_insert_descendants(&self, db_session: &CachingSession) -> Result<(), NodecosmosError> {
+        NodeDescendant::unlogged_batch()
+            .chunked_delete(db_session, &self.new_descendants, crate::constants::BATCH_CHUNK_SIZE)
+            .await
+            .map_err(NodecosmosError::from)
+    }
+
+    async fn delete_descendants(&self, db_session: &CachingSession) -> Result<(), NodecosmosError> {
+        NodeDescendant::unlogged_batch()
+            .chunked_delete(db_session, &self.old_descendants, crate::constants::BATCH_CHUNK_SIZE)
+            .await
+            .map_err(NodecosmosError::from)
+    }
+
+    async fn undo_delete_descendants(&self, db_session: &CachingSession) -> Result<(), NodecosmosError> {
+        NodeDescendant::unlogged_batch()
+            .chunked_insert(db_session, &self.old_descendants, crate::constants::BATCH_CHUNK_SIZE)
+            .await
+            .map_err(NodecosmosError::from)
+    }
+
+    async fn insert_workflows(&self, db_session: &CachingSession) -> Result<(), NodecosmosError> {
+        Workflow::unlogged_batch()
+            .chunked_insert(db_session, &self.new_workflows, crate::constants::BATCH_CHUNK_SIZE)
+            .await
+            .map_err(NodecosmosError::from)
+    }
+
+    async fn undo_insert_workflows(&self, db_session: &CachingSession) -> Result<(), NodecosmosError> {
+        Workflow::unlogged_batch()
+            .chunked_delete(db_session, &self.new_workflows, crate::constants::BATCH_CHUNK_SIZE)
+            .await
+            .map_err(NodecosmosError::from)
+    }
+
+    async fn delete_workflows(&self, db_session: &CachingSession) -> Result<(), NodecosmosError> {
+        Workflow::unlogged_batch()
+            .chunked_delete(db_session, &self.old_workflows, crate::constants::BATCH_CHUNK_SIZE)
+            .await
+            .map_err(NodecosmosError::from)
+    }
+
+    async fn undo_delete_workflows(&self, db_session: &CachingSession) -> Result<(), NodecosmosError> {
+        Workflow::unlogged_batch()
+            .chunked_insert(db_session, &self.old_workflows, crate::constants::BATCH_CHUNK_SIZE)
+            .await
+            .map_err(NodecosmosError::from)
+    }
+
+    async fn insert_flows(&self, db_session: &CachingSession) -> Result<(), NodecosmosError> {
+        Flow::unlogged_batch()
+            .chunked_insert(db_session, &self.new_flows, crate::constants::BATCH_CHUNK_SIZE)
+            .await
+            .map_err(NodecosmosError::from)
+    }
+
+    async fn undo_insert_flows(&self, db_session: &CachingSession) -> Result<(), NodecosmosError> {
+        Flow::unlogged_batch()
+            .chunked_delete(db_session, &self.new_flows, crate::constants::BATCH_CHUNK_SIZE)
+            .await
+            .map_err(NodecosmosError::from)
+    }
+
+    async fn delete_flows(&self, db_session: &CachingSession) -> Result<(), NodecosmosError> {
+        Flow::unlogged_batch()
+            .chunked_delete(db_session, &self.old_flows, crate::constants::BATCH_CHUNK_SIZE)
+            .await
+            .map_err(NodecosmosError::from)
+    }
+
+    async fn undo_delete_flows(&self, db_session: &CachingSession) -> Result<(), NodecosmosError> {
+        Flow::unlogged_batch()
+            .chunked_insert(db_session, &self.old_flows, crate::constants::BATCH_CHUNK_SIZE)
+            .await
+            .map_err(NodecosmosError::from)
+    }
+
+    async fn insert_flow_steps(&self, db_session: &CachingSession) -> Result<(), NodecosmosError> {
+        FlowStep::unlogged_batch()
+            .chunked_insert(db_session, &self.new_flow_steps, crate::constants::BATCH_CHUNK_SIZE)
+            .await
+            .map_err(NodecosmosError::from)
+    }
+
+    async fn undo_insert_flow_steps(&self, db_session: &CachingSession) -> Result<(), NodecosmosError> {
+        FlowStep::unlogged_batch()
+            .chunked_delete(db_session, &self.new_flow_steps, crate::constants::BATCH_CHUNK_SIZE)
+            .await
+            .map_err(NodecosmosError::from)
+    }
+
+    async fn delete_flow_steps(&self, db_session: &CachingSession) -> Result<(), NodecosmosError> {
+        FlowStep::unlogged_batch()
+            .chunked_delete(db_session, &self.old_flow_steps, crate::constants::BATCH_CHUNK_SIZE)
+            .await
+            .map_err(NodecosmosError::from)
+    }
+
+    async fn undo_delete_flow_steps(&self, db_session: &CachingSession) -> Result<(), NodecosmosError> {
+        FlowStep::unlogged_batch()
+            .chunked_insert(db_session, &self.old_flow_steps, crate::constants::BATCH_CHUNK_SIZE)
+            .await
+            .map_err(NodecosmosError::from)
+    }
+
+    async fn insert_ios(&self, db_session: &CachingSession) -> Result<(), NodecosmosError> {
+        Io::unlogged_batch()
+            .chunked_insert(db_session, &self.new_ios, crate::constants::BATCH_CHUNK_SIZE)
+            .await
+            .map_err(NodecosmosError::from)
+    }
+
+    async fn undo_insert_ios(&self, db_session: &CachingSession) -> Result<(), NodecosmosError> {
+        Io::unlogged_batch()
+            .chunked_delete(db_session, &self.new_ios, crate::constants::BATCH_CHUNK_SIZE)
+            .await
+            .map_err(NodecosmosError::from)
+    }
+
+    async fn delete_ios(&self, db_session: &CachingSession) -> Result<(), NodecosmosError> {
+        Io::unlogged_batch()
+            .chunked_delete(db_session, &self.old_ios, crate::constants::BATCH_CHUNK_SIZE)
+            .await
+            .map_err(NodecosmosError::from)
+    }
+
+    async fn undo_delete_ios(&self, db_session: &CachingSession) -> Result<(), NodecosmosError> {
+        Io::unlogged_batch()
+            .chunked_insert(db_session, &self.old_ios, crate::constants::BATCH_CHUNK_SIZE)
+            .await
+            .map_err(NodecosmosError::from)
+    }
+
+    async fn insert_descriptions(&self, db_session: &CachingSession) -> Result<(), NodecosmosError> {
+        Description::unlogged_batch()
+            .chunked_insert(db_session, &self.new_descriptions, crate::constants::BATCH_CHUNK_SIZE)
+            .await
+            .map_err(NodecosmosError::from)
+    }
+
+    async fn undo_insert_descriptions(&self, db_session: &CachingSession) -> Result<(), NodecosmosError> {
+        Description::unlogged_batch()
+            .chunked_delete(db_session, &self.new_descriptions, crate::constants::BATCH_CHUNK_SIZE)
+            .await
+            .map_err(NodecosmosError::from)
+    }
+
+    async fn delete_descriptions(&self, db_session: &CachingSession) -> Result<(), NodecosmosError> {
+        Description::unlogged_batch()
+            .chunked_delete(db_session, &self.old_descriptions, crate::constants::BATCH_CHUNK_SIZE)
+            .await
+            .map_err(NodecosmosError::from)
+    }
+
+    async fn undo_delete_descriptions(&self, db_session: &CachingSession) -> Result<(), NodecosmosError> {
+        Description::unlogged_batch()
+            .chunked_insert(db_session, &self.old_descriptions, crate::constants::BATCH_CHUNK_SIZE)
+            .await
+            .map_err(NodecosmosError::from)
+    }
+
+    async fn insert_attachments(&self, db_session: &CachingSession) -> Result<(), NodecosmosError> {
+        Attachment::unlogged_batch()
+            .chunked_insert(db_session, &self.new_attachments, crate::constants::BATCH_CHUNK_SIZE)
+            .await
+            .map_err(NodecosmosError::from)
+    }
+
+    async fn undo_insert_attachments(&self, db_session: &CachingSession) -> Result<(), NodecosmosError> {
+        Attachment::unlogged_batch()
+            .chunked_delete(db_session, &self.new_attachments, crate::constants::BATCH_CHUNK_SIZE)
+            .await
+            .map_err(NodecosmosError::from)
+    }
+
+    async fn delete_attachments(&self, db_session: &CachingSession) -> Result<(), NodecosmosError> {
+        Attachment::unlogged_batch()
+            .chunked_delete(db_session, &self.old_attachments, crate::constants::BATCH_CHUNK_SIZE)
+            .await
+            .map_err(NodecosmosError::from)
+    }
+
+    async fn undo_delete_attachments(&self, db_session: &CachingSession) -> Result<(), NodecosmosError> {
+        Attachment::unlogged_batch()
+            .chunked_insert(db_session, &self.old_attachments, crate::constants::BATCH_CHUNK_SIZE)
+            .await
+            .map_err(NodecosmosError::from)
+    }
+
+    async fn insert_likes(&self, db_session: &CachingSession) -> Result<(), NodecosmosError> {
+        Like::unlogged_batch()
+            .chunked_insert(db_session, &self.new_likes, crate::constants::BATCH_CHUNK_SIZE)
+            .await
+            .map_err(NodecosmosError::from)
+    }
+
+    async fn undo_insert_likes(&self, db_session: &CachingSession) -> Result<(), NodecosmosError> {
+        Like::unlogged_batch()
+            .chunked_delete(db_session, &self.new_likes, crate::constants::BATCH_CHUNK_SIZE)
+            .await
+            .map_err(NodecosmosError::from)
+    }
+
+    async fn delete_likes(&self, db_session: &CachingSession) -> Result<(), NodecosmosError> {
+        Like::unlogged_batch()
+            .chunked_delete(db_session, &self.old_likes, crate::constants::BATCH_CHUNK_SIZE)
+            .await
+            .map_err(NodecosmosError::from)
+    }
+
+    async fn undo_delete_likes(&self, db_session: &CachingSession) -> Result<(), NodecosmosError> {
+        Like::unlogged_batch()
+            .chunked_insert(db_session, &self.old_likes, crate::constants::BATCH_CHUNK_SIZE)
+            .await
+            .map_err(NodecosmosError::from)
+    }
+
+    async fn update_remaining_ios(&self, db_session: &CachingSession) -> Result<(), NodecosmosError> {
+        Io::unlogged_batch()
+            .chunked_insert(
+                db_session,
+                &self.updated_remaining_ios,
+                crate::constants::BATCH_CHUNK_SIZE,
+            )
+            .await
+            .map_err(NodecosmosError::from)
+    }
+
+    async fn undo_update_remaining_ios(&self, db_session: &CachingSession) -> Result<(), NodecosmosError> {
+        Io::unlogged_batch()
+            .chunked_insert(db_session, &self.old_remaining_ios, crate::constants::BATCH_CHUNK_SIZE)
+            .await
+            .map_err(NodecosmosError::from)
+    }
+
+    async fn prune_old_root(&self, db_session: &CachingSession) -> Result<(), NodecosmosError> {
+        FlowStep::unlogged_batch()
+            .chunked_insert(
+                db_session,
+                &self.pruned_root_flow_steps,
+                crate::constants::BATCH_CHUNK_SIZE,
+            )
+            .await?;
+        Io::unlogged_batch()
+            .chunked_insert(db_session, &self.pruned_root_ios, crate::constants::BATCH_CHUNK_SIZE)
+            .await?;
+        Workflow::unlogged_batch()
+            .chunked_insert(
+                db_session,
+                &self.pruned_root_workflows,
+                crate::constants::BATCH_CHUNK_SIZE,
+            )
+            .await
+            .map_err(NodecosmosError::from)
+    }
+
+    async fn undo_prune_old_root(&self, db_session: &CachingSession) -> Result<(), NodecosmosError> {
+        FlowStep::unlogged_batch()
+            .chunked_insert(
+                db_session,
+                &self.old_root_flow_steps,
+                crate::constants::BATCH_CHUNK_SIZE,
+            )
+            .await?;
+        Io::unlogged_batch()
+            .chunked_insert(db_session, &self.old_root_ios, crate::constants::BATCH_CHUNK_SIZE)
+            .await?;
+        Workflow::unlogged_batch()
+            .chunked_insert(db_session, &self.old_root_workflows, crate::constants::BATCH_CHUNK_SIZE)
+            .await
+            .map_err(NodecosmosError::from)
+    }
+
+    async fn update_elastic_data(&self, data: &RequestData) {
+        let _ = Node::bulk_insert_elastic_documents(data.elastic_client(), &self.new_nodes).await;
+    }
+
+    async fn undo_update_elastic_data(&self, data: &RequestData) {
+        let _ = Node::bulk_insert_elastic_documents(data.elastic_client(), &self.old_nodes).await;
+    }
+
+    /// Descendant count changes of the ancestors in the old and in the new root, as `(branch_id, id, count)`.
+    fn ancestor_counter_changes(&self) -> Vec<(Uuid, Uuid, i64)> {
+        let moved_count = self.node_ids.len() as i64;
+        let old_ancestor_ids = self
+            .old_nodes
+            .iter()
+            .find(|node| node.id == self.params.id)
+            .and_then(|node| node.ancestor_ids.clone())
+            .unwrap_or_default();
+        let new_ancestor_ids = self
+            .new_nodes
+            .iter()
+            .find(|node| node.id == self.params.id)
+            .and_then(|node| node.ancestor_ids.clone())
+            .unwrap_or_default();
+
+        old_ancestor_ids
+            .into_iter()
+            .map(|id| (self.params.root_id, id, -moved_count))
+            .chain(
+                new_ancestor_ids
+                    .into_iter()
+                    .map(|id| (self.params.new_root_id, id, moved_count)),
+            )
+            .collect()
+    }
+
+    // Counter rows can not be recreated once deleted, so old counters are left in place as it's done on delete.
+    async fn move_counters(&self, db_session: &CachingSession) -> Result<(), NodecosmosError> {
+        for counter in &self.counters {
+            Self::increment_counter(db_session, self.params.new_root_id, counter, 1).await?;
+        }
+
+        self.update_ancestor_counters(db_session, 1).await
+    }
+
+    async fn undo_move_counters(&self, db_session: &CachingSession) -> Result<(), NodecosmosError> {
+        for counter in &self.counters {
+            Self::increment_counter(db_session, self.params.new_root_id, counter, -1).await?;
+        }
+
+        self.update_ancestor_counters(db_session, -1).await
+    }
+
+    async fn update_ancestor_counters(&self, db_session: &CachingSession, sign: i64) -> Result<(), NodecosmosError> {
+        for (branch_id, id, count) in self.ancestor_counter_changes() {
+            NodeCounter {
+                branch_id,
+                id,
+                ..Default::default()
+            }
+            .increment_descendants_count(count * sign)
+            .execute(db_session)
+            .await?;
+        }
+
+        Ok(())
+    }
+
+    async fn increment_counter(
+        db_session: &CachingSession,
+        branch_id: Uuid,
+        counter: &NodeCounter,
+        sign: i64,
+    ) -> Result<(), NodecosmosError> {
+        let new_counter = NodeCounter {
+            branch_id,
+            id: counter.id,
+            ..Default::default()
+        };
+        let value = |c: Option<Counter>| c.map_or(0, |c| c.0) * sign;
+
+        let like_count = value(counter.like_count);
+        let descendants_count = value(counter.descendants_count);
+        let contribution_requests_count = value(counter.contribution_requests_count);
+        let threads_count = value(counter.threads_count);
+
+        if like_count != 0 {
+            new_counter.increment_like_count(like_count).execute(db_session).await?;
+        }
+
+        if descendants_count != 0 {
+            new_counter
+                .increment_descendants_count(descendants_count)
+                .execute(db_session)
+                .await?;
+        }
+
+        if contribution_requests_count != 0 {
+            new_counter
+                .increment_contribution_requests_count(contribution_requests_count)
+                .execute(db_session)
+                .await?;
+        }
+
+        if threads_count != 0 {
+            new_counter
+                .increment_threads_count(threads_count)
+                .execute(db_session)
+                .await?;
+        }
+
+        Ok(())
+    }
+}
+
+impl Node {
+    pub async fn move_to_root(data: &RequestData, params: RootMoveParams) -> Result<(), NodecosmosError> {
+        RootMove::new(data, params).await?.run(data).await
+    }
+}
+
+impl RecoveryLog<'_> for RootMove {
+    fn rec_id(&self) -> Uuid {
+        self.params.id
+    }
+
+    fn rec_branch_id(&self) -> Uuid {
+        self.params.root_id
+    }
+
+    fn rec_object_type(&self) -> RecoveryObjectType {
+        RecoveryObjectType::RootMove
+    }
+
+    fn set_step(&mut self, step: i8) -> Result<(), NodecosmosError> {
+        self.move_step = RootMoveStep::try_from_value(step)?;
+
+        Ok(())
+    }
+
+    async fn recover_from_log(&mut self, data: &RequestData) -> Result<(), NodecosmosError> {
+        self.resume_from_log(data).await.map_err(|e| {
+            log::error!("FatalRootMoveError recovering from log: {:?}", e);
+            NodecosmosError::FatalRootMoveError(format!("Error recovering from log: {:?}", e))
+        })
+    }
+}
+
+impl Saga for RootMove {
+    type Step = RootMoveStep;
+
+    fn step(&self) -> RootMoveStep {
+        self.move_step
+    }
+
+    fn set_saga_step(&mut self, step: RootMoveStep) {
+        self.move_step = step;
+    }
+
+    async fn execute(&mut self, data: &RequestData, step: RootMoveStep) -> Result<(), NodecosmosError> {
+        let db_session = data.db_session();
+
+        match step {
+            RootMoveStep::InsertNodes => self.insert_nodes(db_session).await?,
+            RootMoveStep::InsertDescendants => self.insert_descendants(db_session).await?,
+            RootMoveStep::InsertWorkflows => self.insert_workflows(db_session).await?,
+            RootMoveStep::InsertFlows => self.insert_flows(db_session).await?,
+            RootMoveStep::InsertFlowSteps => self.insert_flow_steps(db_session).await?,
+            RootMoveStep::InsertIos => self.insert_ios(db_session).await?,
+            RootMoveStep::UpdateRemainingIos => self.update_remaining_ios(db_session).await?,
+            RootMoveStep::InsertDescriptions => self.insert_descriptions(db_session).await?,
+            RootMoveStep::InsertAttachments => self.insert_attachments(db_session).await?,
+            RootMoveStep::InsertLikes => self.insert_likes(db_session).await?,
+            RootMoveStep::MoveCounters => self.move_counters(db_session).await?,
+            RootMoveStep::DeleteDescendants => self.delete_descendants(db_session).await?,
+            RootMoveStep::DeleteNodes => self.delete_nodes(db_session).await?,
+            RootMoveStep::DeleteWorkflows => self.delete_workflows(db_session).await?,
+            RootMoveStep::DeleteFlows => self.delete_flows(db_session).await?,
+            RootMoveStep::DeleteFlowSteps => self.delete_flow_steps(db_session).await?,
+            RootMoveStep::DeleteIos => self.delete_ios(db_session).await?,
+            RootMoveStep::DeleteDescriptions => self.delete_descriptions(db_session).await?,
+            RootMoveStep::DeleteAttachments => self.delete_attachments(db_session).await?,
+            RootMoveStep::DeleteLikes => self.delete_likes(db_session).await?,
+            RootMoveStep::UpdateElasticData => self.update_elastic_data(data).await,
+            RootMoveStep::PruneOldRoot => self.prune_old_root(db_session).await?,
+            // log and placeholder steps are handled by the saga executor
+            RootMoveStep::BeforeStart | RootMoveStep::Start | RootMoveStep::Finish | RootMoveStep::AfterFinish => (),
+        }
+
+        Ok(())
+    }
+
+    async fn compensate(&mut self, data: &RequestData, step: RootMoveStep) -> Result<(), NodecosmosError> {
+        let db_session = data.db_session();
+
+        match step {
+            RootMoveStep::InsertNodes => self.undo_insert_nodes(db_session).await?,
+            RootMoveStep::InsertDescendants => self.undo_insert_descendants(db_session).await?,
+            RootMoveStep::InsertWorkflows => self.undo_insert_workflows(db_session).await?,
+            RootMoveStep::InsertFlows => self.undo_insert_flows(db_session).await?,
+            RootMoveStep::InsertFlowSteps => self.undo_insert_flow_steps(db_session).await?,
+            RootMoveStep::InsertIos => self.undo_insert_ios(db_session).await?,
+            RootMoveStep::UpdateRemainingIos => self.undo_update_remaining_ios(db_session).await?,
+            RootMoveStep::InsertDescriptions => self.undo_insert_descriptions(db_session).await?,
+            RootMoveStep::InsertAttachments => self.undo_insert_attachments(db_session).await?,
+            RootMoveStep::InsertLikes => self.undo_insert_likes(db_session).await?,
+            RootMoveStep::MoveCounters => self.undo_move_counters(db_session).await?,
+            RootMoveStep::DeleteDescendants => self.undo_delete_descendants(db_session).await?,
+            RootMoveStep::DeleteNodes => self.undo_delete_nodes(db_session).await?,
+            RootMoveStep::DeleteWorkflows => self.undo_delete_workflows(db_session).await?,
+            RootMoveStep::DeleteFlows => self.undo_delete_flows(db_session).await?,
+            RootMoveStep::DeleteFlowSteps => self.undo_delete_flow_steps(db_session).await?,
+            RootMoveStep::DeleteIos => self.undo_delete_ios(db_session).await?,
+            RootMoveStep::DeleteDescriptions => self.undo_delete_descriptions(db_session).await?,
+            RootMoveStep::DeleteAttachments => self.undo_delete_attachments(db_session).await?,
+            RootMoveStep::DeleteLikes => self.undo_delete_likes(db_session).await?,
+            RootMoveStep::UpdateElasticData => self.undo_update_elastic_data(data).await,
+            RootMoveStep::PruneOldRoot => self.undo_prune_old_root(db_session).await?,
+            // log and placeholder steps are handled by the saga executor
+            RootMoveStep::BeforeStart | RootMoveStep::Start | RootMoveStep::Finish | RootMoveStep::AfterFinish => (),
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn root_move(params: RootMoveParams, old_nodes: Vec<Node>) -> RootMove {
+        RootMove {
+            params,
+            node_ids: old_nodes.iter().map(|node| node.id).collect(),
+            old_nodes,
+            ..Default::default()
+        }
+    }
+
+    fn params() -> RootMoveParams {
+        RootMoveParams {
+            root_id: Uuid::new_v4(),
+            id: Uuid::new_v4(),
+            new_root_id: Uuid::new_v4(),
+            new_parent_id: Uuid::new_v4(),
+        }
+    }
+
+    #[test]
+    fn test_log_skips_records() {
+        let node = Node {
+            id: Uuid::new_v4(),
+            ..Default::default()
+        };
+        let root_move = root_move(params(), vec![node]);
+        let log = serde_json::to_value(&root_move).unwrap();
+        let mut fields = log.as_object().unwrap().keys().cloned().collect::<Vec<String>>();
+        fields.sort();
+
+        assert_eq!(
+            fields,
+            vec![
+                "move_step",
+                "node_ids",
+                "order_index",
+                "params",
+                "remaining_io_main_ids"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_build_flow_steps_clears_old_root_ios() {
+        let params = params();
+        let node_id = params.id;
+        let moved_io_id = Uuid::new_v4();
+        let old_root_io_id = Uuid::new_v4();
+        let mut root_move = root_move(params, vec![]);
+
+        root_move.node_ids = vec![node_id];
+
+        root_move.old_ios = vec![Io {
+            id: moved_io_id,
+            node_id,
+            ..Default::default()
+        }];
+        root_move.old_workflows = vec![Workflow {
+            node_id,
+            initial_input_ids: Some(vec![moved_io_id, old_root_io_id]),
+            ..Default::default()
+        }];
+        root_move.old_flow_steps = vec![FlowStep {
+            node_id,
+            input_ids_by_node_id: Some(HashMap::from([
+                (node_id, vec![moved_io_id, old_root_io_id]),
+                (Uuid::new_v4(), vec![old_root_io_id]),
+            ])),
+            output_ids_by_node_id: Some(HashMap::from([(node_id, vec![moved_io_id])])),
+            ..Default::default()
+        }];
+
+        root_move.build_workflows();
+        root_move.build_flow_steps();
+
+        assert_eq!(root_move.new_workflows[0].initial_input_ids, Some(vec![moved_io_id]));
+        assert_eq!(
+            root_move.new_flow_steps[0].input_ids_by_node_id,
+            Some(HashMap::from([(node_id, vec![moved_io_id])]))
+        );
+        assert_eq!(
+            root_move.new_flow_steps[0].output_ids_by_node_id,
+            Some(HashMap::from([(node_id, vec![moved_io_id])]))
+        );
+        assert_eq!(root_move.new_flow_steps[0].branch_id, root_move.params.new_root_id);
+    }
+
+    #[test]
+    fn test_prunes_moved_records_from_old_root() {
+        let params = params();
+        let moved_node_id = params.id;
+        let root_node_id = params.root_id;
+        let moved_io_id = Uuid::new_v4();
+        let root_io_id = Uuid::new_v4();
+        let moved_flow_step_id = Uuid::new_v4();
+        let root_flow_step_id = Uuid::new_v4();
+        let mut root_move = root_move(params, vec![]);
+
+        root_move.node_ids = vec![moved_node_id];
+
+        root_move.old_ios = vec![Io {
+            id: moved_io_id,
+            node_id: moved_node_id,
+            inputted_by_flow_steps: Some(Set::from([moved_flow_step_id, root_flow_step_id])),
+            ..Default::default()
+        }];
+        root_move.old_flow_steps = vec![FlowStep {
+            id: moved_flow_step_id,
+            node_id: moved_node_id,
+            node_ids: Some(vec![moved_node_id, root_node_id]),
+            input_ids_by_node_id: Some(HashMap::from([
+                (moved_node_id, vec![moved_io_id]),
+                (root_node_id, vec![root_io_id]),
+            ])),
+            ..Default::default()
+        }];
+
+        let root_flow_step = FlowStep {
+            id: root_flow_step_id,
+            node_id: root_node_id,
+            node_ids: Some(vec![root_node_id, moved_node_id]),
+            input_ids_by_node_id: Some(HashMap::from([
+                (root_node_id, vec![root_io_id, moved_io_id]),
+                (moved_node_id, vec![moved_io_id]),
+            ])),
+            output_ids_by_node_id: Some(HashMap::from([(moved_node_id, vec![Uuid::new_v4()])])),
+            ..Default::default()
+        };
+        let untouched_flow_step = FlowStep {
+            id: Uuid::new_v4(),
+            node_id: root_node_id,
+            node_ids: Some(vec![root_node_id]),
+            ..Default::default()
+        };
+        let root_io = Io {
+            id: root_io_id,
+            node_id: root_node_id,
+            inputted_by_flow_steps: Some(Set::from([moved_flow_step_id, root_flow_step_id])),
+            ..Default::default()
+        };
+        let root_workflow = Workflow {
+            node_id: root_node_id,
+            initial_input_ids: Some(vec![root_io_id, moved_io_id]),
+            ..Default::default()
+        };
+
+        root_move.build_flow_steps();
+        root_move.build_ios(vec![root_io.clone()]);
+        root_move.build_pruned_root_flow_steps(vec![
+            root_move.old_flow_steps[0].clone(),
+            root_flow_step,
+            untouched_flow_step,
+        ]);
+        root_move.build_pruned_root_workflows(vec![root_workflow]);
+        root_move.build_pruned_root_ios(vec![root_io]);
+
+        assert_eq!(root_move.pruned_root_flow_steps.len(), 1);
+
+        let pruned_flow_step = &root_move.pruned_root_flow_steps[0];
+
+        assert_eq!(pruned_flow_step.id, root_flow_step_id);
+        assert_eq!(pruned_flow_step.node_ids, Some(vec![root_node_id]));
+        assert_eq!(
+            pruned_flow_step.input_ids_by_node_id,
+            Some(HashMap::from([(root_node_id, vec![root_io_id])]))
+        );
+        assert_eq!(pruned_flow_step.output_ids_by_node_id, Some(HashMap::new()));
+        assert_eq!(root_move.old_root_flow_steps[0].id, root_flow_step_id);
+
+        assert_eq!(
+            root_move.pruned_root_ios[0].inputted_by_flow_steps,
+            Some(Set::from([root_flow_step_id]))
+        );
+        assert_eq!(
+            root_move.pruned_root_workflows[0].initial_input_ids,
+            Some(vec![root_io_id])
+        );
+
+        // moved records drop references to the old root as well
+        assert_eq!(root_move.new_flow_steps[0].node_ids, Some(vec![moved_node_id]));
+        assert_eq!(
+            root_move.new_flow_steps[0].input_ids_by_node_id,
+            Some(HashMap::from([(moved_node_id, vec![moved_io_id])]))
+        );
+        assert_eq!(
+            root_move.new_ios[0].inputted_by_flow_steps,
+            Some(Set::from([moved_flow_step_id]))
+        );
+    }
+
+    #[test]
+    fn test_ancestor_counter_changes() {
+        let params = params();
+        let old_root_id = params.root_id;
+        let old_parent_id = Uuid::new_v4();
+        let new_root_id = params.new_root_id;
+        let new_parent_id = params.new_parent_id;
+        let node = Node {
+            id: params.id,
+            branch_id: old_root_id,
+            root_id: old_root_id,
+            parent_id: Some(old_parent_id),
+            ancestor_ids: Some(Set::from([old_root_id, old_parent_id])),
+            ..Default::default()
+        };
+        let child = Node {
+            id: Uuid::new_v4(),
+            branch_id: old_root_id,
+            root_id: old_root_id,
+            parent_id: Some(node.id),
+            ancestor_ids: Some(Set::from([old_root_id, old_parent_id, node.id])),
+            ..Default::default()
+        };
+        let new_parent = Node {
+            id: new_parent_id,
+            branch_id: new_root_id,
+            root_id: new_root_id,
+            ancestor_ids: Some(Set::from([new_root_id])),
+            ..Default::default()
+        };
+        let mut root_move = root_move(params, vec![node, child]);
+
+        root_move.build_nodes(&new_parent, 0.0);
+
+        let mut changes = root_move.ancestor_counter_changes();
+        changes.sort();
+
+        let mut expected = vec![
+            (old_root_id, old_root_id, -2),
+            (old_root_id, old_parent_id, -2),
+            (new_root_id, new_root_id, 2),
+            (new_root_id, new_parent_id, 2),
+        ];
+        expected.sort();
+
+        assert_eq!(changes, expected);
+    }
+}
@@ -1,4 +1,5 @@
 use charybdis::batch::ModelBatch;
+use charybdis::operations::Find;
 use log::error;
 
 use crate::api::data::RequestData;
@@ -42,7 +43,31 @@ impl UpdateTitleNode {
 
     pub async fn update_branch(&self, data: &RequestData) -> Result<(), NodecosmosError> {
         if self.is_branch() {
-            Branch::update(data.db_session(), self.branch_id, BranchUpdate::EditNodeTitle(self.id)).await?;
+            Branch::update(data, self.branch_id, self.id, BranchUpdate::EditNodeTitle(self.id)).await?;
+            self.track_title_change(data).await?;
+        }
+
+        Ok(())
+    }
+
+    async fn track_title_change(&self, data: &RequestData) -> Result<(), NodecosmosError> {
+        let maybe_original = UpdateTitleNode {
+            branch_id: self.original_id(),
+            ..self.clone()
+        }
+        .maybe_find_by_primary_key()
+        .execute(data.db_session())
+        .await?;
+
+        if let Some(original) = maybe_original {
+            Branch::track_title_change(
+                data.db_session(),
+                self.branch_id,
+                self.id,
+                original.title,
+                self.title.clone(),
+            )
+            .await?;
         }
 
         Ok(())
@@ -15,6 +15,11 @@ pub enum NotificationType {
     MergeContributionRequest,
     NewComment,
     NewInvitation,
+    NewReview,
+    ReviewRequest,
+    MergeConflict,
+    MergeFailed,
+    Mention,
 }
 
 #[charybdis_model(
@@ -1,27 +1,51 @@
 use crate::api::data::RequestData;
 use crate::api::types::ActionTypes;
+use crate::constants::PAGE_SIZE;
 use crate::errors::NodecosmosError;
-use crate::models::branch::merge::BranchMerge;
-use crate::models::node::delete::NodeDelete;
-use crate::models::node::reorder::Reorder;
+use crate::models::branch::merge::{BranchMerge, MergeStep};
+use crate::models::branch::stack::{ParentMerge, ParentMergeStep};
+use crate::models::materialized_views::recoveries_by_status::RecoveriesByStatus;
+use crate::models::node::clone::{NodeClone, NodeCloneStep};
+use crate::models::node::delete::{NodeDelete, NodeDeleteStep};
+use crate::models::node::reorder::{Reorder, ReorderStep};
+use crate::models::node::root_move::{RootMove, RootMoveStep};
+use crate::models::saga::SagaStep;
 use crate::resources::resource_locker::ResourceLocker;
 use anyhow::Context;
+use charybdis::errors::CharybdisError;
 use charybdis::macros::charybdis_model;
-use charybdis::operations::{Delete, Insert, Update};
+use charybdis::model::BaseModel;
+use charybdis::operations::{Delete, Find, Insert, Update};
 use charybdis::options::Consistency;
-use charybdis::types::{Text, Timestamp, TinyInt, Uuid};
+use charybdis::scylla::{PagingState, PagingStateResponse};
+use charybdis::types::{Int, Text, Timestamp, TinyInt, Uuid};
 use futures::StreamExt;
 use scylla::client::caching_session::CachingSession;
 use serde::{Deserialize, Serialize};
 use std::fmt::{Display, Formatter};
+use std::future::Future;
 
 pub const RECOVERY_INTERVAL_MIN: i64 = 3;
 
+/// Failed recoveries are retried until this number of attempts. After that they need to be retried
+/// through the admin API.
+pub const MAX_RECOVERY_ATTEMPTS: i32 = 5;
+
+#[derive(PartialEq, strum_macros::Display, strum_macros::EnumString)]
+pub enum RecoveryStatus {
+    Pending,
+    Failed,
+    Abandoned,
+}
+
 #[derive(Deserialize)]
 pub enum RecoveryObjectType {
     NodeDelete = 0,
     Reorder = 1,
     Merge = 2,
+    NodeClone = 3,
+    RootMove = 4,
+    ParentMerge = 5,
 }
 
 impl Display for RecoveryObjectType {
@@ -30,17 +54,26 @@ impl Display for RecoveryObjectType {
             RecoveryObjectType::NodeDelete => write!(f, "NodeDelete"),
             RecoveryObjectType::Reorder => write!(f, "Reorder"),
             RecoveryObjectType::Merge => write!(f, "Merge"),
+            RecoveryObjectType::NodeClone => write!(f, "NodeClone"),
+            RecoveryObjectType::RootMove => write!(f, "RootMove"),
+            RecoveryObjectType::ParentMerge => write!(f, "ParentMerge"),
         }
     }
 }
 
-impl From<i8> for RecoveryObjectType {
-    fn from(value: i8) -> Self {
+impl RecoveryObjectType {
+    pub fn try_from_value(value: i8) -> Result<Self, NodecosmosError> {
         match value {
-            0 => RecoveryObjectType::NodeDelete,
-            1 => RecoveryObjectType::Reorder,
-            2 => RecoveryObjectType::Merge,
-            _ => panic!("Invalid recovery object type"),
+            0 => Ok(RecoveryObjectType::NodeDelete),
+            1 => Ok(RecoveryObjectType::Reorder),
+            2 => Ok(RecoveryObjectType::Merge),
+            3 => Ok(RecoveryObjectType::NodeClone),
+            4 => Ok(RecoveryObjectType::RootMove),
+            5 => Ok(RecoveryObjectType::ParentMerge),
+            _ => Err(NodecosmosError::InternalServerError(format!(
+                "Invalid RecoveryObjectType value: {}",
+                value
+            ))),
         }
     }
 }
@@ -49,7 +82,6 @@ impl From<i8> for RecoveryObjectType {
     table_name = recoveries,
     partition_keys = [branch_id],
     clustering_keys = [object_type, id],
-    global_secondary_indexes = [updated_at],
 )]
 #[derive(Default)]
 pub struct Recovery {
@@ -59,6 +91,10 @@ pub struct Recovery {
     pub id: Uuid,
     pub step: TinyInt,
     pub data: Text,
+    pub attempts: Option<Int>,
+    pub status: Option<Text>,
+    pub last_error: Option<Text>,
+    pub abandon_reason: Option<Text>,
 }
 
 impl Recovery {
@@ -70,22 +106,120 @@ impl Recovery {
             object_type: object_type as i8,
             id,
             data,
+            attempts: Some(0),
+            status: Some(RecoveryStatus::Pending.to_string()),
+            last_error: None,
+            abandon_reason: None,
+        }
+    }
+
+    /// Page of recovery entries for the admin listing.
+    pub async fn find_entries_page(
+        db_session: &CachingSession,
+        paging_state: PagingState,
+    ) -> Result<(Vec<Recovery>, PagingStateResponse), NodecosmosError> {
+        let (recoveries, paging_state_response) = Recovery::find_paged(Recovery::FIND_ALL_QUERY, (), paging_state)
+            .page_size(PAGE_SIZE)
+            .consistency(Consistency::All)
+            .execute(db_session)
+            .await?;
+        let recoveries: Result<Vec<Recovery>, CharybdisError> = recoveries.collect();
+
+        Ok((recoveries?, paging_state_response))
+    }
+
+    pub fn is_pending(&self) -> bool {
+        match &self.status {
+            Some(status) => status == &RecoveryStatus::Pending.to_string(),
+            None => true,
         }
     }
 
+    pub fn object_type_name(&self) -> String {
+        RecoveryObjectType::try_from_value(self.object_type)
+            .map(|object_type| object_type.to_string())
+            .unwrap_or_else(|_| format!("Unknown({})", self.object_type))
+    }
+
+    /// Name of the saga step the entry is at.
+    pub fn step_name(&self) -> String {
+        let object_type = match RecoveryObjectType::try_from_value(self.object_type) {
+            Ok(object_type) => object_type,
+            Err(_) => return format!("Unknown({})", self.step),
+        };
+        let step_name = match object_type {
+            RecoveryObjectType::NodeDelete => NodeDeleteStep::try_from_value(self.step).map(|s| format!("{:?}", s)),
+            RecoveryObjectType::Reorder => ReorderStep::try_from_value(self.step).map(|s| format!("{:?}", s)),
+            RecoveryObjectType::Merge => MergeStep::try_from_value(self.step).map(|s| format!("{:?}", s)),
+            RecoveryObjectType::NodeClone => NodeCloneStep::try_from_value(self.step).map(|s| format!("{:?}", s)),
+            RecoveryObjectType::RootMove => RootMoveStep::try_from_value(self.step).map(|s| format!("{:?}", s)),
+            RecoveryObjectType::ParentMerge => ParentMergeStep::try_from_value(self.step).map(|s| format!("{:?}", s)),
+        };
+
+        step_name.unwrap_or_else(|_| format!("Unknown({})", self.step))
+    }
+
+    /// Top level fields of the serialized saga. Nested values are replaced with their size, so large
+    /// payloads can be listed.
+    pub fn payload_summary(&self) -> serde_json::Value {
+        let payload = match serde_json::from_str::<serde_json::Value>(&self.data) {
+            Ok(serde_json::Value::Object(payload)) => payload,
+            Ok(_) | Err(_) => return serde_json::Value::Null,
+        };
+
+        let summary = payload
+            .into_iter()
+            .map(|(key, value)| {
+                let value = match value {
+                    serde_json::Value::Array(items) => serde_json::Value::String(format!("[{} items]", items.len())),
+                    serde_json::Value::Object(fields) => {
+                        serde_json::Value::String(format!("{{{} fields}}", fields.len()))
+                    }
+                    value => value,
+                };
+
+                (key, value)
+            })
+            .collect();
+
+        serde_json::Value::Object(summary)
+    }
+
     pub async fn run_recovery_task(data: &RequestData) -> Result<(), NodecosmosError> {
         log::info!("Running recovery task");
         let from_min_ago = chrono::Utc::now() - chrono::Duration::minutes(RECOVERY_INTERVAL_MIN);
         // 3 minutes should be enough for main processes to recover from a crash.
         // If the process is still down after 3 minutes, we can assume that the process is dead,
         // and we can recover the data from the log.
-        let mut recoveries = find_recovery!("updated_at <= ? ALLOW FILTERING", (from_min_ago,))
+        let mut pending_recoveries = RecoveriesByStatus::find_by_status(RecoveryStatus::Pending.to_string())
             .consistency(Consistency::All)
             .execute(data.db_session())
             .await?;
 
-        while let Some(recovery) = recoveries.next().await {
-            let recovery = recovery?;
+        while let Some(pending_recovery) = pending_recoveries.next().await {
+            let pending_recovery = pending_recovery?;
+
+            if pending_recovery.updated_at > from_min_ago {
+                continue;
+            }
+
+            // the view is updated asynchronously, so the entry could be already recovered or updated
+            let recovery = Recovery {
+                branch_id: pending_recovery.branch_id,
+                object_type: pending_recovery.object_type,
+                id: pending_recovery.id,
+                ..Default::default()
+            }
+            .maybe_find_by_primary_key()
+            .consistency(Consistency::All)
+            .execute(data.db_session())
+            .await?;
+
+            // failed and abandoned entries are handled through the admin API
+            let mut recovery = match recovery {
+                Some(recovery) if recovery.is_pending() => recovery,
+                _ => continue,
+            };
 
             let locker_res = data
                 .resource_locker()
@@ -111,44 +245,26 @@ impl Recovery {
                 )
                 .await?;
 
-            match recovery.object_type.into() {
-                RecoveryObjectType::NodeDelete => {
-                    let mut node_delete: NodeDelete =
-                        serde_json::from_str(&recovery.data).context("Failed to deserialize node delete data")?;
-                    node_delete.set_step(recovery.step);
-                    node_delete
-                        .recover_from_log(data)
-                        .await
-                        .context("Failed to recover NodeDelete from log")?;
-                }
-                RecoveryObjectType::Reorder => {
-                    let mut reorder: Reorder =
-                        serde_json::from_str(&recovery.data).context("Failed to deserialize reorder data")?;
-                    reorder.set_step(recovery.step);
-                    reorder
-                        .recover_from_log(data)
-                        .await
-                        .context("Failed to recover Reorder from log")?;
+            match recovery.recover(data).await {
+                Ok(_) => {
+                    log::info!(
+                        "Recovery completed for {} with object id: {}",
+                        recovery.object_type,
+                        recovery.id
+                    );
                 }
-                RecoveryObjectType::Merge => {
-                    let mut merge: BranchMerge =
-                        serde_json::from_str(&recovery.data).context("Failed to deserialize branch merge data")?;
-
-                    merge.set_step(recovery.step);
-
-                    merge
-                        .recover_from_log(data)
-                        .await
-                        .context("Failed to recover BranchMerge from log")?;
+                Err(e) => {
+                    log::error!(
+                        "Recovery failed for {} with object id: {}: {:?}",
+                        recovery.object_type,
+                        recovery.id,
+                        e
+                    );
+
+                    recovery.record_failure(data.db_session(), e.to_string()).await?;
                 }
             }
 
-            log::info!(
-                "Recovery completed for {} with object id: {}",
-                recovery.object_type,
-                recovery.id
-            );
-
             data.resource_locker()
                 .unlock_resource_actions(recovery.id, recovery.branch_id, &[ActionTypes::Recover])
                 .await?;
@@ -156,6 +272,136 @@ impl Recovery {
 
         Ok(())
     }
+
+    /// Runs the undo of the saga from the logged step back to `BeforeStart`. Sagas delete their log once
+    /// they are recovered.
+    pub async fn recover(&self, data: &RequestData) -> Result<(), NodecosmosError> {
+        match RecoveryObjectType::try_from_value(self.object_type)? {
+            RecoveryObjectType::NodeDelete => {
+                let mut node_delete: NodeDelete =
+                    serde_json::from_str(&self.data).context("Failed to deserialize node delete data")?;
+                node_delete.set_step(self.step)?;
+                node_delete
+                    .recover_from_log(data)
+                    .await
+                    .context("Failed to recover NodeDelete from log")?;
+            }
+            RecoveryObjectType::Reorder => {
+                let mut reorder: Reorder =
+                    serde_json::from_str(&self.data).context("Failed to deserialize reorder data")?;
+                reorder.set_step(self.step)?;
+                reorder
+                    .recover_from_log(data)
+                    .await
+                    .context("Failed to recover Reorder from log")?;
+            }
+            RecoveryObjectType::Merge => {
+                let mut merge: BranchMerge =
+                    serde_json::from_str(&self.data).context("Failed to deserialize branch merge data")?;
+
+                merge.set_step(self.step)?;
+
+                merge
+                    .recover_from_log(data)
+                    .await
+                    .context("Failed to recover BranchMerge from log")?;
+            }
+            RecoveryObjectType::NodeClone => {
+                let mut node_clone: NodeClone =
+                    serde_json::from_str(&self.data).context("Failed to deserialize node clone data")?;
+                node_clone.set_step(self.step)?;
+                node_clone
+                    .recover_from_log(data)
+                    .await
+                    .context("Failed to recover NodeClone from log")?;
+            }
+            RecoveryObjectType::RootMove => {
+                let mut root_move: RootMove =
+                    serde_json::from_str(&self.data).context("Failed to deserialize root move data")?;
+                root_move.set_step(self.step)?;
+                root_move
+                    .recover_from_log(data)
+                    .await
+                    .context("Failed to recover RootMove from log")?;
+            }
+            RecoveryObjectType::ParentMerge => {
+                let mut parent_merge: ParentMerge =
+                    serde_json::from_str(&self.data).context("Failed to deserialize parent merge data")?;
+                parent_merge.set_step(self.step)?;
+                parent_merge
+                    .recover_from_log(data)
+                    .await
+                    .context("Failed to recover ParentMerge from log")?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Counts the failed attempt. Entries that keep failing are marked as failed, so they are not retried
+    /// on every interval.
+    pub async fn record_failure(&mut self, db_session: &CachingSession, error: String) -> Result<(), NodecosmosError> {
+        // saga could have deleted the log before failing, and updating it would recreate the row
+        let current = DeleteRecovery {
+            branch_id: self.branch_id,
+            object_type: self.object_type,
+            id: self.id,
+        }
+        .maybe_find_by_primary_key()
+        .execute(db_session)
+        .await?;
+
+        if current.is_none() {
+            return Ok(());
+        }
+
+        let attempts = self.attempts.unwrap_or_default() + 1;
+        let status = if attempts >= MAX_RECOVERY_ATTEMPTS {
+            RecoveryStatus::Failed
+        } else {
+            RecoveryStatus::Pending
+        };
+
+        self.attempts = Some(attempts);
+        self.status = Some(status.to_string());
+        self.last_error = Some(error);
+
+        self.update_status(db_session).await
+    }
+
+    /// Puts the entry back to the recovery task with a fresh attempt count.
+    pub async fn retry(&mut self, db_session: &CachingSession) -> Result<(), NodecosmosError> {
+        self.attempts = Some(0);
+        self.status = Some(RecoveryStatus::Pending.to_string());
+        self.abandon_reason = None;
+
+        self.update_status(db_session).await
+    }
+
+    /// Stops recovering the entry. It's kept with the reason, so it can be inspected later.
+    pub async fn abandon(&mut self, db_session: &CachingSession, reason: String) -> Result<(), NodecosmosError> {
+        self.status = Some(RecoveryStatus::Abandoned.to_string());
+        self.abandon_reason = Some(reason);
+
+        self.update_status(db_session).await
+    }
+
+    async fn update_status(&self, db_session: &CachingSession) -> Result<(), NodecosmosError> {
+        UpdateStatusRecovery {
+            branch_id: self.branch_id,
+            object_type: self.object_type,
+            id: self.id,
+            attempts: self.attempts,
+            status: self.status.clone(),
+            last_error: self.last_error.clone(),
+            abandon_reason: self.abandon_reason.clone(),
+        }
+        .update()
+        .execute(db_session)
+        .await?;
+
+        Ok(())
+    }
 }
 
 /// Trait for recovering from a log. Before performing a SAGA operation, we serialize
@@ -170,20 +416,30 @@ pub trait RecoveryLog<'a>: Serialize + Deserialize<'a> {
 
     fn rec_object_type(&self) -> RecoveryObjectType;
 
-    fn set_step(&mut self, step: i8);
+    fn set_step(&mut self, step: i8) -> Result<(), NodecosmosError>;
 
     async fn recover_from_log(&mut self, data: &RequestData) -> Result<(), NodecosmosError>;
 
-    async fn create_recovery_log(&self, db_session: &CachingSession) -> Result<(), NodecosmosError> {
+    // Log futures don't borrow `self`, so they stay `Send` when awaited by the generic saga executor.
+    fn create_recovery_log(
+        &self,
+        db_session: &CachingSession,
+    ) -> impl Future<Output = Result<(), NodecosmosError>> + Send {
         let data = serde_json::to_string(self).expect("Failed to serialize branch merge data");
         let recovery = Recovery::new(self.rec_branch_id(), self.rec_object_type(), self.rec_id(), data);
 
-        recovery.insert().execute(db_session).await?;
+        async move {
+            recovery.insert().execute(db_session).await?;
 
-        Ok(())
+            Ok(())
+        }
     }
 
-    async fn update_recovery_log_step(&self, db_session: &CachingSession, step: i8) -> Result<(), NodecosmosError> {
+    fn update_recovery_log_step(
+        &self,
+        db_session: &CachingSession,
+        step: i8,
+    ) -> impl Future<Output = Result<(), NodecosmosError>> + Send {
         let recovery = UpdateStepRecovery {
             branch_id: self.rec_branch_id(),
             object_type: self.rec_object_type() as i8,
@@ -191,25 +447,61 @@ pub trait RecoveryLog<'a>: Serialize + Deserialize<'a> {
             step,
         };
 
-        recovery.update().execute(db_session).await?;
+        async move {
+            recovery.update().execute(db_session).await?;
 
-        Ok(())
+            Ok(())
+        }
     }
 
-    async fn delete_recovery_log(&self, db_session: &CachingSession) -> Result<(), NodecosmosError> {
-        DeleteRecovery {
+    fn delete_recovery_log(
+        &self,
+        db_session: &CachingSession,
+    ) -> impl Future<Output = Result<(), NodecosmosError>> + Send {
+        let recovery = DeleteRecovery {
             branch_id: self.rec_branch_id(),
             object_type: self.rec_object_type() as i8,
             id: self.rec_id(),
-        }
-        .delete()
-        .execute(db_session)
-        .await?;
+        };
 
-        Ok(())
+        async move {
+            recovery.delete().execute(db_session).await?;
+
+            Ok(())
+        }
     }
 }
 
 partial_recovery!(UpdateStepRecovery, branch_id, object_type, id, step);
 
 partial_recovery!(DeleteRecovery, branch_id, object_type, id);
+
+partial_recovery!(
+    UpdateStatusRecovery,
+    branch_id,
+    object_type,
+    id,
+    attempts,
+    status,
+    last_error,
+    abandon_reason
+);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_unknown_object_type() {
+        assert!(RecoveryObjectType::try_from_value(-1).is_err());
+
+        let recovery = Recovery {
+            object_type: 100,
+            step: 2,
+            ..Default::default()
+        };
+
+        assert_eq!(recovery.object_type_name(), "Unknown(100)");
+        assert_eq!(recovery.step_name(), "Unknown(2)");
+    }
+}
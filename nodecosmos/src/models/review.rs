@@ -0,0 +1,212 @@
+use std::collections::{HashMap, HashSet};
+
+use charybdis::callbacks::Callbacks;
+use charybdis::macros::charybdis_model;
+use charybdis::types::{Frozen, Text, Timestamp, Uuid};
+use scylla::client::caching_session::CachingSession;
+use serde::{Deserialize, Serialize};
+
+use crate::api::data::RequestData;
+use crate::errors::NodecosmosError;
+use crate::models::commit::Commit;
+use crate::models::contribution_request::{ContributionRequest, ContributionRequestStatus};
+use crate::models::notification::{Notification, NotificationType};
+use crate::models::udts::Profile;
+
+#[derive(PartialEq, Deserialize, strum_macros::Display, strum_macros::EnumString)]
+pub enum ReviewStatus {
+    Approve,
+    RequestChanges,
+    Comment,
+}
+
+impl ReviewStatus {
+    pub fn notification_text(&self) -> &str {
+        match self {
+            ReviewStatus::Approve => "approved contribution request",
+            ReviewStatus::RequestChanges => "requested changes on contribution request",
+            ReviewStatus::Comment => "reviewed contribution request",
+        }
+    }
+}
+
+/// Review of a contribution request. Reviews are append only, so the latest review of a reviewer
+/// is the one that counts.
+#[charybdis_model(
+    table_name = reviews,
+    partition_keys = [branch_id],
+    clustering_keys = [id],
+)]
+#[derive(Serialize, Deserialize, Default, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct Review {
+    /// Id of the contribution request.
+    pub branch_id: Uuid,
+
+    #[serde(default)]
+    pub id: Uuid,
+
+    /// Node of the contribution request.
+    pub node_id: Uuid,
+
+    #[serde(default)]
+    pub root_id: Uuid,
+
+    #[serde(default)]
+    pub reviewer_id: Uuid,
+
+    pub reviewer: Option<Frozen<Profile>>,
+    pub status: Text,
+
+    /// Comment thread of the review body.
+    pub thread_id: Option<Uuid>,
+
+    #[serde(default = "chrono::Utc::now")]
+    pub created_at: Timestamp,
+
+    #[serde(default = "chrono::Utc::now")]
+    pub updated_at: Timestamp,
+
+    #[charybdis(ignore)]
+    #[serde(skip)]
+    pub contribution_request: Option<ContributionRequest>,
+}
+
+impl Callbacks for Review {
+    type Extension = RequestData;
+    type Error = NodecosmosError;
+
+    async fn before_insert(&mut self, db_session: &CachingSession, data: &RequestData) -> Result<(), Self::Error> {
+        let now = chrono::Utc::now();
+
+        self.validate(data).await?;
+
+        self.root_id = self.contribution_request(db_session).await?.root_id;
+        self.id = Uuid::new_v4();
+        self.reviewer_id = data.current_user.id;
+        self.reviewer = Some((&data.current_user).into());
+        self.created_at = now;
+        self.updated_at = now;
+
+        Ok(())
+    }
+
+    async fn after_insert(&mut self, db_session: &CachingSession, data: &RequestData) -> Result<(), Self::Error> {
+        let text = self.review_status()?.notification_text().to_string();
+        let contribution_request = self.contribution_request(db_session).await?;
+        let id = contribution_request.id;
+        let title = contribution_request.title.clone();
+        let owner_id = contribution_request.owner_id;
+        let editor_ids = contribution_request.editor_ids.clone();
+        let root_id = contribution_request.root_id;
+        let node_id = contribution_request.node_id;
+        let data = data.clone();
+
+        tokio::spawn(async move {
+            let notification = Notification::new(
+                NotificationType::NewReview,
+                format!("{} - {}", text, title),
+                format!(
+                    "{client_url}/nodes/{original_id}/{node_id}/contribution_requests/{id}",
+                    client_url = &data.app.config.client_url,
+                    original_id = root_id,
+                    node_id = node_id,
+                    id = id
+                ),
+                Some((&data.current_user).into()),
+            );
+            let mut receiver_ids = HashSet::new();
+            receiver_ids.insert(owner_id);
+
+            if let Some(editor_ids) = editor_ids {
+                receiver_ids.extend(editor_ids);
+            }
+
+            let _ = notification
+                .create_for_receivers(&data, receiver_ids)
+                .await
+                .map_err(|e| {
+                    log::error!("Error creating notification for new review: {:?}", e);
+                });
+        });
+
+        Ok(())
+    }
+}
+
+impl Review {
+    pub fn review_status(&self) -> Result<ReviewStatus, NodecosmosError> {
+        self.status
+            .parse::<ReviewStatus>()
+            .map_err(|_| NodecosmosError::BadRequest(format!("Invalid review status: {}", self.status)))
+    }
+
+    pub async fn validate(&mut self, data: &RequestData) -> Result<(), NodecosmosError> {
+        let status = self.review_status()?;
+        let contribution_request = self.contribution_request(data.db_session()).await?;
+
+        if contribution_request.status != Some(ContributionRequestStatus::Published.to_string()) {
+            return Err(NodecosmosError::PreconditionFailed(
+                "Only published contribution requests can be reviewed",
+            ));
+        }
+
+        if status != ReviewStatus::Comment && contribution_request.owner_id == data.current_user.id {
+            return Err(NodecosmosError::Forbidden(
+                "You can not approve or request changes on your own contribution request".to_string(),
+            ));
+        }
+
+        Ok(())
+    }
+
+    pub async fn contribution_request(
+        &mut self,
+        db_session: &CachingSession,
+    ) -> Result<&mut ContributionRequest, NodecosmosError> {
+        if self.contribution_request.is_none() {
+            let contribution_request = ContributionRequest::find_by_node_id_and_id(self.node_id, self.branch_id)
+                .execute(db_session)
+                .await?;
+            self.contribution_request = Some(contribution_request);
+        }
+
+        self.contribution_request
+            .as_mut()
+            .ok_or_else(|| NodecosmosError::NotFound("Contribution request not found".to_string()))
+    }
+
+    /// Number of reviewers whose latest review is an approval. Approvals given before the latest change of the
+    /// branch are stale, so they don't count.
+    pub async fn approval_count(db_session: &CachingSession, branch_id: Uuid) -> Result<usize, NodecosmosError> {
+        let last_change_at = Commit::last_change_at(db_session, branch_id).await?;
+        let reviews: Vec<Review> = Review::find_by_branch_id(branch_id)
+            .execute(db_session)
+            .await?
+            .try_collect()
+            .await?;
+        let mut latest_by_reviewer: HashMap<Uuid, Review> = HashMap::new();
+
+        for review in reviews {
+            // comments don't change the review state of a reviewer
+            if review.status == ReviewStatus::Comment.to_string() {
+                continue;
+            }
+
+            match latest_by_reviewer.get(&review.reviewer_id) {
+                Some(latest) if latest.created_at >= review.created_at => (),
+                _ => {
+                    latest_by_reviewer.insert(review.reviewer_id, review);
+                }
+            }
+        }
+
+        Ok(latest_by_reviewer
+            .values()
+            .filter(|review| {
+                review.status == ReviewStatus::Approve.to_string()
+                    && last_change_at.is_none_or(|last_change_at| review.created_at >= last_change_at)
+            })
+            .count())
+    }
+}
@@ -0,0 +1,62 @@
+use charybdis::macros::charybdis_model;
+use charybdis::operations::Find;
+use charybdis::types::{Int, Timestamp, Uuid};
+use scylla::client::caching_session::CachingSession;
+use serde::{Deserialize, Serialize};
+
+use crate::errors::NodecosmosError;
+use crate::models::review::Review;
+
+/// Review requirements of contribution requests within a root node.
+#[charybdis_model(
+    table_name = review_settings,
+    partition_keys = [root_id],
+    clustering_keys = []
+)]
+#[derive(Serialize, Deserialize, Default, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct ReviewSettings {
+    pub root_id: Uuid,
+
+    /// Minimum number of approvals required to merge a contribution request.
+    #[serde(default)]
+    pub required_approvals: Int,
+
+    #[serde(default = "chrono::Utc::now")]
+    pub updated_at: Timestamp,
+}
+
+impl ReviewSettings {
+    pub async fn find_or_default(db_session: &CachingSession, root_id: Uuid) -> Result<Self, NodecosmosError> {
+        let settings = ReviewSettings {
+            root_id,
+            ..Default::default()
+        };
+        let maybe_settings = settings.maybe_find_by_primary_key().execute(db_session).await?;
+
+        Ok(maybe_settings.unwrap_or(settings))
+    }
+
+    pub async fn validate_approvals(
+        db_session: &CachingSession,
+        root_id: Uuid,
+        branch_id: Uuid,
+    ) -> Result<(), NodecosmosError> {
+        let settings = Self::find_or_default(db_session, root_id).await?;
+
+        if settings.required_approvals <= 0 {
+            return Ok(());
+        }
+
+        let approval_count = Review::approval_count(db_session, branch_id).await?;
+
+        if approval_count < settings.required_approvals as usize {
+            return Err(NodecosmosError::Forbidden(format!(
+                "Contribution request has {} of {} required approvals",
+                approval_count, settings.required_approvals
+            )));
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,137 @@
+use std::fmt::Debug;
+use std::future::Future;
+
+use crate::api::data::RequestData;
+use crate::errors::NodecosmosError;
+use crate::models::recovery::RecoveryLog;
+
+/// Step of a saga. Steps are executed in order of their values from `START` to `FINISH` and compensated in
+/// reverse order. Values are stored in the recovery log, so existing steps must keep their values.
+pub trait SagaStep: Copy + PartialOrd + Debug + Send {
+    const START: Self;
+    const FINISH: Self;
+
+    fn value(self) -> i8;
+
+    fn try_from_value(value: i8) -> Result<Self, NodecosmosError>;
+
+    fn next(self) -> Result<Self, NodecosmosError> {
+        Self::try_from_value(self.value() + 1)
+    }
+
+    fn prev(self) -> Result<Self, NodecosmosError> {
+        Self::try_from_value(self.value() - 1)
+    }
+}
+
+/// Defines step enum of a saga. `BeforeStart` and `Start` are generated, the rest of the steps are given with
+/// their values, which have to follow each other and end with `Finish` and `AfterFinish`. `BeforeStart` and
+/// `AfterFinish` are the placeholders the saga ends at after it's compensated or finished.
+macro_rules! saga_steps {
+    ($(#[$meta:meta])* $vis:vis enum $name:ident { $($step:ident = $value:literal),* $(,)? }) => {
+        $(#[$meta])*
+        #[derive(Clone, Copy, Default, serde::Serialize, serde::Deserialize, PartialOrd, PartialEq, Debug)]
+        #[repr(i8)]
+        $vis enum $name {
+            BeforeStart = -1,
+            #[default]
+            Start = 0,
+            $($step = $value,)*
+        }
+
+        impl crate::models::saga::SagaStep for $name {
+            const START: Self = $name::Start;
+            const FINISH: Self = $name::Finish;
+
+            fn value(self) -> i8 {
+                self as i8
+            }
+
+            fn try_from_value(value: i8) -> Result<Self, crate::errors::NodecosmosError> {
+                [$name::BeforeStart, $name::Start, $($name::$step,)*]
+                    .into_iter()
+                    .find(|step| *step as i8 == value)
+                    .ok_or_else(|| {
+                        crate::errors::NodecosmosError::InternalServerError(format!(
+                            "Invalid {} value: {}",
+                            stringify!($name),
+                            value
+                        ))
+                    })
+            }
+        }
+    };
+}
+
+pub(crate) use saga_steps;
+
+/// ScyllaDB does not support transactions across tables, so multi-step operations are run as sagas.
+/// Saga logs its state before the first step and its progress before each step. If a step fails,
+/// completed steps are compensated in reverse order. If the process dies, the recovery task compensates
+/// the saga from the log.
+pub trait Saga: RecoveryLog<'static> + Send {
+    type Step: SagaStep;
+
+    fn step(&self) -> Self::Step;
+
+    fn set_saga_step(&mut self, step: Self::Step);
+
+    /// Step futures are required to be `Send`, as sagas are run from spawned tasks.
+    fn execute(
+        &mut self,
+        data: &RequestData,
+        step: Self::Step,
+    ) -> impl Future<Output = Result<(), NodecosmosError>> + Send;
+
+    fn compensate(
+        &mut self,
+        data: &RequestData,
+        step: Self::Step,
+    ) -> impl Future<Output = Result<(), NodecosmosError>> + Send;
+
+    /// Executes steps from the current one to the end. Log is deleted once all steps are executed.
+    fn run_saga(&mut self, data: &RequestData) -> impl Future<Output = Result<(), NodecosmosError>> + Send {
+        async move {
+            while self.step() <= Self::Step::FINISH {
+                let step = self.step();
+
+                if step == Self::Step::START {
+                    self.create_recovery_log(data.db_session()).await?;
+                } else if step == Self::Step::FINISH {
+                    self.delete_recovery_log(data.db_session()).await?;
+                } else if step > Self::Step::START {
+                    self.update_recovery_log_step(data.db_session(), step.value()).await?;
+                    self.execute(data, step).await?;
+                } else {
+                    log::error!("should not execute before placeholder");
+                }
+
+                self.set_saga_step(step.next()?);
+            }
+
+            Ok(())
+        }
+    }
+
+    /// Compensates steps from the current one back to the start. Log is deleted once all steps are compensated.
+    fn compensate_saga(&mut self, data: &RequestData) -> impl Future<Output = Result<(), NodecosmosError>> + Send {
+        async move {
+            while self.step() >= Self::Step::START {
+                let step = self.step();
+
+                if step == Self::Step::START {
+                    self.delete_recovery_log(data.db_session()).await?;
+                } else if step < Self::Step::FINISH {
+                    self.update_recovery_log_step(data.db_session(), step.value()).await?;
+                    self.compensate(data, step).await?;
+                } else {
+                    log::error!("should not recover finished process");
+                }
+
+                self.set_saga_step(step.prev()?);
+            }
+
+            Ok(())
+        }
+    }
+}
@@ -6,6 +6,7 @@ use crate::api::data::RequestData;
 use crate::api::request::current_user::OptCurrentUser;
 use crate::errors::NodecosmosError;
 use crate::models::branch::{AuthBranch, Branch};
+use crate::models::branch_protection::BranchProtection;
 use crate::models::comment::Comment;
 use crate::models::comment_thread::{CommentThread, ThreadObjectType};
 use crate::models::contribution_request::ContributionRequest;
@@ -22,6 +23,11 @@ pub trait Authorization: AuthorizationFields {
 
     async fn auth_creation(&mut self, _data: &RequestData) -> Result<(), NodecosmosError>;
 
+    /// Runs after `can_edit` on updates and deletes, so editors can still be restricted from changing the object.
+    async fn auth_protection(&mut self, _data: &RequestData) -> Result<(), NodecosmosError> {
+        Ok(())
+    }
+
     fn can_edit(&mut self, data: &RequestData) -> bool {
         if self.owner_id() == Some(data.current_user.id) {
             return true;
@@ -39,6 +45,15 @@ pub trait Authorization: AuthorizationFields {
     }
 
     async fn auth_update(&mut self, data: &RequestData) -> Result<(), NodecosmosError> {
+        self.auth_edit_access(data).await?;
+        self.auth_protection(data).await?;
+
+        Ok(())
+    }
+
+    /// Authorizes edit access without `auth_protection`. To be used only for actions that don't change the object
+    /// itself, like merging and reviewing its contribution requests or managing its tasks.
+    async fn auth_edit_access(&mut self, data: &RequestData) -> Result<(), NodecosmosError> {
         if !data.current_user.is_confirmed {
             return Err(NodecosmosError::Unauthorized("User is not confirmed"));
         }
@@ -87,6 +102,8 @@ pub trait Authorization: AuthorizationFields {
             ));
         }
 
+        self.auth_protection(data).await?;
+
         Ok(())
     }
 
@@ -157,6 +174,15 @@ macro_rules! imp_authorization_for_node_types {
                 Ok(())
             }
 
+            /// Originals of protected roots can only be changed directly by maintainers.
+            async fn auth_protection(&mut self, data: &RequestData) -> Result<(), NodecosmosError> {
+                if self.is_original() {
+                    BranchProtection::validate_direct_edit(data, self.branch_id, self.original_id()).await?;
+                }
+
+                Ok(())
+            }
+
             async fn auth_creation(&mut self, data: &RequestData) -> Result<(), NodecosmosError> {
                 if !data.current_user.is_confirmed {
                     return Err(NodecosmosError::Unauthorized("User is not confirmed"));
@@ -274,6 +300,15 @@ impl Authorization for CommentThread {
     }
 
     async fn auth_update(&mut self, data: &RequestData) -> Result<(), NodecosmosError> {
+        self.auth_edit_access(data).await?;
+        self.auth_protection(data).await?;
+
+        Ok(())
+    }
+
+    /// Authorizes edit access without `auth_protection`. To be used only for actions that don't change the object
+    /// itself, like merging and reviewing its contribution requests or managing its tasks.
+    async fn auth_edit_access(&mut self, data: &RequestData) -> Result<(), NodecosmosError> {
         if !data.current_user.is_confirmed {
             return Err(NodecosmosError::Unauthorized("User is not confirmed"));
         }
@@ -290,7 +325,7 @@ impl Authorization for CommentThread {
             Ok(ThreadObjectType::Thread) => {
                 let node = self.node(data.db_session()).await?;
 
-                node.auth_update(data).await
+                node.auth_edit_access(data).await
             }
             Err(e) => Err(NodecosmosError::NotFound(format!(
                 "Error getting thread object_type: {}",
@@ -325,7 +360,7 @@ impl Authorization for Invitation {
     }
 
     async fn auth_creation(&mut self, data: &RequestData) -> Result<(), NodecosmosError> {
-        self.node(data.db_session()).await?.auth_update(data).await?;
+        self.node(data.db_session()).await?.auth_edit_access(data).await?;
 
         Ok(())
     }
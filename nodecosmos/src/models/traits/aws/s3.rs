@@ -44,6 +44,19 @@ pub trait S3: Sized {
         Ok(())
     }
 
+    async fn copy_s3_object(&self, data: &RequestData, source_key: &str) -> Result<(), NodecosmosError> {
+        data.s3_client()
+            .copy_object()
+            .copy_source(format!("{}/{}", data.s3_bucket(), source_key))
+            .key(self.s3_key())
+            .bucket(data.s3_bucket())
+            .send()
+            .await
+            .map_err(|e| NodecosmosError::InternalServerError(format!("Failed to copy S3 object: {:?}", e)))?;
+
+        Ok(())
+    }
+
     async fn delete_s3_object(&self, data: &RequestData) -> Result<(), NodecosmosError> {
         let s3_key = self.s3_key().clone();
         let s3_bucket = data.s3_bucket().clone();
@@ -34,7 +34,24 @@ impl Clean for Option<String> {
             sanitizer
                 .add_tag_attributes("img", &["resizable"])
                 .add_tag_attributes("pre", &["spellcheck", "class"])
-                .add_tag_attributes("code", &["spellcheck", "data-code-block-language"])
+                .add_tag_attributes("code", &["spellcheck", "class", "data-code-block-language"])
+                .add_tags(&["input"])
+                .add_tag_attributes("input", &["checked"])
+                .set_tag_attribute_value("input", "type", "checkbox")
+                .set_tag_attribute_value("input", "disabled", "")
+                .add_tag_attributes("ul", &["data-type"])
+                .add_tag_attributes("li", &["data-type", "data-checked"])
+                .add_tag_attributes(
+                    "span",
+                    &[
+                        "data-type",
+                        "data-id",
+                        "data-label",
+                        "data-object-type",
+                        "data-object-id",
+                    ],
+                )
+                .add_tag_attributes("div", &["data-type", "data-latex", "data-callout-type"])
                 .set_tag_attribute_value("a", "rel", "noopener noreferrer nofollow")
                 .set_tag_attribute_value("a", "target", "_blank")
                 .link_rel(None);
@@ -5,6 +5,8 @@ use scylla::client::caching_session::CachingSession;
 
 use crate::api::data::RequestData;
 use crate::errors::NodecosmosError;
+use crate::models::branch::stack::validate_not_deleted;
+use crate::models::branch::Branch;
 use crate::models::description::{find_description, Description};
 use crate::models::flow::{find_flow, find_update_title_flow, Flow, UpdateTitleFlow};
 use crate::models::flow_step::{
@@ -13,7 +15,7 @@ use crate::models::flow_step::{
     UpdateOutputIdsFlowStep,
 };
 use crate::models::node::{BaseNode, GetStructureNode, Node, UpdateTitleNode};
-use crate::models::traits::{ModelContext, WhereInChunksExec};
+use crate::models::traits::{ModelContext, ObjectType, WhereInChunksExec};
 use crate::stream::MergedModelStream;
 
 pub trait FindBranchedOrOriginalNode: Model {
@@ -44,9 +46,26 @@ macro_rules! impl_find_branched_or_original_node {
                     {
                         Some(node) => Ok(node),
                         None => {
+                            // stacked branches read through their parents before the original
+                            let parents = Branch::parent_branches(db_session, params.branch_id).await?;
+
+                            for (index, parent) in parents.iter().enumerate() {
+                                if let Some(mut node) =
+                                    Self::maybe_find_first_by_branch_id_and_id(parent.id, params.node_id)
+                                        .execute(db_session)
+                                        .await?
+                                {
+                                    validate_not_deleted(&parents[..=index], ObjectType::Node, node.id, node.id)?;
+                                    node.branch_id = params.branch_id;
+
+                                    return Ok(node);
+                                }
+                            }
+
                             let mut node = Self::find_by_branch_id_and_id(params.original_id(), params.node_id)
                                 .execute(db_session)
                                 .await?;
+                            validate_not_deleted(&parents, ObjectType::Node, node.id, node.id)?;
                             node.branch_id = params.branch_id;
 
                             Ok(node)
@@ -71,7 +90,7 @@ pub trait FindBranchedOrOriginal: Model {
 }
 
 macro_rules! impl_find_branched_or_original {
-    ($struct_name:ident) => {
+    ($struct_name:ident, $object_type:expr) => {
         impl FindBranchedOrOriginal for $struct_name {
             async fn find_branched_or_original(
                 db_session: &CachingSession,
@@ -91,9 +110,25 @@ macro_rules! impl_find_branched_or_original {
                     {
                         Some(model) => Ok(model),
                         None => {
+                            let parents = Branch::parent_branches(db_session, params.branch_id).await?;
+
+                            for (index, parent) in parents.iter().enumerate() {
+                                if let Some(mut model) =
+                                    Self::maybe_find_first_by_branch_id_and_id(parent.id, params.id)
+                                        .execute(db_session)
+                                        .await?
+                                {
+                                    validate_not_deleted(&parents[..=index], $object_type, model.id, model.node_id)?;
+                                    model.branch_id = params.branch_id;
+
+                                    return Ok(model);
+                                }
+                            }
+
                             let mut model = Self::find_first_by_branch_id_and_id(params.original_id, params.id)
                                 .execute(db_session)
                                 .await?;
+                            validate_not_deleted(&parents, $object_type, model.id, model.node_id)?;
                             model.branch_id = params.branch_id;
 
                             Ok(model)
@@ -105,8 +140,8 @@ macro_rules! impl_find_branched_or_original {
     };
 }
 
-impl_find_branched_or_original!(Flow);
-impl_find_branched_or_original!(FlowStep);
+impl_find_branched_or_original!(Flow, ObjectType::Flow);
+impl_find_branched_or_original!(FlowStep, ObjectType::FlowStep);
 
 pub trait FindOriginalOrBranched: Model {
     async fn find_original_or_branched(
@@ -149,6 +184,7 @@ macro_rules! impl_find_original_or_branched {
     };
 }
 
+impl_find_original_or_branched!(Flow);
 impl_find_original_or_branched!(FlowStep);
 
 pub trait FindOrInsertBranched: Model {
@@ -172,9 +208,31 @@ impl FindOrInsertBranched for Node {
         match node {
             Some(node) => Ok(node),
             None => {
-                let mut node = Self::find_by_primary_key_value((params.original_id, params.id))
-                    .execute(data.db_session())
-                    .await?;
+                let mut node = None;
+                let parents = Branch::parent_branches(data.db_session(), params.branch_id).await?;
+
+                for (index, parent) in parents.iter().enumerate() {
+                    node = Self::maybe_find_by_primary_key_value((parent.id, params.id))
+                        .execute(data.db_session())
+                        .await?;
+
+                    if node.is_some() {
+                        validate_not_deleted(&parents[..=index], ObjectType::Node, params.id, params.id)?;
+                        break;
+                    }
+                }
+
+                let mut node = match node {
+                    Some(node) => node,
+                    None => {
+                        let node = Self::find_by_primary_key_value((params.original_id, params.id))
+                            .execute(data.db_session())
+                            .await?;
+                        validate_not_deleted(&parents, ObjectType::Node, node.id, node.id)?;
+
+                        node
+                    }
+                };
 
                 node.set_branched_init_context();
                 node.branch_id = params.branch_id;
@@ -191,7 +249,7 @@ impl FindOrInsertBranched for Node {
 }
 
 macro_rules! find_or_insert_branched {
-    ($struct:ident) => {
+    ($struct:ident, $object_type:expr) => {
         impl FindOrInsertBranched for $struct {
             async fn find_or_insert_branched(
                 data: &RequestData,
@@ -213,9 +271,31 @@ macro_rules! find_or_insert_branched {
                     if let Some(branched) = maybe_branched {
                         Ok(branched)
                     } else {
-                        let mut new_branched = Self::find_first_by_branch_id_and_id(params.original_id, params.id)
-                            .execute(data.db_session())
-                            .await?;
+                        let mut maybe_parent_branched = None;
+                        let parents = Branch::parent_branches(data.db_session(), params.branch_id).await?;
+                        let mut layer_parents = &parents[..];
+
+                        for (index, parent) in parents.iter().enumerate() {
+                            maybe_parent_branched = Self::maybe_find_first_by_branch_id_and_id(parent.id, params.id)
+                                .execute(data.db_session())
+                                .await?;
+
+                            if maybe_parent_branched.is_some() {
+                                layer_parents = &parents[..=index];
+                                break;
+                            }
+                        }
+
+                        let mut new_branched = match maybe_parent_branched {
+                            Some(parent_branched) => parent_branched,
+                            None => {
+                                Self::find_first_by_branch_id_and_id(params.original_id, params.id)
+                                    .execute(data.db_session())
+                                    .await?
+                            }
+                        };
+
+                        validate_not_deleted(layer_parents, $object_type, new_branched.id, new_branched.node_id)?;
 
                         new_branched.branch_id = params.branch_id;
                         new_branched.set_branched_init_context();
@@ -230,8 +310,8 @@ macro_rules! find_or_insert_branched {
     };
 }
 
-find_or_insert_branched!(Flow);
-find_or_insert_branched!(FlowStep);
+find_or_insert_branched!(Flow, ObjectType::Flow);
+find_or_insert_branched!(FlowStep, ObjectType::FlowStep);
 
 pub trait FindForBranchMerge: Model
 where
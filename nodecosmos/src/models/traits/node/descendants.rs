@@ -31,7 +31,7 @@ macro_rules! impl_descendants {
                 Ok(descendants)
             }
 
-            /// combine descendants from original and branched nodes
+            /// combine descendants from original, parent branches and branched nodes
             async fn branch_descendants(
                 &self,
                 db_session: &CachingSession,
@@ -56,17 +56,33 @@ macro_rules! impl_descendants {
                         .try_collect()
                         .await?;
 
-                let mut branched_ids = HashSet::with_capacity(branched.len());
-                let mut descendants = Vec::with_capacity(original.len() + branched.len());
+                let mut layers = vec![(branched, None)];
 
-                for descendant in branched {
-                    branched_ids.insert(descendant.id);
-                    descendants.push(descendant);
+                // stacked branches see descendants of their parents
+                for parent in crate::models::branch::Branch::parent_branches(db_session, self.branch_id).await? {
+                    let parent_branched =
+                        NodeDescendant::find_by_root_id_and_branch_id_and_node_id(self.root_id, parent.id, self.id)
+                            .execute(db_session)
+                            .await?
+                            .try_collect()
+                            .await?;
+
+                    layers.push((parent_branched, parent.deleted_nodes));
                 }
 
-                for descendant in original {
-                    if !branched_ids.contains(&descendant.id) {
-                        descendants.push(descendant);
+                layers.push((original, None));
+
+                // nodes deleted within a parent branch are not read from layers below it
+                let mut seen_ids = HashSet::new();
+                let mut descendants = Vec::with_capacity(layers.iter().map(|(layer, _)| layer.len()).sum());
+
+                for (layer, deleted_ids) in layers {
+                    seen_ids.extend(deleted_ids.into_iter().flatten());
+
+                    for descendant in layer {
+                        if seen_ids.insert(descendant.id) {
+                            descendants.push(descendant);
+                        }
                     }
                 }
 
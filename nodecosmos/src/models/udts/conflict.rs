@@ -14,4 +14,7 @@ pub struct Conflict {
 
     /// Flow steps with same `step_index` on original and branch
     pub conflicting_flow_steps: Option<Frozen<Set<Uuid>>>,
+
+    /// Nodes, flows and ios whose original title was changed since it was edited in the branch
+    pub edited_titles: Option<Frozen<Set<Uuid>>>,
 }
@@ -0,0 +1,430 @@
+use crate::errors::NodecosmosError;
+use crate::models::description::Description;
+use crate::models::traits::Clean;
+use crate::models::utils::{DescriptionHtmlToXml, DescriptionXmlParser, DescriptionYDocParser};
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use quick_xml::events::Event;
+use quick_xml::Reader;
+use serde::Serialize;
+use yrs::updates::decoder::Decode;
+use yrs::{Doc, GetString, Transact, Update, XmlFragment, XmlOut};
+
+/// Top level block of the description document.
+struct Block {
+    tag: String,
+
+    /// Serialized block. Blocks are compared by content, as the same content has different Yjs items within
+    /// documents that are edited separately.
+    xml: String,
+
+    html: String,
+}
+
+#[derive(Serialize, Clone, Copy, PartialEq, Debug)]
+#[serde(rename_all = "camelCase")]
+pub enum BlockDiffOp {
+    Insert,
+    Delete,
+    Modify,
+}
+
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct BlockDiff {
+    pub op: BlockDiffOp,
+
+    /// Index of the block within the original document.
+    pub original_index: Option<usize>,
+
+    /// Index of the block within the branched document.
+    pub branch_index: Option<usize>,
+
+    pub original_html: Option<String>,
+    pub branch_html: Option<String>,
+}
+
+enum Step {
+    Equal(usize),
+    Delete(usize),
+    Insert(usize),
+}
+
+/// Block level diff of the branched description against the original one.
+#[derive(Serialize, Default, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct DescriptionDiff {
+    pub ops: Vec<BlockDiff>,
+
+    /// Branched document where inserted blocks are wrapped in `<ins>` and deleted blocks in `<del>`. Modified
+    /// blocks are rendered as deleted original block followed by inserted branched block.
+    pub html: String,
+}
+
+impl DescriptionDiff {
+    pub fn new(original: &Description, branch: &Description) -> Result<Self, NodecosmosError> {
+        let original = Self::blocks(original)?;
+        let branch = Self::blocks(branch)?;
+
+        Ok(Self::from_blocks(&original, &branch))
+    }
+
+    /// Blocks of the Yjs document. Descriptions without Yjs state, e.g. imported ones, are read from their html.
+    fn blocks(description: &Description) -> Result<Vec<Block>, NodecosmosError> {
+        let Some(base64) = &description.base64 else {
+            return match &description.html {
+                Some(html) => Self::html_blocks(html),
+                None => Ok(vec![]),
+            };
+        };
+
+        let buf = STANDARD.decode(base64)?;
+        let update = Update::decode_v2(&buf)?;
+        let doc = Doc::new();
+        let xml = doc.get_or_insert_xml_fragment(Description::DESCRIPTION_ROOT);
+
+        let mut transaction = doc.transact_mut();
+        transaction.apply_update(update)?;
+
+        let mut blocks = vec![];
+
+        for child in xml.children(&transaction) {
+            if let XmlOut::Element(element) = child {
+                let tag = element.tag().to_string();
+                let xml = element.get_string(&transaction);
+                let mut html = Some(DescriptionYDocParser::new().run_block(&transaction, element)?.html);
+
+                // diff markup is added around sanitized blocks, so the resulting html needs no further cleaning
+                html.clean()?;
+
+                blocks.push(Block {
+                    tag,
+                    xml,
+                    html: html.unwrap_or_default(),
+                });
+            }
+        }
+
+        Ok(blocks)
+    }
+
+    /// Blocks of the `<description>` xml converted from html. Block html is rendered from its xml, so it matches
+    /// the html of Yjs blocks.
+    fn html_blocks(html: &str) -> Result<Vec<Block>, NodecosmosError> {
+        let xml = DescriptionHtmlToXml::new(html).run()?.xml;
+        let mut reader = Reader::from_str(&xml);
+        let mut blocks = vec![];
+        let mut depth = 0;
+        let mut block_start = 0;
+        let mut tag = String::new();
+
+        loop {
+            let position = reader.buffer_position() as usize;
+
+            match reader.read_event()? {
+                Event::Start(e) => {
+                    depth += 1;
+
+                    // depth 1 is the `<description>` root
+                    if depth == 2 {
+                        block_start = position;
+                        tag = String::from_utf8_lossy(e.name().as_ref()).to_string();
+                    }
+                }
+                Event::End(_) => {
+                    if depth == 2 {
+                        let block_xml = xml[block_start..reader.buffer_position() as usize].to_string();
+                        let mut html = Some(DescriptionXmlParser::new(&block_xml).run()?.html);
+
+                        html.clean()?;
+
+                        blocks.push(Block {
+                            tag: std::mem::take(&mut tag),
+                            xml: block_xml,
+                            html: html.unwrap_or_default(),
+                        });
+                    }
+
+                    depth -= 1;
+                }
+                Event::Eof => break,
+                _ => (),
+            }
+        }
+
+        Ok(blocks)
+    }
+
+    fn from_blocks(original: &[Block], branch: &[Block]) -> Self {
+        let mut diff = Self::default();
+        let mut deleted = vec![];
+        let mut inserted = vec![];
+
+        for step in Self::steps(original, branch) {
+            match step {
+                Step::Equal(branch_index) => {
+                    diff.flush(original, branch, &mut deleted, &mut inserted);
+                    diff.html.push_str(&branch[branch_index].html);
+                }
+                Step::Delete(original_index) => deleted.push(original_index),
+                Step::Insert(branch_index) => inserted.push(branch_index),
+            }
+        }
+
+        diff.flush(original, branch, &mut deleted, &mut inserted);
+
+        diff
+    }
+
+    /// Shortest edit script between blocks. Common prefix and suffix are skipped before the LCS is built, as
+    /// branches usually change few blocks of large documents.
+    fn steps(original: &[Block], branch: &[Block]) -> Vec<Step> {
+        let prefix = original
+            .iter()
+            .zip(branch)
+            .take_while(|(original, branch)| original.xml == branch.xml)
+            .count();
+        let suffix = original[prefix..]
+            .iter()
+            .rev()
+            .zip(branch[prefix..].iter().rev())
+            .take_while(|(original, branch)| original.xml == branch.xml)
+            .count();
+        let old = &original[prefix..original.len() - suffix];
+        let new = &branch[prefix..branch.len() - suffix];
+
+        // lengths of the longest common subsequences of the block suffixes
+        let mut lcs = vec![vec![0u32; new.len() + 1]; old.len() + 1];
+
+        for i in (0..old.len()).rev() {
+            for j in (0..new.len()).rev() {
+                lcs[i][j] = if old[i].xml == new[j].xml {
+                    lcs[i + 1][j + 1] + 1
+                } else {
+                    lcs[i + 1][j].max(lcs[i][j + 1])
+                };
+            }
+        }
+
+        let mut steps: Vec<Step> = (0..prefix).map(Step::Equal).collect();
+        let (mut i, mut j) = (0, 0);
+
+        while i < old.len() || j < new.len() {
+            if i < old.len() && j < new.len() && old[i].xml == new[j].xml {
+                steps.push(Step::Equal(prefix + j));
+                i += 1;
+                j += 1;
+            } else if i < old.len() && (j == new.len() || lcs[i + 1][j] >= lcs[i][j + 1]) {
+                steps.push(Step::Delete(prefix + i));
+                i += 1;
+            } else {
+                steps.push(Step::Insert(prefix + j));
+                j += 1;
+            }
+        }
+
+        steps.extend((branch.len() - suffix..branch.len()).map(Step::Equal));
+
+        steps
+    }
+
+    /// Records blocks changed between two equal blocks. Deleted block is paired with the next inserted block of
+    /// the same type as modified block.
+    fn flush(&mut self, original: &[Block], branch: &[Block], deleted: &mut Vec<usize>, inserted: &mut Vec<usize>) {
+        let mut next_inserted = 0;
+
+        for &original_index in deleted.iter() {
+            let pair = inserted[next_inserted..]
+                .iter()
+                .position(|&branch_index| branch[branch_index].tag == original[original_index].tag);
+
+            match pair {
+                Some(offset) => {
+                    for &branch_index in &inserted[next_inserted..next_inserted + offset] {
+                        self.insert(&branch[branch_index], branch_index);
+                    }
+
+                    let branch_index = inserted[next_inserted + offset];
+
+                    self.modify(
+                        &original[original_index],
+                        original_index,
+                        &branch[branch_index],
+                        branch_index,
+                    );
+
+                    next_inserted += offset + 1;
+                }
+                None => self.delete(&original[original_index], original_index),
+            }
+        }
+
+        for &branch_index in &inserted[next_inserted..] {
+            self.insert(&branch[branch_index], branch_index);
+        }
+
+        deleted.clear();
+        inserted.clear();
+    }
+
+    fn insert(&mut self, block: &Block, branch_index: usize) {
+        self.html
+            .push_str(&format!("<ins data-diff=\"insert\">{}</ins>", block.html));
+        self.ops.push(BlockDiff {
+            op: BlockDiffOp::Insert,
+            original_index: None,
+            branch_index: Some(branch_index),
+            original_html: None,
+            branch_html: Some(block.html.clone()),
+        });
+    }
+
+    fn delete(&mut self, block: &Block, original_index: usize) {
+        self.html
+            .push_str(&format!("<del data-diff=\"delete\">{}</del>", block.html));
+        self.ops.push(BlockDiff {
+            op: BlockDiffOp::Delete,
+            original_index: Some(original_index),
+            branch_index: None,
+            original_html: Some(block.html.clone()),
+            branch_html: None,
+        });
+    }
+
+    fn modify(&mut self, original: &Block, original_index: usize, branch: &Block, branch_index: usize) {
+        self.html.push_str(&format!(
+            "<del data-diff=\"modify\">{}</del><ins data-diff=\"modify\">{}</ins>",
+            original.html, branch.html
+        ));
+        self.ops.push(BlockDiff {
+            op: BlockDiffOp::Modify,
+            original_index: Some(original_index),
+            branch_index: Some(branch_index),
+            original_html: Some(original.html.clone()),
+            branch_html: Some(branch.html.clone()),
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use yrs::{ReadTxn, StateVector, Xml, XmlElementPrelim, XmlTextPrelim};
+
+    fn encode(doc: &Doc) -> Description {
+        Description {
+            base64: Some(STANDARD.encode(doc.transact().encode_state_as_update_v2(&StateVector::default()))),
+            ..Default::default()
+        }
+    }
+
+    fn description(blocks: &[(&str, &str)]) -> Description {
+        let doc = Doc::new();
+        let xml = doc.get_or_insert_xml_fragment(Description::DESCRIPTION_ROOT);
+
+        {
+            let mut txn = doc.transact_mut();
+
+            for (tag, text) in blocks {
+                let block = xml.push_back(&mut txn, XmlElementPrelim::empty(*tag));
+                block.push_back(&mut txn, XmlTextPrelim::new(*text));
+            }
+        }
+
+        encode(&doc)
+    }
+
+    #[test]
+    fn test_block_diff() {
+        let original = description(&[
+            ("paragraph", "Intro"),
+            ("paragraph", "Removed"),
+            ("paragraph", "Kept"),
+            ("paragraph", "Old text"),
+            ("paragraph", "Outro"),
+        ]);
+        let branch = description(&[
+            ("paragraph", "Intro"),
+            ("paragraph", "Kept"),
+            ("heading", "Added"),
+            ("paragraph", "New text"),
+            ("paragraph", "Outro"),
+        ]);
+
+        let diff = DescriptionDiff::new(&original, &branch).unwrap();
+        let ops: Vec<(BlockDiffOp, Option<usize>, Option<usize>)> = diff
+            .ops
+            .iter()
+            .map(|op| (op.op, op.original_index, op.branch_index))
+            .collect();
+
+        assert_eq!(
+            ops,
+            vec![
+                (BlockDiffOp::Delete, Some(1), None),
+                (BlockDiffOp::Insert, None, Some(2)),
+                (BlockDiffOp::Modify, Some(3), Some(3)),
+            ]
+        );
+        assert!(diff
+            .html
+            .starts_with("<p>Intro</p><del data-diff=\"delete\"><p>Removed</p></del><p>Kept</p>"));
+        assert!(diff.html.ends_with("<p>Outro</p>"));
+    }
+
+    #[test]
+    fn test_equal_descriptions_have_no_ops() {
+        let original = description(&[("paragraph", "Same")]);
+        let branch = description(&[("paragraph", "Same")]);
+
+        let diff = DescriptionDiff::new(&original, &branch).unwrap();
+
+        assert!(diff.ops.is_empty());
+        assert_eq!(diff.html, "<p>Same</p>");
+    }
+
+    #[test]
+    fn test_diff_html_is_sanitized() {
+        let original = description(&[("paragraph", "Intro")]);
+        let doc = Doc::new();
+        let xml = doc.get_or_insert_xml_fragment(Description::DESCRIPTION_ROOT);
+
+        {
+            let mut txn = doc.transact_mut();
+            let paragraph = xml.push_back(&mut txn, XmlElementPrelim::empty("paragraph"));
+            let link = paragraph.push_back(&mut txn, XmlElementPrelim::empty("link"));
+            link.insert_attribute(&mut txn, "href", "javascript:alert(1)");
+            link.push_back(&mut txn, XmlTextPrelim::new("Click"));
+            let image = paragraph.push_back(&mut txn, XmlElementPrelim::empty("image"));
+            image.insert_attribute(&mut txn, "src", "x\" onerror=\"alert(1)");
+        }
+
+        let diff = DescriptionDiff::new(&original, &encode(&doc)).unwrap();
+        let branch_html = diff.ops[0].branch_html.as_deref().unwrap();
+
+        assert!(!diff.html.contains("javascript:"));
+        assert!(!diff.html.contains("\" onerror"));
+        assert!(!branch_html.contains("javascript:"));
+        assert!(diff.html.contains("<ins data-diff=\"modify\">"));
+    }
+
+    #[test]
+    fn test_html_description_blocks() {
+        let original = Description {
+            html: Some("<p>Intro</p><p>Old text</p>".to_string()),
+            ..Default::default()
+        };
+        let branch = description(&[("paragraph", "Intro"), ("paragraph", "New text")]);
+
+        let diff = DescriptionDiff::new(&original, &branch).unwrap();
+        let ops: Vec<(BlockDiffOp, Option<usize>, Option<usize>)> = diff
+            .ops
+            .iter()
+            .map(|op| (op.op, op.original_index, op.branch_index))
+            .collect();
+
+        assert_eq!(ops, vec![(BlockDiffOp::Modify, Some(1), Some(1))]);
+        assert_eq!(diff.ops[0].original_html.as_deref(), Some("<p>Old text</p>"));
+        assert!(diff.html.starts_with("<p>Intro</p>"));
+    }
+}
@@ -1,11 +1,11 @@
 use crate::errors::NodecosmosError;
-use quick_xml::events::Event;
+use quick_xml::events::{BytesStart, Event};
 use quick_xml::name::QName;
 use quick_xml::Reader;
 use std::borrow::Cow;
 use std::str::FromStr;
 use std::sync::Arc;
-use yrs::{GetString, TransactionMut, Xml, XmlFragment, XmlFragmentRef, XmlOut};
+use yrs::{GetString, TransactionMut, Xml, XmlElementRef, XmlFragment, XmlFragmentRef, XmlOut};
 
 pub trait DescriptionParser<'a> {
     const SHORT_DESCRIPTION_LENGTH: usize = 255;
@@ -28,6 +28,13 @@ pub trait DescriptionParser<'a> {
     fn set_blockquote_active(&mut self, active: bool);
     fn blockquote_active(&self) -> bool;
 
+    fn set_table_cell_active(&mut self, active: bool);
+    fn table_cell_active(&self) -> bool;
+    fn set_table_row_counter(&mut self, counter: u32);
+    fn table_row_counter(&self) -> u32;
+    fn set_table_column_counter(&mut self, counter: u32);
+    fn table_column_counter(&self) -> u32;
+
     fn short_description(&self) -> &str;
     fn set_short_description(&mut self, short_description: String);
 
@@ -99,10 +106,10 @@ pub trait DescriptionParser<'a> {
         let mut data_lang = String::new();
 
         if let Some(language) = language_tag {
-            data_lang = format!("data-code-block-language=\"{}\"", language);
+            data_lang = format!("data-code-block-language=\"{}\"", quick_xml::escape::escape(language));
         }
 
-        let html = format!("<pre spellcheck=\"false\"<code {}>", data_lang);
+        let html = format!("<pre spellcheck=\"false\"><code {}>", data_lang);
         let markdown = format!("```{}\n", language_tag.unwrap_or_default());
 
         self.push_html(&html);
@@ -112,13 +119,14 @@ pub trait DescriptionParser<'a> {
     fn open_image(&mut self, src: &str, alt: &str) {
         self.push_html(&format!(
             "<img alt=\"{}\" src=\"{}\" title=\"\" resizable=\"false\">",
-            alt, src
+            quick_xml::escape::escape(alt),
+            quick_xml::escape::escape(src)
         ));
         self.push_markdown(&format!("\n![{}]({})", alt, src));
     }
 
     fn open_link(&mut self, href: &str) {
-        let link = &format!("<a href=\"{}\">", href);
+        let link = &format!("<a href=\"{}\">", quick_xml::escape::escape(href));
 
         self.push_html(link);
         self.push_markdown(&format!("[{}](", href));
@@ -128,6 +136,101 @@ pub trait DescriptionParser<'a> {
         self.push_html("<br/>");
     }
 
+    fn open_table(&mut self) {
+        self.push_html("<table><tbody>");
+        self.push_markdown("\n");
+        self.set_table_row_counter(0);
+    }
+
+    fn open_table_row(&mut self) {
+        self.push_html("<tr>");
+        self.push_markdown("|");
+        self.set_table_column_counter(0);
+    }
+
+    fn open_table_cell(&mut self, is_header: bool, colspan: Option<&str>, rowspan: Option<&str>) {
+        let mut html = String::from(if is_header { "<th" } else { "<td" });
+
+        for (name, value) in [("colspan", colspan), ("rowspan", rowspan)] {
+            if let Some(value) = value.filter(|value| *value != "1") {
+                html.push_str(&format!(" {}=\"{}\"", name, quick_xml::escape::escape(value)));
+            }
+        }
+
+        html.push('>');
+
+        self.push_html(&html);
+        self.push_markdown(" ");
+        self.set_table_cell_active(true);
+        self.set_table_column_counter(self.table_column_counter() + 1);
+    }
+
+    fn open_task_list(&mut self) {
+        self.push_html("<ul data-type=\"taskList\">");
+    }
+
+    fn open_task_item(&mut self, checked: bool) {
+        let checked_attr = if checked { " checked=\"\"" } else { "" };
+
+        self.push_html(&format!(
+            "<li data-type=\"taskItem\" data-checked=\"{}\"><input type=\"checkbox\" disabled=\"\"{}/>",
+            checked, checked_attr
+        ));
+        self.push_markdown(if checked { "- [x] " } else { "- [ ] " });
+    }
+
+    fn open_horizontal_rule(&mut self) {
+        self.push_html("<hr/>");
+        self.push_markdown("\n---\n");
+    }
+
+    fn open_mention(&mut self, id: &str, label: &str) {
+        let label_html = quick_xml::escape::escape(label);
+
+        self.push_html(&format!(
+            "<span data-type=\"mention\" data-id=\"{}\" data-label=\"{}\">@{}</span>",
+            quick_xml::escape::escape(id),
+            label_html,
+            label_html
+        ));
+        self.push_markdown(&format!("@{}", label));
+    }
+
+    /// Reference to node or its workflow objects, e.g. flow step or io.
+    fn open_reference(&mut self, object_type: &str, object_id: &str, label: &str) {
+        let label_html = quick_xml::escape::escape(label);
+
+        self.push_html(&format!(
+            "<span data-type=\"reference\" data-object-type=\"{}\" data-object-id=\"{}\" data-label=\"{}\">{}</span>",
+            quick_xml::escape::escape(object_type),
+            quick_xml::escape::escape(object_id),
+            label_html,
+            label_html
+        ));
+        self.push_markdown(&format!("[{}]({}:{})", label, object_type, object_id));
+    }
+
+    fn open_math_block(&mut self, latex: &str) {
+        let latex_html = quick_xml::escape::escape(latex);
+
+        self.push_html(&format!(
+            "<div data-type=\"mathBlock\" data-latex=\"{}\">{}</div>",
+            latex_html, latex_html
+        ));
+        self.push_markdown(&format!("\n$$\n{}\n$$\n", latex));
+    }
+
+    fn open_callout(&mut self, callout_type: &str) {
+        self.push_html(&format!(
+            "<div data-type=\"callout\" data-callout-type=\"{}\">",
+            quick_xml::escape::escape(callout_type)
+        ));
+        self.push_markdown(&format!("> [!{}]\n", callout_type.to_uppercase()));
+
+        // callout content is quoted in markdown
+        self.set_blockquote_active(true);
+    }
+
     fn text(&mut self, text: &str) -> Result<(), NodecosmosError> {
         self.push_html(text);
 
@@ -153,7 +256,10 @@ pub trait DescriptionParser<'a> {
     }
 
     fn close_paragraph(&mut self) {
-        if self.ordered_list_active() || self.bullet_list_active() {
+        if self.table_cell_active() {
+            // markdown table cell has to stay on a single line
+            self.push_html("</p>");
+        } else if self.ordered_list_active() || self.bullet_list_active() {
             self.push_html("</p>");
             self.push_markdown("\n");
         } else if self.blockquote_active() {
@@ -232,6 +338,47 @@ pub trait DescriptionParser<'a> {
         self.push_html(&format!("</h{}>", heading_level));
         self.push_markdown("\n");
     }
+
+    fn close_table_cell(&mut self, is_header: bool) {
+        self.push_html(if is_header { "</th>" } else { "</td>" });
+        self.push_markdown(" |");
+        self.set_table_cell_active(false);
+    }
+
+    fn close_table_row(&mut self) {
+        self.push_html("</tr>");
+        self.push_markdown("\n");
+
+        // markdown header row is separated from the rest of the table
+        if self.table_row_counter() == 0 {
+            let separator = " --- |".repeat(self.table_column_counter() as usize);
+            self.push_markdown(&format!("|{}\n", separator));
+        }
+
+        self.set_table_row_counter(self.table_row_counter() + 1);
+    }
+
+    fn close_table(&mut self) {
+        self.push_html("</tbody></table>");
+        self.push_markdown("\n");
+    }
+
+    fn close_task_item(&mut self) {
+        self.push_html("</li>");
+        self.push_markdown("\n");
+    }
+
+    fn close_task_list(&mut self) {
+        self.push_html("</ul>");
+        self.push_markdown("\n");
+    }
+
+    fn close_callout(&mut self) {
+        self.push_html("</div>");
+        self.push_markdown("\n");
+
+        self.set_blockquote_active(false);
+    }
 }
 
 #[derive(Clone, strum_macros::Display, strum_macros::EnumString)]
@@ -281,6 +428,39 @@ enum Tag {
     #[strum(serialize = "hardBreak")]
     HardBreak,
 
+    #[strum(serialize = "table")]
+    Table,
+
+    #[strum(serialize = "tableRow")]
+    TableRow,
+
+    #[strum(serialize = "tableHeader")]
+    TableHeader,
+
+    #[strum(serialize = "tableCell")]
+    TableCell,
+
+    #[strum(serialize = "taskList")]
+    TaskList,
+
+    #[strum(serialize = "taskItem")]
+    TaskItem,
+
+    #[strum(serialize = "horizontalRule")]
+    HorizontalRule,
+
+    #[strum(serialize = "mention")]
+    Mention,
+
+    #[strum(serialize = "reference")]
+    Reference,
+
+    #[strum(serialize = "mathBlock")]
+    MathBlock,
+
+    #[strum(serialize = "callout")]
+    Callout,
+
     #[strum(serialize = "html")]
     Html,
 
@@ -311,6 +491,9 @@ pub struct DescriptionXmlParser<'a> {
     ordered_list_active: bool,
     bullet_list_active: bool,
     blockquote_active: bool,
+    table_cell_active: bool,
+    table_row_counter: u32,
+    table_column_counter: u32,
 }
 
 // this implementation should have method for each event
@@ -329,6 +512,9 @@ impl<'a> DescriptionXmlParser<'a> {
             ordered_list_active: false,
             bullet_list_active: false,
             blockquote_active: false,
+            table_cell_active: false,
+            table_row_counter: 0,
+            table_column_counter: 0,
         }
     }
 
@@ -402,6 +588,44 @@ impl<'a> DescriptionXmlParser<'a> {
                         self.open_link(&href);
                     }
                     Tag::HardBreak => self.open_hard_break(),
+                    Tag::Table => self.open_table(),
+                    Tag::TableRow => self.open_table_row(),
+                    tag @ (Tag::TableHeader | Tag::TableCell) => {
+                        let colspan = self.attribute(e, b"colspan");
+                        let rowspan = self.attribute(e, b"rowspan");
+
+                        self.open_table_cell(matches!(tag, Tag::TableHeader), colspan.as_deref(), rowspan.as_deref());
+                    }
+                    Tag::TaskList => self.open_task_list(),
+                    Tag::TaskItem => {
+                        let checked = self.attribute(e, b"checked").is_some_and(|checked| checked == "true");
+
+                        self.open_task_item(checked);
+                    }
+                    Tag::HorizontalRule => self.open_horizontal_rule(),
+                    Tag::Mention => {
+                        let id = self.attribute(e, b"id").unwrap_or_default();
+                        let label = self.attribute(e, b"label").unwrap_or_default();
+
+                        self.open_mention(&id, &label);
+                    }
+                    Tag::Reference => {
+                        let object_type = self.attribute(e, b"objectType").unwrap_or_default();
+                        let object_id = self.attribute(e, b"objectId").unwrap_or_default();
+                        let label = self.attribute(e, b"label").unwrap_or_default();
+
+                        self.open_reference(&object_type, &object_id, &label);
+                    }
+                    Tag::MathBlock => {
+                        let latex = self.attribute(e, b"latex").unwrap_or_default();
+
+                        self.open_math_block(&latex);
+                    }
+                    Tag::Callout => {
+                        let callout_type = self.attribute(e, b"type").unwrap_or_else(|| "info".to_string());
+
+                        self.open_callout(&callout_type);
+                    }
                     Tag::Html => (),
                     Tag::Unknown => (),
                 },
@@ -427,6 +651,14 @@ impl<'a> DescriptionXmlParser<'a> {
                     Tag::Image => self.close_image(),
                     Tag::Link => self.close_link(),
                     Tag::HardBreak => (),
+                    Tag::Table => self.close_table(),
+                    Tag::TableRow => self.close_table_row(),
+                    Tag::TableHeader => self.close_table_cell(true),
+                    Tag::TableCell => self.close_table_cell(false),
+                    Tag::TaskList => self.close_task_list(),
+                    Tag::TaskItem => self.close_task_item(),
+                    Tag::HorizontalRule | Tag::Mention | Tag::Reference | Tag::MathBlock => (),
+                    Tag::Callout => self.close_callout(),
                     Tag::Html => (),
                     Tag::Unknown => (),
                 },
@@ -439,6 +671,16 @@ impl<'a> DescriptionXmlParser<'a> {
 
         Ok(self)
     }
+
+    fn attribute(&self, e: &BytesStart, key: &[u8]) -> Option<String> {
+        e.attributes().find_map(|a| {
+            a.ok().filter(|a| a.key == QName(key)).map(|a| {
+                a.decode_and_unescape_value(self.reader.decoder())
+                    .unwrap_or_default()
+                    .to_string()
+            })
+        })
+    }
 }
 
 impl<'a> DescriptionParser<'a> for DescriptionXmlParser<'a> {
@@ -490,6 +732,30 @@ impl<'a> DescriptionParser<'a> for DescriptionXmlParser<'a> {
         self.blockquote_active
     }
 
+    fn set_table_cell_active(&mut self, active: bool) {
+        self.table_cell_active = active;
+    }
+
+    fn table_cell_active(&self) -> bool {
+        self.table_cell_active
+    }
+
+    fn set_table_row_counter(&mut self, counter: u32) {
+        self.table_row_counter = counter;
+    }
+
+    fn table_row_counter(&self) -> u32 {
+        self.table_row_counter
+    }
+
+    fn set_table_column_counter(&mut self, counter: u32) {
+        self.table_column_counter = counter;
+    }
+
+    fn table_column_counter(&self) -> u32 {
+        self.table_column_counter
+    }
+
     fn short_description(&self) -> &str {
         &self.short_description
     }
@@ -508,6 +774,9 @@ pub struct DescriptionYDocParser {
     ordered_list_active: bool,
     bullet_list_active: bool,
     blockquote_active: bool,
+    table_cell_active: bool,
+    table_row_counter: u32,
+    table_column_counter: u32,
 }
 
 impl DescriptionYDocParser {
@@ -521,6 +790,9 @@ impl DescriptionYDocParser {
             ordered_list_active: false,
             bullet_list_active: false,
             blockquote_active: false,
+            table_cell_active: false,
+            table_row_counter: 0,
+            table_column_counter: 0,
         }
     }
 
@@ -530,109 +802,195 @@ impl DescriptionYDocParser {
         Ok(self)
     }
 
+    /// Parses single top level block of the document, e.g. to render blocks of the description diff.
+    pub fn run_block(mut self, txn: &TransactionMut, block: XmlElementRef) -> Result<Self, NodecosmosError> {
+        self.traverse_node(txn, XmlOut::Element(block))?;
+
+        Ok(self)
+    }
+
     fn traverse_children<T: XmlFragment>(
         &mut self,
         txn: &TransactionMut,
         xml_fragment: T,
     ) -> Result<&mut Self, NodecosmosError> {
         for child in xml_fragment.children(txn) {
-            match child {
-                XmlOut::Element(element) => {
-                    match Tag::from(element.tag()) {
-                        Tag::Description => {}
-                        Tag::Heading => {
-                            let heading_level = element
-                                .get_attribute::<TransactionMut>(txn, "level")
-                                .unwrap_or_else(|| "1".to_string());
-
-                            self.open_heading(&heading_level);
-                            self.traverse_children(txn, element)?;
-                            self.close_heading(&heading_level);
-                        }
-                        Tag::Paragraph => {
-                            self.open_paragraph();
-                            self.traverse_children(txn, element)?;
-                            self.close_paragraph();
-                        }
-                        Tag::Bold => {
-                            self.open_bold();
-                            self.traverse_children(txn, element)?;
-                            self.close_bold();
-                        }
-                        Tag::Italic => {
-                            self.open_italic();
-                            self.traverse_children(txn, element)?;
-                            self.close_italic();
-                        }
-                        Tag::Strike => {
-                            self.open_strike();
-                            self.traverse_children(txn, element)?;
-                            self.close_strike();
-                        }
-                        Tag::Code => {
-                            self.open_code();
-                            self.traverse_children(txn, element)?;
-                            self.close_code();
-                        }
-                        Tag::BulletList => {
-                            self.open_bullet_list();
-                            self.traverse_children(txn, element)?;
-                            self.close_bullet_list();
-                        }
-                        Tag::OrderedList => {
-                            self.open_ordered_list();
-                            self.traverse_children(txn, element)?;
-                            self.close_ordered_list();
-                        }
-                        Tag::ListItem => {
-                            self.open_list_item();
-                            self.traverse_children(txn, element)?;
-                            self.close_list_item();
-                        }
-                        Tag::Blockquote => {
-                            self.open_blockquote();
-                            self.traverse_children(txn, element)?;
-                            self.close_blockquote();
-                        }
-                        Tag::CodeBlock => {
-                            let language_tag = element.get_attribute::<TransactionMut>(txn, "language");
-                            self.open_code_block(language_tag.as_deref());
-                            self.traverse_children(txn, element)?;
-                            self.close_code_block();
-                        }
-                        Tag::Image => {
-                            let src = element.get_attribute::<TransactionMut>(txn, "src").unwrap_or_default();
-                            let alt = element.get_attribute::<TransactionMut>(txn, "alt").unwrap_or_default();
+            self.traverse_node(txn, child)?;
+        }
 
-                            self.open_image(&src, &alt);
-                            self.traverse_children(txn, element)?;
-                            self.close_image();
-                        }
-                        Tag::Link => {
-                            let href = element.get_attribute::<TransactionMut>(txn, "href").unwrap_or_default();
+        Ok(self)
+    }
 
-                            self.open_link(&href);
-                            self.traverse_children(txn, element)?;
-                            self.close_link();
-                        }
-                        Tag::HardBreak => {
-                            self.open_hard_break();
-                        }
-                        Tag::Html => (),
-                        Tag::Unknown => {
-                            log::error!("Unknown tag: {}", element.tag());
-                        }
-                    };
-                }
-                XmlOut::Text(text) => {
-                    let text = text.get_string(txn);
+    fn traverse_node(&mut self, txn: &TransactionMut, node: XmlOut) -> Result<&mut Self, NodecosmosError> {
+        match node {
+            XmlOut::Element(element) => {
+                match Tag::from(element.tag()) {
+                    Tag::Description => {}
+                    Tag::Heading => {
+                        let heading_level = element
+                            .get_attribute::<TransactionMut>(txn, "level")
+                            .unwrap_or_else(|| "1".to_string());
+
+                        self.open_heading(&heading_level);
+                        self.traverse_children(txn, element)?;
+                        self.close_heading(&heading_level);
+                    }
+                    Tag::Paragraph => {
+                        self.open_paragraph();
+                        self.traverse_children(txn, element)?;
+                        self.close_paragraph();
+                    }
+                    Tag::Bold => {
+                        self.open_bold();
+                        self.traverse_children(txn, element)?;
+                        self.close_bold();
+                    }
+                    Tag::Italic => {
+                        self.open_italic();
+                        self.traverse_children(txn, element)?;
+                        self.close_italic();
+                    }
+                    Tag::Strike => {
+                        self.open_strike();
+                        self.traverse_children(txn, element)?;
+                        self.close_strike();
+                    }
+                    Tag::Code => {
+                        self.open_code();
+                        self.traverse_children(txn, element)?;
+                        self.close_code();
+                    }
+                    Tag::BulletList => {
+                        self.open_bullet_list();
+                        self.traverse_children(txn, element)?;
+                        self.close_bullet_list();
+                    }
+                    Tag::OrderedList => {
+                        self.open_ordered_list();
+                        self.traverse_children(txn, element)?;
+                        self.close_ordered_list();
+                    }
+                    Tag::ListItem => {
+                        self.open_list_item();
+                        self.traverse_children(txn, element)?;
+                        self.close_list_item();
+                    }
+                    Tag::Blockquote => {
+                        self.open_blockquote();
+                        self.traverse_children(txn, element)?;
+                        self.close_blockquote();
+                    }
+                    Tag::CodeBlock => {
+                        let language_tag = element.get_attribute::<TransactionMut>(txn, "language");
+                        self.open_code_block(language_tag.as_deref());
+                        self.traverse_children(txn, element)?;
+                        self.close_code_block();
+                    }
+                    Tag::Image => {
+                        let src = element.get_attribute::<TransactionMut>(txn, "src").unwrap_or_default();
+                        let alt = element.get_attribute::<TransactionMut>(txn, "alt").unwrap_or_default();
+
+                        self.open_image(&src, &alt);
+                        self.traverse_children(txn, element)?;
+                        self.close_image();
+                    }
+                    Tag::Link => {
+                        let href = element.get_attribute::<TransactionMut>(txn, "href").unwrap_or_default();
 
-                    let escaped = quick_xml::escape::escape(&text);
+                        self.open_link(&href);
+                        self.traverse_children(txn, element)?;
+                        self.close_link();
+                    }
+                    Tag::HardBreak => {
+                        self.open_hard_break();
+                    }
+                    Tag::Table => {
+                        self.open_table();
+                        self.traverse_children(txn, element)?;
+                        self.close_table();
+                    }
+                    Tag::TableRow => {
+                        self.open_table_row();
+                        self.traverse_children(txn, element)?;
+                        self.close_table_row();
+                    }
+                    tag @ (Tag::TableHeader | Tag::TableCell) => {
+                        let is_header = matches!(tag, Tag::TableHeader);
+                        let colspan = element.get_attribute::<TransactionMut>(txn, "colspan");
+                        let rowspan = element.get_attribute::<TransactionMut>(txn, "rowspan");
+
+                        self.open_table_cell(is_header, colspan.as_deref(), rowspan.as_deref());
+                        self.traverse_children(txn, element)?;
+                        self.close_table_cell(is_header);
+                    }
+                    Tag::TaskList => {
+                        self.open_task_list();
+                        self.traverse_children(txn, element)?;
+                        self.close_task_list();
+                    }
+                    Tag::TaskItem => {
+                        let checked = element
+                            .get_attribute::<TransactionMut>(txn, "checked")
+                            .is_some_and(|checked| checked == "true");
+
+                        self.open_task_item(checked);
+                        self.traverse_children(txn, element)?;
+                        self.close_task_item();
+                    }
+                    Tag::HorizontalRule => {
+                        self.open_horizontal_rule();
+                    }
+                    Tag::Mention => {
+                        let id = element.get_attribute::<TransactionMut>(txn, "id").unwrap_or_default();
+                        let label = element
+                            .get_attribute::<TransactionMut>(txn, "label")
+                            .unwrap_or_default();
 
-                    self.text(&escaped)?;
-                }
-                _ => panic!("Unexpected XML fragment type"),
+                        self.open_mention(&id, &label);
+                    }
+                    Tag::Reference => {
+                        let object_type = element
+                            .get_attribute::<TransactionMut>(txn, "objectType")
+                            .unwrap_or_default();
+                        let object_id = element
+                            .get_attribute::<TransactionMut>(txn, "objectId")
+                            .unwrap_or_default();
+                        let label = element
+                            .get_attribute::<TransactionMut>(txn, "label")
+                            .unwrap_or_default();
+
+                        self.open_reference(&object_type, &object_id, &label);
+                    }
+                    Tag::MathBlock => {
+                        let latex = element
+                            .get_attribute::<TransactionMut>(txn, "latex")
+                            .unwrap_or_default();
+
+                        self.open_math_block(&latex);
+                    }
+                    Tag::Callout => {
+                        let callout_type = element
+                            .get_attribute::<TransactionMut>(txn, "type")
+                            .unwrap_or_else(|| "info".to_string());
+
+                        self.open_callout(&callout_type);
+                        self.traverse_children(txn, element)?;
+                        self.close_callout();
+                    }
+                    Tag::Html => (),
+                    Tag::Unknown => {
+                        log::error!("Unknown tag: {}", element.tag());
+                    }
+                };
             }
+            XmlOut::Text(text) => {
+                let text = text.get_string(txn);
+
+                let escaped = quick_xml::escape::escape(&text);
+
+                self.text(&escaped)?;
+            }
+            _ => panic!("Unexpected XML fragment type"),
         }
 
         Ok(self)
@@ -688,6 +1046,30 @@ impl<'a> DescriptionParser<'a> for DescriptionYDocParser {
         self.blockquote_active
     }
 
+    fn set_table_cell_active(&mut self, active: bool) {
+        self.table_cell_active = active;
+    }
+
+    fn table_cell_active(&self) -> bool {
+        self.table_cell_active
+    }
+
+    fn set_table_row_counter(&mut self, counter: u32) {
+        self.table_row_counter = counter;
+    }
+
+    fn table_row_counter(&self) -> u32 {
+        self.table_row_counter
+    }
+
+    fn set_table_column_counter(&mut self, counter: u32) {
+        self.table_column_counter = counter;
+    }
+
+    fn table_column_counter(&self) -> u32 {
+        self.table_column_counter
+    }
+
     fn short_description(&self) -> &str {
         &self.short_description
     }
@@ -696,3 +1078,413 @@ impl<'a> DescriptionParser<'a> for DescriptionYDocParser {
         self.short_description = short_description;
     }
 }
+
+/// Converts html generated by description parsers back to the `<description>` xml that is consumed by
+/// `DescriptionXmlParser`. It's used for descriptions that don't have Y.Doc state (e.g. imported descriptions).
+pub struct DescriptionHtmlToXml<'a> {
+    pub xml: String,
+    reader: Reader<&'a [u8]>,
+    code_block_active: bool,
+
+    /// Position of the open `codeBlock` tag end, where the language is added once it's found.
+    code_block_end: Option<usize>,
+
+    /// Tags of open `ul`, `li`, `div` and `span` elements, as their tag depends on `data-type` attribute.
+    element_stack: Vec<Tag>,
+}
+
+impl<'a> DescriptionHtmlToXml<'a> {
+    pub fn new(html: &'a str) -> Self {
+        let mut reader = Reader::from_str(html);
+        reader.config_mut().check_end_names = false;
+
+        Self {
+            xml: String::new(),
+            reader,
+            code_block_active: false,
+            code_block_end: None,
+            element_stack: Vec::new(),
+        }
+    }
+
+    pub fn run(mut self) -> Result<Self, NodecosmosError> {
+        self.xml.push_str(&format!("<{}>", Tag::Description));
+
+        loop {
+            let event = self.reader.read_event()?;
+
+            match event {
+                Event::Start(ref e) | Event::Empty(ref e) => {
+                    let is_empty = matches!(event, Event::Empty(_));
+                    let name = std::str::from_utf8(e.name().as_ref()).unwrap_or_default().to_string();
+                    let attr = |key: &[u8]| {
+                        e.attributes()
+                            .find_map(|a| {
+                                a.ok()
+                                    .filter(|a| a.key == QName(key))
+                                    .map(|a| String::from_utf8_lossy(&a.value).to_string())
+                            })
+                            .unwrap_or_default()
+                    };
+
+                    match name.as_str() {
+                        "h1" | "h2" | "h3" | "h4" | "h5" | "h6" => {
+                            self.xml
+                                .push_str(&format!("<{} level=\"{}\">", Tag::Heading, &name[1..]));
+                        }
+                        "p" => self.open(Tag::Paragraph),
+                        "strong" => self.open(Tag::Bold),
+                        "em" => self.open(Tag::Italic),
+                        "s" => self.open(Tag::Strike),
+                        "code" if !self.code_block_active => self.open(Tag::Code),
+                        "code" => self.add_code_block_language(&attr(b"class"), &attr(b"data-code-block-language")),
+                        "ol" => self.open(Tag::OrderedList),
+                        "ul" | "li" | "div" | "span" => {
+                            let tag = match (name.as_str(), attr(b"data-type").as_str()) {
+                                ("ul", "taskList") => Tag::TaskList,
+                                ("ul", _) => Tag::BulletList,
+                                ("li", "taskItem") => Tag::TaskItem,
+                                ("li", _) => Tag::ListItem,
+                                ("div", "callout") => Tag::Callout,
+                                ("div", "mathBlock") => Tag::MathBlock,
+                                ("span", "mention") => Tag::Mention,
+                                ("span", "reference") => Tag::Reference,
+                                _ => Tag::Unknown,
+                            };
+
+                            match tag {
+                                Tag::TaskItem => self.xml.push_str(&format!(
+                                    "<{} checked=\"{}\">",
+                                    tag,
+                                    attr(b"data-checked") == "true"
+                                )),
+                                Tag::Callout => {
+                                    self.xml
+                                        .push_str(&format!("<{} type=\"{}\">", tag, attr(b"data-callout-type")))
+                                }
+                                // atoms are closed right away, as their content is held in attributes
+                                Tag::MathBlock => self.xml.push_str(&format!(
+                                    "<{tag} latex=\"{}\"></{tag}>",
+                                    attr(b"data-latex"),
+                                    tag = Tag::MathBlock
+                                )),
+                                Tag::Mention => self.xml.push_str(&format!(
+                                    "<{tag} id=\"{}\" label=\"{}\"></{tag}>",
+                                    attr(b"data-id"),
+                                    attr(b"data-label"),
+                                    tag = Tag::Mention
+                                )),
+                                Tag::Reference => self.xml.push_str(&format!(
+                                    "<{tag} objectType=\"{}\" objectId=\"{}\" label=\"{}\"></{tag}>",
+                                    attr(b"data-object-type"),
+                                    attr(b"data-object-id"),
+                                    attr(b"data-label"),
+                                    tag = Tag::Reference
+                                )),
+                                Tag::Unknown => (),
+                                _ => self.open(tag.clone()),
+                            }
+
+                            if !is_empty {
+                                self.element_stack.push(tag);
+                            }
+                        }
+                        "table" => self.open(Tag::Table),
+                        "tr" => self.open(Tag::TableRow),
+                        "th" | "td" => {
+                            let tag = if name == "th" { Tag::TableHeader } else { Tag::TableCell };
+                            let mut xml = format!("<{}", tag);
+
+                            for key in ["colspan", "rowspan"] {
+                                let value = attr(key.as_bytes());
+
+                                if !value.is_empty() {
+                                    xml.push_str(&format!(" {}=\"{}\"", key, value));
+                                }
+                            }
+
+                            xml.push('>');
+                            self.xml.push_str(&xml);
+                        }
+                        "hr" => self
+                            .xml
+                            .push_str(&format!("<{tag}></{tag}>", tag = Tag::HorizontalRule)),
+                        "blockquote" => self.open(Tag::Blockquote),
+                        "pre" => {
+                            self.code_block_active = true;
+                            self.open(Tag::CodeBlock);
+                            self.code_block_end = Some(self.xml.len() - 1);
+                            self.add_code_block_language(&attr(b"class"), "");
+                        }
+                        "img" => {
+                            // image and hard break are closed explicitly as `DescriptionXmlParser` reads start tags
+                            self.xml.push_str(&format!(
+                                "<{tag} src=\"{}\" alt=\"{}\"></{tag}>",
+                                attr(b"src"),
+                                attr(b"alt"),
+                                tag = Tag::Image
+                            ));
+                        }
+                        "a" => self
+                            .xml
+                            .push_str(&format!("<{} href=\"{}\">", Tag::Link, attr(b"href"))),
+                        "br" => self.xml.push_str(&format!("<{tag}></{tag}>", tag = Tag::HardBreak)),
+                        _ => (),
+                    }
+                }
+                Event::Text(e) => {
+                    let is_atom = matches!(
+                        self.element_stack.last(),
+                        Some(Tag::MathBlock | Tag::Mention | Tag::Reference)
+                    );
+
+                    if !is_atom {
+                        self.xml.push_str(std::str::from_utf8(e.as_ref()).unwrap_or_default());
+                    }
+                }
+                Event::End(ref e) => match e.name().as_ref() {
+                    b"h1" | b"h2" | b"h3" | b"h4" | b"h5" | b"h6" => self.close(Tag::Heading),
+                    b"p" => self.close(Tag::Paragraph),
+                    b"strong" => self.close(Tag::Bold),
+                    b"em" => self.close(Tag::Italic),
+                    b"s" => self.close(Tag::Strike),
+                    b"code" if !self.code_block_active => self.close(Tag::Code),
+                    b"ol" => self.close(Tag::OrderedList),
+                    b"ul" | b"li" | b"div" | b"span" => match self.element_stack.pop() {
+                        Some(Tag::MathBlock | Tag::Mention | Tag::Reference | Tag::Unknown) | None => (),
+                        Some(tag) => self.close(tag),
+                    },
+                    b"table" => self.close(Tag::Table),
+                    b"tr" => self.close(Tag::TableRow),
+                    b"th" => self.close(Tag::TableHeader),
+                    b"td" => self.close(Tag::TableCell),
+                    b"blockquote" => self.close(Tag::Blockquote),
+                    b"pre" => {
+                        self.code_block_active = false;
+                        self.code_block_end = None;
+                        self.close(Tag::CodeBlock);
+                    }
+                    b"a" => self.close(Tag::Link),
+                    _ => (),
+                },
+                Event::Eof => break,
+                _ => (),
+            }
+        }
+
+        self.close(Tag::Description);
+
+        Ok(self)
+    }
+
+    fn open(&mut self, tag: Tag) {
+        self.xml.push_str(&format!("<{}>", tag));
+    }
+
+    /// Language is held in `class="language-*"` of editor html or in `data-code-block-language` of parsed html.
+    fn add_code_block_language(&mut self, class: &str, data_language: &str) {
+        let language = class
+            .split_whitespace()
+            .find_map(|class| class.strip_prefix("language-"))
+            .unwrap_or(data_language);
+
+        if language.is_empty() {
+            return;
+        }
+
+        if let Some(position) = self.code_block_end.take() {
+            self.xml.insert_str(
+                position,
+                &format!(" language=\"{}\"", quick_xml::escape::escape(language)),
+            );
+        }
+    }
+
+    fn close(&mut self, tag: Tag) {
+        self.xml.push_str(&format!("</{}>", tag));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::traits::Clean;
+    use yrs::{Doc, Transact, XmlElementPrelim, XmlTextPrelim};
+
+    const RICH_XML: &str = concat!(
+        "<description>",
+        "<table><tableRow><tableHeader><paragraph>Name</paragraph></tableHeader>",
+        "<tableHeader><paragraph>Value</paragraph></tableHeader></tableRow>",
+        "<tableRow><tableCell colspan=\"2\"><paragraph>Total</paragraph></tableCell></tableRow></table>",
+        "<taskList><taskItem checked=\"true\"><paragraph>Done</paragraph></taskItem>",
+        "<taskItem checked=\"false\"><paragraph>Todo</paragraph></taskItem></taskList>",
+        "<horizontalRule></horizontalRule>",
+        "<paragraph>Ask <mention id=\"8f0e4c4a-3f4e-4a53-9d0c-6f4a3b1e2d11\" label=\"john\"></mention> about ",
+        "<reference objectType=\"NODE\" objectId=\"2b9d6a1e-7c4f-4e0b-8a5d-1f3e9c7b6a20\" label=\"Engine\"></reference>",
+        "</paragraph>",
+        "<mathBlock latex=\"x^2 + y_1\"></mathBlock>",
+        "<callout type=\"warning\"><paragraph>Careful</paragraph></callout>",
+        "</description>"
+    );
+
+    fn parse_xml(xml: &str) -> DescriptionXmlParser<'_> {
+        DescriptionXmlParser::new(xml)
+            .run()
+            .expect("Failed to parse description xml")
+    }
+
+    fn html_to_xml(html: &str) -> String {
+        DescriptionHtmlToXml::new(html)
+            .run()
+            .expect("Failed to convert description html")
+            .xml
+    }
+
+    #[test]
+    fn xml_parser_handles_rich_blocks() {
+        let parsed = parse_xml(RICH_XML);
+
+        assert!(parsed.html.contains("<th><p>Name</p></th>"));
+        assert!(parsed.html.contains("<td colspan=\"2\"><p>Total</p></td>"));
+        assert!(parsed
+            .html
+            .contains("<li data-type=\"taskItem\" data-checked=\"true\">"));
+        assert!(parsed.html.contains("<hr/>"));
+        assert!(parsed.html.contains("data-type=\"mention\""));
+        assert!(parsed.html.contains(">Engine</span>"));
+        assert!(parsed.html.contains("data-latex=\"x^2 + y_1\""));
+        assert!(parsed.html.contains("data-callout-type=\"warning\""));
+
+        assert!(parsed.markdown.contains("| Name | Value |\n| --- | --- |\n| Total |\n"));
+        assert!(parsed.markdown.contains("- [x] Done"));
+        assert!(parsed.markdown.contains("- [ ] Todo"));
+        assert!(parsed.markdown.contains("\n---\n"));
+        assert!(parsed
+            .markdown
+            .contains("Ask @john about [Engine](NODE:2b9d6a1e-7c4f-4e0b-8a5d-1f3e9c7b6a20)"));
+        assert!(parsed.markdown.contains("$$\nx^2 + y_1\n$$"));
+        assert!(parsed.markdown.contains("> [!WARNING]\n> Careful"));
+    }
+
+    #[test]
+    fn xml_parser_round_trips_through_html() {
+        let parsed = parse_xml(RICH_XML);
+        let xml = html_to_xml(&parsed.html);
+        let reparsed = parse_xml(&xml);
+
+        assert_eq!(parsed.html, reparsed.html);
+        assert_eq!(parsed.markdown, reparsed.markdown);
+    }
+
+    #[test]
+    fn html_to_xml_keeps_code_block_language() {
+        let xml = html_to_xml("<pre><code class=\"hljs language-rust\">let a = 1;</code></pre>");
+
+        assert_eq!(
+            xml,
+            "<description><codeBlock language=\"rust\">let a = 1;</codeBlock></description>"
+        );
+
+        let parsed = parse_xml(&xml);
+        let reparsed_xml = html_to_xml(&parsed.html);
+        let reparsed = parse_xml(&reparsed_xml);
+
+        assert!(parsed.html.contains("data-code-block-language=\"rust\""));
+        assert_eq!(parsed.html, reparsed.html);
+    }
+
+    #[test]
+    fn rich_blocks_survive_sanitization() {
+        let parsed = parse_xml(RICH_XML);
+        let mut html = Some(parsed.html.clone());
+
+        html.clean().expect("Failed to clean html");
+
+        let sanitized = html.expect("Missing sanitized html");
+        let xml = html_to_xml(&sanitized);
+        let reparsed = parse_xml(&xml);
+
+        assert!(sanitized.contains("<input"));
+        assert_eq!(parsed.html, reparsed.html);
+        assert_eq!(parsed.markdown, reparsed.markdown);
+    }
+
+    #[test]
+    fn ydoc_parser_matches_xml_parser() {
+        let doc = Doc::new();
+        let fragment = doc.get_or_insert_xml_fragment("description");
+        let mut txn = doc.transact_mut();
+
+        let table = fragment.push_back(&mut txn, XmlElementPrelim::empty("table"));
+        let row = table.push_back(&mut txn, XmlElementPrelim::empty("tableRow"));
+        for title in ["Name", "Value"] {
+            let header = row.push_back(&mut txn, XmlElementPrelim::empty("tableHeader"));
+            let paragraph = header.push_back(&mut txn, XmlElementPrelim::empty("paragraph"));
+            paragraph.push_back(&mut txn, XmlTextPrelim::new(title));
+        }
+
+        let task_list = fragment.push_back(&mut txn, XmlElementPrelim::empty("taskList"));
+        for (title, checked) in [("Done", "true"), ("Todo", "false")] {
+            let task_item = task_list.push_back(&mut txn, XmlElementPrelim::empty("taskItem"));
+            task_item.insert_attribute(&mut txn, "checked", checked);
+            let paragraph = task_item.push_back(&mut txn, XmlElementPrelim::empty("paragraph"));
+            paragraph.push_back(&mut txn, XmlTextPrelim::new(title));
+        }
+
+        fragment.push_back(&mut txn, XmlElementPrelim::empty("horizontalRule"));
+
+        let paragraph = fragment.push_back(&mut txn, XmlElementPrelim::empty("paragraph"));
+        paragraph.push_back(&mut txn, XmlTextPrelim::new("Ask "));
+        let mention = paragraph.push_back(&mut txn, XmlElementPrelim::empty("mention"));
+        mention.insert_attribute(&mut txn, "id", "8f0e4c4a-3f4e-4a53-9d0c-6f4a3b1e2d11");
+        mention.insert_attribute(&mut txn, "label", "john");
+        let reference = paragraph.push_back(&mut txn, XmlElementPrelim::empty("reference"));
+        reference.insert_attribute(&mut txn, "objectType", "FLOW_STEP");
+        reference.insert_attribute(&mut txn, "objectId", "2b9d6a1e-7c4f-4e0b-8a5d-1f3e9c7b6a20");
+        reference.insert_attribute(&mut txn, "label", "Ignition");
+
+        let math_block = fragment.push_back(&mut txn, XmlElementPrelim::empty("mathBlock"));
+        math_block.insert_attribute(&mut txn, "latex", "e = mc^2");
+
+        let callout = fragment.push_back(&mut txn, XmlElementPrelim::empty("callout"));
+        callout.insert_attribute(&mut txn, "type", "info");
+        let paragraph = callout.push_back(&mut txn, XmlElementPrelim::empty("paragraph"));
+        paragraph.push_back(&mut txn, XmlTextPrelim::new("Note"));
+
+        let xml = format!("<description>{}</description>", fragment.get_string(&txn));
+        let parsed = DescriptionYDocParser::new()
+            .run(&txn, fragment.clone())
+            .expect("Failed to parse description Y.Doc");
+        let expected = parse_xml(&xml);
+
+        assert_eq!(parsed.html, expected.html);
+        assert_eq!(parsed.markdown, expected.markdown);
+        assert!(parsed.markdown.contains("- [x] Done"));
+        assert!(parsed
+            .markdown
+            .contains("[Ignition](FLOW_STEP:2b9d6a1e-7c4f-4e0b-8a5d-1f3e9c7b6a20)"));
+
+        let reparsed_xml = html_to_xml(&parsed.html);
+        let reparsed = parse_xml(&reparsed_xml);
+
+        assert_eq!(parsed.html, reparsed.html);
+    }
+
+    #[test]
+    fn link_and_image_attributes_are_escaped() {
+        let parsed = parse_xml(concat!(
+            "<description><paragraph>",
+            "<link href=\"https://a.com/?q=&quot;&gt;&lt;script&gt;\">Link</link>",
+            "<image src=\"x&quot; onerror=&quot;alert(1)\" alt=\"&quot;&gt;\"></image>",
+            "</paragraph></description>"
+        ));
+
+        assert!(parsed
+            .html
+            .contains("<a href=\"https://a.com/?q=&quot;&gt;&lt;script&gt;\">"));
+        assert!(parsed
+            .html
+            .contains("<img alt=\"&quot;&gt;\" src=\"x&quot; onerror=&quot;alert(1)\""));
+        assert!(!parsed.html.contains("<script>"));
+    }
+}
@@ -1,11 +1,13 @@
 pub use chunks::*;
 pub(crate) use default_callbacks::*;
+pub use description_diff::*;
 pub use description_parser::*;
 pub use image::*;
 pub use recaptcha::*;
 
 mod chunks;
 mod default_callbacks;
+mod description_diff;
 mod description_parser;
 mod image;
 mod recaptcha;
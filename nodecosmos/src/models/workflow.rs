@@ -1,13 +1,18 @@
 use charybdis::macros::charybdis_model;
+use charybdis::operations::Find;
 use charybdis::types::{List, Text, Timestamp, Uuid};
 use scylla::client::caching_session::CachingSession;
 use serde::{Deserialize, Serialize};
 use std::collections::HashSet;
 
+use crate::api::data::RequestData;
+use crate::api::types::{ActionObject, ActionTypes};
 use crate::errors::NodecosmosError;
+use crate::models::branch::stack::{combine_layers, BranchLayer};
 use crate::models::branch::update::BranchUpdate;
 use crate::models::branch::Branch;
-use crate::models::traits::{Branchable, Context, Merge, NodeBranchParams, WhereInChunksExec};
+use crate::models::traits::{Branchable, Context, ModelContext, NodeBranchParams, WhereInChunksExec};
+use crate::resources::sse_broadcast::ModelEvent;
 use crate::stream::MergedModelStream;
 use macros::Branchable;
 
@@ -61,34 +66,31 @@ impl Workflow {
                 .await
                 .map_err(NodecosmosError::from)
         } else {
-            let maybe_original =
-                Workflow::maybe_find_first_by_branch_id_and_node_id(params.original_id(), params.node_id)
-                    .execute(db_session)
-                    .await?;
             let maybe_branched = Workflow::maybe_find_first_by_branch_id_and_node_id(params.branch_id, params.node_id)
                 .execute(db_session)
                 .await?;
+            let mut layers = vec![BranchLayer::new(maybe_branched.into_iter().collect())];
 
-            match (maybe_original, maybe_branched) {
-                (Some(mut original), Some(mut branched)) => {
-                    // merge original initial input ids with branched initial input ids
-                    if let Some(original_initial_input_ids) = original.initial_input_ids.as_mut() {
-                        original_initial_input_ids.merge_unique(branched.initial_input_ids.unwrap_or_default());
-                        branched.initial_input_ids = original.initial_input_ids;
-                    }
-
-                    Ok(branched)
-                }
-                (Some(mut original), None) => {
-                    original.branch_id = params.branch_id;
-
-                    Ok(original)
-                }
-                (None, Some(branched)) => Ok(branched),
-                (None, None) => Err(NodecosmosError::NotFound(
-                    "Branch related workflow not found".to_string(),
-                )),
+            for parent in Branch::parent_branches(db_session, params.branch_id).await? {
+                let maybe_parent_branched =
+                    Workflow::maybe_find_first_by_branch_id_and_node_id(parent.id, params.node_id)
+                        .execute(db_session)
+                        .await?;
+
+                layers.push(BranchLayer::parent(maybe_parent_branched.into_iter().collect(), parent));
             }
+
+            let maybe_original =
+                Workflow::maybe_find_first_by_branch_id_and_node_id(params.original_id(), params.node_id)
+                    .execute(db_session)
+                    .await?;
+
+            layers.push(BranchLayer::new(maybe_original.into_iter().collect()));
+
+            // branched initial input ids are merged with the ones of the layers below
+            combine_layers(layers, params.branch_id)
+                .pop()
+                .ok_or_else(|| NodecosmosError::NotFound("Branch related workflow not found".to_string()))
         }
     }
 
@@ -117,47 +119,67 @@ partial_workflow!(
 );
 
 impl UpdateInitialInputsWorkflow {
-    pub async fn push_initial_input(
-        &mut self,
-        db_session: &CachingSession,
-        input_id: Uuid,
-    ) -> Result<(), NodecosmosError> {
-        self.push_initial_input_ids(vec![input_id]).execute(db_session).await?;
+    pub async fn push_initial_input(&mut self, data: &RequestData, input_id: Uuid) -> Result<(), NodecosmosError> {
+        self.push_initial_input_ids(vec![input_id])
+            .execute(data.db_session())
+            .await?;
 
         if self.is_branch() {
             let mut set = HashSet::new();
             set.insert(input_id);
 
             Branch::update(
-                db_session,
+                data,
                 self.branch_id,
+                self.node_id,
                 BranchUpdate::CreateWorkflowInitialInputs(set),
             )
             .await?;
         }
 
+        self.emit_update_event(data).await?;
+
         Ok(())
     }
 
-    pub async fn pull_initial_input(
-        &mut self,
-        db_session: &CachingSession,
-        input_id: Uuid,
-    ) -> Result<(), NodecosmosError> {
-        self.pull_initial_input_ids(vec![input_id]).execute(db_session).await?;
+    pub async fn pull_initial_input(&mut self, data: &RequestData, input_id: Uuid) -> Result<(), NodecosmosError> {
+        self.pull_initial_input_ids(vec![input_id])
+            .execute(data.db_session())
+            .await?;
 
         if self.is_branch() {
             let mut set = HashSet::new();
             set.insert(input_id);
 
             Branch::update(
-                db_session,
+                data,
                 self.branch_id,
+                self.node_id,
                 BranchUpdate::DeleteWorkflowInitialInputs(set),
             )
             .await?;
         }
 
+        self.emit_update_event(data).await?;
+
+        Ok(())
+    }
+
+    /// Sends workflow with current initial inputs, as push and pull don't load them.
+    async fn emit_update_event(&self, data: &RequestData) -> Result<(), NodecosmosError> {
+        if self.is_default_context() {
+            let workflow = self.find_by_primary_key().execute(data.db_session()).await?;
+
+            ModelEvent::new(
+                self.root_id,
+                self.branch_id,
+                ActionTypes::Update(ActionObject::Workflow),
+                &workflow,
+            )
+            .emit(data)
+            .await;
+        }
+
         Ok(())
     }
 }
@@ -0,0 +1,327 @@
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::Duration;
+
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use charybdis::errors::CharybdisError;
+use charybdis::operations::InsertWithCallbacks;
+use charybdis::types::Uuid;
+use scylla::client::caching_session::CachingSession;
+use yrs::sync::awareness::AwarenessUpdateEntry;
+use yrs::sync::{AwarenessUpdate, Message, MessageReader, SyncMessage};
+use yrs::updates::decoder::{Decode, DecoderV1};
+use yrs::updates::encoder::Encode;
+use yrs::{Doc, ReadTxn, StateVector, Transact, Update};
+
+use crate::api::data::RequestData;
+use crate::errors::NodecosmosError;
+use crate::models::description::Description;
+use crate::models::udts::Profile;
+
+/// Updates are persisted once the document doesn't change for this long.
+const PERSIST_DEBOUNCE_MS: u64 = 2000;
+
+/// Awareness state of the client that left.
+const NULL_AWARENESS_STATE: &str = "null";
+
+/// Frames produced by a client frame.
+#[derive(Default)]
+pub struct SyncOutput {
+    /// Frames sent back to the client.
+    pub reply: Vec<Vec<u8>>,
+
+    /// Frames sent to other clients in the room.
+    pub broadcast: Vec<Vec<u8>>,
+
+    pub is_updated: bool,
+}
+
+/// Description document held by the server while clients are connected to its room. Clients sync with the
+/// server document using the y-sync protocol, so late joiners get the state from the server. Merged state is
+/// persisted once updates settle and when the last client leaves.
+pub struct DescriptionRoom {
+    doc: Doc,
+
+    /// Awareness states of connected clients. yrs `Awareness` is not `Send`, so states are relayed by the room
+    /// without it.
+    awareness: HashMap<u64, AwarenessUpdateEntry>,
+
+    /// Last persisted description.
+    description: Description,
+
+    /// Users whose updates are not persisted yet. They are recorded as authors of the description version.
+    editors: HashMap<Uuid, Profile>,
+
+    /// Connections of this instance attached to the room, including the ones that are still starting.
+    connection_count: usize,
+
+    version: u64,
+    persisted_version: u64,
+}
+
+impl DescriptionRoom {
+    /// Seeds the room document from the stored description. `description` holds primary key and object type of
+    /// the description.
+    pub async fn load(db_session: &CachingSession, mut description: Description) -> Result<Self, NodecosmosError> {
+        match description.find_branched_merged(db_session).await {
+            Ok(_) => (),
+            Err(NodecosmosError::CharybdisError(CharybdisError::NotFoundError(_))) => {
+                if description.object_type.is_empty() {
+                    return Err(NodecosmosError::BadRequest(
+                        "Object type is required for new description".to_string(),
+                    ));
+                }
+            }
+            Err(e) => return Err(e),
+        }
+
+        let doc = Doc::new();
+
+        if let Some(base64) = &description.base64 {
+            let buf = STANDARD.decode(base64)?;
+            let update = Update::decode_v2(&buf)?;
+
+            doc.transact_mut().apply_update(update)?;
+        } else {
+            // merge parses the stored state, so new descriptions start with the empty document
+            let state = doc.transact().encode_state_as_update_v2(&StateVector::default());
+
+            description.base64 = Some(STANDARD.encode(state));
+        }
+
+        Ok(Self {
+            doc,
+            awareness: HashMap::new(),
+            description,
+            editors: HashMap::new(),
+            connection_count: 0,
+            version: 0,
+            persisted_version: 0,
+        })
+    }
+
+    pub fn lock(room: &Mutex<Self>) -> MutexGuard<'_, Self> {
+        room.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    pub fn join(&mut self) {
+        self.connection_count += 1;
+    }
+
+    /// Returns whether no connection is left.
+    pub fn leave(&mut self) -> bool {
+        self.connection_count = self.connection_count.saturating_sub(1);
+
+        self.is_empty()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.connection_count == 0
+    }
+
+    /// Frames sent to the client when it joins: server state vector, so the client replies with updates the
+    /// server is missing, and awareness of the other clients.
+    pub fn greeting(&self) -> Result<Vec<Vec<u8>>, NodecosmosError> {
+        let mut frames = vec![self.sync_request()];
+
+        if !self.awareness.is_empty() {
+            frames.push(self.awareness_frame());
+        }
+
+        Ok(frames)
+    }
+
+    /// State vector frame. Peers reply with updates the room is missing.
+    pub fn sync_request(&self) -> Vec<u8> {
+        let state_vector = self.doc.transact().state_vector();
+
+        Message::Sync(SyncMessage::SyncStep1(state_vector)).encode_v1()
+    }
+
+    /// Room document encoded as v2 update.
+    pub fn state(&self) -> Vec<u8> {
+        self.doc.transact().encode_state_as_update_v2(&StateVector::default())
+    }
+
+    /// Frame of the update made on the server, e.g. by restoring description version.
+    pub fn update_frame(update: Vec<u8>) -> Vec<u8> {
+        Message::Sync(SyncMessage::Update(update)).encode_v1()
+    }
+
+    /// Applies y-sync messages of the client frame. Awareness client ids of the client are collected, so their
+    /// states can be removed once the client leaves.
+    pub fn handle_frame(&mut self, frame: &[u8], client_ids: &mut HashSet<u64>) -> Result<SyncOutput, NodecosmosError> {
+        let mut output = SyncOutput::default();
+        let mut decoder = DecoderV1::from(frame);
+
+        for message in MessageReader::new(&mut decoder) {
+            match message.map_err(sync_error)? {
+                Message::Sync(SyncMessage::SyncStep1(state_vector)) => {
+                    let update = self.doc.transact().encode_state_as_update_v1(&state_vector);
+
+                    output
+                        .reply
+                        .push(Message::Sync(SyncMessage::SyncStep2(update)).encode_v1());
+                }
+                Message::Sync(SyncMessage::SyncStep2(update)) | Message::Sync(SyncMessage::Update(update)) => {
+                    let decoded = Update::decode_v1(&update)?;
+
+                    self.doc.transact_mut().apply_update(decoded)?;
+                    self.version += 1;
+
+                    output.is_updated = true;
+                    output
+                        .broadcast
+                        .push(Message::Sync(SyncMessage::Update(update)).encode_v1());
+                }
+                Message::Awareness(update) => {
+                    client_ids.extend(update.clients.keys().copied());
+
+                    output.broadcast.push(Message::Awareness(update.clone()).encode_v1());
+
+                    self.apply_awareness(update);
+                }
+                Message::AwarenessQuery => {
+                    output.reply.push(self.awareness_frame());
+                }
+                Message::Auth(_) | Message::Custom(_, _) => (),
+            }
+        }
+
+        Ok(output)
+    }
+
+    /// Removes awareness states of the client that left. Returns frame that notifies other clients.
+    pub fn remove_clients(&mut self, client_ids: &HashSet<u64>) -> Option<Vec<u8>> {
+        if client_ids.is_empty() {
+            return None;
+        }
+
+        // `null` state with increased clock removes the client from awareness of other clients
+        let clients: HashMap<u64, AwarenessUpdateEntry> = client_ids
+            .iter()
+            .filter_map(|client_id| {
+                self.awareness.remove(client_id).map(|entry| {
+                    let entry = AwarenessUpdateEntry {
+                        clock: entry.clock + 1,
+                        json: Arc::from(NULL_AWARENESS_STATE),
+                    };
+
+                    (*client_id, entry)
+                })
+            })
+            .collect();
+
+        if clients.is_empty() {
+            return None;
+        }
+
+        Some(Message::Awareness(AwarenessUpdate { clients }).encode_v1())
+    }
+
+    /// Keeps the latest state of each client. Clients with `null` state are removed.
+    fn apply_awareness(&mut self, update: AwarenessUpdate) {
+        for (client_id, entry) in update.clients {
+            let is_outdated = self
+                .awareness
+                .get(&client_id)
+                .is_some_and(|current| current.clock > entry.clock);
+
+            if is_outdated {
+                continue;
+            }
+
+            if entry.json.as_ref() == NULL_AWARENESS_STATE {
+                self.awareness.remove(&client_id);
+            } else {
+                self.awareness.insert(client_id, entry);
+            }
+        }
+    }
+
+    fn awareness_frame(&self) -> Vec<u8> {
+        let update = AwarenessUpdate {
+            clients: self.awareness.clone(),
+        };
+
+        Message::Awareness(update).encode_v1()
+    }
+
+    /// Persists the room once no other update comes in during the debounce period.
+    pub fn schedule_persist(room: Arc<Mutex<Self>>, data: RequestData) {
+        let version = {
+            let mut room = Self::lock(&room);
+
+            room.editors
+                .entry(data.current_user.id)
+                .or_insert_with(|| Profile::from(&data.current_user));
+
+            room.version
+        };
+
+        tokio::spawn(async move {
+            tokio::time::sleep(Duration::from_millis(PERSIST_DEBOUNCE_MS)).await;
+
+            // newer updates schedule their own persist
+            if Self::lock(&room).version != version {
+                return;
+            }
+
+            let _ = Self::persist(&room, &data).await.map_err(|e| {
+                log::error!("Failed to persist description room: {:?}", e);
+            });
+        });
+    }
+
+    /// Merges the room document into the stored description. Html and markdown are regenerated by the merge.
+    pub async fn persist(room: &Mutex<Self>, data: &RequestData) -> Result<(), NodecosmosError> {
+        let (mut description, update, version) = {
+            let mut room = Self::lock(room);
+
+            if room.version == room.persisted_version {
+                return Ok(());
+            }
+
+            let state = room
+                .doc
+                .transact()
+                .encode_state_as_update_v2(&StateVector::default());
+            let update = Description {
+                base64: Some(STANDARD.encode(state)),
+                ..Default::default()
+            };
+            let mut description = room.description.clone();
+
+            description.editors = Some(room.editors.drain().map(|(_, editor)| editor).collect());
+
+            (description, update, room.version)
+        };
+        let editors = description.editors.clone().unwrap_or_default();
+
+        let persisted = match description.merge(&update).await {
+            Ok(_) => description.insert_cb(data).execute(data.db_session()).await,
+            Err(e) => Err(e),
+        };
+
+        let mut room = Self::lock(room);
+
+        if let Err(e) = persisted {
+            // editors are recorded by the next persist
+            for editor in editors {
+                room.editors.entry(editor.id).or_insert(editor);
+            }
+
+            return Err(e);
+        }
+
+        room.description = description;
+        room.persisted_version = room.persisted_version.max(version);
+
+        Ok(())
+    }
+}
+
+fn sync_error(e: yrs::encoding::read::Error) -> NodecosmosError {
+    NodecosmosError::BadRequest(format!("Invalid sync message: {}", e))
+}
@@ -12,6 +12,7 @@ const INVITATION_ACCEPTED_EMAIL: &str = "invitation_accepted_email";
 const RESET_PASSWORD_EMAIL: &str = "reset_password_email";
 const PASSWORD_CHANGED_EMAIL: &str = "password_changed_email";
 const CONTACT_US_EMAIL: &str = "contact_us_email";
+const MENTION_EMAIL: &str = "mention_email";
 
 pub struct Mailer {
     pub templates: Handlebars<'static>,
@@ -53,6 +54,10 @@ impl Mailer {
             .register_template_string(CONTACT_US_EMAIL, include_str!("mailer/contact_us_email.html"))
             .expect("Template should be valid");
 
+        templates
+            .register_template_string(MENTION_EMAIL, include_str!("mailer/mention_email.html"))
+            .expect("Template should be valid");
+
         Self {
             templates,
             client_url: config.client_url.clone(),
@@ -171,6 +176,28 @@ impl Mailer {
             .await
     }
 
+    pub async fn send_mention_email(
+        &self,
+        to: String,
+        username: &str,
+        mention_text: &str,
+        url: &str,
+    ) -> Result<(), NodecosmosError> {
+        let mut ctx = HashMap::<&str, &str>::new();
+        ctx.insert("username", username);
+        ctx.insert("mention_text", mention_text);
+        ctx.insert("url", url);
+
+        let message = self
+            .templates
+            .render(MENTION_EMAIL, &ctx)
+            .map_err(|e| NodecosmosError::TemplateError(e.to_string()))?;
+
+        self.client
+            .send_email(to, "You have been mentioned on nodecosmos", message)
+            .await
+    }
+
     pub async fn send_contact_us_email(&self, contact: &Contact) -> Result<(), NodecosmosError> {
         let mut ctx = HashMap::<&str, &str>::new();
         ctx.insert("first_name", &contact.first_name);
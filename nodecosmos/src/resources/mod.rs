@@ -1,5 +1,7 @@
+pub mod description_room;
 pub mod email_client;
 pub mod mailer;
+pub mod presence;
 pub mod resource;
 pub mod resource_locker;
 pub mod sse_broadcast;
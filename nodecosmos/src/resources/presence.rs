@@ -0,0 +1,257 @@
+use std::collections::{HashMap, HashSet};
+
+use charybdis::types::Uuid;
+use dashmap::DashMap;
+use log::error;
+use serde::{Deserialize, Serialize};
+
+use crate::api::data::RequestData;
+use crate::api::types::ActionTypes;
+use crate::errors::NodecosmosError;
+use crate::models::user::CurrentUser;
+use crate::resources::sse_broadcast::SseMessage;
+
+/// Sessions held by the instance are refreshed at this interval.
+pub const PRESENCE_HEARTBEAT_SEC: u64 = 15;
+
+/// Session is gone if it's not refreshed for this long, e.g. because its instance died.
+const PRESENCE_TTL_SEC: i64 = 45;
+
+#[derive(Clone, Copy, PartialEq, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum PresenceKind {
+    /// Listens to events of the root tree.
+    Viewing,
+
+    /// Connected to the description room of the object.
+    Editing,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Presence {
+    pub session_id: Uuid,
+    pub root_id: Uuid,
+    pub branch_id: Option<Uuid>,
+    pub object_id: Option<Uuid>,
+    pub kind: PresenceKind,
+    pub user_id: Uuid,
+    pub username: String,
+    pub full_name: String,
+    pub profile_image_url: Option<String>,
+
+    /// Unix timestamp in seconds.
+    pub expires_at: i64,
+}
+
+impl Presence {
+    fn new(current_user: &CurrentUser, root_id: Uuid, kind: PresenceKind) -> Self {
+        Self {
+            session_id: Uuid::new_v4(),
+            root_id,
+            branch_id: None,
+            object_id: None,
+            kind,
+            user_id: current_user.id,
+            username: current_user.username.clone(),
+            full_name: current_user.full_name(),
+            profile_image_url: current_user.profile_image_url.clone(),
+            expires_at: 0,
+        }
+    }
+
+    pub fn viewing(current_user: &CurrentUser, root_id: Uuid) -> Self {
+        Self::new(current_user, root_id, PresenceKind::Viewing)
+    }
+
+    pub fn editing(current_user: &CurrentUser, root_id: Uuid, branch_id: Uuid, object_id: Uuid) -> Self {
+        Self {
+            branch_id: Some(branch_id),
+            object_id: Some(object_id),
+            ..Self::new(current_user, root_id, PresenceKind::Editing)
+        }
+    }
+
+    fn is_expired(&self) -> bool {
+        self.expires_at <= chrono::Utc::now().timestamp()
+    }
+}
+
+/// Tracks who is viewing root trees and editing descriptions. Sessions are stored in redis hash of the root, so
+/// presence is shared across instances. Each instance refreshes its own sessions, and sessions that are not
+/// refreshed are swept, so they are removed once their instance dies. Join and leave are sent as SSE events.
+#[derive(Default)]
+pub struct PresenceTracker {
+    sessions: DashMap<Uuid, Presence>,
+}
+
+impl PresenceTracker {
+    fn key(root_id: Uuid) -> String {
+        format!("PRESENCE:{}", root_id)
+    }
+
+    /// Registers the session and returns its id.
+    pub async fn join(&self, data: &RequestData, mut presence: Presence) -> Result<Uuid, NodecosmosError> {
+        presence.expires_at = chrono::Utc::now().timestamp() + PRESENCE_TTL_SEC;
+
+        Self::store(data, &presence).await?;
+        Self::send_event(data, ActionTypes::PresenceJoin, &presence).await?;
+
+        let session_id = presence.session_id;
+        self.sessions.insert(session_id, presence);
+
+        Ok(session_id)
+    }
+
+    pub async fn leave(&self, data: &RequestData, session_id: Uuid) -> Result<(), NodecosmosError> {
+        if let Some((_, presence)) = self.sessions.remove(&session_id) {
+            Self::remove(data, &presence).await?;
+        }
+
+        Ok(())
+    }
+
+    /// Leaves the session in the background. Used when connection closes outside of async context.
+    pub fn spawn_leave(data: &RequestData, session_id: Uuid) {
+        let data = data.clone();
+
+        tokio::spawn(async move {
+            let _ = data.presence().leave(&data, session_id).await.map_err(|e| {
+                error!("Failed to leave presence session {}: {:?}", session_id, e);
+            });
+        });
+    }
+
+    /// Lists current sessions of the root.
+    pub async fn list(data: &RequestData, root_id: Uuid) -> Result<Vec<Presence>, NodecosmosError> {
+        let presences = Self::find_all(data, root_id).await?;
+
+        Ok(presences
+            .into_iter()
+            .filter(|presence| !presence.is_expired())
+            .collect())
+    }
+
+    /// Refreshes sessions held by this instance and sweeps expired sessions of roots it serves.
+    pub async fn heartbeat(&self, data: &RequestData) {
+        let expires_at = chrono::Utc::now().timestamp() + PRESENCE_TTL_SEC;
+        let mut root_ids: HashSet<Uuid> = data
+            .sse_broadcast()
+            .root_channels
+            .iter()
+            .map(|channel| *channel.key())
+            .collect();
+
+        let presences: Vec<Presence> = self
+            .sessions
+            .iter_mut()
+            .map(|mut presence| {
+                presence.expires_at = expires_at;
+                presence.clone()
+            })
+            .collect();
+
+        for presence in presences {
+            root_ids.insert(presence.root_id);
+
+            let _ = Self::store(data, &presence).await.map_err(|e| {
+                error!("Failed to refresh presence session {}: {:?}", presence.session_id, e);
+            });
+        }
+
+        for root_id in root_ids {
+            let _ = Self::sweep(data, root_id).await.map_err(|e| {
+                error!("Failed to sweep presence of root {}: {:?}", root_id, e);
+            });
+        }
+    }
+
+    async fn sweep(data: &RequestData, root_id: Uuid) -> Result<(), NodecosmosError> {
+        let presences = Self::find_all(data, root_id).await?;
+
+        for presence in presences.iter().filter(|presence| presence.is_expired()) {
+            Self::remove(data, presence).await?;
+        }
+
+        Ok(())
+    }
+
+    async fn find_all(data: &RequestData, root_id: Uuid) -> Result<Vec<Presence>, NodecosmosError> {
+        let mut connection = data.redis_connection().await?;
+
+        let sessions: HashMap<String, String> = redis::cmd("HGETALL")
+            .arg(Self::key(root_id))
+            .query_async(&mut *connection)
+            .await?;
+
+        let presences = sessions
+            .values()
+            .filter_map(|value| serde_json::from_str::<Presence>(value).ok())
+            .collect();
+
+        Ok(presences)
+    }
+
+    async fn store(data: &RequestData, presence: &Presence) -> Result<(), NodecosmosError> {
+        let mut connection = data.redis_connection().await?;
+        let key = Self::key(presence.root_id);
+
+        redis::cmd("HSET")
+            .arg(&key)
+            .arg(presence.session_id.to_string())
+            .arg(serde_json::to_string(presence)?)
+            .query_async::<()>(&mut *connection)
+            .await?;
+
+        // hash is dropped once nobody refreshes it
+        redis::cmd("EXPIRE")
+            .arg(&key)
+            .arg(PRESENCE_TTL_SEC)
+            .query_async::<()>(&mut *connection)
+            .await?;
+
+        Ok(())
+    }
+
+    async fn remove(data: &RequestData, presence: &Presence) -> Result<(), NodecosmosError> {
+        let mut connection = data.redis_connection().await?;
+
+        let removed: i64 = redis::cmd("HDEL")
+            .arg(Self::key(presence.root_id))
+            .arg(presence.session_id.to_string())
+            .query_async(&mut *connection)
+            .await?;
+
+        // only the instance that removed the session sends the event
+        if removed > 0 {
+            Self::send_event(data, ActionTypes::PresenceLeave, presence).await?;
+        }
+
+        Ok(())
+    }
+
+    async fn send_event(
+        data: &RequestData,
+        action_type: ActionTypes,
+        presence: &Presence,
+    ) -> Result<(), NodecosmosError> {
+        let msg = SseMessage::new(
+            presence.root_id,
+            format!("event: {}\ndata: {}\n\n", action_type, serde_json::to_string(presence)?),
+        );
+
+        data.sse_broadcast().broadcast_message(data, msg).await
+    }
+}
+
+/// Leaves the presence session once the connection that holds the guard is dropped.
+pub struct PresenceGuard {
+    pub data: RequestData,
+    pub session_id: Uuid,
+}
+
+impl Drop for PresenceGuard {
+    fn drop(&mut self) {
+        PresenceTracker::spawn_leave(&self.data, self.session_id);
+    }
+}
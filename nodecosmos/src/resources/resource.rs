@@ -221,7 +221,15 @@ impl<'a> Resource<'a> for crate::resources::ws_broadcast::WsBroadcast {
     type Cfg = ();
 
     async fn init_resource(_config: ()) -> Self {
-        crate::resources::ws_broadcast::WsBroadcast::default()
+        crate::resources::ws_broadcast::WsBroadcast::new()
+    }
+}
+
+impl<'a> Resource<'a> for crate::resources::presence::PresenceTracker {
+    type Cfg = ();
+
+    async fn init_resource(_config: ()) -> Self {
+        crate::resources::presence::PresenceTracker::default()
     }
 }
 
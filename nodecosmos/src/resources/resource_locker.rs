@@ -104,6 +104,41 @@ impl ResourceLocker {
         Ok(())
     }
 
+    /// Locks the action only if it's not locked already. Returns false if it's held by someone else, so it can be
+    /// used to claim work between server instances.
+    pub async fn try_lock_resource_action(
+        &self,
+        action: ActionTypes,
+        id: Uuid,
+        branch_id: Uuid,
+        ttl: usize,
+    ) -> Result<bool, NodecosmosError> {
+        let mut connection = self.pool.get().await?;
+
+        let res: Option<String> = redis::cmd("SET")
+            .arg(self.action_key(&action, id, branch_id))
+            .arg("1")
+            .arg("NX")
+            .arg("PX")
+            .arg(ttl)
+            .query_async(&mut *connection)
+            .await
+            .map_err(|e| {
+                NodecosmosError::LockerError(format!(
+                    "Failed to lock resource action: {} for resource: {}! Error: {:?}",
+                    action, id, e
+                ))
+            })?;
+
+        if res.is_none() {
+            return Ok(false);
+        }
+
+        self.wait_for_write_replication().await?;
+
+        Ok(true)
+    }
+
     pub async fn unlock_resource_actions(
         &self,
         id: Uuid,
@@ -1,24 +1,103 @@
-use charybdis::model::BaseModel;
-use charybdis::serializers::ToJson;
 use charybdis::types::Uuid;
 use dashmap::DashMap;
 use log::error;
 use redis::{FromRedisValue, RedisWrite, ToRedisArgs, Value};
 use serde::{Deserialize, Serialize};
 use serde_json::json;
+use std::collections::HashMap;
 use std::fmt::Display;
+use std::sync::Arc;
 use tokio::sync::broadcast;
 
 use crate::api::data::RequestData;
 use crate::api::types::ActionTypes;
 use crate::errors::NodecosmosError;
 
-#[derive(Debug, Serialize, Deserialize)]
+/// Node events of the root are kept in redis stream of this length, so reconnecting clients can replay them.
+const EVENT_STREAM_MAX_LEN: usize = 1000;
+
+/// Event stream of the root is dropped once no event is added for this long.
+const EVENT_STREAM_TTL_SEC: usize = 3600;
+
+/// Sent when missed events are no longer kept, so the client has to reload the tree.
+const RESYNC_REQUIRED_EVENT: &str = "event: RESYNC_REQUIRED\ndata: {}\n\n";
+
+/// Id of the event in redis stream of the root, `<ms>-<seq>`. Ids increase monotonically.
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug)]
+pub struct EventId(u64, u64);
+
+impl EventId {
+    pub fn parse(id: &str) -> Option<Self> {
+        let (ms, seq) = id.trim().split_once('-')?;
+
+        Some(EventId(ms.parse().ok()?, seq.parse().ok()?))
+    }
+}
+
+impl Display for EventId {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}-{}", self.0, self.1)
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct SseMessage {
     pub root_id: Uuid,
+
+    /// Branch of the model event. Events without branch, e.g. ping or presence, are sent to all listeners.
+    #[serde(default)]
+    pub branch_id: Option<Uuid>,
+
+    /// Object type of the model event, e.g. `FLOW_STEP`.
+    #[serde(default)]
+    pub object_type: Option<String>,
+
+    /// Id of the event in the stream of the root. Assigned once the event is broadcast.
+    #[serde(default)]
+    pub id: Option<String>,
+
+    /// Event frame sent to clients.
     pub data: String,
 }
 
+impl SseMessage {
+    pub fn new(root_id: Uuid, data: String) -> Self {
+        Self {
+            root_id,
+            branch_id: None,
+            object_type: None,
+            id: None,
+            data,
+        }
+    }
+}
+
+/// Filter of the events listener. Object types are comma separated, e.g. `FLOW,FLOW_STEP`.
+#[derive(Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct EventFilter {
+    pub branch_id: Option<Uuid>,
+    pub object_types: Option<String>,
+}
+
+impl EventFilter {
+    pub fn matches(&self, msg: &SseMessage) -> bool {
+        if let (Some(branch_id), Some(msg_branch_id)) = (self.branch_id, msg.branch_id) {
+            if branch_id != msg_branch_id {
+                return false;
+            }
+        }
+
+        if let (Some(object_types), Some(object_type)) = (&self.object_types, &msg.object_type) {
+            return object_types
+                .split(',')
+                .any(|filter_type| filter_type.trim() == object_type);
+        }
+
+        true
+    }
+}
+
 impl Display for SseMessage {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(f, "root_id:{}\ndata: {}", &self.root_id, &self.data)
@@ -57,7 +136,7 @@ impl FromRedisValue for SseMessage {
 
 #[derive(Debug)]
 pub struct SseBroadcast {
-    pub root_channels: DashMap<Uuid, broadcast::Sender<actix_web::web::Bytes>>,
+    pub root_channels: DashMap<Uuid, broadcast::Sender<Arc<SseMessage>>>,
 }
 
 impl SseBroadcast {
@@ -67,9 +146,11 @@ impl SseBroadcast {
         }
     }
 
-    pub async fn send_message(&self, root_id: Uuid, model_msg: actix_web::web::Bytes) -> Result<(), NodecosmosError> {
+    pub async fn send_message(&self, msg: SseMessage) -> Result<(), NodecosmosError> {
+        let root_id = msg.root_id;
+
         if let Some(sender) = self.root_channels.get(&root_id) {
-            sender.send(model_msg).map_err(|e| {
+            sender.send(Arc::new(msg)).map_err(|e| {
                 error!("Error sending message to room {}: {}", root_id, e);
 
                 NodecosmosError::BroadcastError(format!("Error sending message to room {}: {}", root_id, e))
@@ -83,12 +164,15 @@ impl SseBroadcast {
         for channel in self.root_channels.iter() {
             let root_id = channel.key();
             if let Some(sender) = self.root_channels.get(root_id) {
-                let _ = sender.send(actix_web::web::Bytes::from("event: PING\ndata: PONG\n\n"));
+                let _ = sender.send(Arc::new(SseMessage::new(
+                    *root_id,
+                    "event: PING\ndata: PONG\n\n".to_string(),
+                )));
             }
         }
     }
 
-    pub fn build_receiver(&self, root_id: Uuid) -> broadcast::Receiver<actix_web::web::Bytes> {
+    pub fn build_receiver(&self, root_id: Uuid) -> broadcast::Receiver<Arc<SseMessage>> {
         let receiver;
 
         if let Some(sender) = self.root_channels.get(&root_id) {
@@ -110,9 +194,123 @@ impl SseBroadcast {
         });
     }
 
+    fn events_key(root_id: Uuid) -> String {
+        format!("SSE_EVENTS:{}", root_id)
+    }
+
+    /// Replays events of the root added after `last_event_id`. Returns events and id of the last replayed event,
+    /// so live events that were replayed can be skipped. If the events are no longer kept, only the resync
+    /// required event is returned.
+    pub async fn replay(
+        &self,
+        data: &RequestData,
+        root_id: Uuid,
+        last_event_id: &str,
+    ) -> Result<(Vec<SseMessage>, Option<EventId>), NodecosmosError> {
+        let resync = || (vec![SseMessage::new(root_id, RESYNC_REQUIRED_EVENT.to_string())], None);
+
+        let Some(last_id) = EventId::parse(last_event_id) else {
+            return Ok(resync());
+        };
+
+        let mut connection = data.redis_connection().await?;
+        let key = Self::events_key(root_id);
+
+        let first: Vec<(String, Vec<String>)> = redis::cmd("XRANGE")
+            .arg(&key)
+            .arg("-")
+            .arg("+")
+            .arg("COUNT")
+            .arg(1)
+            .query_async(&mut *connection)
+            .await?;
+
+        if Self::missed_events_trimmed(first.first().map(|(id, _)| id.as_str()), last_id) {
+            return Ok(resync());
+        }
+
+        let events: Vec<(String, Vec<String>)> = redis::cmd("XRANGE")
+            .arg(&key)
+            .arg(format!("({}", last_id))
+            .arg("+")
+            .query_async(&mut *connection)
+            .await?;
+
+        Ok(Self::replay_messages(root_id, events))
+    }
+
+    /// Events after the last received one could have been trimmed by `MAXLEN` or expired with the stream. They are
+    /// still kept only if the first event of the stream is not newer than the last received one.
+    fn missed_events_trimmed(first_id: Option<&str>, last_id: EventId) -> bool {
+        match first_id.and_then(EventId::parse) {
+            Some(first_id) => first_id > last_id,
+            None => true,
+        }
+    }
+
+    /// Builds messages from `XRANGE` entries in stream order and returns them with the id of the last one.
+    fn replay_messages(root_id: Uuid, events: Vec<(String, Vec<String>)>) -> (Vec<SseMessage>, Option<EventId>) {
+        let mut last_replayed_id = None;
+        let messages = events
+            .into_iter()
+            .filter_map(|(id, fields)| {
+                let mut fields: HashMap<String, String> = fields
+                    .chunks_exact(2)
+                    .map(|field| (field[0].clone(), field[1].clone()))
+                    .collect();
+                let event = fields.remove("data")?;
+                last_replayed_id = EventId::parse(&id);
+
+                Some(SseMessage {
+                    root_id,
+                    branch_id: fields.get("branch_id").and_then(|id| Uuid::parse_str(id).ok()),
+                    object_type: fields.remove("object_type"),
+                    data: format!("id: {}\n{}", id, event),
+                    id: Some(id),
+                })
+            })
+            .collect();
+
+        (messages, last_replayed_id)
+    }
+
     // this will broadcast message to all app instances through redis
-    async fn broadcast_message(&self, data: &RequestData, msg: SseMessage) -> Result<(), NodecosmosError> {
+    pub(crate) async fn broadcast_message(
+        &self,
+        data: &RequestData,
+        mut msg: SseMessage,
+    ) -> Result<(), NodecosmosError> {
         let mut connection = data.redis_connection().await?;
+        let key = Self::events_key(msg.root_id);
+
+        // event is kept in the stream of the root, so reconnecting clients can replay it
+        let mut xadd = redis::cmd("XADD");
+        xadd.arg(&key)
+            .arg("MAXLEN")
+            .arg("~")
+            .arg(EVENT_STREAM_MAX_LEN)
+            .arg("*")
+            .arg("data")
+            .arg(&msg.data);
+
+        if let Some(branch_id) = msg.branch_id {
+            xadd.arg("branch_id").arg(branch_id.to_string());
+        }
+
+        if let Some(object_type) = &msg.object_type {
+            xadd.arg("object_type").arg(object_type);
+        }
+
+        let id: String = xadd.query_async(&mut *connection).await?;
+
+        redis::cmd("EXPIRE")
+            .arg(&key)
+            .arg(EVENT_STREAM_TTL_SEC)
+            .query_async::<()>(&mut *connection)
+            .await?;
+
+        msg.data = format!("id: {}\n{}", id, msg.data);
+        msg.id = Some(id);
 
         redis::cmd("PUBLISH")
             .arg("BROADCAST_MESSAGE")
@@ -130,16 +328,29 @@ impl SseBroadcast {
     }
 }
 
-pub struct ModelEvent<'a, M: BaseModel> {
+/// Mutation of a model in the root tree. Event data holds object type, action, branch and the model.
+pub struct ModelEvent<'a, M: Serialize> {
     pub root_id: Uuid,
+    pub branch_id: Uuid,
     pub action_type: ActionTypes,
     pub model: &'a M,
 }
 
-impl<'a, M: BaseModel + Serialize> ModelEvent<'a, M> {
-    pub fn new(root_id: Uuid, action_type: ActionTypes, model: &'a M) -> Self {
+/// Data of the model event frame. Model is sent under `payload`, see `doc/Events.md`.
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct ModelEventData<'a, M: Serialize> {
+    branch_id: Uuid,
+    object_type: Option<String>,
+    action: &'static str,
+    payload: &'a M,
+}
+
+impl<'a, M: Serialize> ModelEvent<'a, M> {
+    pub fn new(root_id: Uuid, branch_id: Uuid, action_type: ActionTypes, model: &'a M) -> Self {
         ModelEvent {
             root_id,
+            branch_id,
             action_type,
             model,
         }
@@ -152,10 +363,134 @@ impl<'a, M: BaseModel + Serialize> ModelEvent<'a, M> {
         sse_broadcast.broadcast_message(data, msg).await
     }
 
+    /// Sends the event and logs failure, as mutation is already done at this point.
+    pub async fn emit(&self, data: &RequestData) {
+        if let Err(e) = self.send(data).await {
+            error!(
+                "Error sending {} event to room {}: {}",
+                self.action_type, self.root_id, e
+            );
+        }
+    }
+
     fn to_sse(&self) -> Result<SseMessage, NodecosmosError> {
+        let object_type = self.action_type.object().map(|object| object.to_string());
+        let event_data = ModelEventData {
+            branch_id: self.branch_id,
+            object_type: object_type.clone(),
+            action: self.action_type.action(),
+            payload: self.model,
+        };
+
         Ok(SseMessage {
             root_id: self.root_id,
-            data: format!("event: {}\ndata: {}\n\n", self.action_type, self.model.to_json()?),
+            branch_id: Some(self.branch_id),
+            object_type,
+            id: None,
+            data: format!(
+                "event: {}\ndata: {}\n\n",
+                self.action_type,
+                serde_json::to_string(&event_data)?
+            ),
         })
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn stream_entry(id: &str, fields: &[(&str, &str)]) -> (String, Vec<String>) {
+        (
+            id.to_string(),
+            fields
+                .iter()
+                .flat_map(|(field, value)| [field.to_string(), value.to_string()])
+                .collect(),
+        )
+    }
+
+    #[test]
+    fn test_parse_event_id() {
+        assert_eq!(EventId::parse("1700000000000-3"), Some(EventId(1700000000000, 3)));
+        assert_eq!(EventId::parse(" 1-0\n"), Some(EventId(1, 0)));
+
+        for malformed_id in [
+            "",
+            "-",
+            "1",
+            "1-",
+            "-1",
+            "a-1",
+            "1-b",
+            "1-2-3",
+            "-1-2",
+            "1.5-2",
+            "18446744073709551616-0",
+        ] {
+            assert_eq!(EventId::parse(malformed_id), None, "{malformed_id}");
+        }
+    }
+
+    #[test]
+    fn test_event_id_order() {
+        // sequence is compared numerically within the same ms
+        assert!(EventId::parse("1-10").unwrap() > EventId::parse("1-9").unwrap());
+        assert!(EventId::parse("2-0").unwrap() > EventId::parse("1-99").unwrap());
+        assert_eq!(EventId(5, 1).to_string(), "5-1");
+    }
+
+    #[test]
+    fn test_missed_events_trimmed() {
+        let last_id = EventId(10, 1);
+
+        assert!(!SseBroadcast::missed_events_trimmed(Some("9-0"), last_id));
+        assert!(!SseBroadcast::missed_events_trimmed(Some("10-1"), last_id));
+
+        // last received event was already trimmed by MAXLEN
+        assert!(SseBroadcast::missed_events_trimmed(Some("10-2"), last_id));
+        assert!(SseBroadcast::missed_events_trimmed(Some("11-0"), last_id));
+
+        // stream expired
+        assert!(SseBroadcast::missed_events_trimmed(None, last_id));
+    }
+
+    #[test]
+    fn test_replay_messages() {
+        let root_id = Uuid::new_v4();
+        let branch_id = Uuid::new_v4();
+        let events = vec![
+            stream_entry(
+                "10-2",
+                &[
+                    ("data", "event: CREATE\ndata: {}\n\n"),
+                    ("branch_id", &branch_id.to_string()),
+                    ("object_type", "FLOW"),
+                ],
+            ),
+            stream_entry("10-3", &[("object_type", "FLOW")]),
+            stream_entry("11-0", &[("data", "event: UPDATE\ndata: {}\n\n")]),
+        ];
+
+        let (messages, last_replayed_id) = SseBroadcast::replay_messages(root_id, events);
+
+        assert_eq!(
+            messages.iter().map(|msg| msg.id.as_deref()).collect::<Vec<_>>(),
+            vec![Some("10-2"), Some("11-0")]
+        );
+        assert_eq!(last_replayed_id, Some(EventId(11, 0)));
+        assert_eq!(messages[0].branch_id, Some(branch_id));
+        assert_eq!(messages[0].object_type.as_deref(), Some("FLOW"));
+        assert_eq!(messages[0].data, "id: 10-2\nevent: CREATE\ndata: {}\n\n");
+        assert_eq!(messages[1].branch_id, None);
+        assert!(messages.iter().all(|msg| msg.root_id == root_id));
+    }
+
+    #[test]
+    fn test_replay_messages_without_events() {
+        let (messages, last_replayed_id) = SseBroadcast::replay_messages(Uuid::new_v4(), vec![]);
+
+        assert!(messages.is_empty());
+        assert_eq!(last_replayed_id, None);
+    }
+}
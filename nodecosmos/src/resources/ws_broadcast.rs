@@ -1,25 +1,397 @@
-use std::sync::Arc;
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
 
 use actix::prelude::*;
 use actix_web_actors::ws;
+use charybdis::types::Uuid;
+use dashmap::mapref::entry::Entry;
 use dashmap::DashMap;
 use log::error;
+use redis::{FromRedisValue, RedisWrite, ToRedisArgs, Value};
+use tokio::sync::mpsc;
+
+use crate::api::data::RequestData;
+use crate::errors::NodecosmosError;
+use crate::resources::description_room::DescriptionRoom;
+use crate::resources::presence::PresenceTracker;
 
 type RoomId = String; // BranchId + RoomId
 
-#[derive(Default)]
+/// Room frames are relayed between instances through `WS_ROOM:{room_id}` channels.
+pub const ROOM_CHANNEL_PREFIX: &str = "WS_ROOM:";
+
+/// Instances publish heartbeat to channels of rooms they hold at this interval.
+pub const HEARTBEAT_INTERVAL_SEC: u64 = 10;
+
+/// Clients of the instance are removed from the room if its heartbeat is not received for this long.
+const INSTANCE_TIMEOUT_SEC: u64 = 30;
+
+/// Y-sync frames published to the room channel, tagged with the publishing instance. Empty frame is heartbeat.
+#[derive(Debug)]
+pub struct RoomMessage {
+    pub instance_id: Uuid,
+    pub frame: Vec<u8>,
+}
+
+impl ToRedisArgs for RoomMessage {
+    fn write_redis_args<W>(&self, out: &mut W)
+    where
+        W: ?Sized + RedisWrite,
+    {
+        let mut bytes = self.instance_id.as_bytes().to_vec();
+        bytes.extend_from_slice(&self.frame);
+
+        out.write_arg(&bytes)
+    }
+}
+
+impl FromRedisValue for RoomMessage {
+    fn from_redis_value(v: &Value) -> redis::RedisResult<Self> {
+        match v {
+            Value::BulkString(data) if data.len() >= 16 => {
+                let instance_id = Uuid::from_slice(&data[..16])
+                    .map_err(|_| redis::RedisError::from((redis::ErrorKind::TypeError, "Invalid instance id")))?;
+
+                Ok(RoomMessage {
+                    instance_id,
+                    frame: data[16..].to_vec(),
+                })
+            }
+            _ => Err(redis::RedisError::from((
+                redis::ErrorKind::TypeError,
+                "Invalid data type",
+            ))),
+        }
+    }
+}
+
+/// Instance subscribes to the room channel while it holds the room, so it only receives frames of its rooms.
+pub enum RoomSubscription {
+    Subscribe(RoomId),
+    Unsubscribe(RoomId),
+}
+
+/// Clients connected to the room through another instance.
+pub struct RemoteClients {
+    pub client_ids: HashSet<u64>,
+    pub last_seen: Instant,
+}
+
 pub struct WsBroadcast {
+    /// Frames published by this instance are tagged with its id, so they are not relayed back to its clients.
+    pub instance_id: Uuid,
     pub connections: DashMap<RoomId, Vec<Addr<WsConnection>>>,
+    pub rooms: DashMap<RoomId, Arc<Mutex<DescriptionRoom>>>,
+    pub remote_clients: DashMap<RoomId, HashMap<Uuid, RemoteClients>>,
+
+    /// Subscriptions are applied in order by the room events task.
+    subscriptions: mpsc::UnboundedSender<RoomSubscription>,
+    subscriptions_receiver: Mutex<Option<mpsc::UnboundedReceiver<RoomSubscription>>>,
+}
+
+impl Default for WsBroadcast {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl WsBroadcast {
+    pub fn new() -> Self {
+        let (subscriptions, subscriptions_receiver) = mpsc::unbounded_channel();
+
+        WsBroadcast {
+            instance_id: Uuid::new_v4(),
+            connections: DashMap::new(),
+            rooms: DashMap::new(),
+            remote_clients: DashMap::new(),
+            subscriptions,
+            subscriptions_receiver: Mutex::new(Some(subscriptions_receiver)),
+        }
+    }
+
+    /// Receiver of room subscriptions. It can be taken only once, by the room events task.
+    pub fn take_subscriptions(&self) -> Option<mpsc::UnboundedReceiver<RoomSubscription>> {
+        self.subscriptions_receiver
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .take()
+    }
+
+    /// Subscribes to the room channel once the room is loaded for the first local connection.
+    pub fn subscribe_room(&self, room_id: &str) {
+        let _ = self
+            .subscriptions
+            .send(RoomSubscription::Subscribe(room_id.to_string()))
+            .map_err(|e| error!("Failed to subscribe to room {}: {}", room_id, e));
+    }
+
+    /// Unsubscribes from the room channel once the last local connection leaves the room.
+    pub fn unsubscribe_room(&self, room_id: &str) {
+        let _ = self
+            .subscriptions
+            .send(RoomSubscription::Unsubscribe(room_id.to_string()))
+            .map_err(|e| error!("Failed to unsubscribe from room {}: {}", room_id, e));
+    }
+
+    fn broadcast(&self, room_id: &RoomId, frames: Vec<Vec<u8>>, origin_address: Option<Addr<WsConnection>>) {
+        if let Some(connections) = self.connections.get(room_id) {
+            for frame in frames {
+                for conn in connections.iter() {
+                    // this will call `handle` method of `WsMessage` actor
+                    let message = WsMessage {
+                        message: ws::Message::Binary(frame.clone().into()),
+                        origin_address: origin_address.clone(),
+                    };
+                    conn.do_send(message);
+                }
+            }
+        }
+    }
+
+    // this will relay frames to the room on other app instances through redis
+    pub async fn publish(
+        &self,
+        data: &RequestData,
+        room_id: &str,
+        frames: Vec<Vec<u8>>,
+    ) -> Result<(), NodecosmosError> {
+        let msg = RoomMessage {
+            instance_id: self.instance_id,
+            // y-sync messages are read in sequence, so frames are published at once
+            frame: frames.concat(),
+        };
+        let mut connection = data.redis_connection().await?;
+
+        redis::cmd("PUBLISH")
+            .arg(format!("{}{}", ROOM_CHANNEL_PREFIX, room_id))
+            .arg(&msg)
+            .query_async::<()>(&mut *connection)
+            .await
+            .map_err(|e| {
+                NodecosmosError::LockerError(format!(
+                    "[query_async] Failed to publish frames to room {}! Error: {:?}",
+                    room_id, e
+                ))
+            })?;
+
+        Ok(())
+    }
+
+    fn spawn_publish(self: &Arc<Self>, data: &RequestData, room_id: &str, frames: Vec<Vec<u8>>) {
+        if frames.is_empty() {
+            return;
+        }
+
+        let broadcast = self.clone();
+        let data = data.clone();
+        let room_id = room_id.to_string();
+
+        tokio::spawn(async move {
+            let _ = broadcast.publish(&data, &room_id, frames).await.map_err(|e| {
+                error!("{}", e);
+            });
+        });
+    }
+
+    /// Asks other instances holding the room for updates the freshly loaded room is missing. It's sent once the
+    /// room channel is subscribed, so replies are not missed.
+    pub fn request_sync(self: &Arc<Self>, data: &RequestData, room_id: &str) {
+        let Some(room) = self.rooms.get(room_id).map(|room| room.clone()) else {
+            return;
+        };
+
+        let frame = DescriptionRoom::lock(&room).sync_request();
+
+        self.spawn_publish(data, room_id, vec![frame]);
+    }
+
+    /// Applies update made on the server to the room held by this instance and relays it to clients of all
+    /// instances, so it is merged like an update of a client.
+    pub async fn apply_update(
+        &self,
+        data: &RequestData,
+        room_id: &str,
+        update: Vec<u8>,
+    ) -> Result<(), NodecosmosError> {
+        let frame = DescriptionRoom::update_frame(update);
+
+        if let Some(room) = self.rooms.get(room_id).map(|room| room.clone()) {
+            let output = DescriptionRoom::lock(&room).handle_frame(&frame, &mut HashSet::new())?;
+
+            self.broadcast(&room_id.to_string(), output.broadcast, None);
+
+            DescriptionRoom::schedule_persist(room, data.clone());
+        }
+
+        self.publish(data, room_id, vec![frame]).await
+    }
+
+    /// Applies frame published by another instance to the room and relays it to the clients of this instance.
+    /// Replies, e.g. updates for the sync request of the new instance, are published back to the room channel.
+    pub async fn handle_remote(&self, data: &RequestData, room_id: &str, msg: RoomMessage) {
+        if msg.instance_id == self.instance_id {
+            return;
+        }
+
+        let Some(room) = self.rooms.get(room_id).map(|room| room.clone()) else {
+            return;
+        };
+
+        let output = {
+            let mut remote_clients = self.remote_clients.entry(room_id.to_string()).or_default();
+            let instance = remote_clients.entry(msg.instance_id).or_insert_with(|| RemoteClients {
+                client_ids: HashSet::new(),
+                last_seen: Instant::now(),
+            });
+
+            instance.last_seen = Instant::now();
+
+            if msg.frame.is_empty() {
+                return;
+            }
+
+            DescriptionRoom::lock(&room).handle_frame(&msg.frame, &mut instance.client_ids)
+        };
+
+        match output {
+            Ok(output) => {
+                self.broadcast(&room_id.to_string(), output.broadcast, None);
+
+                if !output.reply.is_empty() {
+                    let _ = self.publish(data, room_id, output.reply).await.map_err(|e| {
+                        error!("{}", e);
+                    });
+                }
+            }
+            Err(e) => error!("Failed to handle remote frame in room {}: {:?}", room_id, e),
+        }
+    }
+
+    /// Attaches the connection to the room held by this instance. Connection is counted while the room entry is
+    /// held, so the last leaving connection can't drop the room in the meantime.
+    pub fn join_room(&self, room_id: &str) -> Option<Arc<Mutex<DescriptionRoom>>> {
+        let room = self.rooms.get(room_id)?;
+
+        DescriptionRoom::lock(&room).join();
+
+        Some(room.clone())
+    }
+
+    /// Holds the loaded room and attaches the connection to it. If another connection loaded the room in the
+    /// meantime, the connection is attached to that room instead.
+    pub fn insert_room(&self, room_id: &str, mut room: DescriptionRoom) -> Arc<Mutex<DescriptionRoom>> {
+        match self.rooms.entry(room_id.to_string()) {
+            Entry::Occupied(entry) => {
+                DescriptionRoom::lock(entry.get()).join();
+
+                entry.get().clone()
+            }
+            Entry::Vacant(entry) => {
+                room.join();
+
+                let room = entry.insert(Arc::new(Mutex::new(room))).clone();
+
+                // other instances could hold updates that are not persisted yet, so the sync is requested once
+                // the room channel is subscribed
+                self.subscribe_room(room_id);
+
+                room
+            }
+        }
+    }
+
+    /// Detaches the connection from the room. Room is dropped once its last connection leaves, unless another
+    /// connection joined it in the meantime. Returns whether the connection was the last one, so the room
+    /// document is persisted.
+    pub fn leave_room(&self, room_id: &str, room: &Arc<Mutex<DescriptionRoom>>) -> bool {
+        if !DescriptionRoom::lock(room).leave() {
+            return false;
+        }
+
+        let removed_room = self.rooms.remove_if(room_id, |_, held| {
+            Arc::ptr_eq(held, room) && DescriptionRoom::lock(held).is_empty()
+        });
+
+        if removed_room.is_some() {
+            self.connections
+                .remove_if(room_id, |_, connections| connections.is_empty());
+            self.remote_clients.remove(room_id);
+            self.unsubscribe_room(room_id);
+        }
+
+        true
+    }
+
+    /// Publishes heartbeat to channels of rooms held by this instance.
+    pub async fn heartbeat(&self, data: &RequestData) {
+        let room_ids: Vec<RoomId> = self.rooms.iter().map(|room| room.key().clone()).collect();
+
+        for room_id in room_ids {
+            let _ = self.publish(data, &room_id, vec![]).await.map_err(|e| {
+                error!("{}", e);
+            });
+        }
+    }
+
+    /// Removes awareness states of clients whose instance stopped sending heartbeats, e.g. because it died.
+    pub fn cleanup_remote_clients(&self) {
+        let timeout = Duration::from_secs(INSTANCE_TIMEOUT_SEC);
+
+        // rooms dropped by this instance don't need remote clients anymore
+        self.remote_clients
+            .retain(|room_id, _| self.rooms.contains_key(room_id));
+
+        for mut remote_clients in self.remote_clients.iter_mut() {
+            let room_id = remote_clients.key().clone();
+            let Some(room) = self.rooms.get(&room_id).map(|room| room.clone()) else {
+                continue;
+            };
+
+            remote_clients.retain(|instance_id, instance| {
+                if instance.last_seen.elapsed() < timeout {
+                    return true;
+                }
+
+                log::info!("Removing clients of instance {} from room {}", instance_id, room_id);
+
+                if let Some(frame) = DescriptionRoom::lock(&room).remove_clients(&instance.client_ids) {
+                    self.broadcast(&room_id, vec![frame], None);
+                }
+
+                false
+            });
+        }
+    }
 }
 
 #[derive(Clone)]
 pub struct WsConnection {
     pub room_id: RoomId,
     pub broadcast: Arc<WsBroadcast>,
+    pub room: Arc<Mutex<DescriptionRoom>>,
+    pub data: RequestData,
+
+    /// Awareness client ids of the connection.
+    pub client_ids: HashSet<u64>,
+
+    /// Editing presence session of the connected user.
+    pub presence_id: Option<Uuid>,
 }
 
 impl Actor for WsConnection {
     type Context = ws::WebsocketContext<Self>;
+
+    fn started(&mut self, ctx: &mut Self::Context) {
+        match DescriptionRoom::lock(&self.room).greeting() {
+            Ok(frames) => {
+                for frame in frames {
+                    ctx.binary(frame);
+                }
+            }
+            Err(e) => error!("Failed to greet connection to room {}: {:?}", self.room_id, e),
+        }
+    }
 }
 
 impl StreamHandler<Result<ws::Message, ws::ProtocolError>> for WsConnection {
@@ -27,20 +399,32 @@ impl StreamHandler<Result<ws::Message, ws::ProtocolError>> for WsConnection {
         match msg {
             Ok(ws::Message::Ping(msg)) => ctx.pong(&msg),
             Ok(ws::Message::Binary(bin)) => {
-                if let Some(connections) = self.broadcast.connections.get(&self.room_id) {
-                    for conn in connections.iter() {
-                        let address = ctx.address();
-                        // this will call `handle` method of `WsMessage` actor
-                        let message = WsMessage {
-                            message: ws::Message::Binary(bin.clone()),
-                            origin_address: address,
-                        };
-                        conn.do_send(message);
-                    }
-                } else {
+                if !self.broadcast.connections.contains_key(&self.room_id) {
                     ctx.close(None);
                     ctx.stop();
                     error!("No connections for node {}", self.room_id);
+
+                    return;
+                }
+
+                let output = DescriptionRoom::lock(&self.room).handle_frame(&bin, &mut self.client_ids);
+
+                match output {
+                    Ok(output) => {
+                        for frame in output.reply {
+                            ctx.binary(frame);
+                        }
+
+                        self.broadcast
+                            .broadcast(&self.room_id, output.broadcast.clone(), Some(ctx.address()));
+                        self.broadcast
+                            .spawn_publish(&self.data, &self.room_id, output.broadcast);
+
+                        if output.is_updated {
+                            DescriptionRoom::schedule_persist(self.room.clone(), self.data.clone());
+                        }
+                    }
+                    Err(e) => error!("Failed to handle frame in room {}: {:?}", self.room_id, e),
                 }
             }
             Ok(ws::Message::Close(reason)) => {
@@ -57,6 +441,32 @@ impl StreamHandler<Result<ws::Message, ws::ProtocolError>> for WsConnection {
             connections.retain(|addr| *addr != ctx.address());
         }
 
+        let awareness_frame = DescriptionRoom::lock(&self.room).remove_clients(&self.client_ids);
+
+        if let Some(frame) = awareness_frame {
+            self.broadcast
+                .broadcast(&self.room_id, vec![frame.clone()], Some(ctx.address()));
+            self.broadcast.spawn_publish(&self.data, &self.room_id, vec![frame]);
+        }
+
+        self.client_ids.clear();
+
+        if let Some(presence_id) = self.presence_id.take() {
+            PresenceTracker::spawn_leave(&self.data, presence_id);
+        }
+
+        // document is dropped once the last client leaves, so the room is persisted right away
+        if self.broadcast.leave_room(&self.room_id, &self.room) {
+            let room = self.room.clone();
+            let data = self.data.clone();
+
+            tokio::spawn(async move {
+                let _ = DescriptionRoom::persist(&room, &data).await.map_err(|e| {
+                    error!("Failed to persist description room: {:?}", e);
+                });
+            });
+        }
+
         ctx.stop();
     }
 }
@@ -65,14 +475,16 @@ impl StreamHandler<Result<ws::Message, ws::ProtocolError>> for WsConnection {
 #[rtype(result = "()")]
 pub struct WsMessage {
     pub message: ws::Message,
-    pub origin_address: Addr<WsConnection>,
+
+    /// Connection that sent the frame. `None` for frames relayed from other instances.
+    pub origin_address: Option<Addr<WsConnection>>,
 }
 
 impl Handler<WsMessage> for WsConnection {
     type Result = ();
 
     fn handle(&mut self, msg: WsMessage, ctx: &mut Self::Context) {
-        if msg.origin_address != ctx.address() {
+        if msg.origin_address.as_ref() != Some(&ctx.address()) {
             match msg.message {
                 ws::Message::Binary(bin) => ctx.binary(bin),
                 ws::Message::Text(text) => ctx.text(text),
@@ -1,6 +1,8 @@
 use crate::api::data::RequestData;
 use crate::app::App;
+use crate::resources::presence::PRESENCE_HEARTBEAT_SEC;
 use crate::resources::sse_broadcast::{SseBroadcast, SseMessage};
+use crate::resources::ws_broadcast::{RoomMessage, RoomSubscription, HEARTBEAT_INTERVAL_SEC, ROOM_CHANNEL_PREFIX};
 use futures::StreamExt;
 use log::info;
 use std::sync::Arc;
@@ -31,6 +33,30 @@ pub async fn recovery_task(data: RequestData) {
     });
 }
 
+pub async fn merge_queue_task(data: RequestData) {
+    let mut merge_queue_interval = time::interval(Duration::from_secs(
+        crate::models::merge_queue::MERGE_QUEUE_INTERVAL_SEC,
+    ));
+
+    tokio::spawn(async move {
+        loop {
+            tokio::select! {
+                _ = merge_queue_interval.tick() => {
+                    let _ = crate::models::merge_queue::MergeQueueItem::run_merge_queue_task(&data)
+                        .await
+                        .map_err(|e| {
+                            log::error!("Merge queue task failed: {:?}", e);
+                        });
+                }
+                _ = tokio::signal::ctrl_c() => {
+                    info!("Merge queue task is shutting down due to Ctrl-C.");
+                    break;
+                }
+            }
+        }
+    });
+}
+
 pub async fn cleanup_rooms_task(sse_broadcast: Arc<SseBroadcast>) {
     let mut cleanup_interval = time::interval(Duration::from_secs(600));
     let sse_broadcast_clone = sse_broadcast.clone();
@@ -75,7 +101,7 @@ pub async fn listen_redis_events(app: &App) {
                         match payload {
                             Ok(payload) => {
                                 let _ = sse_broadcast
-                                    .send_message(payload.root_id, actix_web::web::Bytes::from(payload.data))
+                                    .send_message(payload)
                                     .await
                                     .map_err(|e| {
                                         log::error!("Failed to send message: {:?}", e);
@@ -95,3 +121,100 @@ pub async fn listen_redis_events(app: &App) {
         }
     });
 }
+
+pub async fn ws_heartbeat_task(data: RequestData) {
+    let mut heartbeat_interval = time::interval(Duration::from_secs(HEARTBEAT_INTERVAL_SEC));
+    let ws_broadcast = data.ws_broadcast();
+
+    tokio::spawn(async move {
+        loop {
+            tokio::select! {
+                _ = heartbeat_interval.tick() => {
+                    ws_broadcast.heartbeat(&data).await;
+                    ws_broadcast.cleanup_remote_clients();
+                }
+                _ = tokio::signal::ctrl_c() => {
+                    info!("WS heartbeat task is shutting down due to Ctrl-C.");
+                    break;
+                }
+            }
+        }
+    });
+}
+
+pub async fn presence_task(data: RequestData) {
+    let mut presence_interval = time::interval(Duration::from_secs(PRESENCE_HEARTBEAT_SEC));
+    let presence = data.presence();
+
+    tokio::spawn(async move {
+        loop {
+            tokio::select! {
+                _ = presence_interval.tick() => {
+                    presence.heartbeat(&data).await;
+                }
+                _ = tokio::signal::ctrl_c() => {
+                    info!("Presence task is shutting down due to Ctrl-C.");
+                    break;
+                }
+            }
+        }
+    });
+}
+
+pub async fn listen_ws_room_events(data: RequestData) {
+    // TODO: get redis client from the same zone
+    let client = data.app.redis_clients.first().expect("Redis should have one client");
+
+    let pubsub = client.get_async_pubsub().await.expect("Failed to get redis connection");
+    let ws_broadcast = data.ws_broadcast();
+    let mut subscriptions = ws_broadcast
+        .take_subscriptions()
+        .expect("Room subscriptions should be taken only once");
+
+    tokio::spawn(async move {
+        let (mut sink, mut on_message) = pubsub.split();
+
+        loop {
+            tokio::select! {
+                subscription = subscriptions.recv() => {
+                    match subscription {
+                        Some(RoomSubscription::Subscribe(room_id)) => {
+                            let channel = format!("{}{}", ROOM_CHANNEL_PREFIX, room_id);
+
+                            match sink.subscribe(channel).await {
+                                Ok(_) => ws_broadcast.request_sync(&data, &room_id),
+                                Err(e) => {
+                                    log::error!("Failed to subscribe to room {}: {}", room_id, e);
+                                }
+                            }
+                        }
+                        Some(RoomSubscription::Unsubscribe(room_id)) => {
+                            let channel = format!("{}{}", ROOM_CHANNEL_PREFIX, room_id);
+
+                            if let Err(e) = sink.unsubscribe(channel).await {
+                                log::error!("Failed to unsubscribe from room {}: {}", room_id, e);
+                            }
+                        }
+                        None => break,
+                    }
+                }
+                msg = on_message.next() => {
+                    if let Some(msg) = msg {
+                        let room_id = msg.get_channel_name().trim_start_matches(ROOM_CHANNEL_PREFIX).to_string();
+
+                        match msg.get_payload::<RoomMessage>() {
+                            Ok(payload) => ws_broadcast.handle_remote(&data, &room_id, payload).await,
+                            Err(e) => {
+                                log::error!("Failed to get room payload: {}", e);
+                            }
+                        }
+                    }
+                }
+                _ = tokio::signal::ctrl_c() => {
+                    info!("WS room task is shutting down due to Ctrl-C.");
+                    break;
+                }
+            }
+        }
+    });
+}